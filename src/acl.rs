@@ -0,0 +1,152 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+};
+
+use relative_path::RelativePathBuf;
+use thiserror::Error;
+
+use crate::repo::SetConfig;
+
+// ACLs go through getfacl and setfacl, the same way copying goes through rsync,
+// so sets that don't opt into preserving them never need the acl package.
+#[derive(Error, Debug)]
+pub enum AclError {
+    #[error(
+        "{0} wasn't found, but is needed for sets that preserve ACLs. It usually comes with the acl package."
+    )]
+    Missing(&'static str),
+
+    #[error("Unable to run {0}.")]
+    Io(&'static str, #[source] std::io::Error),
+
+    // the usual cause is a filesystem without ACL support, or one mounted without it
+    #[error(
+        "{tool} exited unsuccessfully ({status}) for '{}', which may be on a filesystem without ACL support: {stderr}",
+        path.display()
+    )]
+    Unsuccessful {
+        tool: &'static str,
+        path: PathBuf,
+        status: ExitStatus,
+        stderr: String,
+    },
+}
+
+// the file's extended entries, in setfacl's comma-separated form, like 'user:alice:r--,mask::r--'.
+// None if it only has the entries that its mode already covers.
+// names are kept instead of ids, so that they mean the same thing on other machines.
+fn capture(path: &Path) -> Result<Option<String>, AclError> {
+    let stdout = run(
+        "getfacl",
+        path,
+        &["--omit-header", "--no-effective", "--skip-base"],
+    )?;
+    Ok(extended_entries(&String::from_utf8_lossy(&stdout)))
+}
+
+// for files just copied into a set that preserves ACLs, along with their local paths.
+// a file without any extended entries drops its recorded ones, so removing them locally carries over too.
+// returns whether the config changed, so that it's only saved when needed.
+pub(crate) fn record(
+    config: &mut SetConfig,
+    files: impl IntoIterator<Item = (RelativePathBuf, PathBuf)>,
+) -> Result<bool, AclError> {
+    let mut changed = false;
+    for (path_in_set, local_path) in files {
+        let key = path_in_set.to_path("");
+        let acl = capture(&local_path)?;
+        if config.acls.get(&key) == acl.as_ref() {
+            continue;
+        }
+        match acl {
+            Some(acl) => config.acls.insert(key, acl),
+            None => config.acls.remove(&key),
+        };
+        changed = true;
+    }
+
+    Ok(changed)
+}
+
+// only adds to what the file already has, since its mode takes care of the base entries
+pub(crate) fn apply(path: &Path, entries: &str) -> Result<(), AclError> {
+    run("setfacl", path, &["--modify", entries])?;
+    Ok(())
+}
+
+fn run(tool: &'static str, path: &Path, args: &[&str]) -> Result<Vec<u8>, AclError> {
+    let output = Command::new(tool)
+        .args(args)
+        .arg("--")
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => AclError::Missing(tool),
+            _ => AclError::Io(tool, e),
+        })?;
+    if !output.status.success() {
+        return Err(AclError::Unsuccessful {
+            tool,
+            path: path.to_path_buf(),
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    Ok(output.stdout)
+}
+
+// named users and groups, along with the mask that limits them.
+// default entries only apply to directories, which aren't tracked.
+fn extended_entries(getfacl_output: &str) -> Option<String> {
+    let entries: Vec<&str> = getfacl_output
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|entry| match entry.split(':').collect::<Vec<_>>()[..] {
+            ["user" | "group", qualifier, _] => !qualifier.is_empty(),
+            ["mask", _, _] => true,
+            _ => false,
+        })
+        .collect();
+    match entries.is_empty() {
+        true => None,
+        false => Some(entries.join(",")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use googletest::prelude::*;
+
+    use super::extended_entries;
+
+    #[gtest]
+    fn keeps_extended_entries() {
+        let output = "user::rw-\nuser:alice:r--\t#effective:r--\ngroup::r--\ngroup:staff:rw-\nmask::rw-\nother::r--\n\n";
+        expect_that!(
+            extended_entries(output),
+            some(eq("user:alice:r--,group:staff:rw-,mask::rw-"))
+        );
+    }
+
+    #[gtest]
+    fn base_entries_only() {
+        expect_that!(
+            extended_entries("user::rw-\ngroup::r--\nother::r--\n"),
+            none()
+        );
+        // what --skip-base leaves for them
+        expect_that!(extended_entries(""), none());
+    }
+
+    #[gtest]
+    fn skips_defaults() {
+        expect_that!(
+            extended_entries("user::rwx\ndefault:user:alice:r-x\ndefault:mask::r-x\n"),
+            none()
+        );
+    }
+}
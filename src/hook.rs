@@ -0,0 +1,153 @@
+use std::{
+    fmt::Display,
+    process::{Command, ExitStatus, Stdio},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{ExecutionOptions, MonjaProfile};
+
+// commands are run with `sh -c`, from the local root
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HooksConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_pull: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_pull: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_push: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_push: Option<String>,
+}
+
+impl HooksConfig {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pre_pull.is_none()
+            && self.post_pull.is_none()
+            && self.pre_push.is_none()
+            && self.post_push.is_none()
+    }
+
+    fn command(&self, kind: HookKind) -> Option<&str> {
+        match kind {
+            HookKind::PrePull => self.pre_pull.as_deref(),
+            HookKind::PostPull => self.post_pull.as_deref(),
+            HookKind::PrePush => self.pre_push.as_deref(),
+            HookKind::PostPush => self.post_push.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    PrePull,
+    PostPull,
+    PrePush,
+    PostPush,
+}
+
+impl Display for HookKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            HookKind::PrePull => "pre-pull",
+            HookKind::PostPull => "post-pull",
+            HookKind::PrePush => "pre-push",
+            HookKind::PostPush => "post-push",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum HookError {
+    #[error("Unable to run the {0} hook.")]
+    Io(HookKind, #[source] std::io::Error),
+
+    // like rsync, stderr is captured so that callers can find out why
+    #[error("The {kind} hook exited unsuccessfully ({status}): {stderr}")]
+    Unsuccessful {
+        kind: HookKind,
+        status: ExitStatus,
+        stderr: String,
+    },
+}
+
+// in a dry run, this is a hook that would have run
+#[derive(Debug)]
+pub struct HookRun {
+    pub kind: HookKind,
+    pub command: String,
+}
+
+// post-hooks don't fail the operation, since its changes have already been made.
+// whatever ran is added to hooks_run, even if it failed.
+pub(crate) fn run_post(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    kind: HookKind,
+    hooks_run: &mut Vec<HookRun>,
+) -> Option<HookError> {
+    let command = profile.config.hooks.command(kind)?;
+    let result = run(profile, opts, kind);
+    hooks_run.push(HookRun {
+        kind,
+        command: command.to_string(),
+    });
+    result.err()
+}
+
+pub(crate) fn run(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    kind: HookKind,
+) -> Result<Option<HookRun>, HookError> {
+    let Some(command) = profile.config.hooks.command(kind) else {
+        return Ok(None);
+    };
+    let hook_run = HookRun {
+        kind,
+        command: command.to_string(),
+    };
+
+    if opts.dry_run {
+        return Ok(Some(hook_run));
+    }
+
+    let mut child = Command::new("sh");
+    child
+        .arg("-c")
+        .arg(command)
+        .current_dir(&profile.local_root)
+        .stdin(Stdio::null());
+
+    // when verbose, output goes straight to the terminal. otherwise, only stderr is kept for errors.
+    let (status, stderr) = match opts.verbosity > 0 {
+        true => {
+            log::info!("Running {} hook: {}", kind, command);
+            let status = child.status().map_err(|e| HookError::Io(kind, e))?;
+            (status, String::new())
+        }
+        false => {
+            let output = child
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .output()
+                .map_err(|e| HookError::Io(kind, e))?;
+            (
+                output.status,
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            )
+        }
+    };
+
+    match status.success() {
+        true => Ok(Some(hook_run)),
+        false => Err(HookError::Unsuccessful {
+            kind,
+            status,
+            stderr,
+        }),
+    }
+}
@@ -2,50 +2,103 @@
 #![deny(clippy::unwrap_used)]
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     ffi::{OsStr, OsString},
     io::{Read, Write},
     ops::Deref,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
     sync::LazyLock,
 };
 
-use clap::Args;
 use relative_path::{PathExt, RelativePathBuf};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+pub(crate) mod acl;
+pub(crate) mod hook;
 pub(crate) mod local;
 pub(crate) mod repo;
 pub(crate) mod rsync;
+pub(crate) mod secret;
+pub(crate) mod template;
 pub mod operation {
     pub mod clean;
+    pub mod diff;
+    pub mod doctor;
+    pub mod edit;
+    pub mod history;
     pub mod init;
+    pub mod list_sets;
     pub mod new_set;
+    pub mod preview_set;
+    pub mod prune_index;
     pub mod pull;
     pub mod push;
     pub mod put;
+    pub mod remove_set;
+    pub mod rename_set;
+    pub mod restore;
     pub mod set_shortcut;
     pub mod status;
     pub mod transfer;
+    pub mod verify;
+    pub mod which;
 }
 
 pub use crate::{
-    operation::clean::*, operation::init::*, operation::new_set::*, operation::pull::*,
-    operation::push::*, operation::put::*, operation::set_shortcut::*, operation::status::*,
-    operation::transfer::*, repo::SetConfig, repo::SetConfigError, repo::SetCreationError,
-    repo::SetName, repo::SetShortcutError,
+    acl::AclError, hook::HookError, hook::HookKind, hook::HookRun, hook::HooksConfig,
+    operation::clean::*, operation::diff::*, operation::doctor::*, operation::edit::*,
+    operation::history::*, operation::init::*, operation::list_sets::*, operation::new_set::*,
+    operation::preview_set::*, operation::prune_index::*, operation::pull::*, operation::push::*,
+    operation::put::*, operation::remove_set::*, operation::rename_set::*, operation::restore::*,
+    operation::set_shortcut::*, operation::status::*, operation::transfer::*, operation::verify::*,
+    operation::which::*, repo::SetConfig, repo::SetConfigError, repo::SetCreationError,
+    repo::SetName, repo::SetNameError, repo::SetShortcutError, repo::TargetSetsError,
+    rsync::ProgressCallback, rsync::RsyncError, rsync::RsyncStats, secret::SECRET_KEY_VAR,
+    secret::SecretError, template::TemplateError,
 };
 
 pub type LocalStateInitializationError = local::StateInitializationError;
 pub type RepoStateInitializationError = repo::StateInitializationError;
+pub type FileIndexError = local::FileIndexError;
+pub type IndexKind = local::IndexKind;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct MonjaProfileConfig {
     pub repo_dir: PathBuf,
     // while a hashset would be handy, we use a vec because order is important
     pub target_sets: Vec<SetName>,
+    // by default, symlinked local files are pushed as regular files containing the link target's contents.
+    // with this, the links themselves end up in the set instead.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub preserve_symlinks: bool,
+    // the most files clean will remove without ExecutionOptions::force, defaulting to DEFAULT_CLEAN_THRESHOLD.
+    // a corrupted index or an empty repo can otherwise make clean propose removing most of the local root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clean_threshold: Option<usize>,
+    // passed to rsync before the source and dest, like `--chmod=F644` or `--bwlimit=1000`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rsync_extra_args: Vec<String>,
+    #[serde(default, skip_serializing_if = "HooksConfig::is_empty")]
+    pub hooks: HooksConfig,
+    // file names that, like monja's own special files, are never pulled or pushed, wherever they are
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reserved_names: Vec<String>,
+    // gitignore-style patterns relative to the local root, for machine-specific paths that shouldn't go in a .monjaignore
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excludes: Vec<String>,
+    // substituted into templates in sets when pulling, which is handy for things like hostnames and emails
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+    // fails reading the repo if targeted sets have local files whose paths only differ in case,
+    // which can't coexist on case-insensitive file systems, like macOS usually has
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub case_insensitive_check: bool,
+    // where monja-index.toml and monja-index-prev.toml live instead of the data directory, relative to the repo.
+    // handy for committing the index, though it shouldn't be inside a set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_dir: Option<PathBuf>,
 }
 
 #[derive(Error, Debug)]
@@ -57,32 +110,117 @@ pub enum MonjaProfileConfigError {
     Serialization(#[from] toml::ser::Error),
 
     #[error("Unable to read from monja-profile.toml.")]
-    Load(#[source] AbsolutePathError),
+    Load(#[source] MonjaProfileError),
 
     #[error("Unable to write to monja-profile.toml.")]
     Write(#[source] std::io::Error),
 
     #[error("Unable to read to monja-profile.toml.")]
     Read(#[source] std::io::Error),
+
+    #[error("monja-profile.toml redirects to '{0}', which can't be read.")]
+    RedirectTarget(PathBuf, #[source] std::io::Error),
+
+    #[error("Gave up following monja-profile.toml's redirects at '{0}', since they likely loop.")]
+    RedirectLoop(PathBuf),
+}
+
+// redirects in a chain longer than this are assumed to loop
+const MAX_PROFILE_REDIRECTS: usize = 8;
+
+#[derive(Error, Debug)]
+pub enum ProfileValidationError {
+    // later sets win, so a set appearing twice makes it unclear where it sits in the order
+    #[error("Set `{0}` is targeted more than once.")]
+    DuplicateSet(SetName),
+
+    #[error("Set `{0}` is missing from the repo.")]
+    MissingSet(SetName),
 }
 
 impl MonjaProfileConfig {
-    // we take a path to config file, not folder, since the profile could be one located in the repo, pointed to by local
+    pub fn is_special_file(&self, location: FileLocation) -> bool {
+        let path = match location {
+            FileLocation::InSet(path) | FileLocation::Local(path) => path,
+        };
+        is_monja_special_file(location)
+            || path
+                .file_name()
+                .is_some_and(|f: &OsStr| self.reserved_names.iter().any(|name| f == name.as_str()))
+    }
+
+    // we take a path to config file, not folder, since the profile could be one located in the repo, pointed to by local.
+    // that's done with a file containing only `redirect = '<path>'`, where relative paths are relative to that file.
     pub fn load(config_path: &AbsolutePath) -> Result<MonjaProfileConfig, MonjaProfileConfigError> {
+        let config_path = Self::resolve_redirects(config_path)?;
         let config = std::fs::read(config_path).map_err(MonjaProfileConfigError::Read)?;
 
         Ok(toml::from_slice(&config)?)
     }
 
+    // also follows redirects, so that a redirect isn't replaced with the whole profile
     pub fn save(&self, config_path: &AbsolutePath) -> Result<(), MonjaProfileConfigError> {
+        let config_path = Self::resolve_redirects(config_path)?;
         std::fs::write(config_path, toml::to_string(&self)?)
             .map_err(MonjaProfileConfigError::Write)?;
 
         Ok(())
     }
+
+    // the path of the actual profile, which is config_path itself if it isn't a redirect or doesn't exist yet
+    pub fn resolve_redirects(config_path: &Path) -> Result<PathBuf, MonjaProfileConfigError> {
+        let mut path = config_path.to_path_buf();
+        for _ in 0..=MAX_PROFILE_REDIRECTS {
+            let contents = match std::fs::read(&path) {
+                Ok(contents) => contents,
+                // init saves the profile before it exists, and load reports the missing file itself
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && path == config_path => {
+                    return Ok(path);
+                }
+                Err(e) => return Err(MonjaProfileConfigError::RedirectTarget(path, e)),
+            };
+
+            // anything that isn't a lone redirect is left for deserialization to complain about
+            let Ok(table) = toml::from_slice::<toml::Table>(&contents) else {
+                return Ok(path);
+            };
+            let redirect = match table.get("redirect") {
+                Some(toml::Value::String(redirect)) if table.len() == 1 => redirect,
+                _ => return Ok(path),
+            };
+            path = path
+                .parent()
+                .expect("Profile path is a file, so it has a parent.")
+                .join(redirect);
+        }
+
+        Err(MonjaProfileConfigError::RedirectLoop(path))
+    }
+
+    pub fn validate(&self, repo_root: &Path) -> Result<(), Vec<ProfileValidationError>> {
+        let mut errors = Vec::new();
+        let mut seen = HashSet::with_capacity(self.target_sets.len());
+
+        for set_name in self.target_sets.iter() {
+            if !seen.insert(set_name) {
+                // already reported as missing, if it is
+                errors.push(ProfileValidationError::DuplicateSet(set_name.clone()));
+                continue;
+            }
+
+            if !repo_root.join(&set_name.0).is_dir() {
+                errors.push(ProfileValidationError::MissingSet(set_name.clone()));
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MonjaProfile {
     pub local_root: AbsolutePath,
     pub repo_root: AbsolutePath,
@@ -96,41 +234,151 @@ impl MonjaProfile {
         config: MonjaProfileConfig,
         local_root: AbsolutePath,
         data_root: AbsolutePath,
-    ) -> Result<MonjaProfile, AbsolutePathError> {
+    ) -> Result<MonjaProfile, MonjaProfileError> {
         let repo_root = match config.repo_dir.is_relative() {
             true => AbsolutePath::for_existing_path(&local_root.join(&config.repo_dir))?,
             false => AbsolutePath::for_existing_path(&config.repo_dir)?,
         };
+        // otherwise, we'd only find out once something tries to read the sets in it
+        if !repo_root.is_dir() {
+            return Err(MonjaProfileError::RepoNotDirectory(
+                repo_root.into_path_buf(),
+            ));
+        }
+
+        // operations only ever see the expanded sets, so they don't need to know about globs
+        let mut config = config;
+        config.target_sets = repo::expand_target_sets(&repo_root, config.target_sets)?;
+
+        MonjaProfile::from_roots(local_root, repo_root, data_root, config)
+    }
+
+    // for embedders that already have their roots, which may not exist yet, such as a layout they're about to create.
+    // unlike from_config, the repo isn't read, so globs in target_sets aren't expanded and are taken as set names.
+    pub fn from_roots(
+        local_root: AbsolutePath,
+        repo_root: AbsolutePath,
+        data_root: AbsolutePath,
+        config: MonjaProfileConfig,
+    ) -> Result<MonjaProfile, MonjaProfileError> {
+        // otherwise, it'd be rsync failing in the middle of a pull
+        if let Some(arg) = config
+            .rsync_extra_args
+            .iter()
+            .find(|arg| !rsync::is_allowed_extra_arg(arg))
+        {
+            return Err(MonjaProfileError::RsyncExtraArg(arg.clone()));
+        }
 
-        Ok(MonjaProfile {
+        let mut profile = MonjaProfile {
             local_root,
             repo_root,
             data_root,
             config,
-        })
+        };
+        profile.order_by_priority();
+        Ok(profile)
+    }
+
+    // for when a single operation should use a different list of sets, like `monja pull --sets`.
+    // monja-profile.toml itself is left alone.
+    pub fn with_target_sets(&self, target_sets: Vec<SetName>) -> MonjaProfile {
+        let mut profile = self.clone();
+        profile.config.target_sets = target_sets;
+        profile.order_by_priority();
+        profile
+    }
+
+    // like globs, done up front so that everything layering sets only needs to go by target_sets' order.
+    // the sort is stable, so sets with the same priority stay in profile order.
+    // a set whose config can't be loaded is left at the default priority, and the error comes up once the repo is read.
+    fn order_by_priority(&mut self) {
+        let mut prioritized: Vec<(i32, SetName)> = std::mem::take(&mut self.config.target_sets)
+            .into_iter()
+            .map(|set_name| {
+                let priority = repo::SetConfig::load(self, &set_name)
+                    .ok()
+                    .and_then(|c| c.priority)
+                    .unwrap_or_default();
+                (priority, set_name)
+            })
+            .collect();
+        prioritized.sort_by_key(|(priority, _)| *priority);
+        self.config.target_sets = prioritized.into_iter().map(|(_, s)| s).collect();
+    }
+
+    // not an AbsolutePath because the directory may not exist until the index is first saved
+    pub fn index_dir(&self) -> PathBuf {
+        match &self.config.index_dir {
+            Some(index_dir) => self.repo_root.join(index_dir),
+            None => self.data_root.to_path_buf(),
+        }
+    }
+
+    // happens on every run, so it's fine for the directory to already exist
+    pub fn create_data_root(data_dir: &Path) -> Result<AbsolutePath, DataRootError> {
+        std::fs::create_dir_all(data_dir)
+            .map_err(|e| DataRootError::Create(data_dir.to_path_buf(), e))?;
+        Ok(AbsolutePath::for_existing_path(data_dir)?)
     }
 }
 
-// would ideally not depend on clap in this crate, but it's not worth the effort otherwise
-// one alternative option is to expose a trait here, implemented main side
-// another alternative is to use an object mapper like o2o
-#[derive(Args)]
-#[group(multiple = true)]
+#[derive(Error, Debug)]
+pub enum MonjaProfileError {
+    #[error("Unable to load the repo directory.")]
+    RepoRoot(#[from] AbsolutePathError),
+
+    #[error("The repo path '{0}' is not a directory.")]
+    RepoNotDirectory(PathBuf),
+
+    #[error("Unable to expand target-sets.")]
+    TargetSets(#[from] repo::TargetSetsError),
+
+    #[error(
+        "'{0}' can't be in rsync-extra-args. Only options are allowed, written like `--option=value`, and not ones that change rsync's file list or output."
+    )]
+    RsyncExtraArg(String),
+}
+
+#[derive(Error, Debug)]
+pub enum DataRootError {
+    #[error("Unable to create the data directory: {0}")]
+    Create(PathBuf, #[source] std::io::Error),
+
+    #[error("Unable to load the data directory.")]
+    Load(#[from] AbsolutePathError),
+}
+
+// the cli has its own copy with clap's attributes, converted into this, so that this crate doesn't depend on clap
+#[derive(Debug, Default, Clone)]
 pub struct ExecutionOptions {
-    /// Enables more verbose logging.
-    #[arg(short, long = "verbose", action = clap::ArgAction::Count)]
+    // 0 only logs warnings, 1 adds info, and anything higher adds debug
     pub verbosity: u8,
-
-    /// No disk operations will be performed.
-    #[arg(long)]
+    // only logs errors, regardless of verbosity
+    pub quiet: bool,
     pub dry_run: bool,
-
-    /// Skip confirmations
-    #[arg(long = "yes", short = 'y')]
     pub skip_confirmations: bool,
+    // pull backs up local files that would be overwritten with different contents into monja-backup/<timestamp>.
+    // put backs up the set's copies of the files being put into put-backups/<timestamp>.
+    // both are in the data directory.
+    pub backup: bool,
+    // lets clean go past the profile's clean-threshold
+    pub force: bool,
 }
 
 impl ExecutionOptions {
+    // library messages go through the log crate, leaving it to the consumer to install a logger
+    pub fn log_level(&self) -> log::LevelFilter {
+        if self.quiet {
+            return log::LevelFilter::Error;
+        }
+        match self.verbosity {
+            0 => log::LevelFilter::Warn,
+            1 => log::LevelFilter::Info,
+            _ => log::LevelFilter::Debug,
+        }
+    }
+
     pub fn user_confirm(&self, message: &str) -> bool {
         if self.skip_confirmations {
             return true;
@@ -150,7 +398,7 @@ impl ExecutionOptions {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AbsolutePath {
     path: PathBuf,
 }
@@ -162,12 +410,53 @@ pub enum AbsolutePathError {
 }
 
 impl AbsolutePath {
+    // UNCHECKED: the path is trusted to already be absolute and canonical, and isn't touched at all.
+    // a relative or non-canonical path makes local paths resolve incorrectly, so prefer for_existing_path
+    // or for_path_allow_nonexistent unless the path came from one of them or is known to be canonical.
+    pub fn trusted(path: PathBuf) -> AbsolutePath {
+        AbsolutePath { path }
+    }
+
     pub fn for_existing_path(path: &Path) -> Result<AbsolutePath, AbsolutePathError> {
         std::fs::canonicalize(path)
             .map(|path| AbsolutePath { path })
             .map_err(|e| AbsolutePathError::Canonicalize(path.to_path_buf(), e))
     }
 
+    // for paths that may not exist yet, like where something is about to be created or would be in a dry run.
+    // only the deepest existing ancestor gets canonicalized, and the rest is resolved lexically.
+    // that's fine for `..`, since nothing under a missing directory can be a symlink.
+    // relative paths are relative to cwd, like with for_existing_path.
+    pub fn for_path_allow_nonexistent(path: &Path) -> Result<AbsolutePath, AbsolutePathError> {
+        let error = |e| AbsolutePathError::Canonicalize(path.to_path_buf(), e);
+        let absolute = std::path::absolute(path).map_err(error)?;
+
+        let mut existing = absolute.components();
+        let mut missing = Vec::new();
+        let mut resolved = loop {
+            match std::fs::canonicalize(existing.as_path()) {
+                Ok(resolved) => break resolved,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    // the root always exists, so this only fails if something else is wrong
+                    missing.push(existing.next_back().ok_or_else(|| error(e))?);
+                }
+                Err(e) => return Err(error(e)),
+            }
+        };
+
+        for component in missing.into_iter().rev() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                component => resolved.push(component),
+            }
+        }
+
+        Ok(AbsolutePath { path: resolved })
+    }
+
     // could implement Into, but won't implement From because this is fallible and meant to use for_existing_path
     // could implement TryFrom, though, instead of naming it for_existing_path,
     // but we'd still want this because it doesn't copy
@@ -206,7 +495,7 @@ impl std::fmt::Display for AbsolutePath {
 // it would also be nice for it to support paths rooted under local_root (regardless of cwd), which is what local::FilePath is.
 // however, it would be hard to disambiguate. instead, commands can provide a switch that causes
 // LocalFilePath::from to be invoked with cwd=local_root.
-#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct LocalFilePath(PathBuf);
 
 #[derive(Error, Debug)]
@@ -259,6 +548,11 @@ impl LocalFilePath {
         Ok(LocalFilePath(path.to_path("")))
     }
 
+    // always `/`-separated, regardless of platform, so it's stable for things like json
+    pub fn as_relative_str(&self) -> String {
+        relative_str(&self.0)
+    }
+
     pub(crate) fn to_internal(&self) -> local::FilePath {
         local::FilePath::create_from_public(self)
     }
@@ -310,7 +604,7 @@ impl PartialEq<Path> for LocalFilePath {
 
 impl std::fmt::Display for LocalFilePath {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0.display())
+        write!(f, "{}", self.as_relative_str())
     }
 }
 
@@ -320,6 +614,24 @@ pub struct RepoFilePath {
     pub local_path: PathBuf,
 }
 
+impl RepoFilePath {
+    pub fn path_in_set_as_relative_str(&self) -> String {
+        relative_str(&self.path_in_set)
+    }
+
+    pub fn local_path_as_relative_str(&self) -> String {
+        relative_str(&self.local_path)
+    }
+}
+
+// both types only ever hold relative paths, so we don't need to worry about prefixes or roots
+fn relative_str(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
 impl From<repo::FilePath> for RepoFilePath {
     fn from(value: repo::FilePath) -> Self {
         RepoFilePath {
@@ -340,21 +652,140 @@ impl TryFrom<RepoFilePath> for repo::FilePath {
     }
 }
 
+// whether a file is special depends on where it is.
+// for instance, a .monja-set.toml only configures a set when it's at the set's root,
+// so one deeper in the set is just another file.
+#[derive(Debug, Clone, Copy)]
+pub enum FileLocation<'a> {
+    // relative to the set's root
+    InSet(&'a Path),
+    // relative to the local root
+    Local(&'a Path),
+}
+
 // not actually sure this is the best way, but it probably works
 // and we can just test on windows if we ever support it
-// test coverage also theoretically ensures we keep this list up to date
-static MONJA_SPECIAL_FILES: LazyLock<HashSet<OsString>> = LazyLock::new(|| {
+// test coverage also theoretically ensures we keep these lists up to date
+// configures the directory it's in, wherever that is in the set
+static MONJA_SET_FILES: LazyLock<HashSet<OsString>> =
+    LazyLock::new(|| HashSet::from([OsString::from(".monja-dir.toml")]));
+// configures the set, but only at its root
+static MONJA_SET_ROOT_FILES: LazyLock<HashSet<OsString>> =
+    LazyLock::new(|| HashSet::from([OsString::from(".monja-set.toml")]));
+// these normally live in the config and data directories, which are usually under the local root.
+// the set's config files are included so that they don't accidentally get put or pushed into a set,
+// where they would configure a directory, or the set itself if they ended up at its root.
+static MONJA_LOCAL_FILES: LazyLock<HashSet<OsString>> = LazyLock::new(|| {
     HashSet::from([
-        OsString::from(".monja-set.toml"),
         OsString::from(".monja-dir.toml"),
+        OsString::from(".monja-set.toml"),
         OsString::from("monja-profile.toml"),
         OsString::from("monja-index.toml"),
         OsString::from("monja-index-prev.toml"),
+        OsString::from(operation::history::HISTORY_FILE),
     ])
 });
-pub fn is_monja_special_file(path: &Path) -> bool {
-    path.file_name()
-        .is_some_and(|f: &OsStr| MONJA_SPECIAL_FILES.contains(f))
+// doesn't include MonjaProfileConfig::reserved_names, which MonjaProfileConfig::is_special_file also checks
+pub fn is_monja_special_file(location: FileLocation) -> bool {
+    match location {
+        FileLocation::InSet(path) => {
+            let is_at_root = path.parent().is_none_or(|p| p.as_os_str().is_empty());
+            path.file_name().is_some_and(|f: &OsStr| {
+                MONJA_SET_FILES.contains(f) || (is_at_root && MONJA_SET_ROOT_FILES.contains(f))
+            })
+        }
+        FileLocation::Local(path) => path
+            .file_name()
+            .is_some_and(|f: &OsStr| MONJA_LOCAL_FILES.contains(f)),
+    }
+}
+
+// sizes are compared first, so most differing files don't need to be read.
+// done in chunks to avoid reading large files fully into memory.
+pub(crate) fn contents_differ(a: &Path, b: &Path) -> std::io::Result<bool> {
+    if std::fs::metadata(a)?.len() != std::fs::metadata(b)?.len() {
+        return Ok(true);
+    }
+
+    let mut a_file = std::fs::File::open(a)?;
+    let mut b_file = std::fs::File::open(b)?;
+    let mut a_buf = [0u8; 8192];
+    let mut b_buf = [0u8; 8192];
+    loop {
+        let a_read = a_file.read(&mut a_buf)?;
+        if a_read == 0 {
+            return Ok(false);
+        }
+        b_file.read_exact(&mut b_buf[..a_read])?;
+        if a_buf[..a_read] != b_buf[..a_read] {
+            return Ok(true);
+        }
+    }
+}
+
+// whether pushing the local file would change the set's copy of it
+pub(crate) fn local_differs_from_set(
+    profile: &MonjaProfile,
+    local_path: &Path,
+    repo_path: &Path,
+    secret: bool,
+) -> std::io::Result<bool> {
+    if secret {
+        return secret::local_differs(local_path, repo_path);
+    }
+
+    if profile.config.preserve_symlinks && local_path.is_symlink() {
+        // the set gets the link itself, which may not even resolve from within the repo
+        return Ok(!repo_path.is_symlink()
+            || std::fs::read_link(local_path)? != std::fs::read_link(repo_path)?);
+    }
+
+    contents_differ(local_path, repo_path)
+}
+
+// copies a local file into a set, the same way push would
+pub(crate) fn copy_local_file_to_set(
+    profile: &MonjaProfile,
+    from: &Path,
+    to: &Path,
+    secret: bool,
+) -> std::io::Result<()> {
+    // the secret's contents are what get encrypted, even if it's a link
+    if secret {
+        return secret::write_encrypted(from, to);
+    }
+
+    if profile.config.preserve_symlinks && from.is_symlink() {
+        // unlike fs::copy, symlink won't overwrite
+        if to.symlink_metadata().is_ok() {
+            std::fs::remove_file(to)?;
+        }
+        return std::os::unix::fs::symlink(std::fs::read_link(from)?, to);
+    }
+
+    std::fs::copy(from, to)?;
+    Ok(())
+}
+
+// for files whose local contents aren't a straight copy of the set's, like templates.
+// written the same way a pull would, including taking on the repo file's permissions.
+// secrets don't use this, since their plaintext shouldn't take on the ciphertext's permissions.
+// returns the number of bytes written, like rsync would report.
+pub(crate) fn write_pulled_contents(
+    contents: &[u8],
+    repo_path: &Path,
+    local_path: &Path,
+) -> std::io::Result<u64> {
+    if let Some(local_dir) = local_path.parent() {
+        std::fs::create_dir_all(local_dir)?;
+    }
+    // like rsync, replace the file instead of writing into it, which also works for read-only files
+    if local_path.symlink_metadata().is_ok() {
+        std::fs::remove_file(local_path)?;
+    }
+    std::fs::write(local_path, contents)?;
+    std::fs::set_permissions(local_path, std::fs::metadata(repo_path)?.permissions())?;
+    Ok(contents.len() as u64)
 }
 
 // want to keep local/repo::File internal, so gonna bite the bullet on allocating another vector.
@@ -402,6 +833,64 @@ pub(crate) fn convert_set_repofile_result(
     result
 }
 
+#[cfg(test)]
+mod executionoptions_tests {
+    use googletest::prelude::*;
+
+    use crate::ExecutionOptions;
+
+    #[gtest]
+    fn log_level() {
+        let level = |verbosity, quiet| {
+            ExecutionOptions {
+                verbosity,
+                quiet,
+                ..Default::default()
+            }
+            .log_level()
+        };
+
+        expect_that!(
+            ExecutionOptions::default().log_level(),
+            eq(log::LevelFilter::Warn)
+        );
+        expect_that!(level(1, false), eq(log::LevelFilter::Info));
+        expect_that!(level(2, false), eq(log::LevelFilter::Debug));
+        // there's nothing past debug, so extra -v's are harmless
+        expect_that!(level(u8::MAX, false), eq(log::LevelFilter::Debug));
+        // quiet wins, even though the cli doesn't allow both
+        expect_that!(level(2, true), eq(log::LevelFilter::Error));
+    }
+}
+
+#[cfg(test)]
+mod absolutepath_tests {
+    use std::path::Path;
+
+    use googletest::prelude::*;
+
+    use crate::AbsolutePath;
+
+    #[gtest]
+    fn allow_nonexistent() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let root = temp.path().canonicalize()?;
+        std::fs::create_dir(root.join("exists"))?;
+
+        let path = AbsolutePath::for_path_allow_nonexistent(&temp.path().join("exists"))?;
+        expect_that!(path.to_path_buf(), eq(&root.join("exists")));
+
+        // the existing part is canonicalized, and the rest is resolved lexically
+        let path = AbsolutePath::for_path_allow_nonexistent(
+            &temp.path().join("exists/../missing/./a/../b"),
+        )?;
+        expect_that!(path.to_path_buf(), eq(&root.join("missing/b")));
+        expect_that!(Path::new(&*path).exists(), is_false());
+
+        Ok(())
+    }
+}
+
 // unit testing because we wouldn't otherwise get coverage on LocalFilePath without e2e tests
 #[cfg(test)]
 mod localfilepath_tests {
@@ -409,21 +898,29 @@ mod localfilepath_tests {
 
     use googletest::prelude::*;
 
-    use crate::{AbsolutePath, LocalFilePath, MonjaProfile, MonjaProfileConfig};
+    use crate::{AbsolutePath, LocalFilePath, MonjaProfile, MonjaProfileConfig, RepoFilePath};
 
     #[gtest]
     fn normal() -> Result<()> {
         let config = MonjaProfileConfig {
             repo_dir: "/home/foo/repo".into(),
             target_sets: Vec::new(),
+            preserve_symlinks: false,
+            clean_threshold: None,
+            rsync_extra_args: Vec::new(),
+            hooks: Default::default(),
+            reserved_names: Vec::new(),
+            excludes: Vec::new(),
+            vars: Default::default(),
+            case_insensitive_check: false,
+            index_dir: None,
         };
-        // don't use ::new because it requires paths to exist
-        let profile = MonjaProfile {
-            local_root: "/home/foo".into(),
-            repo_root: "/home/foo/repo".into(),
-            data_root: "/home/foo/data".into(),
+        let profile = MonjaProfile::from_roots(
+            AbsolutePath::trusted("/home/foo".into()),
+            AbsolutePath::trusted("/home/foo/repo".into()),
+            AbsolutePath::trusted("/home/foo/data".into()),
             config,
-        };
+        )?;
 
         let path = LocalFilePath::from(&profile, "bar/baz".as_ref(), "/home/foo".as_ref())?;
         expect_that!(path, pat!(LocalFilePath(Path::new("bar/baz"))));
@@ -436,14 +933,22 @@ mod localfilepath_tests {
         let config = MonjaProfileConfig {
             repo_dir: "/home/foo/repo".into(),
             target_sets: Vec::new(),
+            preserve_symlinks: false,
+            clean_threshold: None,
+            rsync_extra_args: Vec::new(),
+            hooks: Default::default(),
+            reserved_names: Vec::new(),
+            excludes: Vec::new(),
+            vars: Default::default(),
+            case_insensitive_check: false,
+            index_dir: None,
         };
-        // don't use ::new because it requires paths to exist
-        let profile = MonjaProfile {
-            local_root: "/home/foo".into(),
-            repo_root: "/home/foo/repo".into(),
-            data_root: "/home/foo/data".into(),
+        let profile = MonjaProfile::from_roots(
+            AbsolutePath::trusted("/home/foo".into()),
+            AbsolutePath::trusted("/home/foo/repo".into()),
+            AbsolutePath::trusted("/home/foo/data".into()),
             config,
-        };
+        )?;
 
         let path =
             LocalFilePath::from(&profile, "/home/foo/bar/baz".as_ref(), "/home/foo".as_ref())?;
@@ -457,14 +962,22 @@ mod localfilepath_tests {
         let config = MonjaProfileConfig {
             repo_dir: "/home/foo/repo".into(),
             target_sets: Vec::new(),
+            preserve_symlinks: false,
+            clean_threshold: None,
+            rsync_extra_args: Vec::new(),
+            hooks: Default::default(),
+            reserved_names: Vec::new(),
+            excludes: Vec::new(),
+            vars: Default::default(),
+            case_insensitive_check: false,
+            index_dir: None,
         };
-        // don't use ::new because it requires paths to exist
-        let profile = MonjaProfile {
-            local_root: "/home/foo".into(),
-            repo_root: "/home/foo/repo".into(),
-            data_root: "/home/foo/data".into(),
+        let profile = MonjaProfile::from_roots(
+            AbsolutePath::trusted("/home/foo".into()),
+            AbsolutePath::trusted("/home/foo/repo".into()),
+            AbsolutePath::trusted("/home/foo/data".into()),
             config,
-        };
+        )?;
 
         let path = LocalFilePath::from(&profile, "baz".as_ref(), "/home/foo/bar".as_ref())?;
         expect_that!(path, pat!(LocalFilePath(Path::new("bar/baz"))));
@@ -477,14 +990,22 @@ mod localfilepath_tests {
         let config = MonjaProfileConfig {
             repo_dir: "/home/foo/repo".into(),
             target_sets: Vec::new(),
+            preserve_symlinks: false,
+            clean_threshold: None,
+            rsync_extra_args: Vec::new(),
+            hooks: Default::default(),
+            reserved_names: Vec::new(),
+            excludes: Vec::new(),
+            vars: Default::default(),
+            case_insensitive_check: false,
+            index_dir: None,
         };
-        // don't use ::new because it requires paths to exist
-        let profile = MonjaProfile {
-            local_root: "/home/foo".into(),
-            repo_root: "/home/foo/repo".into(),
-            data_root: "/home/foo/data".into(),
+        let profile = MonjaProfile::from_roots(
+            AbsolutePath::trusted("/home/foo".into()),
+            AbsolutePath::trusted("/home/foo/repo".into()),
+            AbsolutePath::trusted("/home/foo/data".into()),
             config,
-        };
+        )?;
 
         let result = LocalFilePath::from(
             &profile,
@@ -501,14 +1022,22 @@ mod localfilepath_tests {
         let config = MonjaProfileConfig {
             repo_dir: "/home/foo/repo".into(),
             target_sets: Vec::new(),
+            preserve_symlinks: false,
+            clean_threshold: None,
+            rsync_extra_args: Vec::new(),
+            hooks: Default::default(),
+            reserved_names: Vec::new(),
+            excludes: Vec::new(),
+            vars: Default::default(),
+            case_insensitive_check: false,
+            index_dir: None,
         };
-        // don't use ::new because it requires paths to exist
-        let profile = MonjaProfile {
-            local_root: "/home/foo".into(),
-            repo_root: "/home/foo/repo".into(),
-            data_root: "/home/foo/data".into(),
+        let profile = MonjaProfile::from_roots(
+            AbsolutePath::trusted("/home/foo".into()),
+            AbsolutePath::trusted("/home/foo/repo".into()),
+            AbsolutePath::trusted("/home/foo/data".into()),
             config,
-        };
+        )?;
 
         let result = LocalFilePath::from(&profile, "../..".as_ref(), "/home/foo/bar".as_ref());
         expect_that!(result, err(anything()));
@@ -516,12 +1045,18 @@ mod localfilepath_tests {
         Ok(())
     }
 
-    impl From<&str> for AbsolutePath {
-        fn from(value: &str) -> Self {
-            let path: &Path = value.as_ref();
-            AbsolutePath {
-                path: path.to_path_buf(),
-            }
-        }
+    #[gtest]
+    fn relative_str() -> Result<()> {
+        let path = LocalFilePath(Path::new("bar/./baz/").to_path_buf());
+        expect_that!(path.as_relative_str(), eq("bar/baz"));
+
+        let path = RepoFilePath {
+            path_in_set: Path::new("baz").to_path_buf(),
+            local_path: Path::new(".config/bar//baz").to_path_buf(),
+        };
+        expect_that!(path.path_in_set_as_relative_str(), eq("baz"));
+        expect_that!(path.local_path_as_relative_str(), eq(".config/bar/baz"));
+
+        Ok(())
     }
 }
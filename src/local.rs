@@ -1,8 +1,14 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
-use crate::{LocalFilePath, MonjaProfile, repo};
+use crate::{FileLocation, LocalFilePath, MonjaProfile, repo, secret};
 
-use ignore::WalkBuilder;
+use ignore::{
+    WalkBuilder,
+    gitignore::{Gitignore, GitignoreBuilder},
+};
 use relative_path::{RelativePath, RelativePathBuf};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -12,6 +18,11 @@ pub(crate) use index::*;
 
 pub(crate) struct LocalState {
     pub files_to_push: HashMap<repo::SetName, Vec<FilePath>>,
+    // the subset of files_to_push whose contents differ from the set's copy
+    pub modified_files: HashMap<repo::SetName, Vec<FilePath>>,
+    // the subset of modified_files where both the local file and the set's copy changed since they were last synced.
+    // pushing these would lose the repo's changes.
+    pub conflicted_files: HashMap<repo::SetName, Vec<FilePath>>,
     pub files_with_missing_sets: HashMap<repo::SetName, Vec<FilePath>>,
     pub missing_files: HashMap<repo::SetName, Vec<FilePath>>,
     pub untracked_files: Vec<FilePath>,
@@ -26,6 +37,8 @@ pub(crate) fn retrieve_state(
     let mut curr_index = FileIndex::load(profile, IndexKind::Current)?;
 
     let mut files_to_push = HashMap::with_capacity(repo.sets.len());
+    let mut modified_files = HashMap::with_capacity(repo.sets.len());
+    let mut conflicted_files = HashMap::with_capacity(repo.sets.len());
     let mut untracked_files = Vec::new();
     let mut files_with_missing_sets = HashMap::with_capacity(repo.sets.len());
     let mut missing_files = HashMap::with_capacity(repo.sets.len());
@@ -35,7 +48,11 @@ pub(crate) fn retrieve_state(
 
     for local_path in walk(profile) {
         let local_path = local_path?;
-        let Some(set_name) = curr_index.take(&local_path) else {
+        let Some(IndexEntry {
+            set: set_name,
+            hash: synced_hash,
+        }) = curr_index.take(&local_path)
+        else {
             untracked_files.push(local_path);
             continue;
         };
@@ -48,6 +65,11 @@ pub(crate) fn retrieve_state(
             continue;
         };
 
+        // pull-only sets don't get pushed to, so whether or not they still track the file doesn't matter
+        if set.nopush {
+            continue;
+        }
+
         if !set.tracks_file(&local_path) {
             missing_files
                 .entry(set_name)
@@ -56,14 +78,65 @@ pub(crate) fn retrieve_state(
             continue;
         }
 
+        // rendered templates can't be turned back into the template, so they're pull-only, too
+        if set.locally_mapped_files[&local_path].template {
+            continue;
+        }
+
+        let local_abs_path = local_path.to_absolute_path(profile);
+        let repo_abs_path = set
+            .get_repo_absolute_path_for(&local_path)
+            .expect("Tracked files always have a path in the set.");
+        let secret = set.locally_mapped_files[&local_path].secret;
+        if crate::local_differs_from_set(profile, &local_abs_path, &repo_abs_path, secret)
+            .map_err(|e| StateInitializationError::Comparison(local_abs_path.clone(), e))?
+        {
+            // only modified files can conflict, since otherwise there's nothing to lose by pushing.
+            // secrets are hashed unencrypted, since encrypting the same contents twice gives different files.
+            let repo_hash = |path: &Path| match secret {
+                true => secret::hash_decrypted(path)
+                    .map(Some)
+                    .map_err(|e| FileIndexError::Hash(path.to_path_buf(), e)),
+                false => hash_for_index(path),
+            };
+            if let Some(synced_hash) = synced_hash
+                && hash_for_index(&local_abs_path)?.is_some_and(|h| h != synced_hash)
+                && repo_hash(&repo_abs_path)?.is_some_and(|h| h != synced_hash)
+            {
+                conflicted_files
+                    .entry(set_name.clone())
+                    .or_insert_with(Vec::new)
+                    .push(local_path.clone());
+            }
+
+            modified_files
+                .entry(set_name.clone())
+                .or_insert_with(Vec::new)
+                .push(local_path.clone());
+        }
+
         files_to_push
             .entry(set_name)
             .or_insert_with(Vec::new)
             .push(local_path);
     }
 
+    // the walk's order depends on the file system, so results are sorted to keep output the same between runs
+    for files in files_to_push
+        .values_mut()
+        .chain(modified_files.values_mut())
+        .chain(conflicted_files.values_mut())
+        .chain(files_with_missing_sets.values_mut())
+        .chain(missing_files.values_mut())
+        .chain(std::iter::once(&mut untracked_files))
+    {
+        files.sort_by(|l, r| l.as_ref().cmp(r.as_ref()));
+    }
+
     Ok(LocalState {
         files_to_push,
+        modified_files,
+        conflicted_files,
         files_with_missing_sets,
         missing_files,
         untracked_files,
@@ -85,6 +158,14 @@ impl FilePath {
         self.0.starts_with(&base.0)
     }
 
+    // None for files directly in the local root, since the root itself isn't a local path we manage
+    pub(crate) fn parent(&self) -> Option<Self> {
+        self.0
+            .parent()
+            .filter(|p| !p.as_str().is_empty())
+            .map(|p| FilePath(p.to_relative_path_buf()))
+    }
+
     pub(crate) fn current_location() -> Self {
         FilePath(RelativePathBuf::new())
     }
@@ -142,41 +223,142 @@ pub enum StateInitializationError {
     // instead, am opting for making extra sure we have an accurate picture of local state by failing fast
     #[error("Error when walking local files.")]
     LocalWalk(#[from] LocalWalkError),
+
+    #[error("Unable to compare '{0}' with its copy in the repo.")]
+    Comparison(PathBuf, #[source] std::io::Error),
 }
 
 pub(super) fn walk(
     profile: &MonjaProfile,
 ) -> impl Iterator<Item = Result<FilePath, LocalWalkError>> {
-    let local_root = &profile.local_root;
-    let repo_root = &profile.repo_root;
-    let walker = WalkBuilder::new(local_root)
+    let (global_ignores, global_ignore_error) = match global_ignores(profile) {
+        Ok(global_ignores) => (global_ignores, None),
+        Err(e) => (Gitignore::empty(), Some(LocalWalkError(e.into()))),
+    };
+    let exclusions = Exclusions {
+        profile: profile.clone(),
+        global_ignores,
+    };
+    // .monjaignore files layer like .gitignore files: for a given path, the deepest one with a matching pattern decides,
+    // and within a file, the last matching pattern does. so the one in local_root can't re-include what a deeper one
+    // ignores, but a deeper one can re-include what local_root's ignores. ignored directories aren't descended into,
+    // though, so nothing can re-include files under them.
+    // everything else is up to Exclusions, so it always applies, and its negations only affect each other.
+    let walker = WalkBuilder::new(&profile.local_root)
         .standard_filters(false)
         .add_custom_ignore_filename(".monjaignore")
         .follow_links(false)
         .hidden(false)
+        // ignored directories are pruned, so only the entry itself needs checking
+        .filter_entry(move |e| {
+            let is_dir = e.file_type().is_some_and(|t| t.is_dir());
+            !exclusions.excludes(e.path(), is_dir)
+        })
         .build();
-    walker
+    global_ignore_error
+        .into_iter()
+        .map(Err)
+        .chain(walker.map(|r| r.map_err(|e| LocalWalkError(e.into()))))
         // not returning a Result<Iter, ...> because we we're opting to fail fast on the first walk error.
         // using map_or in this way is the only way I can think of at the moment
-        .filter(|r| r.as_ref().map_or(true, |e| e.path().is_file()))
-        .filter(move |r| {
-            r.as_ref()
-                .map_or(true, |e| !e.path().starts_with(repo_root))
-        })
-        .filter(|r| {
-            r.as_ref()
-                .map_or(true, |e| !crate::is_monja_special_file(e.path()))
-        })
-        .map(move |entry| {
-            // would be convenient to map path out earlier, but that requires a clone
-            // because the path comes from a dropped Entry.
-            let entry = entry.map_err(|e| LocalWalkError(e.into()))?;
-            let path = entry
-                .path()
-                .strip_prefix(local_root)
-                .expect("Should naturally be a prefix.");
-            Ok(FilePath(
-                RelativePathBuf::from_path(path).expect("Generated a relative path."),
-            ))
-        })
+        .filter(|r| r.as_ref().map_or(true, is_trackable))
+        .map(move |r| r.and_then(|e| to_walked_file_path(profile, e.path())))
+}
+
+// the one place that decides which local paths push, status, and clean leave out, since they all see local files
+// through walk. the exception is the .monjaignore files found along the way, which layer per directory,
+// so they're left to the walker.
+struct Exclusions {
+    profile: MonjaProfile,
+    // set ignores and the profile's excludes
+    global_ignores: Gitignore,
+}
+
+impl Exclusions {
+    fn excludes(&self, path: &Path, is_dir: bool) -> bool {
+        self.global_ignores.matched(path, is_dir).is_ignore()
+            || is_monja_owned(&self.profile, path, is_dir)
+    }
+}
+
+// the repo and data roots, and monja's special files, which are never tracked, even when put directly.
+// neither root is necessarily under local_root, in which case they never match.
+// monja's own files in the data root are special files anyway, but anything else there isn't either.
+pub(crate) fn is_monja_owned(profile: &MonjaProfile, path: &Path, is_dir: bool) -> bool {
+    if path.starts_with(&profile.repo_root) || path.starts_with(&profile.data_root) {
+        return true;
+    }
+    if is_dir {
+        let canonical_roots = [
+            profile.repo_root.to_path_buf(),
+            profile.data_root.to_path_buf(),
+        ];
+        return resolves_into(path, &canonical_roots);
+    }
+    path.strip_prefix(&profile.local_root)
+        .is_ok_and(|path| profile.config.is_special_file(FileLocation::Local(path)))
+}
+
+// walked paths should always be under local_root, but a non-UTF-8 file name, for instance, can't be a relative path.
+fn to_walked_file_path(profile: &MonjaProfile, path: &Path) -> Result<FilePath, LocalWalkError> {
+    let relative_path = path.strip_prefix(&profile.local_root).map_err(|_| {
+        LocalWalkError(anyhow::anyhow!(
+            "Walked to '{}', which is not under the local root '{}'.",
+            path.display(),
+            profile.local_root
+        ))
+    })?;
+    let relative_path = RelativePathBuf::from_path(relative_path).map_err(|e| {
+        LocalWalkError(
+            anyhow::Error::new(e).context(format!("Unable to track '{}'.", path.display())),
+        )
+    })?;
+    Ok(FilePath(relative_path))
+}
+
+// a targeted set can have a .monjaignore at its root, which gets combined with the ones found locally.
+// like a .monjaignore placed directly in local_root, patterns are relative to local_root,
+// regardless of the set's shortcut.
+// the profile's excludes come last, so that their negations can re-include what a set's .monjaignore ignores.
+fn global_ignores(profile: &MonjaProfile) -> Result<Gitignore, ignore::Error> {
+    let mut builder = GitignoreBuilder::new(&profile.local_root);
+    for set_name in profile.config.target_sets.iter() {
+        let path = profile.repo_root.join(set_name).join(".monjaignore");
+        if !path.is_file() {
+            continue;
+        }
+        if let Some(e) = builder.add(path) {
+            return Err(e);
+        }
+    }
+    for pattern in profile.config.excludes.iter() {
+        builder.add_line(None, pattern)?;
+    }
+    builder.build()
+}
+
+// since links aren't followed, a symlink shows up as its own entry instead of its target.
+// we track symlinks to regular files, which either get pushed as the target's contents or as links,
+// depending on MonjaProfileConfig::preserve_symlinks.
+// symlinks to directories aren't descended into, and dangling symlinks aren't tracked.
+// the repo and data roots are canonical, but walked paths are only as canonical as local_root, which from_roots takes as-is.
+// so a root reached through a symlinked ancestor is caught by its canonical path instead.
+// directory links themselves aren't followed, so only real directories need checking, and pruning them skips the whole repo.
+fn resolves_into(dir: &Path, canonical_roots: &[PathBuf]) -> bool {
+    let is_under = |dir: &Path| canonical_roots.iter().any(|root| dir.starts_with(root));
+    is_under(dir) || std::fs::canonicalize(dir).is_ok_and(|dir| is_under(&dir))
+}
+
+fn is_trackable(entry: &ignore::DirEntry) -> bool {
+    let Some(file_type) = entry.file_type() else {
+        // only stdin has no file type
+        return false;
+    };
+
+    if file_type.is_symlink() {
+        // is_file follows the link
+        return entry.path().is_file();
+    }
+
+    file_type.is_file()
 }
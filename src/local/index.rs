@@ -3,17 +3,59 @@ use std::{
     fmt::Display,
     fs,
     path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
+use toml::value::{Date, Datetime, Offset, Time};
 
 use crate::{MonjaProfile, local, repo};
 
-#[derive(Serialize, Deserialize)]
+// bump this whenever the format changes, adding a migration to FileIndex::deserialize
+const CURRENT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct FileIndex {
-    #[serde(flatten)]
-    set_mapping: HashMap<local::FilePath, repo::SetName>,
+    version: u32,
+    // when the last successful pull happened, in UTC. added without a version bump, since it's optional.
+    // it has to come before files, since toml values can't follow tables.
+    #[serde(rename = "last-pull", default, skip_serializing_if = "Option::is_none")]
+    last_pull: Option<Datetime>,
+    #[serde(rename = "files")]
+    set_mapping: HashMap<local::FilePath, IndexEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct IndexEntry {
+    pub set: repo::SetName,
+    // the file's contents as of the last time it was synced with the repo (pull, push, put, etc.).
+    // used to tell if both the local file and the repo's file changed since then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+// version 0 had no version field, with files mapped directly at the top level
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IndexEntryV0 {
+    SetOnly(repo::SetName),
+    // briefly written before the index was versioned
+    Full {
+        set: repo::SetName,
+        #[serde(default)]
+        hash: Option<String>,
+    },
+}
+
+impl From<IndexEntryV0> for IndexEntry {
+    fn from(value: IndexEntryV0) -> Self {
+        match value {
+            IndexEntryV0::SetOnly(set) => IndexEntry { set, hash: None },
+            IndexEntryV0::Full { set, hash } => IndexEntry { set, hash },
+        }
+    }
 }
 
 impl FileIndex {
@@ -24,19 +66,50 @@ impl FileIndex {
         let index_path = FileIndex::path(profile, &kind);
 
         if !index_path.exists() {
-            return Ok(FileIndex {
-                set_mapping: HashMap::new(),
-            });
+            return Ok(FileIndex::new());
         }
 
         let index = fs::read(index_path).map_err(|e| FileIndexError::Read(kind.clone(), e))?;
 
-        toml::from_slice(&index).map_err(|e| FileIndexError::Deserialization(kind, e))
+        FileIndex::deserialize(&index, kind)
     }
 
     pub(crate) fn new() -> FileIndex {
         FileIndex {
+            version: CURRENT_VERSION,
             set_mapping: HashMap::new(),
+            last_pull: None,
+        }
+    }
+
+    // older versions get migrated in memory, and the next save writes the current version
+    fn deserialize(contents: &[u8], kind: IndexKind) -> Result<FileIndex, FileIndexError> {
+        let table: toml::Table = toml::from_slice(contents)
+            .map_err(|e| FileIndexError::Deserialization(kind.clone(), e))?;
+
+        // in version 0, a top-level "version" would be a file whose value is a set name or table, not an integer
+        let version = match table.get("version") {
+            Some(toml::Value::Integer(version)) => *version,
+            _ => 0,
+        };
+
+        match version {
+            0 => {
+                let set_mapping: HashMap<local::FilePath, IndexEntryV0> = table
+                    .try_into()
+                    .map_err(|e| FileIndexError::Deserialization(kind, e))?;
+                Ok(FileIndex {
+                    version: CURRENT_VERSION,
+                    set_mapping: set_mapping
+                        .into_iter()
+                        .map(|(file, entry)| (file, entry.into()))
+                        .collect(),
+                    last_pull: None,
+                })
+            }
+            // parsed again instead of converting the table, since the conversion turns datetimes into strings
+            1 => toml::from_slice(contents).map_err(|e| FileIndexError::Deserialization(kind, e)),
+            _ => Err(FileIndexError::UnsupportedVersion(kind, version)),
         }
     }
 
@@ -47,23 +120,97 @@ impl FileIndex {
     ) -> Result<(), FileIndexError> {
         let path = FileIndex::path(profile, &kind);
 
+        // the data directory always exists, but a configured index_dir might not yet
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| FileIndexError::Write(kind.clone(), e))?;
+        }
         fs::write(
             &path,
             toml::to_string(self).map_err(|e| FileIndexError::Serialization(kind.clone(), e))?,
         )
-        .map_err(|e| FileIndexError::Write(IndexKind::Current, e))
+        .map_err(|e| FileIndexError::Write(kind, e))
+    }
+
+    // None if never pulled, or if the timestamp can't be represented, which would take a hand-edited index
+    pub(crate) fn last_pull(&self) -> Option<SystemTime> {
+        self.last_pull.as_ref().and_then(from_datetime)
+    }
+
+    pub(crate) fn set_last_pull(&mut self, time: SystemTime) {
+        self.last_pull = Some(to_datetime(time));
+    }
+
+    // ignores when the last pull happened, which is different for every pull
+    pub(crate) fn has_same_entries(&self, other: &FileIndex) -> bool {
+        self.set_mapping == other.set_mapping
     }
 
     pub(crate) fn tracks(&self, local_file: &local::FilePath) -> bool {
         self.set_mapping.contains_key(local_file)
     }
 
-    pub(crate) fn take(&mut self, local_file: &local::FilePath) -> Option<repo::SetName> {
+    pub(crate) fn get(&self, local_file: &local::FilePath) -> Option<&IndexEntry> {
+        self.set_mapping.get(local_file)
+    }
+
+    pub(crate) fn take(&mut self, local_file: &local::FilePath) -> Option<IndexEntry> {
         self.set_mapping.remove(local_file)
     }
 
-    pub(crate) fn set(&mut self, local_file: local::FilePath, owning_set: repo::SetName) {
-        self.set_mapping.insert(local_file, owning_set);
+    pub(crate) fn set(
+        &mut self,
+        local_file: local::FilePath,
+        owning_set: repo::SetName,
+        hash: Option<String>,
+    ) {
+        self.set_mapping.insert(
+            local_file,
+            IndexEntry {
+                set: owning_set,
+                hash,
+            },
+        );
+    }
+
+    // does nothing if the file isn't in the index
+    pub(crate) fn set_hash(&mut self, local_file: &local::FilePath, hash: Option<String>) {
+        if let Some(entry) = self.set_mapping.get_mut(local_file) {
+            entry.hash = hash;
+        }
+    }
+
+    // returns the files whose owning set was changed
+    pub(crate) fn rename_set(
+        &mut self,
+        old: &repo::SetName,
+        new: &repo::SetName,
+    ) -> Vec<local::FilePath> {
+        let mut renamed = Vec::new();
+        for (file, entry) in self.set_mapping.iter_mut() {
+            if entry.set == *old {
+                entry.set = new.clone();
+                renamed.push(file.clone());
+            }
+        }
+        renamed
+    }
+
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&local::FilePath, &IndexEntry)> {
+        self.set_mapping.iter()
+    }
+
+    pub(crate) fn sets(&self) -> HashSet<&repo::SetName> {
+        self.set_mapping.values().map(|entry| &entry.set).collect()
+    }
+
+    pub(crate) fn files_owned_by<'a>(
+        &'a self,
+        set_name: &'a repo::SetName,
+    ) -> impl Iterator<Item = &'a local::FilePath> {
+        self.set_mapping
+            .iter()
+            .filter(move |(_, entry)| entry.set == *set_name)
+            .map(|(file, _)| file)
     }
 
     pub(crate) fn into_files_not_in(
@@ -87,7 +234,7 @@ impl FileIndex {
 
     // not an AbsolutePath because the index may not exist
     fn path(profile: &MonjaProfile, kind: &IndexKind) -> PathBuf {
-        profile.data_root.join(kind.file_name())
+        profile.index_dir().join(kind.file_name())
     }
 }
 
@@ -112,6 +259,96 @@ impl Display for IndexKind {
     }
 }
 
+// toml has its own datetime type, but no way to convert from SystemTime, so we convert via days since the epoch.
+// see http://howardhinnant.github.io/date_algorithms.html for how days map to dates.
+fn to_datetime(time: SystemTime) -> Datetime {
+    // times before the epoch would take a clock that's very wrong
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let days = (secs / 86400) as i64;
+    let secs_of_day = secs % 86400;
+
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    Datetime {
+        date: Some(Date {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+        }),
+        time: Some(Time {
+            hour: (secs_of_day / 3600) as u8,
+            minute: (secs_of_day % 3600 / 60) as u8,
+            second: (secs_of_day % 60) as u8,
+            nanosecond: since_epoch.subsec_nanos(),
+        }),
+        offset: Some(Offset::Z),
+    }
+}
+
+fn from_datetime(datetime: &Datetime) -> Option<SystemTime> {
+    let (Some(date), Some(time)) = (datetime.date, datetime.time) else {
+        return None;
+    };
+    let offset_minutes = match datetime.offset? {
+        Offset::Z => 0,
+        Offset::Custom { minutes } => minutes as i64,
+    };
+
+    let month = date.month as i64;
+    let year = date.year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * mp + 2) / 5 + date.day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let secs =
+        days * 86400 + time.hour as i64 * 3600 + time.minute as i64 * 60 + time.second as i64
+            - offset_minutes * 60;
+    let secs = u64::try_from(secs).ok()?;
+    Some(UNIX_EPOCH + Duration::new(secs, time.nanosecond))
+}
+
+// symlinks aren't hashed, since whether their target or the link itself gets synced depends on the profile.
+// so they never get considered in conflict detection.
+pub(crate) fn hash_for_index(path: &Path) -> Result<Option<String>, FileIndexError> {
+    if path.is_symlink() {
+        return Ok(None);
+    }
+
+    let hash = hash_contents(path).map_err(|e| FileIndexError::Hash(path.to_path_buf(), e))?;
+    Ok(Some(hash))
+}
+
+// streamed through the hasher, so large files aren't read fully into memory
+pub(crate) fn hash_contents(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+// for contents that don't come straight from a file, like decrypted secrets and rendered templates
+pub(crate) fn hash_bytes(contents: &[u8]) -> String {
+    to_hex(&Sha256::digest(contents))
+}
+
+fn to_hex(hash: &[u8]) -> String {
+    hash.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>()
+}
+
 // while we could get rid of this in favor of using LocalState,
 // it's a lot cheaper to do it this way, since we only need indices instead of both local and repo state.
 pub(crate) fn old_files_since_last_pull(
@@ -148,4 +385,105 @@ pub enum FileIndexError {
 
     #[error("Error when walking local files to find out which are ignored.")]
     LocalWalk(#[from] LocalWalkError),
+
+    #[error("The file index is version {1}, which is newer than this version of monja supports.")]
+    UnsupportedVersion(IndexKind, i64),
+
+    #[error("Unable to hash '{0}' for the file index.")]
+    Hash(PathBuf, #[source] std::io::Error),
+}
+
+// unit testing because old index files can't be made through the public api
+#[cfg(test)]
+mod migration_tests {
+    use googletest::prelude::*;
+
+    use super::{CURRENT_VERSION, FileIndex, FileIndexError, IndexEntry, IndexKind};
+    use crate::{local, repo::SetName};
+
+    fn path(path: &str) -> local::FilePath {
+        std::path::PathBuf::from(path)
+            .try_into()
+            .expect("Test paths are relative.")
+    }
+
+    #[gtest]
+    fn v0_round_trip() -> Result<()> {
+        let v0 = indoc::indoc! {r#"
+            ".bashrc" = "set1"
+            version = "set2"
+
+            [".config/nvim/init.lua"]
+            set = "set1"
+            hash = "abc"
+        "#};
+
+        let index = FileIndex::deserialize(v0.as_bytes(), IndexKind::Current)?;
+        let serialized = toml::to_string(&index)?;
+        let index = FileIndex::deserialize(serialized.as_bytes(), IndexKind::Current)?;
+
+        expect_that!(index.version, eq(CURRENT_VERSION));
+        expect_that!(
+            index.set_mapping,
+            unordered_elements_are![
+                (
+                    eq(&path(".bashrc")),
+                    eq(&IndexEntry {
+                        set: SetName("set1".into()),
+                        hash: None
+                    })
+                ),
+                (
+                    eq(&path("version")),
+                    eq(&IndexEntry {
+                        set: SetName("set2".into()),
+                        hash: None
+                    })
+                ),
+                (
+                    eq(&path(".config/nvim/init.lua")),
+                    eq(&IndexEntry {
+                        set: SetName("set1".into()),
+                        hash: Some("abc".into())
+                    })
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[gtest]
+    fn last_pull_round_trip() -> Result<()> {
+        // 2024-02-29T12:34:56.789Z, to make sure leap days land in the right place
+        let time = std::time::UNIX_EPOCH + std::time::Duration::new(1709210096, 789_000_000);
+        let mut index = FileIndex::new();
+        index.set_last_pull(time);
+
+        let serialized = toml::to_string(&index)?;
+        expect_that!(
+            serialized,
+            contains_substring("last-pull = 2024-02-29T12:34:56.789Z")
+        );
+
+        let index = FileIndex::deserialize(serialized.as_bytes(), IndexKind::Current)?;
+        expect_that!(index.last_pull(), some(eq(time)));
+
+        Ok(())
+    }
+
+    #[gtest]
+    fn newer_version() -> Result<()> {
+        let result = FileIndex::deserialize(b"version = 999\n[files]\n", IndexKind::Current);
+        // FileIndex isn't Debug, so we only look at the error
+        expect_that!(
+            result.err(),
+            some(pat!(FileIndexError::UnsupportedVersion(
+                anything(),
+                eq(&999)
+            )))
+        );
+
+        Ok(())
+    }
 }
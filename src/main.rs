@@ -1,34 +1,112 @@
 // #![deny(exported_private_dependencies)]
 #![deny(clippy::unwrap_used)]
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs,
     io::{BufRead, IsTerminal, Write},
+    os::unix::process::CommandExt,
     path::{Path, PathBuf},
     process::{Command, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
 use monja::{
-    AbsolutePath, CleanMode, ExecutionOptions, InitSpec, LocalFilePath, MonjaProfile, SetName,
+    AbsolutePath, CleanDecision, CleanMode, CleanSuccess, DoctorStatus, ExecutionOptions, FileDiff,
+    FileStatus, HookError, HookRun, InitSpec, LaterSetsPolicy, LocalFilePath, MonjaProfile,
+    ProfileValidationError, PullAction, PullOptions, PushOptions, PutError, PutOptions,
+    RestoreError, SetIndex, SetName,
 };
 
 use anyhow::anyhow;
-use clap::{Args, Parser, Subcommand, command};
+use clap::{Args, Parser, Subcommand, ValueEnum, command};
 use clap_complete::engine::ArgValueCandidates;
 
 mod completions;
 
+// set from --quiet before any command runs
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+// for output that only reports what a command did, which --quiet suppresses.
+// output that's the point of the command, like `monja status` or `--name-only`, uses println directly.
+macro_rules! info_println {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
     // also considering shoving everything except command into a flattened struct, but meh it fine for now
     #[command(flatten)]
-    opts: ExecutionOptions,
+    opts: CliExecutionOptions,
+
+    /// Uses the given directory in place of the home directory, such as to stage files into a container.
+    ///
+    /// Paths passed to commands are still relative to cwd, which then needs to be under this directory,
+    /// unless `--nocwd` is used. A relative `repo-dir` in the profile is still relative to the home directory.
+    #[arg(long, global = true)]
+    local_root: Option<PathBuf>,
+
+    /// Uses the given repo in place of the profile's `repo-dir`, such as to work with a second clone of it.
+    ///
+    /// A relative path is relative to the local root, like the profile's `repo-dir`. The profile itself is left alone.
+    #[arg(long, global = true)]
+    repo_dir: Option<PathBuf>,
 
     #[command(subcommand)]
     command: Commands,
 }
 
+// monja's ExecutionOptions, but with clap's attributes, since the library doesn't depend on clap
+#[derive(Args)]
+#[group(multiple = true)]
+struct CliExecutionOptions {
+    /// Enables more verbose logging.
+    #[arg(short, long = "verbose", action = clap::ArgAction::Count)]
+    verbosity: u8,
+
+    /// Only prints errors, for use in scripts and cron jobs.
+    ///
+    /// Output that's the point of a command, like that of `monja status` or `--name-only`, is still printed.
+    #[arg(short, long, conflicts_with = "verbosity")]
+    quiet: bool,
+
+    /// No disk operations will be performed.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Skip confirmations
+    #[arg(long = "yes", short = 'y')]
+    skip_confirmations: bool,
+
+    /// When pulling, first back up local files that would be overwritten with different contents.
+    /// When putting, first back up the set's copies of the files being put.
+    ///
+    /// Backups go in a timestamped directory under `monja-backup` (or `put-backups`) in the data directory.
+    #[arg(long)]
+    backup: bool,
+
+    /// Lets `monja clean` remove more files than the profile's `clean-threshold` (100 by default).
+    #[arg(long)]
+    force: bool,
+}
+
+impl From<CliExecutionOptions> for ExecutionOptions {
+    fn from(value: CliExecutionOptions) -> Self {
+        ExecutionOptions {
+            verbosity: value.verbosity,
+            quiet: value.quiet,
+            dry_run: value.dry_run,
+            skip_confirmations: value.skip_confirmations,
+            backup: value.backup,
+            force: value.force,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 #[command(rename_all = "lower")]
 enum Commands {
@@ -84,9 +162,16 @@ enum Commands {
     /// The destination set must be able to support each file (e.g. shortcut compatibility).
     ///
     /// Note that this command ignores `.monjaignore` files.
-    #[command(name = "transfer")]
+    #[command(name = "transfer", visible_alias = "move")]
     Transfer(TransferCommand),
 
+    /// Copies files from a specific set in the repo locally.
+    ///
+    /// Unlike `monja pull`, set layering is ignored, so the set's version of the file is used
+    /// even if a later set in the profile also has the file.
+    /// Only the specified files are copied, and the local index is left untouched.
+    Restore(RestoreCommand),
+
     /// Changes a set's shortcut path.
     ///
     /// The shortcut determines the common prefix stripped from local paths when storing files in the set.
@@ -98,6 +183,36 @@ enum Commands {
     /// Note that this command ignores `.monjaignore` files.
     NewSet(NewSetCommand),
 
+    /// Removes a set from the repo, as well as from the profile.
+    ///
+    /// Fails if local files were last pulled from the set, unless `--force` is specified.
+    RemoveSet(RemoveSetCommand),
+
+    /// Renames a set, updating the profile and the local index to use the new name.
+    RenameSet(RenameSetCommand),
+
+    /// Lists the sets in the repo.
+    ///
+    /// Sets targeted by the profile are listed first, in profile order, followed by the rest alphabetically.
+    ListSets(ListSetsCommand),
+
+    /// Lists the files a set would pull, and whether each would overwrite an existing local file.
+    ///
+    /// Handy before adding a set to the profile. Only the given set is considered, so a later set in the profile
+    /// may still win some of these files. Nothing is modified.
+    PreviewSet(PreviewSetCommand),
+
+    /// Opens the repo's copy of a local file in `$EDITOR`.
+    ///
+    /// The file's set is the one it was last pulled from or pushed to.
+    Edit(EditCommand),
+
+    /// Prints the set a local file comes from, after layering the profile's sets.
+    ///
+    /// Unlike `edit`, this goes by the repo's current contents instead of the last pull,
+    /// so it's the set the file would be pulled from now.
+    Which(WhichCommand),
+
     /// Prints detailed local status information.
     ///
     /// This command prints a few kinds of useful information, which can be filtered by additional args.
@@ -105,6 +220,25 @@ enum Commands {
     #[command(id = "status")]
     LocalStatus(StatusCommand),
 
+    /// Checks for common problems with the profile, repo, and local state.
+    ///
+    /// Nothing is modified. Exits unsuccessfully if any check fails.
+    Doctor(DoctorCommand),
+
+    /// Checks that every previously pulled or pushed file still matches its set's copy in the repo.
+    ///
+    /// Unlike `status`, this isn't about what can be pushed, and nothing is modified.
+    /// Exits unsuccessfully if any file is out of sync.
+    Verify(VerifyCommand),
+
+    /// Removes file index entries for files that are gone both locally and from their set in the repo.
+    ///
+    /// Only the index is modified. Use `monja clean` to remove local files.
+    PruneIndex(PruneIndexCommand),
+
+    /// Shows the most recent pulls, pushes, puts, cleans, and new sets on this machine, newest first.
+    Log(LogCommand),
+
     /// Prints the repo's directory so that it can be piped into `cd`.
     RepoDir(RepoDirCommand),
 
@@ -127,9 +261,20 @@ impl Commands {
             Commands::Clean(command) => command.execute(profile, opts),
             Commands::Put(command) => command.execute(profile, opts),
             Commands::Transfer(command) => command.execute(profile, opts),
+            Commands::Restore(command) => command.execute(profile, opts),
             Commands::SetShortcut(command) => command.execute(profile, opts),
             Commands::NewSet(command) => command.execute(profile, opts),
+            Commands::RemoveSet(command) => command.execute(profile, opts),
+            Commands::RenameSet(command) => command.execute(profile, opts),
+            Commands::ListSets(command) => command.execute(profile, opts),
+            Commands::PreviewSet(command) => command.execute(profile, opts),
+            Commands::Edit(command) => command.execute(profile, opts),
+            Commands::Which(command) => command.execute(profile, opts),
             Commands::LocalStatus(command) => command.execute(profile, opts),
+            Commands::Doctor(command) => command.execute(profile, opts),
+            Commands::Verify(command) => command.execute(profile, opts),
+            Commands::PruneIndex(command) => command.execute(profile, opts),
+            Commands::Log(command) => command.execute(profile, opts),
             Commands::RepoDir(command) => command.execute(profile, opts),
             Commands::Profile(command) => command.execute(profile, opts),
             Commands::Completions(command) => command.execute(),
@@ -138,7 +283,13 @@ impl Commands {
 }
 
 #[derive(Args)]
-struct InitCommand {}
+struct InitCommand {
+    /// Overwrites an existing profile, such as to fix a corrupted one.
+    ///
+    /// The initial set is created if it's missing. If it already exists, its contents are left alone.
+    #[arg(long)]
+    force: bool,
+}
 impl InitCommand {
     fn execute(
         &self,
@@ -150,10 +301,6 @@ impl InitCommand {
     ) -> anyhow::Result<()> {
         let repo_root = base.create_data_directory("repo")?;
         let repo_root = AbsolutePath::for_existing_path(&repo_root)?;
-        let relative_repo_root = repo_root
-            .strip_prefix(&local_root)
-            .expect("Should naturally be a prefix")
-            .to_path_buf();
 
         let machine = fs::read_to_string("/proc/sys/kernel/hostname")
             .expect("If doesn't exist, would prefer panic.")
@@ -165,25 +312,48 @@ impl InitCommand {
             local_root,
             repo_root,
             data_root,
-            relative_repo_root,
-            initial_set_name: machine,
+            initial_set_name: machine.clone(),
+            force: self.force,
         };
         let result = monja::init(&opts, spec)?;
 
         match result.profile {
             Some(profile) => {
-                println!("Initialization successful!");
-                println!(
-                    "Profile can be found at '{}'.",
-                    result.profile_config_path.display()
-                );
-                println!("Repo can be found in '{}'.", profile.repo_root);
-                println!(
-                    "Set '{}' automatically created.",
-                    profile.config.target_sets[0]
-                );
+                info_println!("Initialization successful!");
+                match result.profile_overwritten {
+                    true => info_println!(
+                        "Existing profile at '{}' was overwritten.",
+                        result.profile_config_path.display()
+                    ),
+                    false => info_println!(
+                        "Profile can be found at '{}'.",
+                        result.profile_config_path.display()
+                    ),
+                }
+                info_println!("Repo can be found in '{}'.", profile.repo_root);
+                match result.set_created {
+                    true => info_println!(
+                        "Set '{}' automatically created.",
+                        profile.config.target_sets[0]
+                    ),
+                    false => info_println!(
+                        "Set '{}' already exists, so it was left alone.",
+                        profile.config.target_sets[0]
+                    ),
+                }
+            }
+            None => {
+                if result.profile_overwritten {
+                    info_println!(
+                        "Would overwrite the existing profile at '{}'.",
+                        result.profile_config_path.display()
+                    );
+                }
+                if result.set_created {
+                    info_println!("Would create set '{}'.", machine);
+                }
+                info_println!("No changed made because dry-run.");
             }
-            None => println!("No changed made because dry-run."),
         };
 
         Ok(())
@@ -191,10 +361,26 @@ impl InitCommand {
 }
 
 #[derive(Args)]
-struct PushCommand {}
+struct PushCommand {
+    /// Only pushes files from the given set, leaving other sets' changes for a later push.
+    #[arg(long, add = ArgValueCandidates::new(completions::set_names))]
+    only: Option<SetName>,
+
+    /// Only pushes files that `git diff` reports as changed in the repo since the given ref, such as `HEAD`.
+    #[arg(long)]
+    since: Option<String>,
+
+    /// Only prints the local paths of the files pushed, including unchanged ones, one per line, for piping into other tools.
+    #[arg(long)]
+    name_only: bool,
+}
 impl PushCommand {
-    fn execute(&self, profile: MonjaProfile, opts: ExecutionOptions) -> anyhow::Result<()> {
-        let result = monja::push(&profile, &opts);
+    fn execute(self, profile: MonjaProfile, opts: ExecutionOptions) -> anyhow::Result<()> {
+        let push_opts = PushOptions {
+            only: self.only,
+            since: self.since,
+        };
+        let result = monja::push(&profile, &opts, &push_opts);
 
         // want better logging for this
         if let Err(monja::PushError::Consistency {
@@ -247,7 +433,7 @@ impl PushCommand {
                     "\t* If the files should use a different set (such as the last specified in monja-profile.toml), "
                 );
                 eprint!(
-                    "use some variation of `monja put` to specify that set and copy files to that set. "
+                    "use some variation of `monja put` (like `monja put --set-index last`) to copy files to that set. "
                 );
                 eprintln!("Then, use `monja push` to push the rest of the files to the right set.");
 
@@ -263,32 +449,123 @@ impl PushCommand {
             return Err(anyhow::Error::msg("Failed to push."));
         }
 
+        if let Err(monja::PushError::Conflicts(conflicted_files)) = result {
+            eprintln!(
+                "These files changed both locally and in the repo since they were last pulled or pushed:"
+            );
+            for (set_name, file_paths) in conflicted_files {
+                eprintln!("\tSet: {}", set_name);
+                for path in file_paths {
+                    eprintln!("\t\t{}", path);
+                }
+            }
+            eprintln!(
+                "Pushing would overwrite the repo's changes. Merge the repo's changes into the local files, \
+                 or back up the local files and `monja pull`."
+            );
+            return Err(anyhow::Error::msg("Failed to push."));
+        }
+
         // log rest of errors like this because lazy
         let result = result?;
 
+        if self.name_only {
+            print_names(
+                result
+                    .files_pushed
+                    .iter()
+                    .flat_map(|(_, file_paths)| file_paths)
+                    .map(LocalFilePath::as_relative_str),
+            );
+            return report_hooks(Vec::new(), result.post_hook_error);
+        }
+
         if !result.files_pushed.is_empty() {
-            println!(
+            info_println!(
                 "Files pushed (including unchanged), as grouped under their corresponding sets:"
             );
             for (set_name, file_paths) in result.files_pushed.iter() {
-                eprintln!("\tSet: {}", set_name);
+                info_println!("\tSet: {}", set_name);
                 for path in file_paths {
-                    eprintln!("\t\t{}", path);
+                    info_println!("\t\t{}", path);
                 }
             }
         } else {
-            println!("No files pushed.");
+            info_println!("No files pushed.");
         }
 
-        Ok(())
+        if !result.files_modified.is_empty() {
+            info_println!(
+                "Files whose changes were pushed, as grouped under their corresponding sets:"
+            );
+            for (set_name, file_paths) in result.files_modified.iter() {
+                info_println!("\tSet: {}", set_name);
+                for path in file_paths {
+                    info_println!("\t\t{}", path);
+                }
+            }
+        }
+
+        report_hooks(result.hooks_run, result.post_hook_error)
     }
 }
 
 #[derive(Args)]
-struct PullCommand {}
+struct PullCommand {
+    /// Skips files whose local paths match the glob, leaving them out of this pull.
+    ///
+    /// Skipped files are also left out of the file index, as if the sets didn't have them.
+    /// May be specified multiple times.
+    #[arg(long)]
+    skip: Vec<String>,
+
+    /// After pulling, deletes local files that are no longer pulled from the repo, like `monja clean` would.
+    ///
+    /// Files covered by `.monjaignore` are never deleted.
+    #[arg(long)]
+    delete: bool,
+
+    /// Ends the output with a one-line summary of how many files were pulled.
+    #[arg(long)]
+    stat: bool,
+
+    /// Pulls from these comma-separated sets instead of the profile's target sets, without changing monja-profile.toml.
+    ///
+    /// Like the profile's, later sets take precedence over earlier ones.
+    #[arg(long, value_delimiter = ',', add = ArgValueCandidates::new(completions::set_names))]
+    sets: Option<Vec<SetName>>,
+
+    /// Skips targeted sets that are missing from the repo, instead of failing, and pulls the rest.
+    #[arg(long)]
+    force: bool,
+
+    /// Pulls every file that can be pulled, instead of failing the whole set when rsync fails on some of them.
+    ///
+    /// Files that failed are reported and keep their previous file index entries, so pull again once the issue is fixed.
+    #[arg(long)]
+    best_effort: bool,
+
+    /// Also pulls sets with a `dest-root` in their `.monja-set.toml`, which write outside of the home directory.
+    #[arg(long)]
+    allow_dest_roots: bool,
+
+    /// Only prints the local paths of the files created or overwritten, one per line, for piping into other tools.
+    #[arg(long, conflicts_with = "stat")]
+    name_only: bool,
+}
 impl PullCommand {
-    fn execute(&self, profile: MonjaProfile, opts: ExecutionOptions) -> anyhow::Result<()> {
-        let result = monja::pull(&profile, &opts);
+    fn execute(self, profile: MonjaProfile, opts: ExecutionOptions) -> anyhow::Result<()> {
+        warn_if_sets_overridden(&profile, self.sets.as_deref());
+        let pull_opts = PullOptions {
+            skip: self.skip,
+            progress: None,
+            delete: self.delete,
+            sets: self.sets,
+            allow_missing: self.force,
+            best_effort: self.best_effort,
+            allow_dest_roots: self.allow_dest_roots,
+        };
+        let result = monja::pull(&profile, &opts, &pull_opts);
 
         if let Err(monja::PullError::MissingSets(missing_sets)) = result {
             eprintln!(
@@ -296,43 +573,197 @@ impl PullCommand {
                 missing_sets
             );
             eprintln!("Verify that the right set of sets in 'monja-profile.toml' are present.");
+            eprintln!("To pull the sets that are present anyway, use `--force`.");
             // probably something better to use, but we don't want to double log with the below `result?`.
             return Err(anyhow::Error::msg("Failed to pull."));
         }
 
+        if let Err(monja::PullError::DestRootNotAllowed(sets)) = result {
+            eprintln!(
+                "These sets are pulled outside of the home directory, according to their `dest-root`: {:?}",
+                sets
+            );
+            eprintln!("To pull them anyway, use `--allow-dest-roots`.");
+            return Err(anyhow::Error::msg("Failed to pull."));
+        }
+
+        if let Err(monja::PullError::Rsync(failures)) = result {
+            for (set_name, error) in failures {
+                eprintln!("Failed to pull set '{}': {}", set_name, error);
+            }
+            eprintln!(
+                "Local files and the file index were left as they were, so pull again once the issue is fixed."
+            );
+            return Err(anyhow::Error::msg("Failed to pull."));
+        }
+
         let result = result?;
 
-        if !result.files_pulled.is_empty() {
-            println!(
-                "Files pulled (including unchanged), as grouped under their corresponding sets:"
+        if !result.skipped_sets.is_empty() {
+            log::warn!(
+                "Sets missing from the repo were skipped: {:?}",
+                result.skipped_sets
             );
-            for (set_name, file_paths) in result.files_pulled.into_iter() {
-                println!("\tSet: {}", set_name);
-                for path in file_paths {
-                    println!(
-                        "\t\t'{}' -> '{}'",
-                        path.path_in_set.display(),
-                        path.local_path.display()
+        }
+        if result.repo_dirty == Some(true) {
+            log::warn!(
+                "The repo has uncommitted changes. Consider committing them before pushing, so that it's clear what came from where."
+            );
+        }
+        if !result.failed_files.is_empty() {
+            eprintln!("These files couldn't be pulled:");
+            for (path, error) in result.failed_files.iter() {
+                eprintln!("\t{} ({})", path.local_path_as_relative_str(), error);
+            }
+        }
+
+        if self.name_only {
+            print_names(
+                result
+                    .files_pulled
+                    .iter()
+                    .flat_map(|(_, file_paths)| file_paths)
+                    .filter(|(_, action)| *action != PullAction::Unchanged)
+                    .map(|(path, _)| path.local_path_as_relative_str()),
+            );
+            return report_hooks(Vec::new(), result.post_hook_error);
+        }
+
+        let unchanged_count = result
+            .files_pulled
+            .iter()
+            .flat_map(|(_, file_paths)| file_paths)
+            .filter(|(_, action)| *action == PullAction::Unchanged)
+            .count();
+        let changed_count = result
+            .files_pulled
+            .iter()
+            .flat_map(|(_, file_paths)| file_paths)
+            .count()
+            - unchanged_count;
+        let cleanable_count = result.cleanable_files.len();
+        let changed_files: Vec<_> = result
+            .files_pulled
+            .into_iter()
+            .map(|(set_name, file_paths)| {
+                let file_paths: Vec<_> = file_paths
+                    .into_iter()
+                    .filter(|(_, action)| *action != PullAction::Unchanged)
+                    .collect();
+                (set_name, file_paths)
+            })
+            .filter(|(_, file_paths)| !file_paths.is_empty())
+            .collect();
+
+        if !changed_files.is_empty() {
+            info_println!("Files pulled, as grouped under their corresponding sets:");
+            for (set_name, file_paths) in changed_files.into_iter() {
+                info_println!("\tSet: {}", set_name);
+                for (path, action) in file_paths {
+                    let action = match action {
+                        PullAction::Created => "created",
+                        _ => "overwritten",
+                    };
+                    info_println!(
+                        "\t\t'{}' -> '{}' ({})",
+                        path.path_in_set_as_relative_str(),
+                        path.local_path_as_relative_str(),
+                        action
                     );
                 }
             }
         } else {
-            println!("No files pulled.");
+            info_println!("No files changed.");
+        }
+        if unchanged_count > 0 {
+            info_println!("{} files were already up to date.", unchanged_count);
+        }
+
+        if let Some(backup_dir) = result.backup_dir {
+            info_println!(
+                "Local files that differed from the repo were backed up to '{}':",
+                backup_dir.display()
+            );
+            for file_path in result.files_backed_up.into_iter() {
+                info_println!("\t{}", file_path);
+            }
+        }
+
+        if !result.skipped_files.is_empty() {
+            info_println!("Files skipped:");
+            for file_path in result.skipped_files.into_iter() {
+                info_println!("\t{}", file_path);
+            }
+        }
+
+        for (set_name, file_paths) in result.dest_root_files.into_iter() {
+            info_println!(
+                "Files pulled outside of the home directory from set '{}':",
+                set_name
+            );
+            for file_path in file_paths.into_iter() {
+                info_println!("\t{}", file_path.display());
+            }
+        }
+
+        if !result.redundant_overrides.is_empty() {
+            info_println!("Files overridden by a later set with identical contents:");
+            info_println!("Consider keeping each of these in only one of the sets.");
+            for (file_path, overridden_set, overriding_set) in
+                result.redundant_overrides.into_iter()
+            {
+                info_println!(
+                    "\t{} ('{}' -> '{}')",
+                    file_path,
+                    overridden_set,
+                    overriding_set
+                );
+            }
+        }
+
+        if !result.permissions_adjusted.is_empty() {
+            info_println!("Permissions adjusted to match set configs:");
+            for file_path in result.permissions_adjusted.into_iter() {
+                info_println!("\t{}", file_path);
+            }
         }
 
         if !result.cleanable_files.is_empty() {
-            println!("There are files present locally that are no longer pulled from the repo.");
-            println!("If this is expected, do a `monja clean` to remove them.");
-            println!(
+            info_println!(
+                "There are files present locally that are no longer pulled from the repo."
+            );
+            info_println!("If this is expected, do a `monja clean` to remove them.");
+            info_println!(
                 "If any are unexpected, copy them to a new set before performing `monja clean`."
             );
 
             for file_path in result.cleanable_files.into_iter() {
-                println!("\t{}", file_path);
+                info_println!("\t{}", file_path);
             }
         }
 
-        Ok(())
+        let deleted_count = result.deleted_files.len();
+        if !result.deleted_files.is_empty() {
+            info_println!("Deleted files that are no longer pulled from the repo:");
+            for file_path in result.deleted_files.into_iter() {
+                info_println!("\t{}", file_path);
+            }
+        }
+
+        if self.stat {
+            // nothing gets transferred in a dry run, so there are no bytes to speak of
+            let bytes = match (opts.dry_run, result.transfer_stats.bytes_transferred) {
+                (false, Some(bytes)) => format!(" ({} bytes)", bytes),
+                _ => String::new(),
+            };
+            let cleanable = match self.delete {
+                true => format!("{} deleted", deleted_count),
+                false => format!("{} cleanable", cleanable_count),
+            };
+            info_println!("{} files pulled{}, {}", changed_count, bytes, cleanable);
+        }
+
+        report_hooks(result.hooks_run, result.post_hook_error)
     }
 }
 
@@ -343,33 +774,137 @@ struct CleanCommand {
     /// If not set, the previous two `monja pull`s are used to determine which files to clean.
     #[arg(long, short)]
     full: bool,
+
+    /// Leaves files under this path alone, even if they're not tracked in the repo. Can be repeated.
+    ///
+    /// Only used with `--full`.
+    #[arg(long = "exclude", requires = "full")]
+    exclude: Vec<PathBuf>,
+
+    /// Ends the output with a one-line summary of how many files were cleaned.
+    #[arg(long)]
+    stat: bool,
+
+    /// Asks about each file before removing it, instead of confirming them all at once.
+    ///
+    /// Answer `y` to remove the file, `n` to keep it, or `q` to cancel the clean without removing anything.
+    #[arg(long, short)]
+    interactive: bool,
+
+    /// Only prints the local paths of the files cleaned, one per line, for piping into other tools.
+    ///
+    /// Combine with `--yes` to keep the confirmation out of the output.
+    #[arg(long, conflicts_with = "stat")]
+    name_only: bool,
 }
 impl CleanCommand {
     fn execute(&self, profile: MonjaProfile, opts: ExecutionOptions) -> anyhow::Result<()> {
         let mode = match self.full {
-            true => CleanMode::Full,
+            true => CleanMode::Full {
+                exclude: to_local_paths(&profile, &self.exclude, &std::env::current_dir()?)?,
+            },
             false => CleanMode::Index,
         };
-        let clean_result = monja::clean(&profile, &opts, mode)?;
+        let confirm = |planned: &CleanSuccess| {
+            let mut message = String::from("These files will be removed locally:\n");
+            for path in planned.files_cleaned.iter() {
+                message.push_str(&format!("\t{}\n", path));
+            }
+            if !planned.dirs_pruned.is_empty() {
+                message.push_str("Along with these directories, which will be left empty:\n");
+                for path in planned.dirs_pruned.iter() {
+                    message.push_str(&format!("\t{}\n", path));
+                }
+            }
+            opts.user_confirm(&message)
+        };
+        let clean_result = match self.interactive {
+            true => monja::clean_interactive(&profile, &opts, mode, &mut prompt_clean_decision)?,
+            false => monja::clean(&profile, &opts, mode, &confirm)?,
+        };
+        let cleaned_count = clean_result.files_cleaned.len();
+
+        if self.name_only {
+            print_names(
+                clean_result
+                    .files_cleaned
+                    .iter()
+                    .map(LocalFilePath::as_relative_str),
+            );
+            return Ok(());
+        }
 
         if !clean_result.files_cleaned.is_empty() {
-            println!("Local files cleaned:");
+            info_println!("Local files cleaned:");
             for path in clean_result.files_cleaned.into_iter() {
-                println!("{}", path);
+                info_println!("{}", path);
             }
         } else {
-            println!("No local files cleaned.")
+            info_println!("No local files cleaned.")
+        }
+
+        if !clean_result.dirs_pruned.is_empty() {
+            info_println!("Empty directories removed:");
+            for path in clean_result.dirs_pruned.into_iter() {
+                info_println!("{}", path);
+            }
+        }
+
+        if !clean_result.protected.is_empty() {
+            info_println!("Excluded from cleaning:");
+            for path in clean_result.protected.into_iter() {
+                info_println!("{}", path);
+            }
+        }
+
+        if self.stat {
+            info_println!("{} files cleaned", cleaned_count);
         }
 
         Ok(())
     }
 }
 
+fn prompt_clean_decision(path: &LocalFilePath) -> CleanDecision {
+    loop {
+        print!("Remove '{}'? [y/n/q] ", path);
+        let _ = std::io::stdout().flush();
+
+        let mut answer = String::new();
+        // a closed stdin shouldn't remove anything
+        if std::io::stdin().read_line(&mut answer).unwrap_or(0) == 0 {
+            return CleanDecision::Quit;
+        }
+        match answer.trim() {
+            "y" | "Y" => return CleanDecision::Remove,
+            "n" | "N" => return CleanDecision::Keep,
+            "q" | "Q" => return CleanDecision::Quit,
+            _ => continue,
+        }
+    }
+}
+
 #[derive(Args)]
 struct PutCommand {
     /// The set into which the files will be copied
-    #[arg(long = "set", add = ArgValueCandidates::new(completions::set_names))]
-    owning_set: String,
+    #[arg(
+        long = "set",
+        required_unless_present_any = ["set_index", "from_index"],
+        add = ArgValueCandidates::new(completions::set_names)
+    )]
+    owning_set: Option<SetName>,
+
+    /// Instead of naming the set, picks it by its position in the profile's target sets.
+    ///
+    /// Either `last` or a position starting from 1, in the order the sets are listed in monja-profile.toml.
+    #[arg(long, conflicts_with = "owning_set")]
+    set_index: Option<SetIndex>,
+
+    /// Instead of using a single set, puts each file back into the set it was last pulled from or pushed to.
+    ///
+    /// Fails if any file isn't in the index. Handy for restoring files that were accidentally deleted from the repo.
+    #[arg(long, conflicts_with_all = ["owning_set", "set_index"])]
+    from_index: bool,
 
     /// If set, the paths provided will be relative to the local root, ignoring cwd.
     ///
@@ -381,9 +916,24 @@ struct PutCommand {
     #[arg(long, short)]
     interactive: bool,
 
-    /// The local files to copy.
+    /// Allows files outside of the set's shortcut, which are stored in the set relative to the local root instead.
+    ///
+    /// Each such file's directory gets a `.monja-dir.toml` with its own shortcut, so `monja pull` puts it back in the same place.
+    /// The directory must not already exist in the set.
+    #[arg(long)]
+    outside_shortcut: bool,
+
+    /// What to do with files that are also in a later targeted set, whose copy `monja pull` would use instead.
+    ///
+    /// `warn` puts them anyway and lists them, `error` fails without putting anything,
+    /// and `promote` puts them into the last targeted set that has them instead.
+    #[arg(long, default_value = "warn")]
+    later_sets: LaterSetsPolicy,
+
+    /// The local files to copy. Directories are copied recursively.
     ///
     /// These will be combined with any newline-delimited files provided through stdin.
+    /// Passing `-` reads from stdin even if it's a terminal.
     /// These will also be combined with files provided via `--interactive`.
     ///
     /// A limit of 100 paths may be passed through stdin to prevent accidental mass copying.
@@ -397,11 +947,14 @@ impl PutCommand {
             true => &profile.local_root,
             false => &AbsolutePath::for_existing_path(&std::env::current_dir()?)?,
         };
-        let owning_set = SetName(self.owning_set);
+        let owning_set = match self.set_index {
+            Some(index) => Some(monja::resolve_set_index(&profile, index)?),
+            None => self.owning_set,
+        };
 
         let mut files = to_local_paths(&profile, &self.files, cwd)?;
 
-        let mut stdin_files = read_paths_from_stdin(&profile, cwd)?;
+        let mut stdin_files = read_paths_from_stdin(&profile, &self.files, cwd)?;
         files.append(&mut stdin_files);
 
         if self.interactive {
@@ -426,7 +979,7 @@ impl PutCommand {
                     status
                         .files_to_push
                         .into_iter()
-                        .filter(|(set_name, _)| set_name != &owning_set)
+                        .filter(|(set_name, _)| Some(set_name) != owning_set.as_ref())
                         .flat_map(|(_, files)| files),
                 )
                 .chain(status.untracked_files);
@@ -439,47 +992,140 @@ impl PutCommand {
 
         if files.is_empty() {
             // could consider it an error, but it's not a big deal that the user didn't provide anything
-            println!("No files selected.");
+            info_println!("No files selected.");
             return Ok(());
         }
 
-        let result = monja::put(&profile, &opts, files, owning_set)?;
+        let put_opts = PutOptions {
+            outside_shortcut: self.outside_shortcut,
+            later_sets: self.later_sets,
+        };
+        let result = match monja::put(&profile, &opts, files, owning_set, &put_opts) {
+            Ok(result) => result,
+            Err(PutError::InLaterSets(files)) => {
+                eprintln!(
+                    "These files are also in later sets, so `monja pull` would use those sets' copies instead:"
+                );
+                for (path, set_names) in files.into_iter() {
+                    eprintln!("\t{}", path);
+                    for set_name in set_names.into_iter() {
+                        eprintln!("\t\t{}", set_name);
+                    }
+                }
+                eprintln!(
+                    "To put them into the last of those sets instead, use `--later-sets promote`."
+                );
+                return Err(anyhow::Error::msg("Failed to put files."));
+            }
+            Err(e) => return Err(e.into()),
+        };
 
-        println!(
-            "Successfully changed the following files to use set `{}` (including copying them to the set):",
-            result.owning_set
-        );
-        for file in result.files.into_iter() {
-            println!("\t{}", file);
-        }
+        let dest = match &result.owning_set {
+            Some(owning_set) => {
+                info_println!(
+                    "Successfully changed the following files to use set `{}` (including copying them to the set):",
+                    owning_set
+                );
+                for file in result
+                    .files
+                    .into_iter()
+                    .filter(|f| !result.files_promoted.iter().any(|(p, _)| p == f))
+                {
+                    info_println!("\t{}", file);
+                }
+                format!("set `{}`", owning_set)
+            }
+            None => {
+                info_println!(
+                    "Successfully put the following files back into the sets they were last synced with:"
+                );
+                for (set_name, files) in result.files_by_set.iter() {
+                    info_println!("\tSet: {}", set_name);
+                    for file in files.iter() {
+                        info_println!("\t\t{}", file);
+                    }
+                }
+                "their sets".to_string()
+            }
+        };
 
         if !result.set_is_targeted {
-            println!(
-                "Note that set `{}` isn't targeted by the current profile, so it will not be eligible to be copied by `monja pull`.",
-                result.owning_set
+            let untargeted = match &result.owning_set {
+                Some(_) => format!("{} isn't", dest),
+                None => "some of their sets aren't".to_string(),
+            };
+            info_println!(
+                "Note that {} targeted by the current profile, so it will not be eligible to be copied by `monja pull`.",
+                untargeted
             );
         }
 
         if !result.files_in_later_sets.is_empty() {
-            println!(
-                "There were some files put into set `{0}` that, because they are also in sets later than `{0}`, wouldn't be copied by `monja pull`.",
-                result.owning_set
+            info_println!(
+                "There were some files put into {} that, because they are also in later sets, wouldn't be copied by `monja pull`.",
+                dest
             );
             for (path, set_names) in result.files_in_later_sets.into_iter() {
-                println!("\t{}", path);
+                info_println!("\t{}", path);
                 for set_name in set_names.into_iter() {
-                    println!("\t\t{}", set_name);
+                    info_println!("\t\t{}", set_name);
                 }
             }
         }
 
+        if !result.files_promoted.is_empty() {
+            info_println!(
+                "These files were put into the last targeted set that has them instead, so that `monja pull` uses them:"
+            );
+            for (path, set_name) in result.files_promoted.into_iter() {
+                info_println!("\t{} -> {}", path, set_name);
+            }
+        }
+
         if !result.untracked_files.is_empty() {
-            println!(
-                "There were some files put into set `{}` that aren't in any of the sets used by the current profile.",
-                result.owning_set
+            info_println!(
+                "There were some files put into {} that aren't in any of the sets used by the current profile.",
+                dest
             );
             for file in result.untracked_files.into_iter() {
-                println!("\t{}", file);
+                info_println!("\t{}", file);
+            }
+        }
+
+        if !result.files_outside_shortcut.is_empty() {
+            info_println!(
+                "The following files were outside of the shortcut of {}, so their directories were given their own:",
+                dest
+            );
+            for file in result.files_outside_shortcut.into_iter() {
+                info_println!("\t{}", file);
+            }
+        }
+
+        if !result.modes_recorded.is_empty() {
+            info_println!(
+                "The permissions of these executable files were recorded, so pulling keeps them executable:"
+            );
+            for file in result.modes_recorded.into_iter() {
+                info_println!("\t{}", file);
+            }
+        }
+
+        if let Some(backup_dir) = result.backup_dir {
+            info_println!(
+                "The set's previous copies were backed up to '{}':",
+                backup_dir.display()
+            );
+            for file in result.files_backed_up.into_iter() {
+                info_println!("\t{}", file);
+            }
+        } else {
+            let overwritten_count = result.overwritten.iter().filter(|(_, o)| *o).count();
+            if overwritten_count > 0 {
+                info_println!(
+                    "{} files replaced their existing copies in the set. Use --backup to keep the old copies.",
+                    overwritten_count
+                );
             }
         }
 
@@ -491,11 +1137,11 @@ impl PutCommand {
 struct TransferCommand {
     /// The set to move files from
     #[arg(long = "from", add = ArgValueCandidates::new(completions::set_names))]
-    source_set: String,
+    source_set: SetName,
 
     /// The set to move files to
     #[arg(long = "to", add = ArgValueCandidates::new(completions::set_names))]
-    dest_set: String,
+    dest_set: SetName,
 
     /// If set, the paths provided will be relative to the local root, ignoring cwd.
     ///
@@ -510,6 +1156,7 @@ struct TransferCommand {
     /// The local files to transfer.
     ///
     /// These will be combined with any newline-delimited files provided through stdin.
+    /// Passing `-` reads from stdin even if it's a terminal.
     /// These will also be combined with files provided via `--interactive`.
     ///
     /// A limit of 100 paths may be passed through stdin to prevent accidental mass copying.
@@ -523,12 +1170,12 @@ impl TransferCommand {
             true => &profile.local_root,
             false => &AbsolutePath::for_existing_path(&std::env::current_dir()?)?,
         };
-        let source_set = SetName(self.source_set);
-        let dest_set = SetName(self.dest_set);
+        let source_set = self.source_set;
+        let dest_set = self.dest_set;
 
         let mut files = to_local_paths(&profile, &self.files, cwd)?;
 
-        let mut stdin_files = read_paths_from_stdin(&profile, cwd)?;
+        let mut stdin_files = read_paths_from_stdin(&profile, &self.files, cwd)?;
         files.append(&mut stdin_files);
 
         if self.interactive {
@@ -547,18 +1194,19 @@ impl TransferCommand {
         }
 
         if files.is_empty() {
-            println!("No files selected.");
+            info_println!("No files selected.");
             return Ok(());
         }
 
         let result = monja::transfer(&profile, &opts, files, source_set, dest_set)?;
 
-        println!(
+        info_println!(
             "Successfully transferred the following files from set `{}` to set `{}`:",
-            result.source_set, result.dest_set
+            result.source_set,
+            result.dest_set
         );
         for file in result.files.into_iter() {
-            println!("\t{}", file);
+            info_println!("\t{}", file);
         }
 
         Ok(())
@@ -566,51 +1214,160 @@ impl TransferCommand {
 }
 
 #[derive(Args)]
-struct SetShortcutCommand {
-    /// The set whose shortcut to change
-    #[arg(long = "set", add = ArgValueCandidates::new(completions::set_names))]
-    set_name: String,
+struct EditCommand {
+    /// If set, the path provided will be relative to the local root, ignoring cwd.
+    #[arg(long = "nocwd")]
+    no_cwd: bool,
 
-    /// The new shortcut path (relative to local root)
-    path: PathBuf,
+    /// The local file whose repo copy will be edited.
+    file: PathBuf,
 }
-impl SetShortcutCommand {
+
+impl EditCommand {
+    fn execute(self, profile: MonjaProfile, _: ExecutionOptions) -> anyhow::Result<()> {
+        let cwd = std::env::current_dir()?;
+        let file = to_local_path(&profile, &self.file, &cwd, self.no_cwd)?;
+
+        let Some((set_name, repo_path)) = monja::resolve_repo_path(&profile, file.clone())? else {
+            return Err(anyhow!(
+                "'{}' is untracked, so it has no copy in the repo. Use `monja put` to add it to a set.",
+                file
+            ));
+        };
+
+        info_println!("Editing '{}' from set `{}`.", file, set_name);
+
+        // run through the shell, since $EDITOR can contain arguments, like `code -w`
+        let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".into());
+        let error = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(repo_path)
+            .exec();
+        // exec only returns on failure
+        Err(anyhow::Error::new(error).context("Failed to start the editor."))
+    }
+}
+
+#[derive(Args)]
+struct WhichCommand {
+    /// If set, the path provided will be relative to the local root, ignoring cwd.
+    #[arg(long = "nocwd")]
+    no_cwd: bool,
+
+    /// The local file to look up.
+    file: PathBuf,
+}
+
+impl WhichCommand {
+    fn execute(self, profile: MonjaProfile, _: ExecutionOptions) -> anyhow::Result<()> {
+        let cwd = std::env::current_dir()?;
+        let file = to_local_path(&profile, &self.file, &cwd, self.no_cwd)?;
+
+        match monja::which(&profile, file)? {
+            Some(set_name) => println!("{}", set_name),
+            None => println!("Not provided by any targeted set."),
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct RestoreCommand {
+    /// The set to restore files from
+    #[arg(long = "set", add = ArgValueCandidates::new(completions::set_names))]
+    set_name: SetName,
+
+    /// If set, the paths provided will be relative to the local root, ignoring cwd.
+    ///
+    /// This is typically used when using external tools like `fzf` to select files.
+    #[arg(long = "nocwd")]
+    no_cwd: bool,
+
+    /// The local files to restore.
+    ///
+    /// These will be combined with any newline-delimited files provided through stdin.
+    /// Passing `-` reads from stdin even if it's a terminal.
+    ///
+    /// A limit of 100 paths may be passed through stdin to prevent accidental mass copying.
+    #[arg(last = true)]
+    files: Vec<PathBuf>,
+}
+
+impl RestoreCommand {
     fn execute(self, profile: MonjaProfile, opts: ExecutionOptions) -> anyhow::Result<()> {
-        let path = if self.path.is_absolute() {
-            self.path
-        } else {
-            let cwd = AbsolutePath::for_existing_path(&std::env::current_dir()?)?;
-            cwd.join(&self.path)
+        let cwd = match self.no_cwd {
+            true => &profile.local_root,
+            false => &AbsolutePath::for_existing_path(&std::env::current_dir()?)?,
         };
-        let path = path
-            .strip_prefix(&profile.local_root)
-            .map_err(|_| {
-                anyhow!(
-                    "Path '{}' is not under local root '{}'",
-                    path.display(),
-                    profile.local_root
-                )
-            })?
-            .to_path_buf();
 
-        let set_name = SetName(self.set_name);
+        let mut files = to_local_paths(&profile, &self.files, cwd)?;
+
+        let mut stdin_files = read_paths_from_stdin(&profile, &self.files, cwd)?;
+        files.append(&mut stdin_files);
+
+        if files.is_empty() {
+            info_println!("No files selected.");
+            return Ok(());
+        }
+
+        let result = match monja::restore(&profile, &opts, self.set_name, files) {
+            Ok(result) => result,
+            Err(RestoreError::NotInSet { set_name, files }) => {
+                eprintln!("The following files are not tracked by set `{}`:", set_name);
+                for file in files.into_iter() {
+                    eprintln!("\t{}", file);
+                }
+                return Err(anyhow::Error::msg("Failed to restore files."));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        info_println!(
+            "Successfully restored the following files from set `{}`:",
+            result.set_name
+        );
+        for file in result.files_restored.into_iter() {
+            info_println!("\t{}", file);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct SetShortcutCommand {
+    /// The set whose shortcut to change
+    #[arg(long = "set", add = ArgValueCandidates::new(completions::set_names))]
+    set_name: SetName,
+
+    /// The new shortcut path (relative to local root)
+    path: PathBuf,
+}
+impl SetShortcutCommand {
+    fn execute(self, profile: MonjaProfile, opts: ExecutionOptions) -> anyhow::Result<()> {
+        let path = to_shortcut_path(&profile, self.path)?;
+
+        let set_name = self.set_name;
 
         let result = monja::set_shortcut(&profile, &opts, set_name, path)?;
 
         if result.old_shortcut.as_os_str().is_empty() {
-            println!(
+            info_println!(
                 "Set `{}` shortcut changed from (none) to '{}'.",
                 result.set_name,
                 result.new_shortcut.display()
             );
         } else if result.new_shortcut.as_os_str().is_empty() {
-            println!(
+            info_println!(
                 "Set `{}` shortcut changed from '{}' to (none).",
                 result.set_name,
                 result.old_shortcut.display()
             );
         } else {
-            println!(
+            info_println!(
                 "Set `{}` shortcut changed from '{}' to '{}'.",
                 result.set_name,
                 result.old_shortcut.display(),
@@ -618,9 +1375,9 @@ impl SetShortcutCommand {
             );
         }
         for file in result.files_moved.iter() {
-            println!("\t{}", file.display());
+            info_println!("\t{}", file.display());
         }
-        println!("{} file(s) restructured.", result.files_moved.len());
+        info_println!("{} file(s) restructured.", result.files_moved.len());
 
         Ok(())
     }
@@ -630,7 +1387,7 @@ impl SetShortcutCommand {
 struct NewSetCommand {
     /// The set into which the files will be copied
     #[arg(long = "set")]
-    new_set: String,
+    new_set: SetName,
 
     /// If set, the paths provided will be relative to the local root, ignoring cwd.
     ///
@@ -642,9 +1399,14 @@ struct NewSetCommand {
     #[arg(long, short)]
     interactive: bool,
 
-    /// The local files to copy.
+    /// The set's shortcut, instead of the deepest directory that all of the files are in.
+    #[arg(long)]
+    shortcut: Option<PathBuf>,
+
+    /// The local files to copy. Directories are copied recursively.
     ///
     /// These will be combined with any newline-delimited files provided through stdin.
+    /// Passing `-` reads from stdin even if it's a terminal.
     /// These will also be combined with files provided via `--interactive`.
     ///
     /// A limit of 100 paths may be passed through stdin to prevent accidental mass copying.
@@ -660,7 +1422,7 @@ impl NewSetCommand {
         };
         let mut files = to_local_paths(&profile, &self.files, cwd)?;
 
-        let mut stdin_files = read_paths_from_stdin(&profile, cwd)?;
+        let mut stdin_files = read_paths_from_stdin(&profile, &self.files, cwd)?;
         files.append(&mut stdin_files);
 
         // even though put is similar, there isn't really room to factor this code out.
@@ -698,27 +1460,216 @@ impl NewSetCommand {
 
         if files.is_empty() {
             // could consider it an error, but it's not a big deal that the user didn't provide anything
-            println!("No files selected.");
+            info_println!("No files selected.");
             return Ok(());
         }
 
         let base = xdg::BaseDirectories::with_prefix("monja");
         let path = AbsolutePath::for_existing_path(&base.place_config_file("monja-profile.toml")?)?;
-        let result = monja::new_set(&profile, &opts, &path, files, SetName(self.new_set))?;
+        let shortcut = self
+            .shortcut
+            .map(|p| to_shortcut_path(&profile, p))
+            .transpose()?;
+        let result = monja::new_set(&profile, &opts, &path, files, self.new_set, shortcut)?;
 
-        println!(
+        info_println!(
             "Successfully created new set `{}` with the following files:",
             result.new_set,
         );
         for file in result.files.into_iter() {
-            println!("\t{}", file);
+            info_println!("\t{}", file);
         }
-        println!("The set has also been added to the profile.");
+        info_println!("The set has also been added to the profile.");
 
         Ok(())
     }
 }
 
+#[derive(Args)]
+struct RemoveSetCommand {
+    /// The set to remove
+    #[arg(long = "set", add = ArgValueCandidates::new(completions::set_names))]
+    set_name: SetName,
+
+    /// Removes the set even if local files were last pulled from it.
+    ///
+    /// These files will then need to be `monja put` into another set before `monja push` works again.
+    #[arg(long)]
+    force: bool,
+}
+impl RemoveSetCommand {
+    fn execute(self, profile: MonjaProfile, opts: ExecutionOptions) -> anyhow::Result<()> {
+        let base = xdg::BaseDirectories::with_prefix("monja");
+        let path = AbsolutePath::for_existing_path(&base.place_config_file("monja-profile.toml")?)?;
+        let result = monja::remove_set(&profile, &opts, &path, self.set_name, self.force);
+
+        if let Err(monja::RemoveSetError::SetInUse { set_name, files }) = result {
+            eprintln!("Set `{}` is still used by these local files:", set_name);
+            for file in files.into_iter() {
+                eprintln!("\t{}", file);
+            }
+            eprintln!("Use `monja put` to move them to another set, or use `--force`.");
+            return Err(anyhow::Error::msg("Failed to remove set."));
+        }
+
+        let result = result?;
+
+        info_println!("Successfully removed set `{}`.", result.set_name);
+        if result.removed_from_profile {
+            info_println!("The set has also been removed from the profile.");
+        }
+        if !result.orphaned_files.is_empty() {
+            info_println!(
+                "These local files were last pulled from the set and now need a new set:"
+            );
+            for file in result.orphaned_files.into_iter() {
+                info_println!("\t{}", file);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct RenameSetCommand {
+    /// The set to rename
+    #[arg(long = "from", add = ArgValueCandidates::new(completions::set_names))]
+    old_name: SetName,
+
+    /// The new name of the set
+    #[arg(long = "to")]
+    new_name: SetName,
+}
+impl RenameSetCommand {
+    fn execute(self, profile: MonjaProfile, opts: ExecutionOptions) -> anyhow::Result<()> {
+        let base = xdg::BaseDirectories::with_prefix("monja");
+        let path = AbsolutePath::for_existing_path(&base.place_config_file("monja-profile.toml")?)?;
+        let result = monja::rename_set(&profile, &opts, &path, self.old_name, self.new_name)?;
+
+        info_println!(
+            "Successfully renamed set `{}` to `{}`.",
+            result.old_name,
+            result.new_name
+        );
+        if result.profile_updated {
+            info_println!("The profile has also been updated to use the new name.");
+        }
+        if result.no_longer_targeted {
+            log::warn!(
+                "`{}` was only targeted by a glob in target-sets that `{}` doesn't match, so it won't be pulled until it's added back.",
+                result.old_name,
+                result.new_name
+            );
+        }
+        if !result.files_reindexed.is_empty() {
+            info_println!("Local files now associated with the new name:");
+            for file in result.files_reindexed.into_iter() {
+                info_println!("\t{}", file);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct PreviewSetCommand {
+    /// The set to preview
+    #[arg(long = "set", add = ArgValueCandidates::new(completions::set_names))]
+    set_name: SetName,
+}
+impl PreviewSetCommand {
+    fn execute(&self, profile: MonjaProfile, _opts: ExecutionOptions) -> anyhow::Result<()> {
+        let files = monja::preview_set(&profile, &self.set_name)?;
+
+        if files.is_empty() {
+            println!("Set '{}' has no files to pull.", self.set_name);
+            return Ok(());
+        }
+
+        println!("Files set '{}' would pull:", self.set_name);
+        for (path, exists) in files.iter() {
+            let action = match exists {
+                true => "overwrite",
+                false => "create",
+            };
+            println!(
+                "\t'{}' -> '{}' ({})",
+                path.path_in_set_as_relative_str(),
+                path.local_path_as_relative_str(),
+                action
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct ListSetsCommand {}
+impl ListSetsCommand {
+    fn execute(&self, profile: MonjaProfile, _opts: ExecutionOptions) -> anyhow::Result<()> {
+        let monja::SetList { sets, ignored_dirs } = monja::list_sets(&profile)?;
+
+        if !ignored_dirs.is_empty() {
+            let ignored_dirs: Vec<String> = ignored_dirs
+                .iter()
+                .map(|d| d.display().to_string())
+                .collect();
+            println!(
+                "Ignored by the repo's .monjaignore: {}",
+                ignored_dirs.join(", ")
+            );
+        }
+
+        if sets.is_empty() {
+            println!("No sets found in the repo.");
+            return Ok(());
+        }
+
+        let rows: Vec<[String; 4]> = sets
+            .into_iter()
+            .map(|set| {
+                [
+                    set.profile_position
+                        .map_or("-".to_string(), |p| (p + 1).to_string()),
+                    set.name.0,
+                    match set.shortcut.as_os_str().is_empty() {
+                        true => "(none)".to_string(),
+                        false => set.shortcut.display().to_string(),
+                    },
+                    set.file_count.to_string(),
+                ]
+            })
+            .collect();
+
+        let header = ["Position", "Set", "Shortcut", "Files"];
+        let mut widths = header.map(|h| h.len());
+        for row in rows.iter() {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        print_row(&header, &widths);
+        for row in rows.iter() {
+            print_row(row, &widths);
+        }
+
+        return Ok(());
+
+        fn print_row(row: &[impl AsRef<str>], widths: &[usize]) {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell.as_ref()))
+                .collect();
+            println!("{}", cells.join("  ").trim_end());
+        }
+    }
+}
+
 #[derive(Args)]
 struct StatusCommand {
     /// If set, the `location` argument provided will be relative to the local root, ignoring cwd.
@@ -730,10 +1681,56 @@ struct StatusCommand {
     /// The local location for which to view status.
     location: Option<PathBuf>,
 
+    /// How to print the status. The json format ignores filters and always includes everything.
+    #[arg(long, value_enum, default_value_t = StatusFormat::Text)]
+    format: StatusFormat,
+
+    /// Treats `location` as a single file, printing just its status, such as `to-push <set>` or `untracked`.
+    ///
+    /// This is much faster, since only the file's set is read, making it suitable for shell prompts.
+    /// Note that `.monjaignore` files aren't consulted.
+    #[arg(long, requires = "location", conflicts_with_all = ["format", "StatusFilter"])]
+    file: bool,
+
+    /// Prints one line per file, as a status code, a tab, and the file's path relative to the local root.
+    ///
+    /// Unlike the normal output, this format is stable across versions, making it suitable for editor integrations.
+    /// Lines are grouped by code in this order, then sorted by path:
+    /// `S` (set missing), `M` (file missing from set), `P` (to push), `U` (untracked), `O` (old since last pull).
+    /// Files removed from the repo since the last pull are also untracked, so they appear as both `U` and `O`.
+    #[arg(long, conflicts_with_all = ["format", "file", "StatusFilter"])]
+    porcelain: bool,
+
+    /// Shows a unified diff from the repo's copy to the local file for each file to push.
+    ///
+    /// Binary files are only noted as differing.
+    #[arg(long, conflicts_with_all = ["format", "file", "porcelain"])]
+    diff: bool,
+
+    /// Shows the absolute path in the repo that each file to push maps to, as `local -> repo`.
+    #[arg(long, conflicts_with_all = ["format", "file", "porcelain", "diff", "summary"])]
+    repo_paths: bool,
+
+    /// Prints a single line counting the files in each status, such as `to-push=3 untracked=1 ...`, for shell prompts.
+    ///
+    /// Counts cover the whole local root, and the keys and their order are stable across versions.
+    #[arg(long, conflicts_with_all = ["location", "format", "file", "porcelain", "diff", "StatusFilter"])]
+    summary: bool,
+
+    /// Uses these comma-separated sets instead of the profile's target sets, like `monja pull --sets`.
+    #[arg(long, value_delimiter = ',', add = ArgValueCandidates::new(completions::set_names))]
+    sets: Option<Vec<SetName>>,
+
     #[command(flatten)]
     filter: Option<StatusFilter>,
 }
 
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+enum StatusFormat {
+    Text,
+    Json,
+}
+
 #[derive(Args)]
 #[group(required = false, multiple = true)]
 struct StatusFilter {
@@ -753,12 +1750,40 @@ struct StatusFilter {
     #[arg(long)]
     to_push: bool,
 
-    /// Filter to files that would be pushed (if no error condition).
+    /// Filter to files that would be pushed and differ from the repo.
+    #[arg(long)]
+    modified: bool,
+
+    /// Filter to files that changed both locally and in the repo since they were last pulled or pushed.
+    #[arg(long)]
+    conflicted: bool,
+
+    /// Filter to files that were removed from the repo since the last pull, which `monja clean` would remove.
+    ///
+    /// On its own, this is much faster than other filters, since the repo doesn't need to be read.
     #[arg(long)]
     old_files: bool,
 }
+
+impl StatusFilter {
+    fn only_old_files(&self) -> bool {
+        self.old_files
+            && !(self.untracked
+                || self.sets_missing
+                || self.files_missing
+                || self.to_push
+                || self.modified
+                || self.conflicted)
+    }
+}
 impl StatusCommand {
     fn execute(&self, profile: MonjaProfile, _: ExecutionOptions) -> anyhow::Result<()> {
+        warn_if_sets_overridden(&profile, self.sets.as_deref());
+        let profile = match &self.sets {
+            Some(sets) => profile.with_target_sets(sets.clone()),
+            None => profile,
+        };
+
         let cwd = std::env::current_dir()?;
         let location = to_local_path(
             &profile,
@@ -766,10 +1791,83 @@ impl StatusCommand {
             &cwd,
             self.no_cwd,
         )?;
-        print!(
-            "Status of local files under {}\n\n",
+
+        if self.file {
+            let status = match monja::file_status(&profile, location)? {
+                FileStatus::NotFound => "not-found".to_string(),
+                FileStatus::Untracked => "untracked".to_string(),
+                FileStatus::SetMissing(set_name) => format!("set-missing {}", set_name),
+                FileStatus::FileMissing(set_name) => format!("file-missing {}", set_name),
+                FileStatus::Tracked(set_name) => format!("tracked {}", set_name),
+                FileStatus::ToPush(set_name) => format!("to-push {}", set_name),
+            };
+            println!("{}", status);
+            return Ok(());
+        }
+
+        if self.summary {
+            let summary = monja::status_summary(&profile)?;
+            println!(
+                "to-push={} modified={} conflicted={} untracked={} missing-files={} missing-sets={} cleanable={}",
+                summary.to_push,
+                summary.modified,
+                summary.conflicted,
+                summary.untracked,
+                summary.missing_files,
+                summary.missing_sets,
+                summary.cleanable
+            );
+            return Ok(());
+        }
+
+        if self.porcelain {
+            print!(
+                "{}",
+                monja::local_status(&profile, location)?.to_porcelain()
+            );
+            return Ok(());
+        }
+
+        if self.format == StatusFormat::Json {
+            let mut status = monja::local_status(&profile, location)?;
+            // files come from walking the file system, so we sort them to keep output stable across runs.
+            // sets are already in profile order.
+            for (_, file_paths) in status
+                .files_to_push
+                .iter_mut()
+                .chain(status.modified_files.iter_mut())
+                .chain(status.conflicted_files.iter_mut())
+                .chain(status.files_with_missing_sets.iter_mut())
+                .chain(status.missing_files.iter_mut())
+            {
+                file_paths.sort();
+            }
+            status.untracked_files.sort();
+            status.old_files_after_last_pull.sort();
+
+            println!("{}", serde_json::to_string_pretty(&status)?);
+            return Ok(());
+        }
+
+        println!(
+            "Status of local files under {}",
             profile.local_root.join(&location).display()
         );
+        if let Some(last_pull) = monja::last_pull_time(&profile)? {
+            println!("Last pulled {}.", time_ago(last_pull));
+        }
+        println!();
+
+        if self.filter.as_ref().is_some_and(|f| f.only_old_files()) {
+            println!("Files removed from repo since last pull (also found in untracked):");
+            for path in monja::cleanable_files(&profile)?.into_iter() {
+                if path.starts_with(&*location) {
+                    println!("{}", path);
+                }
+            }
+            println!();
+            return Ok(());
+        }
 
         let status = monja::local_status(&profile, location)?;
 
@@ -810,9 +1908,28 @@ impl StatusCommand {
         }
 
         if self.filter.as_ref().is_none_or(|f| f.to_push) {
+            let message =
+                "Files to push (including unchanged), as grouped under their corresponding sets:";
+            match (self.diff, self.repo_paths) {
+                (true, _) => print_diffs(message, monja::diff(&profile, &status.files_to_push)?),
+                (false, true) => {
+                    print_repo_paths(message, status.files_to_push, &status.repo_paths_to_push)
+                }
+                (false, false) => print(message, status.files_to_push),
+            }
+        }
+
+        if self.filter.as_ref().is_none_or(|f| f.modified) {
+            print(
+                "Files modified locally, as grouped under their corresponding sets:",
+                status.modified_files,
+            );
+        }
+
+        if self.filter.as_ref().is_none_or(|f| f.conflicted) {
             print(
-                "Files to push (including unchanged), as grouped under their corresponding sets:",
-                status.files_to_push,
+                "Files modified both locally and in the repo since last synced, as grouped under their corresponding sets:",
+                status.conflicted_files,
             );
         }
 
@@ -831,6 +1948,168 @@ impl StatusCommand {
             }
             println!()
         }
+
+        fn print_repo_paths(
+            message: &str,
+            info: Vec<(SetName, Vec<LocalFilePath>)>,
+            repo_paths: &HashMap<LocalFilePath, PathBuf>,
+        ) {
+            println!("{}", message);
+
+            for (set_name, file_paths) in info {
+                println!("\tSet: {}", set_name);
+                for path in file_paths {
+                    match repo_paths.get(&path) {
+                        Some(repo_path) => println!("\t\t{} -> {}", path, repo_path.display()),
+                        None => println!("\t\t{}", path),
+                    }
+                }
+            }
+            println!()
+        }
+
+        fn print_diffs(message: &str, info: monja::SetDiffs) {
+            println!("{}", message);
+
+            for (set_name, file_diffs) in info {
+                println!("\tSet: {}", set_name);
+                for (path, file_diff) in file_diffs {
+                    match file_diff {
+                        FileDiff::Unchanged => println!("\t\t{}", path),
+                        FileDiff::Binary => println!("\t\t{} (binary differs)", path),
+                        FileDiff::Text(diff) => {
+                            println!("\t\t{}", path);
+                            print!("{}", diff);
+                        }
+                    }
+                }
+            }
+            println!()
+        }
+    }
+}
+
+#[derive(Args)]
+struct DoctorCommand {}
+impl DoctorCommand {
+    fn execute(&self, profile: MonjaProfile, _: ExecutionOptions) -> anyhow::Result<()> {
+        let report = monja::doctor(&profile);
+
+        for check in report.checks.iter() {
+            let status = match check.status {
+                DoctorStatus::Ok => "ok",
+                DoctorStatus::Warning => "warning",
+                DoctorStatus::Failed => "FAILED",
+            };
+            println!("[{}] {}: {}", status, check.name, check.detail);
+        }
+
+        if report.has_failures() {
+            return Err(anyhow::Error::msg("Some checks failed."));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct VerifyCommand {
+    /// Only verifies files from the given set.
+    #[arg(long, add = ArgValueCandidates::new(completions::set_names))]
+    set: Option<String>,
+}
+impl VerifyCommand {
+    fn execute(self, profile: MonjaProfile, _: ExecutionOptions) -> anyhow::Result<()> {
+        let report = monja::verify(&profile, self.set.map(SetName).as_ref())?;
+
+        print(
+            "Files whose contents differ from the repo, as grouped under their corresponding sets:",
+            &report.mismatched_files,
+        );
+        print(
+            "Files missing locally, as grouped under their corresponding sets:",
+            &report.missing_local_files,
+        );
+        print(
+            "Files missing from the repo, as grouped under the sets they were expected to be in:",
+            &report.missing_repo_files,
+        );
+        println!("{} files are in sync.", report.verified_count);
+
+        if !report.is_in_sync() {
+            return Err(anyhow::Error::msg("Some files are out of sync."));
+        }
+
+        return Ok(());
+
+        fn print(message: &str, info: &[(SetName, Vec<LocalFilePath>)]) {
+            if info.is_empty() {
+                return;
+            }
+
+            println!("{}", message);
+            for (set_name, file_paths) in info {
+                println!("\tSet: {}", set_name);
+                for path in file_paths {
+                    println!("\t\t{}", path);
+                }
+            }
+            println!()
+        }
+    }
+}
+
+#[derive(Args)]
+struct PruneIndexCommand {}
+impl PruneIndexCommand {
+    fn execute(self, profile: MonjaProfile, opts: ExecutionOptions) -> anyhow::Result<()> {
+        let result = monja::prune_index(&profile, &opts)?;
+
+        if result.entries_removed.is_empty() {
+            info_println!("No stale index entries found.");
+            return Ok(());
+        }
+
+        info_println!("Index entries removed for files gone both locally and from the repo:");
+        for (file_path, set_name) in result.entries_removed.into_iter() {
+            info_println!("\t{} ({})", file_path, set_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct LogCommand {
+    /// The number of operations to show.
+    #[arg(short = 'n', long, default_value_t = 20)]
+    limit: usize,
+}
+impl LogCommand {
+    fn execute(self, profile: MonjaProfile, _opts: ExecutionOptions) -> anyhow::Result<()> {
+        let records = monja::history(&profile, Some(self.limit))?;
+
+        if records.is_empty() {
+            println!("No operations recorded yet.");
+            return Ok(());
+        }
+
+        for record in records.into_iter() {
+            let counts: Vec<String> = record
+                .counts
+                .iter()
+                .map(|(name, count)| format!("{}: {}", name, count))
+                .collect();
+            println!(
+                "{}\t{}{}\t{}",
+                time_ago(record.time()),
+                record.operation,
+                if record.dry_run { " (dry run)" } else { "" },
+                counts.join(", ")
+            );
+        }
+
+        Ok(())
     }
 }
 
@@ -851,6 +2130,8 @@ impl ProfileCommand {
         // TODO: dedupe logic. used here, in main, and in NewSetCommand
         let base = xdg::BaseDirectories::with_prefix("monja");
         let path = base.place_config_file("monja-profile.toml")?;
+        // the actual profile, so that `cat $(monja profile)` works with redirects
+        let path = monja::MonjaProfileConfig::resolve_redirects(&path)?;
 
         println!("{}", path.display());
 
@@ -858,28 +2139,77 @@ impl ProfileCommand {
     }
 }
 
+// goes to stderr so that it doesn't get mixed in with output like `monja status --format json`
+struct CliLogger;
+impl log::Log for CliLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        match record.level() {
+            log::Level::Error => eprintln!("Error: {}", record.args()),
+            log::Level::Warn => eprintln!("Warning: {}", record.args()),
+            _ => eprintln!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
 fn main() -> anyhow::Result<()> {
     completions::init();
 
     // goes first so that help and version commands can work before our code
     let cli = Cli::parse();
+    let opts: ExecutionOptions = cli.opts.into();
+    QUIET.store(opts.quiet, Ordering::Relaxed);
+
+    // only fails if a logger is already set, which can't happen
+    let _ = log::set_logger(&CliLogger);
+    log::set_max_level(opts.log_level());
 
     let base = xdg::BaseDirectories::with_prefix("monja");
 
     let profile_config_path = base.place_config_file("monja-profile.toml")?;
 
-    let local_root = std::env::home_dir().expect("We got bigger problems if there's no home.");
-    let local_root = AbsolutePath::for_existing_path(&local_root)?;
+    let home_dir = std::env::home_dir().expect("We got bigger problems if there's no home.");
+    let local_root = match cli.local_root.as_deref() {
+        Some(local_root) => {
+            if !local_root.is_dir() {
+                return Err(anyhow!(
+                    "The local root must be an existing directory: {}",
+                    local_root.display()
+                ));
+            }
+            AbsolutePath::for_existing_path(local_root)?
+        }
+        None => AbsolutePath::for_existing_path(&home_dir)?,
+    };
 
     let data_root = base
         .get_data_home()
         .expect("We got bigger problems if there's no home.");
-    fs::create_dir_all(&data_root)?;
-    let data_root = AbsolutePath::for_existing_path(&data_root)?;
+    let data_root = monja::MonjaProfile::create_data_root(&data_root)?;
 
     // is a special case, since profile may not exist yet, etc.
     if let Commands::Init(init) = cli.command {
-        return init.execute(cli.opts, profile_config_path, local_root, data_root, &base);
+        // the profile would end up with a repo-dir relative to the wrong directory
+        if cli.local_root.is_some() {
+            return Err(anyhow::Error::msg(
+                "`--local-root` can't be used with `monja init`.",
+            ));
+        }
+        if cli.repo_dir.is_some() {
+            return Err(anyhow::Error::msg(
+                "`--repo-dir` can't be used with `monja init`.",
+            ));
+        }
+        return init.execute(opts, profile_config_path, local_root, data_root, &base);
     }
 
     if !profile_config_path.is_file() {
@@ -890,20 +2220,119 @@ fn main() -> anyhow::Result<()> {
     }
 
     let profile_config_path = AbsolutePath::for_existing_path(&profile_config_path)?;
-    let profile_config = monja::MonjaProfileConfig::load(&profile_config_path)?;
+    let mut profile_config = monja::MonjaProfileConfig::load(&profile_config_path)?;
+    // the repo stays where it is, even if files go somewhere else
+    if cli.local_root.is_some() && profile_config.repo_dir.is_relative() {
+        profile_config.repo_dir = home_dir.join(&profile_config.repo_dir);
+    }
+    if let Some(repo_dir) = cli.repo_dir.as_deref() {
+        // joining leaves absolute paths as they are
+        let repo_dir = local_root.join(repo_dir);
+        if !repo_dir.is_dir() {
+            return Err(anyhow!(
+                "The repo directory must be an existing directory: {}",
+                repo_dir.display()
+            ));
+        }
+        // absolute, since from_config would otherwise take it as relative to the local root
+        profile_config.repo_dir = AbsolutePath::for_existing_path(&repo_dir)?.into_path_buf();
+    }
 
     let profile = monja::MonjaProfile::from_config(profile_config, local_root, data_root)?;
 
-    let dryrun = cli.opts.dry_run;
-    cli.command.execute(profile, cli.opts)?;
+    if let Err(errors) = profile.config.validate(&profile.repo_root) {
+        // missing sets are left to the operations that need them, since some (like `monja newset`) can fix them
+        let mut has_hard_errors = false;
+        for error in errors.iter() {
+            match error {
+                ProfileValidationError::MissingSet(_) => eprintln!("Warning: {}", error),
+                _ => {
+                    eprintln!("{}", error);
+                    has_hard_errors = true;
+                }
+            }
+        }
+        if has_hard_errors {
+            eprintln!(
+                "Fix `target-sets` in the profile: {}",
+                profile_config_path.display()
+            );
+            return Err(anyhow::Error::msg("Invalid monja profile."));
+        }
+    }
+
+    let dryrun = opts.dry_run;
+    cli.command.execute(profile, opts)?;
 
     if dryrun {
-        println!("Note that, due to being a dry-run, no changes were actually made.");
+        info_println!("Note that, due to being a dry-run, no changes were actually made.");
     }
 
     Ok(())
 }
 
+// coarse, since it's only meant to give an idea of how stale things are
+fn time_ago(time: std::time::SystemTime) -> String {
+    // a time in the future would take a clock change, so treat it as now
+    let secs = time.elapsed().unwrap_or_default().as_secs();
+    let (count, unit) = match secs {
+        0..60 => return "just now".to_string(),
+        60..3600 => (secs / 60, "minute"),
+        3600..86400 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    match count {
+        1 => format!("1 {} ago", unit),
+        _ => format!("{} {}s ago", count, unit),
+    }
+}
+
+// sorted and deduplicated, with forward slashes regardless of platform
+fn print_names(names: impl Iterator<Item = String>) {
+    let mut names: Vec<String> = names.collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        println!("{}", name);
+    }
+}
+
+// the operation itself succeeded by the time a post-hook runs, but it still warrants a failing exit code
+fn report_hooks(hooks_run: Vec<HookRun>, post_hook_error: Option<HookError>) -> anyhow::Result<()> {
+    if !hooks_run.is_empty() {
+        info_println!("Hooks run:");
+        for hook_run in hooks_run.into_iter() {
+            info_println!("\t{}: {}", hook_run.kind, hook_run.command);
+        }
+    }
+
+    if let Some(error) = post_hook_error {
+        eprintln!("{}", error);
+        return Err(anyhow::Error::msg("Failed to run post-hook."));
+    }
+
+    Ok(())
+}
+
+fn warn_if_sets_overridden(profile: &MonjaProfile, sets: Option<&[SetName]>) {
+    let Some(sets) = sets else {
+        return;
+    };
+    if sets != profile.config.target_sets {
+        let join = |sets: &[SetName]| {
+            sets.iter()
+                .map(|s| s.0.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        log::warn!(
+            "Using sets [{}] instead of the profile's [{}].",
+            join(sets),
+            join(&profile.config.target_sets)
+        );
+    }
+}
+
 // commands that take local paths have a nocwd arg in order to be more easily used with fzf, etc
 // where operations using external tools will preferably use paths relative to local_root
 fn to_local_path(
@@ -928,38 +2357,65 @@ fn to_local_paths(
 ) -> anyhow::Result<Vec<LocalFilePath>> {
     let files: Result<Vec<LocalFilePath>, monja::LocalFilePathError> = files
         .iter()
+        .filter(|f| f.as_ref() != Path::new(STDIN_MARKER))
         .map(|f| LocalFilePath::from(profile, f.as_ref(), cwd))
         .collect();
     Ok(files?)
 }
 
-fn read_paths_from_stdin(profile: &MonjaProfile, cwd: &Path) -> anyhow::Result<Vec<LocalFilePath>> {
+// passed as a file to explicitly read from stdin, which is handy when stdin is a terminal
+const STDIN_MARKER: &str = "-";
+
+// somewhat arbitrary, but better than mass copying, presumably
+const STDIN_PATH_LIMIT: usize = 100;
+
+// shortcuts are given like any other path, relative to cwd, but stored relative to the local root.
+// the shortcut's directory may not exist yet.
+fn to_shortcut_path(profile: &MonjaProfile, path: PathBuf) -> anyhow::Result<PathBuf> {
+    let path = AbsolutePath::for_path_allow_nonexistent(&path)?;
+    let path = path
+        .strip_prefix(&profile.local_root)
+        .map_err(|_| {
+            anyhow!(
+                "Path '{}' is not under local root '{}'",
+                path.display(),
+                profile.local_root
+            )
+        })?
+        .to_path_buf();
+    Ok(path)
+}
+
+fn read_paths_from_stdin(
+    profile: &MonjaProfile,
+    files: &[impl AsRef<Path>],
+    cwd: &Path,
+) -> anyhow::Result<Vec<LocalFilePath>> {
     let stdin = std::io::stdin().lock();
-    if stdin.is_terminal() {
+    let forced = files.iter().any(|f| f.as_ref() == Path::new(STDIN_MARKER));
+    if stdin.is_terminal() && !forced {
         return Ok(Vec::new());
     }
 
-    let mut ctr = 0;
-    let paths: anyhow::Result<Vec<LocalFilePath>> = stdin
+    // only splitting on newlines, so paths with spaces are fine. blank lines are skipped.
+    // one past the limit is read, to tell whether there were too many.
+    let lines: Vec<String> = stdin
         .lines()
-        .take_while(move |_| {
-            ctr += 1;
-            ctr < 100
-        })
-        .map(|s| {
-            s.map_err(anyhow::Error::new).and_then(|s| {
-                LocalFilePath::from(profile, s.as_ref(), cwd).map_err(anyhow::Error::new)
-            })
-        })
-        .collect();
-    if ctr >= 100 {
-        // somewhat arbitrary, but better than mass copying, presumably
-        Err(anyhow!(
-            "There is a limit of 100 paths passed through stdin."
-        ))
-    } else {
-        paths
+        .filter(|s| s.as_ref().map_or(true, |s| !s.is_empty()))
+        .take(STDIN_PATH_LIMIT + 1)
+        .collect::<std::io::Result<_>>()?;
+    if lines.len() > STDIN_PATH_LIMIT {
+        return Err(anyhow!(
+            "There is a limit of {} paths passed through stdin.",
+            STDIN_PATH_LIMIT
+        ));
     }
+
+    let paths: Result<Vec<LocalFilePath>, monja::LocalFilePathError> = lines
+        .iter()
+        .map(|s| LocalFilePath::from(profile, s.as_ref(), cwd))
+        .collect();
+    Ok(paths?)
 }
 
 // arguably, this should be moved into operations. will decide later.
@@ -1,10 +1,11 @@
-use std::fs;
+use std::{collections::HashSet, fs, path::PathBuf};
 
 use thiserror::Error;
 
 use crate::{
     ExecutionOptions, LocalFilePath, MonjaProfile,
     local::{self, FileIndexError},
+    operation::history::{self, HistoryOperation},
     repo,
 };
 
@@ -19,99 +20,253 @@ pub enum CleanError {
     #[error("Failed to remove file.")]
     RemoveFile(#[source] std::io::Error),
 
+    #[error("Unable to check if '{0}' would be empty after cleaning.")]
+    ReadDir(PathBuf, #[source] std::io::Error),
+
+    #[error("Failed to remove empty directory '{0}'.")]
+    RemoveDir(PathBuf, #[source] std::io::Error),
+
     #[error("Unable to load an index file.")]
     FileIndex(#[from] FileIndexError),
 
     #[error("Clean cancelled by user.")]
     UserCancellation,
+
+    #[error(
+        "Refusing to clean {count} files, which is more than the profile's clean-threshold of {threshold}. Pass --force if this is intended."
+    )]
+    TooManyFiles { count: usize, threshold: usize },
 }
 
+pub const DEFAULT_CLEAN_THRESHOLD: usize = 100;
+
 #[derive(Debug)]
 pub struct CleanSuccess {
     pub files_cleaned: Vec<LocalFilePath>,
+    // directories left empty by cleaning, which are removed as well
+    pub dirs_pruned: Vec<LocalFilePath>,
+    // files that would have been cleaned, but are under one of CleanMode::Full's excluded paths
+    pub protected: Vec<LocalFilePath>,
 }
 
 pub enum CleanMode {
     Index,
-    Full,
+    // files under any of the excluded paths are left alone, for local-only files under a directory a set also manages
+    Full { exclude: Vec<LocalFilePath> },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanDecision {
+    Remove,
+    Keep,
+    // cancels the whole clean, including files already answered with Remove
+    Quit,
+}
+
+// confirm gets the files that would be cleaned, before anything is removed.
+// it isn't called if ExecutionOptions::skip_confirmations is set or if there's nothing to clean.
+// if it returns false, nothing is removed.
+// when confirming, cleaning more files than the profile's clean_threshold fails unless ExecutionOptions::force is set.
 pub fn clean(
     profile: &MonjaProfile,
     opts: &ExecutionOptions,
     mode: CleanMode,
+    confirm: &dyn Fn(&CleanSuccess) -> bool,
+) -> Result<CleanSuccess, CleanError> {
+    let (files_to_clean, protected) = files_for_mode(profile, mode)?;
+    check_threshold(profile, opts, files_to_clean.len())?;
+
+    let success = remove_files(profile, opts, files_to_clean, protected, confirm)?;
+    record_history(profile, opts, &success);
+    Ok(success)
+}
+
+// like clean, but decide is asked about each file in path order, instead of confirming all of them at once.
+// files answered with Keep are left alone and aren't in files_cleaned.
+// decide isn't called if ExecutionOptions::skip_confirmations is set, in which case every file is removed.
+pub fn clean_interactive(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    mode: CleanMode,
+    decide: &mut dyn FnMut(&LocalFilePath) -> CleanDecision,
 ) -> Result<CleanSuccess, CleanError> {
+    let (mut files_to_clean, protected) = files_for_mode(profile, mode)?;
+    files_to_clean.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+    check_threshold(profile, opts, files_to_clean.len())?;
+
+    if !opts.skip_confirmations {
+        let mut files_to_remove = Vec::with_capacity(files_to_clean.len());
+        for file in files_to_clean {
+            match decide(&file.clone().into()) {
+                CleanDecision::Remove => files_to_remove.push(file),
+                CleanDecision::Keep => {}
+                CleanDecision::Quit => return Err(CleanError::UserCancellation),
+            }
+        }
+        files_to_clean = files_to_remove;
+    }
+
+    // each file was already confirmed
+    let success = remove_files(profile, opts, files_to_clean, protected, &|_| true)?;
+    record_history(profile, opts, &success);
+    Ok(success)
+}
+
+// not in remove_files, since pull --delete is recorded as part of the pull
+fn record_history(profile: &MonjaProfile, opts: &ExecutionOptions, success: &CleanSuccess) {
+    history::record(
+        profile,
+        opts,
+        HistoryOperation::Clean,
+        [
+            ("files-cleaned", success.files_cleaned.len()),
+            ("dirs-pruned", success.dirs_pruned.len()),
+        ],
+    );
+}
+
+// checked before asking anything, since it's easy to say yes to a list too long to actually read.
+// skipping confirmations is already an explicit go-ahead, so the threshold doesn't apply then.
+fn check_threshold(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    count: usize,
+) -> Result<(), CleanError> {
+    let threshold = profile
+        .config
+        .clean_threshold
+        .unwrap_or(DEFAULT_CLEAN_THRESHOLD);
+    match !opts.skip_confirmations && !opts.force && count > threshold {
+        true => Err(CleanError::TooManyFiles { count, threshold }),
+        false => Ok(()),
+    }
+}
+
+// the files to clean, along with the ones protected from cleaning
+fn files_for_mode(
+    profile: &MonjaProfile,
+    mode: CleanMode,
+) -> Result<(Vec<local::FilePath>, Vec<local::FilePath>), CleanError> {
     match mode {
-        CleanMode::Index => index_clean(profile, opts),
-        CleanMode::Full => full_clean(profile, opts),
+        CleanMode::Index => Ok((local::old_files_since_last_pull(profile)?, Vec::new())),
+        CleanMode::Full { exclude } => {
+            let exclude: Vec<local::FilePath> = exclude.iter().map(|p| p.to_internal()).collect();
+            Ok(full_clean_files(profile)?
+                .into_iter()
+                .partition(|f| !exclude.iter().any(|e| f.is_child_of(e))))
+        }
     }
 }
 
-fn index_clean(
+// also used by pull, which already knows which files are cleanable
+pub(crate) fn remove_files(
     profile: &MonjaProfile,
     opts: &ExecutionOptions,
+    files_to_clean: Vec<local::FilePath>,
+    protected: Vec<local::FilePath>,
+    confirm: &dyn Fn(&CleanSuccess) -> bool,
 ) -> Result<CleanSuccess, CleanError> {
-    let files_to_clean = local::old_files_since_last_pull(profile)?;
+    // figured out before removing anything, so that confirmations and dry runs know about them
+    let dirs_to_prune = dirs_left_empty(profile, &files_to_clean)?;
 
-    let confirmation = confirm(opts, &files_to_clean);
-    if !confirmation {
+    let mut files_cleaned: Vec<LocalFilePath> =
+        files_to_clean.iter().map(|f| f.clone().into()).collect();
+    // deref coercion to Path
+    files_cleaned.sort();
+    let mut dirs_pruned: Vec<LocalFilePath> =
+        dirs_to_prune.iter().map(|d| d.clone().into()).collect();
+    dirs_pruned.sort();
+    let mut protected: Vec<LocalFilePath> = protected.into_iter().map(|f| f.into()).collect();
+    protected.sort();
+    let planned = CleanSuccess {
+        files_cleaned,
+        dirs_pruned,
+        protected,
+    };
+    if !opts.skip_confirmations && !planned.files_cleaned.is_empty() && !confirm(&planned) {
         return Err(CleanError::UserCancellation);
     }
 
+    // confirmation and dry runs are independent, so a dry run can still be cancelled
     if !opts.dry_run {
-        for file in files_to_clean.iter() {
-            let path = file.as_ref().to_path(&profile.local_root);
+        for file in files_to_clean {
+            let path = file.to_absolute_path(profile);
             fs::remove_file(path).map_err(CleanError::RemoveFile)?;
         }
+
+        // deepest first, so children are gone before their parents
+        for dir in dirs_to_prune {
+            let path = dir.to_absolute_path(profile);
+            fs::remove_dir(&path).map_err(|e| CleanError::RemoveDir(path, e))?;
+        }
     }
 
-    let files_cleaned = files_to_clean.into_iter().map(|f| f.into()).collect();
-    Ok(CleanSuccess { files_cleaned })
+    Ok(planned)
 }
 
-fn full_clean(profile: &MonjaProfile, opts: &ExecutionOptions) -> Result<CleanSuccess, CleanError> {
-    let repo = repo::initialize_full_state(profile).map_err(CleanError::RepoStateInitialization)?;
-
-    let local_state = local::retrieve_state(profile, &repo)?;
-
-    let mut files_cleaned = Vec::with_capacity(
-        local_state.missing_files.len()
-            + local_state.files_with_missing_sets.len()
-            + local_state.untracked_files.len(),
-    );
-
-    let files_to_clean: Vec<local::FilePath> = local_state
-        .untracked_files
+// walks up from each cleaned file, stopping at the first directory that would still have something in it.
+// the local root is never included.
+// cleaned files are never covered by .monjaignore, and ignored directories aren't walked into,
+// so none of these directories are covered by .monjaignore either.
+fn dirs_left_empty(
+    profile: &MonjaProfile,
+    files_to_clean: &[local::FilePath],
+) -> Result<Vec<local::FilePath>, CleanError> {
+    let mut candidates: Vec<local::FilePath> = files_to_clean
+        .iter()
+        .flat_map(|f| std::iter::successors(f.parent(), |d| d.parent()))
+        .collect::<HashSet<_>>()
         .into_iter()
-        .chain(local_state.files_with_missing_sets.into_values().flatten())
-        .chain(local_state.missing_files.into_values().flatten())
         .collect();
+    // deepest first, so that we know about empty children before checking their parents
+    candidates.sort_by_key(|d| std::cmp::Reverse(d.as_ref().components().count()));
 
-    let confirmation = confirm(opts, &files_to_clean);
-    if !confirmation {
-        return Err(CleanError::UserCancellation);
-    }
-
-    for file in files_to_clean {
-        let path = file.as_ref().to_path(&profile.local_root);
-
-        if !opts.dry_run {
-            fs::remove_file(path).map_err(CleanError::RemoveFile)?;
+    let mut removed: HashSet<PathBuf> = files_to_clean
+        .iter()
+        .map(|f| f.to_absolute_path(profile))
+        .collect();
+    let mut dirs_to_prune = Vec::new();
+    for dir in candidates {
+        let path = dir.to_absolute_path(profile);
+        // these may hypothetically be under the local root, and we'll never want to remove them
+        if path == *profile.repo_root || path == *profile.data_root {
+            continue;
         }
 
-        files_cleaned.push(file.into());
+        let mut entries = fs::read_dir(&path).map_err(|e| CleanError::ReadDir(path.clone(), e))?;
+        let would_be_empty = entries.try_fold(true, |empty, entry| {
+            entry.map(|e| empty && removed.contains(&e.path()))
+        });
+        if would_be_empty.map_err(|e| CleanError::ReadDir(path.clone(), e))? {
+            removed.insert(path);
+            dirs_to_prune.push(dir);
+        }
     }
 
-    // deref coercion to Path
-    files_cleaned.sort();
-    Ok(CleanSuccess { files_cleaned })
+    Ok(dirs_to_prune)
 }
 
-fn confirm(opts: &ExecutionOptions, files_to_clean: &[local::FilePath]) -> bool {
-    let mut message = String::from("These files will be removed locally:\n");
-    for file in files_to_clean {
-        message.push_str(&format!("\t{}", file));
-    }
+fn full_clean_files(profile: &MonjaProfile) -> Result<Vec<local::FilePath>, CleanError> {
+    let repo = repo::initialize_full_state(profile).map_err(CleanError::RepoStateInitialization)?;
+
+    let local_state = local::retrieve_state(profile, &repo)?;
+
+    // only targeted sets are considered, since they decide what's local
+    let is_noclean = |file: &local::FilePath| {
+        profile
+            .config
+            .target_sets
+            .iter()
+            .filter_map(|name| repo.sets.get(name))
+            .any(|set| set.is_noclean(file))
+    };
+    let files_to_clean = local_state
+        .untracked_files
+        .into_iter()
+        .chain(local_state.files_with_missing_sets.into_values().flatten())
+        .chain(local_state.missing_files.into_values().flatten())
+        .filter(|f| !is_noclean(f))
+        .collect();
 
-    opts.user_confirm(&message)
+    Ok(files_to_clean)
 }
@@ -0,0 +1,157 @@
+use std::{
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+};
+
+use thiserror::Error;
+
+use crate::{LocalFilePath, MonjaProfile, SetName, repo, secret};
+
+#[derive(Error, Debug)]
+pub enum DiffError {
+    #[error("Unable to initialize the state of set `{0}`.")]
+    SetStateInitialization(SetName, #[source] Box<repo::StateInitializationError>),
+
+    #[error("Set `{0}` is missing from the repo.")]
+    SetNotFound(SetName),
+
+    #[error("'{0}' is not in set `{1}`.")]
+    NotInSet(LocalFilePath, SetName),
+
+    #[error("Unable to compare '{0}' with its copy in the repo.")]
+    Comparison(PathBuf, #[source] std::io::Error),
+
+    #[error("Unable to run diff.")]
+    Io(#[source] std::io::Error),
+
+    #[error("diff exited unsuccessfully ({status}): {stderr}")]
+    Unsuccessful { status: ExitStatus, stderr: String },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FileDiff {
+    Unchanged,
+    // a unified diff from the set's copy to the local file
+    Text(String),
+    // either side has a NUL byte, in which case only the fact that they differ is useful
+    Binary,
+}
+
+// grouped by set, in the same order as the files passed in
+pub type SetDiffs = Vec<(SetName, Vec<(LocalFilePath, FileDiff)>)>;
+
+// diffs each file against its copy in the given set, such as the ones in Status::files_to_push.
+// secrets are compared with their decrypted contents.
+pub fn diff(
+    profile: &MonjaProfile,
+    files: &[(SetName, Vec<LocalFilePath>)],
+) -> Result<SetDiffs, DiffError> {
+    let mut diffs = Vec::with_capacity(files.len());
+    for (set_name, file_paths) in files.iter() {
+        let set = repo::initialize_set_state(profile, set_name)
+            .map_err(|e| DiffError::SetStateInitialization(set_name.clone(), Box::new(e)))?
+            .ok_or_else(|| DiffError::SetNotFound(set_name.clone()))?;
+
+        let mut set_diffs = Vec::with_capacity(file_paths.len());
+        for file_path in file_paths.iter() {
+            let local_path = file_path.to_internal();
+            let (Some(repo_file), Some(repo_path)) = (
+                set.locally_mapped_files.get(&local_path),
+                set.get_repo_absolute_path_for(&local_path).ok(),
+            ) else {
+                return Err(DiffError::NotInSet(file_path.clone(), set_name.clone()));
+            };
+            let local_abs_path = local_path.to_absolute_path(profile);
+
+            let file_diff = diff_file(
+                profile,
+                file_path,
+                &local_abs_path,
+                &repo_path,
+                repo_file.secret,
+            )?;
+            set_diffs.push((file_path.clone(), file_diff));
+        }
+        diffs.push((set_name.clone(), set_diffs));
+    }
+
+    Ok(diffs)
+}
+
+fn diff_file(
+    profile: &MonjaProfile,
+    file_path: &LocalFilePath,
+    local_path: &Path,
+    repo_path: &Path,
+    secret: bool,
+) -> Result<FileDiff, DiffError> {
+    let comparison = |e| DiffError::Comparison(local_path.to_path_buf(), e);
+    if !crate::local_differs_from_set(profile, local_path, repo_path, secret).map_err(comparison)? {
+        return Ok(FileDiff::Unchanged);
+    }
+
+    // the repo only has the encrypted file, so the decrypted contents go through stdin instead
+    let decrypted = match secret {
+        true => Some(secret::read_decrypted(repo_path).map_err(comparison)?),
+        false => None,
+    };
+    let repo_is_binary = match &decrypted {
+        Some(decrypted) => decrypted.contains(&0),
+        None => has_nul(repo_path).map_err(comparison)?,
+    };
+    if repo_is_binary || has_nul(local_path).map_err(comparison)? {
+        return Ok(FileDiff::Binary);
+    }
+
+    let relative_path = file_path.as_relative_str();
+    let mut child = Command::new("diff")
+        .arg("-u")
+        .arg("--label")
+        .arg(format!("a/{}", relative_path))
+        .arg("--label")
+        .arg(format!("b/{}", relative_path))
+        .arg("--")
+        .arg(match decrypted {
+            Some(_) => Path::new("-"),
+            None => repo_path,
+        })
+        .arg(local_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(DiffError::Io)?;
+    if let Some(decrypted) = decrypted {
+        let mut stdin = child.stdin.take().expect("Stdin was piped.");
+        stdin.write_all(&decrypted).map_err(DiffError::Io)?;
+    }
+    let output = child.wait_with_output().map_err(DiffError::Io)?;
+
+    // 1 means the files differ, which we already knew
+    match output.status.code() {
+        Some(0) | Some(1) => Ok(FileDiff::Text(
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        )),
+        _ => Err(DiffError::Unsuccessful {
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }),
+    }
+}
+
+// read in chunks, so large files aren't read fully into memory
+fn has_nul(path: &Path) -> std::io::Result<bool> {
+    let mut file = fs::File::open(path)?;
+    let mut buf = [0u8; 8192];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            return Ok(false);
+        }
+        if buf[..read].contains(&0) {
+            return Ok(true);
+        }
+    }
+}
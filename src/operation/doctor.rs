@@ -0,0 +1,196 @@
+use std::{
+    error::Error,
+    fs,
+    io::ErrorKind,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{MonjaProfile, SetName, local, repo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoctorStatus {
+    Ok,
+    // things work, but maybe not as well as they could
+    Warning,
+    Failed,
+}
+
+#[derive(Debug)]
+pub struct DoctorCheck {
+    pub name: &'static str,
+    pub status: DoctorStatus,
+    pub detail: String,
+}
+
+#[derive(Debug)]
+pub struct DoctorReport {
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    pub fn has_failures(&self) -> bool {
+        self.checks.iter().any(|c| c.status == DoctorStatus::Failed)
+    }
+}
+
+// only reads things, so it's safe to run whenever.
+// failures become part of the report instead of errors, since finding them is the whole point.
+pub fn doctor(profile: &MonjaProfile) -> DoctorReport {
+    let checks = vec![
+        check_rsync(),
+        check_target_sets(profile),
+        check_index(profile),
+        check_writable("repo directory", &profile.repo_root),
+        check_writable("data directory", &profile.data_root),
+        check_shortcuts(profile),
+    ];
+
+    return DoctorReport { checks };
+
+    fn check_rsync() -> DoctorCheck {
+        let name = "rsync";
+        let status = Command::new("rsync")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        match status {
+            Ok(status) if status.success() => ok(name, "rsync is installed."),
+            Ok(status) => failed(name, format!("`rsync --version` exited with {}.", status)),
+            Err(e) if e.kind() == ErrorKind::NotFound => DoctorCheck {
+                name,
+                status: DoctorStatus::Warning,
+                detail:
+                    "rsync isn't installed, so files will be copied without it, which is slower."
+                        .into(),
+            },
+            Err(e) => failed(name, describe(&e)),
+        }
+    }
+
+    fn check_target_sets(profile: &MonjaProfile) -> DoctorCheck {
+        let name = "target sets";
+        match profile.config.validate(&profile.repo_root) {
+            Ok(()) => ok(name, "All targeted sets exist in the repo."),
+            Err(errors) => failed(
+                name,
+                errors
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+        }
+    }
+
+    fn check_index(profile: &MonjaProfile) -> DoctorCheck {
+        let name = "file index";
+        let index = match local::FileIndex::load(profile, local::IndexKind::Current) {
+            Ok(index) => index,
+            Err(e) => return failed(name, describe(&e)),
+        };
+
+        let mut missing_sets: Vec<&SetName> = index
+            .sets()
+            .into_iter()
+            .filter(|set_name| !profile.repo_root.join(set_name).is_dir())
+            .collect();
+        if missing_sets.is_empty() {
+            return ok(
+                name,
+                "Every set that files were pulled from exists in the repo.",
+            );
+        }
+
+        missing_sets.sort_by(|a, b| a.0.cmp(&b.0));
+        failed(
+            name,
+            format!(
+                "Files were pulled from sets that are now missing from the repo: {}. \
+                 Use `monja put` to move the files to other sets.",
+                join(missing_sets)
+            ),
+        )
+    }
+
+    // actually writing something would be more accurate, but we don't want to touch anything.
+    // so this only catches directories that nobody can write to.
+    fn check_writable(name: &'static str, path: &Path) -> DoctorCheck {
+        match fs::metadata(path) {
+            Ok(metadata) if !metadata.permissions().readonly() => {
+                ok(name, format!("'{}' is writable.", path.display()))
+            }
+            Ok(_) => failed(name, format!("'{}' is read-only.", path.display())),
+            Err(e) => failed(
+                name,
+                format!("Unable to read '{}': {}", path.display(), describe(&e)),
+            ),
+        }
+    }
+
+    fn check_shortcuts(profile: &MonjaProfile) -> DoctorCheck {
+        let name = "set shortcuts";
+        let mut problems = Vec::new();
+        for set_name in profile.config.target_sets.iter() {
+            // missing sets are covered by check_target_sets
+            if set_name.is_empty() || !profile.repo_root.join(set_name).is_dir() {
+                continue;
+            }
+
+            let shortcut = repo::SetConfig::load(profile, set_name)
+                .map_err(|e| describe(&e))
+                .and_then(|config| {
+                    repo::SetShortcut::from_path(config.shortcut.unwrap_or_default())
+                        .map_err(|e| describe(&e))
+                });
+            if let Err(e) = shortcut {
+                problems.push(format!("Set `{}`: {}", set_name, e));
+            }
+        }
+
+        match problems.is_empty() {
+            true => ok(
+                name,
+                "Every targeted set's shortcut is under the home directory.",
+            ),
+            false => failed(name, problems.join(" ")),
+        }
+    }
+
+    fn ok(name: &'static str, detail: impl Into<String>) -> DoctorCheck {
+        DoctorCheck {
+            name,
+            status: DoctorStatus::Ok,
+            detail: detail.into(),
+        }
+    }
+
+    fn failed(name: &'static str, detail: impl Into<String>) -> DoctorCheck {
+        DoctorCheck {
+            name,
+            status: DoctorStatus::Failed,
+            detail: detail.into(),
+        }
+    }
+
+    // our errors tend to keep the interesting part in their sources
+    fn describe(error: &dyn Error) -> String {
+        let mut description = error.to_string();
+        let mut source = error.source();
+        while let Some(e) = source {
+            description.push_str(": ");
+            description.push_str(&e.to_string());
+            source = e.source();
+        }
+        description
+    }
+
+    fn join(set_names: Vec<&SetName>) -> String {
+        set_names
+            .iter()
+            .map(|s| format!("`{}`", s))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
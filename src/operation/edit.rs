@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::{LocalFilePath, MonjaProfile, SetName, local, repo};
+
+#[derive(Error, Debug)]
+pub enum ResolveRepoPathError {
+    #[error("Unable to load the file index.")]
+    FileIndex(#[from] local::FileIndexError),
+
+    #[error("Unable to initialize the state of set `{0}`.")]
+    SetStateInitialization(SetName, #[source] Box<repo::StateInitializationError>),
+
+    #[error("Set `{0}`, which the file was last synced with, is missing from the repo.")]
+    SetNotFound(SetName),
+
+    #[error("The file is no longer in set `{0}`, which it was last synced with.")]
+    NotInSet(SetName),
+
+    // the repo only has the encrypted file, so there's nothing useful to edit
+    #[error("The file is a secret in set `{0}`, so it can only be edited locally.")]
+    Secret(SetName),
+}
+
+// None if the file is untracked.
+// like file_status, the index is used to find the owning set, so only that set gets read.
+pub fn resolve_repo_path(
+    profile: &MonjaProfile,
+    file: LocalFilePath,
+) -> Result<Option<(SetName, PathBuf)>, ResolveRepoPathError> {
+    let local_path = file.to_internal();
+    let index = local::FileIndex::load(profile, local::IndexKind::Current)?;
+    let Some(entry) = index.get(&local_path) else {
+        return Ok(None);
+    };
+    let set_name = entry.set.clone();
+
+    let set = repo::initialize_set_state(profile, &set_name)
+        .map_err(|e| ResolveRepoPathError::SetStateInitialization(set_name.clone(), Box::new(e)))?
+        .ok_or_else(|| ResolveRepoPathError::SetNotFound(set_name.clone()))?;
+
+    match set.locally_mapped_files.get(&local_path) {
+        None => return Err(ResolveRepoPathError::NotInSet(set_name)),
+        Some(file) if file.secret => return Err(ResolveRepoPathError::Secret(set_name)),
+        Some(_) => (),
+    }
+
+    let repo_path = set
+        .get_repo_absolute_path_for(&local_path)
+        .expect("Tracked files always have a path in the set.");
+    Ok(Some((set_name, repo_path)))
+}
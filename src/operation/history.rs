@@ -0,0 +1,136 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{ExecutionOptions, MonjaProfile};
+
+// one json record per line, appended to by each mutating operation.
+// kept in the data root instead of the index dir, since it's about this machine, even if the index is shared.
+pub(crate) const HISTORY_FILE: &str = "monja-history.jsonl";
+
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    #[error("Unable to read {HISTORY_FILE}.")]
+    Read(#[source] std::io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HistoryOperation {
+    Pull,
+    Push,
+    Put,
+    Clean,
+    NewSet,
+}
+
+impl Display for HistoryOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            HistoryOperation::Pull => "pull",
+            HistoryOperation::Push => "push",
+            HistoryOperation::Put => "put",
+            HistoryOperation::Clean => "clean",
+            HistoryOperation::NewSet => "new-set",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HistoryRecord {
+    // seconds since the unix epoch
+    pub timestamp: u64,
+    pub operation: HistoryOperation,
+    pub dry_run: bool,
+    // what each count means depends on the operation, like files-pulled or files-cleaned
+    pub counts: BTreeMap<String, usize>,
+}
+
+impl HistoryRecord {
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.timestamp)
+    }
+}
+
+// the most recent records, newest first.
+// lines that can't be parsed, like one cut short by a crash, are skipped.
+pub fn history(
+    profile: &MonjaProfile,
+    limit: Option<usize>,
+) -> Result<Vec<HistoryRecord>, HistoryError> {
+    let contents = match fs::read_to_string(history_path(profile)) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(HistoryError::Read(e)),
+    };
+
+    let records = contents
+        .lines()
+        .rev()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                log::debug!("Skipping unparseable history record: {}", e);
+                None
+            }
+        });
+    Ok(match limit {
+        Some(limit) => records.take(limit).collect(),
+        None => records.collect(),
+    })
+}
+
+// best effort, since the operation itself already succeeded by the time this is called
+pub(crate) fn record<'a>(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    operation: HistoryOperation,
+    counts: impl IntoIterator<Item = (&'a str, usize)>,
+) {
+    let record = HistoryRecord {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        operation,
+        dry_run: opts.dry_run,
+        counts: counts
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect(),
+    };
+
+    if let Err(e) = append(profile, &record) {
+        log::warn!(
+            "Unable to record the {} in {}: {}",
+            operation,
+            HISTORY_FILE,
+            e
+        );
+    }
+
+    fn append(profile: &MonjaProfile, record: &HistoryRecord) -> std::io::Result<()> {
+        let mut line = serde_json::to_string(record).map_err(std::io::Error::other)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(history_path(profile))?
+            .write_all(line.as_bytes())
+    }
+}
+
+fn history_path(profile: &MonjaProfile) -> PathBuf {
+    profile.data_root.join(HISTORY_FILE)
+}
@@ -5,14 +5,17 @@ use thiserror::Error;
 
 use crate::{
     AbsolutePath, ExecutionOptions, MonjaProfile, MonjaProfileConfig, MonjaProfileConfigError,
-    PullError, SetName, repo,
+    PullError, SetName, SetNameError, repo,
 };
 
 #[derive(Error, Debug)]
 pub enum InitError {
-    #[error("monja has already been initialized.")]
+    #[error("monja has already been initialized. Use force to overwrite the profile.")]
     AlreadyInitialized,
 
+    #[error("The initial set name is invalid.")]
+    SetName(#[from] SetNameError),
+
     #[error("Failed to create monja-profile.")]
     Profile(#[source] std::io::Error),
 
@@ -37,6 +40,10 @@ pub struct InitSuccess {
     // only returns None on dryrun
     pub profile: Option<MonjaProfile>,
     pub profile_config_path: PathBuf,
+    // only with InitSpec::force. in a dry run, whether it would have been.
+    pub profile_overwritten: bool,
+    // false if the initial set already existed, which only happens with InitSpec::force
+    pub set_created: bool,
 }
 
 pub struct InitSpec {
@@ -45,22 +52,37 @@ pub struct InitSpec {
     pub local_root: AbsolutePath,
     pub repo_root: AbsolutePath,
     pub data_root: AbsolutePath,
-    pub relative_repo_root: PathBuf,
     pub initial_set_name: String,
+    // overwrites an existing profile, such as to fix a corrupted one.
+    // an existing initial set is kept as-is, and only gets created if it's missing.
+    pub force: bool,
 }
 
 pub fn init(opts: &ExecutionOptions, spec: InitSpec) -> Result<InitSuccess, InitError> {
-    if spec.profile_config_path.exists() {
+    let profile_overwritten = spec.profile_config_path.exists();
+    if profile_overwritten && !spec.force {
         return Err(InitError::AlreadyInitialized);
     }
+    // checked up front, since it goes into the profile before the set gets created
+    let initial_set_name = SetName::new(&spec.initial_set_name)?;
+    let set_created = !spec.repo_root.join(&initial_set_name).exists();
 
     if opts.dry_run {
         return Ok(InitSuccess {
             profile: None,
             profile_config_path: spec.profile_config_path,
+            profile_overwritten,
+            set_created,
         });
     }
 
+    // relative when possible, in case the home dir differs between machines.
+    // but the repo could be configured to be anywhere, in which case we use the full path.
+    let repo_dir = match spec.repo_root.strip_prefix(&spec.local_root) {
+        Ok(relative_repo_root) => relative_repo_root,
+        Err(_) => &spec.repo_root,
+    };
+
     fs::write(
         &spec.profile_config_path,
         formatdoc! {"
@@ -69,7 +91,7 @@ pub fn init(opts: &ExecutionOptions, spec: InitSpec) -> Result<InitSuccess, Init
             target-sets = [
                 '{}',
             ]
-        ", spec.relative_repo_root.display(), &spec.initial_set_name },
+        ", repo_dir.display(), &spec.initial_set_name },
     )
     .map_err(InitError::Profile)?;
 
@@ -80,11 +102,14 @@ pub fn init(opts: &ExecutionOptions, spec: InitSpec) -> Result<InitSuccess, Init
     let profile = MonjaProfile::from_config(profile, spec.local_root, spec.data_root)
         .map_err(MonjaProfileConfigError::Load)?;
 
-    let set_path = repo::create_empty_set(&profile, &SetName(spec.initial_set_name))?;
+    // never touching an existing set's contents, including its .monjaignore
+    if set_created {
+        let set_path = repo::create_empty_set(&profile, &initial_set_name)?;
 
-    // goes before creating profile for move reasons
-    let ignorefile = set_path.join(".monjaignore");
-    fs::write(ignorefile, DEFAULT_IGNORE).map_err(InitError::IgnoreFile)?;
+        // goes before creating profile for move reasons
+        let ignorefile = set_path.join(".monjaignore");
+        fs::write(ignorefile, DEFAULT_IGNORE).map_err(InitError::IgnoreFile)?;
+    }
 
     let readme = spec.repo_root.join("README.md");
     if !readme.exists() {
@@ -93,11 +118,13 @@ pub fn init(opts: &ExecutionOptions, spec: InitSpec) -> Result<InitSuccess, Init
 
     // any files placed in the set here (like .monjaignore) need to be pulled
     // we don't write directly to the local dir because we want them to be in the index
-    crate::operation::pull::pull(&profile, opts)?;
+    crate::operation::pull::pull(&profile, opts, &Default::default())?;
 
     Ok(InitSuccess {
         profile: Some(profile),
         profile_config_path: spec.profile_config_path,
+        profile_overwritten,
+        set_created,
     })
 }
 
@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::{MonjaProfile, repo};
+
+#[derive(Error, Debug)]
+pub enum ListSetsError {
+    #[error("Unable to initialize repo state.")]
+    RepoStateInitialization(Vec<repo::StateInitializationError>),
+}
+
+#[derive(Debug)]
+pub struct SetInfo {
+    pub name: repo::SetName,
+    pub shortcut: PathBuf,
+    pub file_count: usize,
+    // position in the profile's target_sets, or None if the set isn't targeted
+    pub profile_position: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct SetList {
+    pub sets: Vec<SetInfo>,
+    // directories at the repo root that aren't sets because of the repo's .monjaignore, relative to the repo root
+    pub ignored_dirs: Vec<PathBuf>,
+}
+
+// targeted sets come first in profile order, followed by untargeted sets alphabetically
+pub fn list_sets(profile: &MonjaProfile) -> Result<SetList, ListSetsError> {
+    let repo =
+        repo::initialize_full_state(profile).map_err(ListSetsError::RepoStateInitialization)?;
+
+    let mut sets: Vec<SetInfo> = repo
+        .sets
+        .into_values()
+        .chain(repo.dest_root_sets.into_values())
+        .map(|set| {
+            let profile_position = profile
+                .config
+                .target_sets
+                .iter()
+                .position(|s| *s == set.name);
+            SetInfo {
+                shortcut: set.shortcut.to_path(""),
+                file_count: set.locally_mapped_files.len(),
+                profile_position,
+                name: set.name,
+            }
+        })
+        .collect();
+
+    // Option orders None first, so we flip that part of the comparison to get untargeted sets last
+    sets.sort_by(|l, r| {
+        l.profile_position
+            .is_none()
+            .cmp(&r.profile_position.is_none())
+            .then(l.profile_position.cmp(&r.profile_position))
+            .then_with(|| l.name.0.cmp(&r.name.0))
+    });
+
+    let ignored_dirs = repo
+        .ignored_dirs
+        .into_iter()
+        .map(|dir| {
+            dir.strip_prefix(&profile.repo_root)
+                .expect("Ignored dirs are read from the repo root.")
+                .to_path_buf()
+        })
+        .collect();
+
+    Ok(SetList { sets, ignored_dirs })
+}
@@ -1,10 +1,15 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use thiserror::Error;
 
 use crate::{
     AbsolutePath, ExecutionOptions, LocalFilePath, MonjaProfile, MonjaProfileConfig,
-    MonjaProfileConfigError, SetName, operation, repo,
+    MonjaProfileConfigError, SetName,
+    operation::{
+        self,
+        history::{self, HistoryOperation},
+    },
+    repo,
 };
 
 #[derive(Error, Debug)]
@@ -18,8 +23,17 @@ pub enum NewSetError {
     #[error("Failed to configure the set's shortcut.")]
     SetShortcut(SetName, PathBuf, repo::SetConfigError),
 
+    #[error("The given shortcut is invalid.")]
+    InvalidShortcut(#[from] repo::SetShortcutError),
+
     #[error("The put operation to place files in the new set failed.")]
     PutFiles(#[from] operation::put::PutError),
+
+    #[error("The directory '{0}' does not exist.")]
+    DirectoryNotFound(PathBuf),
+
+    #[error("The directory '{0}' has no files to put in the new set.")]
+    EmptyDirectory(PathBuf),
 }
 
 #[derive(Debug)]
@@ -34,9 +48,22 @@ pub fn new_set(
     profile_config_path: &AbsolutePath,
     files: Vec<LocalFilePath>,
     new_set: SetName,
+    // relative to the local root. if not given, it's the deepest directory that all files are in.
+    shortcut: Option<PathBuf>,
     // boxing error because large, according to clippy
 ) -> Result<NewSetSuccess, Box<NewSetError>> {
+    // checked up front, since we'd otherwise only find out after the set is created
+    if let Some(shortcut) = &shortcut {
+        repo::SetShortcut::from_path(shortcut.clone()).map_err(|e| Box::new(e.into()))?;
+    }
+
     if opts.dry_run {
+        history::record(
+            profile,
+            opts,
+            HistoryOperation::NewSet,
+            [("files", files.len())],
+        );
         return Ok(NewSetSuccess { new_set, files });
     }
 
@@ -49,7 +76,11 @@ pub fn new_set(
         .save(profile_config_path)
         .map_err(|e| NewSetError::ProfileModification(new_set.clone(), e))?;
 
-    let shortcut = compute_shortcut(&files);
+    let shortcut = shortcut.unwrap_or_else(|| {
+        compute_shortcut(&files, |f| {
+            f.to_internal().to_absolute_path(profile).is_dir()
+        })
+    });
     let mut set_config = repo::SetConfig::load(profile, &new_set)
         .map_err(|e| NewSetError::SetShortcut(new_set.clone(), shortcut.clone(), e))?;
     set_config.shortcut = Some(shortcut.clone());
@@ -58,28 +89,80 @@ pub fn new_set(
         .map_err(|e| NewSetError::SetShortcut(new_set.clone(), shortcut, e))?;
 
     // note that this wouldn't work in a dry run because the set isn't created, causing put to fail
-    let put_result =
-        operation::put::put(profile, opts, files, new_set).map_err(|e| Box::new(e.into()))?;
-
+    let put_result = operation::put::put(
+        profile,
+        opts,
+        files,
+        Some(new_set.clone()),
+        &Default::default(),
+    )
+    .map_err(|e| Box::new(e.into()))?;
+
+    // the put is recorded on its own, too
+    history::record(
+        profile,
+        opts,
+        HistoryOperation::NewSet,
+        [("files", put_result.files.len())],
+    );
     Ok(NewSetSuccess {
-        new_set: put_result.owning_set,
+        new_set,
         files: put_result.files,
     })
 }
 
-fn compute_shortcut(files: &[LocalFilePath]) -> PathBuf {
+// for turning a directory like ~/.config/nvim into its own set, with the directory as the shortcut.
+// same as put, .monjaignore files aren't respected.
+pub fn new_set_from_dir(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    profile_config_path: &AbsolutePath,
+    dir: LocalFilePath,
+    new_set_name: SetName,
+) -> Result<NewSetSuccess, Box<NewSetError>> {
+    let abs_dir = dir.to_internal().to_absolute_path(profile);
+    if !abs_dir.is_dir() {
+        return Err(Box::new(NewSetError::DirectoryNotFound(abs_dir)));
+    }
+
+    let shortcut = dir.to_path_buf();
+    let files = operation::put::expand_dirs(profile, vec![dir]).map_err(|e| Box::new(e.into()))?;
+    if files.is_empty() {
+        return Err(Box::new(NewSetError::EmptyDirectory(abs_dir)));
+    }
+
+    new_set(
+        profile,
+        opts,
+        profile_config_path,
+        files,
+        new_set_name,
+        Some(shortcut),
+    )
+}
+
+// the common prefix of the directories the files are in, so a file's own name never ends up in the shortcut.
+// directories being put count as their own directory, so that their contents end up at the root of the set.
+fn compute_shortcut(files: &[LocalFilePath], is_dir: impl Fn(&LocalFilePath) -> bool) -> PathBuf {
     if files.is_empty() {
         return PathBuf::new();
     }
 
     let mut prefix = PathBuf::new();
-    let mut files: Vec<std::path::Components> = files.iter().map(|p| p.components()).collect();
+    let mut files: Vec<std::path::Components> = files
+        .iter()
+        .map(|p| match is_dir(p) {
+            true => p.components(),
+            false => p.parent().unwrap_or(Path::new("")).components(),
+        })
+        .collect();
     loop {
-        let mut set = files.iter_mut().filter_map(|it| it.next());
-        let Some(component) = set.next() else {
+        // a directory running out of components ends the prefix, too
+        let mut set = files.iter_mut().map(|it| it.next());
+        let Some(Some(component)) = set.next() else {
             break;
         };
-        if set.all(|f| f == component) {
+        if set.all(|f| f == Some(component)) {
             prefix.push(component);
         } else {
             break;
@@ -109,11 +192,29 @@ mod localfilepath_tests {
             LocalFilePath("foo/bar/aaaa/a".into()),
         ];
 
-        let shortcut = super::compute_shortcut(&paths);
+        let shortcut = super::compute_shortcut(&paths, |_| false);
         expect_that!(shortcut, eq(Path::new("foo/bar")));
         Ok(())
     }
 
+    #[gtest]
+    fn single_file() -> Result<()> {
+        let paths: [LocalFilePath; _] = [LocalFilePath("foo/bar/baz/yay".into())];
+
+        let shortcut = super::compute_shortcut(&paths, |_| false);
+        expect_that!(shortcut, eq(Path::new("foo/bar/baz")));
+        Ok(())
+    }
+
+    #[gtest]
+    fn single_dir() -> Result<()> {
+        let paths: [LocalFilePath; _] = [LocalFilePath("foo/bar/baz".into())];
+
+        let shortcut = super::compute_shortcut(&paths, |_| true);
+        expect_that!(shortcut, eq(Path::new("foo/bar/baz")));
+        Ok(())
+    }
+
     #[gtest]
     fn no_shortcut() -> Result<()> {
         let paths: [LocalFilePath; _] = [
@@ -125,7 +226,7 @@ mod localfilepath_tests {
             LocalFilePath("f/bar/aaaa/a".into()),
         ];
 
-        let shortcut = super::compute_shortcut(&paths);
+        let shortcut = super::compute_shortcut(&paths, |_| false);
         expect_that!(shortcut, eq(Path::new("")));
         Ok(())
     }
@@ -0,0 +1,40 @@
+use std::fs;
+
+use thiserror::Error;
+
+use crate::{MonjaProfile, RepoFilePath, SetName, repo};
+
+#[derive(Error, Debug)]
+pub enum PreviewSetError {
+    #[error("Unable to initialize the state of set `{0}`.")]
+    SetStateInitialization(SetName, #[source] Box<repo::StateInitializationError>),
+
+    #[error("Set not found in repo.")]
+    SetNotFound(SetName),
+}
+
+// what the set would pull, sorted by local path, along with whether each local file already exists.
+// only this set is read, so layering isn't considered: another targeted set may end up winning some of these.
+// nothing is modified, including the index.
+pub fn preview_set(
+    profile: &MonjaProfile,
+    set_name: &SetName,
+) -> Result<Vec<(RepoFilePath, bool)>, PreviewSetError> {
+    let set = repo::initialize_set_state(profile, set_name)
+        .map_err(|e| PreviewSetError::SetStateInitialization(set_name.clone(), Box::new(e)))?
+        .ok_or_else(|| PreviewSetError::SetNotFound(set_name.clone()))?;
+
+    let mut files: Vec<(RepoFilePath, bool)> = set
+        .locally_mapped_files
+        .into_values()
+        .map(|file| {
+            // a dangling symlink would still get overwritten
+            let exists =
+                fs::symlink_metadata(file.path.local_path.to_absolute_path(profile)).is_ok();
+            (file.path.into(), exists)
+        })
+        .collect();
+    files.sort_by(|(l, _), (r, _)| l.local_path.cmp(&r.local_path));
+
+    Ok(files)
+}
@@ -0,0 +1,62 @@
+use thiserror::Error;
+
+use crate::{ExecutionOptions, LocalFilePath, MonjaProfile, local, repo};
+
+#[derive(Error, Debug)]
+pub enum PruneIndexError {
+    #[error("Unable to initialize repo state.")]
+    RepoStateInitialization(Vec<repo::StateInitializationError>),
+
+    #[error("Failed to load or save monja-index.toml.")]
+    FileIndex(#[from] local::FileIndexError),
+}
+
+#[derive(Debug)]
+pub struct PruneIndexSuccess {
+    // sorted by path, along with the set the index had them in
+    pub entries_removed: Vec<(LocalFilePath, repo::SetName)>,
+}
+
+// unlike clean, this only touches the index, and only for files that are gone from both sides.
+// a file still in the repo would come back on the next pull, and a local file would show up as missing from its set.
+pub fn prune_index(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+) -> Result<PruneIndexSuccess, PruneIndexError> {
+    let repo =
+        repo::initialize_full_state(profile).map_err(PruneIndexError::RepoStateInitialization)?;
+    let mut index = local::FileIndex::load(profile, local::IndexKind::Current)?;
+
+    let mut stale: Vec<(local::FilePath, repo::SetName)> = index
+        .entries()
+        .filter(|(local_path, entry)| {
+            // symlink_metadata, so that a dangling symlink still counts as being there
+            let exists_locally = local_path
+                .to_absolute_path(profile)
+                .symlink_metadata()
+                .is_ok();
+            let tracked_by_set = repo
+                .sets
+                .get(&entry.set)
+                .is_some_and(|set| set.tracks_file(local_path));
+            !exists_locally && !tracked_by_set
+        })
+        .map(|(local_path, entry)| (local_path.clone(), entry.set.clone()))
+        .collect();
+    stale.sort_by(|a, b| a.0.as_ref().cmp(b.0.as_ref()));
+
+    for (local_path, _) in stale.iter() {
+        index.take(local_path);
+    }
+
+    if !opts.dry_run && !stale.is_empty() {
+        index.save(profile, local::IndexKind::Current)?;
+    }
+
+    Ok(PruneIndexSuccess {
+        entries_removed: stale
+            .into_iter()
+            .map(|(local_path, set_name)| (local_path.into(), set_name))
+            .collect(),
+    })
+}
@@ -1,10 +1,32 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    num::NonZeroUsize,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Mutex,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use relative_path::RelativePathBuf;
 use thiserror::Error;
 
 use crate::{
-    AbsolutePath, ExecutionOptions, LocalFilePath, MonjaProfile, RepoFilePath, SetName,
-    convert_set_repofile_result, local, repo, rsync::rsync,
+    AbsolutePath, ExecutionOptions, LocalFilePath, MonjaProfile, RepoFilePath, SetName, acl,
+    convert_set_repofile_result,
+    hook::{self, HookError, HookKind, HookRun},
+    local,
+    operation::{
+        clean::{self, CleanError},
+        history::{self, HistoryOperation},
+    },
+    repo,
+    rsync::{ProgressCallback, ProgressFn, RsyncError, RsyncStats, rsync},
+    secret,
+    template::{self, TemplateError},
 };
 
 #[derive(Error, Debug)]
@@ -12,27 +34,163 @@ pub enum PullError {
     #[error("Unable to initialize repo state.")]
     RepoStateInitialization(Vec<repo::StateInitializationError>),
 
+    #[error("Invalid skip pattern '{0}'.")]
+    SkipPattern(String, #[source] globset::Error),
+
+    #[error("The pre-pull hook failed.")]
+    Hook(#[from] HookError),
+
     #[error("Sets needed by the profile are missing from the repo.")]
     MissingSets(Vec<repo::SetName>),
 
+    #[error(
+        "Sets with a dest-root are only pulled when writing outside of the local root is allowed."
+    )]
+    DestRootNotAllowed(Vec<SetName>),
+
+    #[error("Failed to write '{0}' under its set's dest-root.")]
+    DestRootWrite(PathBuf, #[source] std::io::Error),
+
+    // sets is in profile order, starting with the set that has the file
+    #[error("'{path}' would be a file in one set but a directory in another.")]
+    PathConflict { path: PathBuf, sets: Vec<SetName> },
+
+    // sets are pulled concurrently, so there may be more than one failure.
+    // unless best_effort is set, local files that were already synced get restored.
     #[error("Failed to copy files via rsync.")]
-    Rsync(#[source] std::io::Error),
+    Rsync(Vec<(SetName, RsyncError)>),
+
+    #[error("Unable to compare '{0}' with the file in the repo.")]
+    Comparison(PathBuf, #[source] std::io::Error),
+
+    #[error("Unable to compare '{0}' with the file it overrides in an earlier set.")]
+    OverrideComparison(PathBuf, #[source] std::io::Error),
+
+    #[error("Failed to back up '{0}'.")]
+    Backup(PathBuf, #[source] std::io::Error),
+
+    #[error("Failed to stage pulled files in '{0}'.")]
+    Staging(PathBuf, #[source] std::io::Error),
+
+    #[error(
+        "Failed to undo a pull that failed partway through. Copies of the local files it overwrote are in '{0}'."
+    )]
+    Rollback(PathBuf, #[source] std::io::Error),
+
+    #[error("Failed to decrypt the secret file '{0}'.")]
+    Decrypt(PathBuf, #[source] std::io::Error),
+
+    #[error("Failed to render the template '{0}'.")]
+    Template(PathBuf, #[source] TemplateError),
+
+    #[error("Failed to set permissions of '{0}'.")]
+    Permissions(PathBuf, #[source] std::io::Error),
+
+    #[error("Failed to apply the ACLs recorded for '{0}'.")]
+    Acl(PathBuf, #[source] acl::AclError),
 
     #[error("Unable to save file index.")]
     FileIndex(#[from] local::FileIndexError),
 
     #[error("Error when walking local files to find out which are ignored.")]
     LocalWalk(#[from] local::LocalWalkError),
+
+    #[error("Failed to delete files that are no longer pulled.")]
+    Delete(#[source] CleanError),
+}
+
+// options specific to a single pull, as opposed to being persisted in the profile
+#[derive(Debug, Default)]
+pub struct PullOptions {
+    // globs matched against local paths. matching files are neither pulled nor added to the index.
+    pub skip: Vec<String>,
+    pub progress: Option<ProgressCallback>,
+    // after pulling, removes cleanable files, like `clean` without --full would
+    pub delete: bool,
+    // used instead of the profile's target_sets for just this pull, such as to try out a combination of sets.
+    // the index ends up reflecting these sets, as it would if the profile had them.
+    pub sets: Option<Vec<SetName>>,
+    // targeted sets missing from the repo are skipped instead of failing the pull, ending up in PullSuccess::skipped_sets
+    pub allow_missing: bool,
+    // when rsync fails, the files it was given are split up and retried until the failing ones are found.
+    // the rest get pulled, and the failing ones end up in PullSuccess::failed_files instead of failing the pull.
+    // slower when something fails, since each retry runs rsync again.
+    pub best_effort: bool,
+    // sets with a dest-root write outside of the local root, so they fail the pull unless this is set
+    pub allow_dest_roots: bool,
+}
+
+// what happened (or, in a dry run, would happen) to the local file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PullAction {
+    Created,
+    Overwritten,
+    Unchanged,
 }
 
 #[derive(Debug)]
 pub struct PullSuccess {
-    pub files_pulled: Vec<(SetName, Vec<RepoFilePath>)>,
+    pub files_pulled: Vec<(SetName, Vec<(RepoFilePath, PullAction)>)>,
 
     pub cleanable_files: Vec<LocalFilePath>,
+    // only populated when PullOptions::delete is set, in which case these are no longer in cleanable_files
+    pub deleted_files: Vec<LocalFilePath>,
+
+    // files whose permissions were changed to match what their set config specifies
+    pub permissions_adjusted: Vec<LocalFilePath>,
+
+    // only populated when ExecutionOptions::backup is set.
+    // in a dry run, these are the files that would have been backed up.
+    pub files_backed_up: Vec<LocalFilePath>,
+    pub backup_dir: Option<PathBuf>,
+
+    // files in the targeted sets that matched PullOptions::skip
+    pub skipped_files: Vec<LocalFilePath>,
+    // targeted sets missing from the repo, which only get skipped with PullOptions::allow_missing
+    pub skipped_sets: Vec<SetName>,
+
+    // what was actually copied locally, including decrypted secrets. always empty in a dry run.
+    pub transfer_stats: RsyncStats,
+
+    // only populated when PullOptions::best_effort is set, with the files rsync couldn't pull.
+    // these aren't in files_pulled, and their index entries are left as they were.
+    pub failed_files: Vec<(RepoFilePath, RsyncError)>,
+
+    // files pulled from sets with a dest-root, as absolute paths under it, in profile order.
+    // in a dry run, these are the files that would be copied.
+    pub dest_root_files: Vec<(SetName, Vec<PathBuf>)>,
+
+    // files that a later set overrides with the exact same contents, as (file, overridden set, overriding set).
+    // these are usually a sign that the file should only be in one of the sets.
+    pub redundant_overrides: Vec<(LocalFilePath, SetName, SetName)>,
+
+    // whether the repo has uncommitted changes, which can make later pushes and pulls confusing.
+    // None if git isn't installed or the repo isn't in a git working tree.
+    pub repo_dirty: Option<bool>,
+
+    pub hooks_run: Vec<HookRun>,
+    // a failing post-pull hook doesn't fail the pull, since the files have already been pulled
+    pub post_hook_error: Option<HookError>,
 }
 
-pub fn pull(profile: &MonjaProfile, opts: &ExecutionOptions) -> Result<PullSuccess, PullError> {
+pub fn pull(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    pull_opts: &PullOptions,
+) -> Result<PullSuccess, PullError> {
+    let skip = build_skip_set(&pull_opts.skip)?;
+
+    let overridden_profile;
+    let profile = match &pull_opts.sets {
+        Some(sets) => {
+            overridden_profile = profile.with_target_sets(sets.clone());
+            &overridden_profile
+        }
+        None => profile,
+    };
+
+    let mut hooks_run = Vec::new();
+    hooks_run.extend(hook::run(profile, opts, HookKind::PrePull)?);
     let mut set_info = HashMap::with_capacity(profile.config.target_sets.len());
 
     let mut repo =
@@ -42,14 +200,26 @@ pub fn pull(profile: &MonjaProfile, opts: &ExecutionOptions) -> Result<PullSucce
     let mut files: HashMap<local::FilePath, repo::File> = HashMap::new();
 
     let mut missing_sets = Vec::new();
+    let mut dest_root_sets = Vec::new();
+    let mut disallowed_dest_roots = Vec::new();
+    let mut redundant_overrides: Vec<(LocalFilePath, SetName, SetName)> = Vec::new();
     for set_name in profile.config.target_sets.iter() {
+        // they don't layer with the other sets, since their files go somewhere else entirely
+        if let Some(set) = repo.dest_root_sets.remove(set_name) {
+            match pull_opts.allow_dest_roots {
+                true => dest_root_sets.push(set),
+                false => disallowed_dest_roots.push(set_name.clone()),
+            }
+            continue;
+        }
+
         if !repo.sets.contains_key(set_name) {
             missing_sets.push(set_name.clone());
             continue;
         };
 
         // if we find a missing set, save us the trouble of handling files
-        if !missing_sets.is_empty() {
+        if !missing_sets.is_empty() && !pull_opts.allow_missing {
             continue;
         }
 
@@ -62,75 +232,455 @@ pub fn pull(profile: &MonjaProfile, opts: &ExecutionOptions) -> Result<PullSucce
             SetInfo {
                 root: set.root,
                 shortcut: set.shortcut,
+                dir_mappings: set.dir_mappings,
             },
         );
 
         for (local_path, repo_file) in set.locally_mapped_files.into_iter() {
+            if let Some(overridden) = files.get(&local_path)
+                && is_redundant(&set_info, overridden, &repo_file)?
+            {
+                redundant_overrides.push((
+                    local_path.clone().into(),
+                    overridden.owning_set.clone(),
+                    set_name.clone(),
+                ));
+            }
             files.insert(local_path, repo_file);
         }
     }
     // since we removed from the sets to get ownership of them, we want to move sets to ensure it doesn't get used.
     std::mem::drop(repo.sets);
 
-    if !missing_sets.is_empty() {
+    if !missing_sets.is_empty() && !pull_opts.allow_missing {
         return Err(PullError::MissingSets(missing_sets));
     }
+    if !disallowed_dest_roots.is_empty() {
+        return Err(PullError::DestRootNotAllowed(disallowed_dest_roots));
+    }
+    // stable, so overrides of the same file stay in profile order
+    redundant_overrides.sort_by(|a, b| a.0.cmp(&b.0));
+
+    // rsync would otherwise fail partway through, and confusingly
+    if let Some((path, sets)) = find_path_conflict(profile, &files, &skip) {
+        return Err(PullError::PathConflict { path, sets });
+    }
 
     let mut files_to_pull = HashMap::with_capacity(set_info.len());
     let mut updated_index = local::FileIndex::new();
+    let mut file_modes = Vec::new();
+    let mut file_acls = Vec::new();
+    let mut files_to_back_up = Vec::new();
+    let mut skipped_files: Vec<LocalFilePath> = Vec::new();
+    let mut actions = HashMap::new();
+    let mut transfer_stats = RsyncStats::default();
+    // secrets get decrypted instead of going through rsync
+    let mut secret_files = HashMap::new();
+    // templates get rendered up front, so that undefined variables fail the pull before anything is copied
+    let mut template_files = HashMap::new();
     for (local_path, repo_file) in files.into_iter() {
+        if skip.is_match(local_path.as_ref().to_path("")) {
+            log::debug!("Skipping '{}'", local_path.as_ref());
+            skipped_files.push(local_path.into());
+            continue;
+        }
+
+        let set = set_info
+            .get(&repo_file.owning_set)
+            .expect("Already checked for missing sets.");
+        let repo_abs_path = repo_file.path.path_in_set.to_path(&set.root);
+        let local_abs_path = local_path.to_absolute_path(profile);
+
+        let rendered = match repo_file.template {
+            true => Some(
+                template::render_file(&repo_abs_path, &profile.config.vars)
+                    .map_err(|e| PullError::Template(repo_abs_path.clone(), e))?,
+            ),
+            false => None,
+        };
+
+        let action = pull_action(
+            &local_abs_path,
+            &repo_abs_path,
+            repo_file.secret,
+            rendered.as_deref(),
+        )
+        .map_err(|e| PullError::Comparison(local_abs_path.clone(), e))?;
+        actions.insert(local_path.as_ref().to_path(""), action);
+        if repo_file.secret {
+            secret_files.insert(local_path.clone(), (repo_abs_path.clone(), action));
+        }
+
+        // only files that would actually change get backed up.
+        // and if the local path is something like a directory, rsync will fail on it anyway.
+        if opts.backup
+            && action == PullAction::Overwritten
+            && fs::metadata(&local_abs_path).is_ok_and(|m| m.is_file())
+        {
+            files_to_back_up.push(local_path.clone());
+        }
+
         files_to_pull
             .entry(repo_file.owning_set.clone())
             .or_insert_with(Vec::new)
             .push(repo_file.path);
 
+        if let Some(mode) = repo_file.mode {
+            file_modes.push((local_path.clone(), mode));
+        }
+        if let Some(acl) = &repo_file.acl {
+            file_acls.push((local_path.clone(), acl.clone()));
+        }
+
+        // after the pull, the local file will match the repo's, so that's what gets hashed
+        let hash = match (repo_file.secret, rendered) {
+            (true, _) => secret::hash_decrypted(&repo_abs_path)
+                .map(Some)
+                .map_err(|e| PullError::Decrypt(local_abs_path.clone(), e))?,
+            (false, Some(rendered)) => {
+                let hash = local::hash_bytes(&rendered);
+                template_files.insert(local_path.clone(), (repo_abs_path, rendered, action));
+                Some(hash)
+            }
+            (false, None) => local::hash_for_index(&repo_abs_path)?,
+        };
+
         // TODO: what if rsync failed and we don't update index even though some copies happened?
-        updated_index.set(local_path, repo_file.owning_set);
+        updated_index.set(local_path, repo_file.owning_set, hash);
     }
 
+    files_to_back_up.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+    let backup_dir = match files_to_back_up.is_empty() {
+        true => None,
+        false => Some(back_up(profile, opts, &files_to_back_up)?),
+    };
+
+    let prev_index = local::FileIndex::load(profile, local::IndexKind::Current)?;
+    let mut failed_files = Vec::new();
+
     if !opts.dry_run {
-        for set_name in profile.config.target_sets.iter() {
-            let Some(file_paths) = files_to_pull.get(set_name) else {
-                // would happen if there are no files to pull for the set
-                continue;
-            };
-            let set = set_info
-                .get(set_name)
-                .expect("Already checked for missing sets.");
+        // everything is first copied into a staging dir laid out like the local root, where nothing can get in the way.
+        // only then is it synced into place, which gets undone if it fails partway.
+        // so unless best_effort is set, a failed pull leaves both the local files and the index as they were.
+        let mut staging = Staging::new(profile)?;
 
-            // lets say set shortcut is foo/bar and file baz
-            // transfer looks something like this: /monja/set/baz -> /home/xx/foo/bar/baz
-            // here, the source is /monja/set/, dest is /home/xx/foo/bar/, and file is baz
-            // incidentally, local::FilePath is foo/bar/baz
+        // sets are independent of each other, since each local file is only pulled from one set.
+        // so we rsync them concurrently, keeping to profile order for anything we report.
+        let jobs: Vec<(&SetName, &SetInfo, Vec<&repo::FilePath>)> = profile
+            .config
+            .target_sets
+            .iter()
+            .filter_map(|set_name| {
+                // would be missing if there are no files to pull for the set
+                let file_paths: Vec<_> = files_to_pull
+                    .get(set_name)?
+                    .iter()
+                    .filter(|p| {
+                        !secret_files.contains_key(&p.local_path)
+                            && !template_files.contains_key(&p.local_path)
+                    })
+                    .collect();
+                let set = set_info
+                    .get(set_name)
+                    .expect("Already checked for missing sets.");
+                Some((set_name, set, file_paths))
+            })
+            .filter(|(_, _, file_paths)| !file_paths.is_empty())
+            .collect();
 
-            rsync(
-                set.root.as_ref(),
-                &set.shortcut.to_path(&profile.local_root),
-                file_paths.iter().map(|p| p.path_in_set.to_path("")),
+        let results = run_concurrently(&jobs, |(set_name, set, file_paths)| {
+            log::debug!("Staging {} files from set `{}`", file_paths.len(), set_name);
+            stage_set(
+                profile,
                 opts,
+                set,
+                file_paths,
+                &staging.staged_dir,
+                pull_opts.best_effort,
             )
-            .map_err(PullError::Rsync)?;
+        });
+        let mut failures = Vec::new();
+        // as local paths, since files fail either while staging or while being synced into place
+        let mut set_failed_files: Vec<(SetName, RelativePathBuf, RsyncError)> = Vec::new();
+        for ((set_name, _, file_paths), result) in jobs.iter().zip(results) {
+            match result {
+                Ok(failed) => {
+                    set_failed_files.extend(failed.into_iter().map(|(path_in_set, e)| {
+                        let file_path = file_paths
+                            .iter()
+                            .find(|p| p.path_in_set == path_in_set)
+                            .expect("Failed files come from the set's files.");
+                        let local_path = file_path.local_path.as_ref().to_relative_path_buf();
+                        ((*set_name).clone(), local_path, e)
+                    }))
+                }
+                Err(e) => failures.push(((*set_name).clone(), e)),
+            }
+        }
+        if !failures.is_empty() {
+            return Err(PullError::Rsync(failures));
+        }
+
+        for (local_path, (repo_abs_path, action)) in secret_files.iter() {
+            if *action == PullAction::Unchanged {
+                continue;
+            }
+            let staged_path = local_path.as_ref().to_path(&staging.staged_dir);
+            secret::write_decrypted(repo_abs_path, &staged_path)
+                .map_err(|e| PullError::Decrypt(local_path.to_absolute_path(profile), e))?;
+        }
+        for (local_path, (repo_abs_path, rendered, action)) in template_files.iter() {
+            if *action == PullAction::Unchanged {
+                continue;
+            }
+            let staged_path = local_path.as_ref().to_path(&staging.staged_dir);
+            crate::write_pulled_contents(rendered, repo_abs_path, &staged_path)
+                .map_err(|e| PullError::Staging(staged_path, e))?;
         }
+
+        // unchanged secrets and templates weren't written, since there's nothing to sync for them
+        let failed_paths: HashSet<RelativePathBuf> = set_failed_files
+            .iter()
+            .map(|(_, local_path, _)| local_path.clone())
+            .collect();
+        let is_staged = |p: &repo::FilePath| {
+            let written_directly = secret_files.contains_key(&p.local_path)
+                || template_files.contains_key(&p.local_path);
+            let changed = actions[&p.local_path.as_ref().to_path("")] != PullAction::Unchanged;
+            !failed_paths.contains(p.local_path.as_ref()) && (changed || !written_directly)
+        };
+        let commit_jobs: Vec<(&SetName, Vec<RelativePathBuf>)> = profile
+            .config
+            .target_sets
+            .iter()
+            .filter_map(|set_name| {
+                let mut local_paths: Vec<RelativePathBuf> = files_to_pull
+                    .get(set_name)?
+                    .iter()
+                    .filter(|p| is_staged(p))
+                    .map(|p| p.local_path.as_ref().to_relative_path_buf())
+                    .collect();
+                local_paths.sort();
+                Some((set_name, local_paths))
+            })
+            .filter(|(_, local_paths)| !local_paths.is_empty())
+            .collect();
+
+        // unchanged files are left as they are by rsync, so they don't need to be restored
+        let rollback = Rollback::prepare(
+            profile,
+            staging.dir.join("rollback"),
+            commit_jobs
+                .iter()
+                .flat_map(|(_, local_paths)| local_paths)
+                .filter(|p| actions[&p.to_path("")] != PullAction::Unchanged),
+        )?;
+
+        let results = run_concurrently(&commit_jobs, |(set_name, local_paths)| {
+            log::info!(
+                "Pulling {} files from set `{}`",
+                local_paths.len(),
+                set_name
+            );
+            // the staging dir mirrors the local root, so rsync's paths are already what progress expects
+            let transfer = |files: &[RelativePathBuf]| {
+                rsync(
+                    &staging.staged_dir,
+                    &profile.local_root,
+                    files.iter().map(|p| p.to_path("")),
+                    // symlinks in the set were deliberately preserved, so they stay links
+                    false,
+                    &profile.config.rsync_extra_args,
+                    opts,
+                    pull_opts
+                        .progress
+                        .as_ref()
+                        .map(|p| &*p.0 as &ProgressFn<'_>),
+                )
+            };
+            let mut failed = Vec::new();
+            let stats = match pull_opts.best_effort {
+                true => transfer_best_effort(local_paths, &transfer, &mut failed),
+                false => transfer(local_paths)?,
+            };
+            Ok::<_, RsyncError>((stats, failed))
+        });
+        for ((set_name, _), result) in commit_jobs.iter().zip(results) {
+            match result {
+                Ok((stats, failed)) => {
+                    transfer_stats += stats;
+                    set_failed_files.extend(
+                        failed
+                            .into_iter()
+                            .map(|(local_path, e)| ((*set_name).clone(), local_path, e)),
+                    );
+                }
+                Err(e) => failures.push(((*set_name).clone(), e)),
+            }
+        }
+        if !failures.is_empty() {
+            if let Err(e) = rollback.restore(profile) {
+                // the copies of the overwritten files are the only ones left
+                staging.keep = true;
+                return Err(PullError::Rollback(rollback.dir, e));
+            }
+            return Err(PullError::Rsync(failures));
+        }
+
+        // the local files are whatever they were before, so their index entries are, too
+        for (set_name, local_path, error) in set_failed_files {
+            let set_files = files_to_pull
+                .get_mut(&set_name)
+                .expect("Failed files come from files_to_pull.");
+            let pos = set_files
+                .iter()
+                .position(|p| p.local_path.as_ref() == local_path)
+                .expect("Failed files come from files_to_pull.");
+            let repo_file = set_files.remove(pos);
+            match prev_index.get(&repo_file.local_path) {
+                Some(entry) => updated_index.set(
+                    repo_file.local_path.clone(),
+                    entry.set.clone(),
+                    entry.hash.clone(),
+                ),
+                None => {
+                    updated_index.take(&repo_file.local_path);
+                }
+            }
+            file_modes.retain(|(local_path, _)| *local_path != repo_file.local_path);
+            file_acls.retain(|(local_path, _)| *local_path != repo_file.local_path);
+            failed_files.push((RepoFilePath::from(repo_file), error));
+        }
+        failed_files.sort_by(|a, b| a.0.local_path.cmp(&b.0.local_path));
     }
 
-    let prev_index = local::FileIndex::load(profile, local::IndexKind::Current)?;
+    // rsync already preserves the modes of files in the repo, but git only tracks the execute bit.
+    // so files like private keys need their modes applied explicitly.
+    // ownership is left alone, since local files are all expected to be owned by the user.
+    let mut permissions_adjusted = Vec::with_capacity(file_modes.len());
+    for (local_path, mode) in file_modes {
+        let path = local_path.to_absolute_path(profile);
+        // in a dry run, the file may not exist locally yet, in which case it would get adjusted
+        let current_mode = fs::metadata(&path)
+            .ok()
+            .map(|m| m.permissions().mode() & 0o7777);
+        if current_mode == Some(mode) {
+            continue;
+        }
+
+        if !opts.dry_run {
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode))
+                .map_err(|e| PullError::Permissions(path, e))?;
+        }
+        permissions_adjusted.push(local_path.into());
+    }
+    permissions_adjusted.sort();
+    skipped_files.sort();
+
+    // git doesn't keep ACLs, so the recorded ones are applied after the modes, since a chmod would change their mask
     if !opts.dry_run {
+        for (local_path, acl) in file_acls {
+            let path = local_path.to_absolute_path(profile);
+            acl::apply(&path, &acl).map_err(|e| PullError::Acl(path, e))?;
+        }
+    }
+
+    if !opts.dry_run {
+        updated_index.set_last_pull(SystemTime::now());
+        log::debug!("Saving the file index");
         updated_index.save(profile, local::IndexKind::Current)?;
         // could also hypothetically copy the file. in fact, it's technically better, but it doesn't really matter.
-        prev_index.save(profile, local::IndexKind::Previous)?;
+        // a pull that didn't change anything leaves the previous index alone, so that an index-mode clean
+        // still knows what the last real change to the index left behind.
+        if !updated_index.has_same_entries(&prev_index) {
+            prev_index.save(profile, local::IndexKind::Previous)?;
+        }
     }
 
-    let files_pulled = convert_set_repofile_result(&profile.config.target_sets, files_to_pull);
-    let cleanable_files = prev_index
-        .into_files_not_in(profile, &updated_index)?
-        .into_iter()
-        .map(|f| f.into())
-        .collect();
+    // after the index is saved, since a failure here doesn't change what happened to the local files
+    let mut dest_root_files = Vec::with_capacity(dest_root_sets.len());
+    for set in dest_root_sets {
+        let files = pull_dest_root_set(profile, opts, &set)?;
+        dest_root_files.push((set.name, files));
+    }
+
+    let files_pulled: Vec<(SetName, Vec<(RepoFilePath, PullAction)>)> =
+        convert_set_repofile_result(&profile.config.target_sets, files_to_pull)
+            .into_iter()
+            .map(|(set_name, file_paths)| {
+                let file_paths = file_paths
+                    .into_iter()
+                    .map(|p| {
+                        let action = actions[&p.local_path];
+                        (p, action)
+                    })
+                    .collect();
+                (set_name, file_paths)
+            })
+            .collect();
+    // .monjaignore'd files are left out, so they're never deleted
+    let cleanable_files = prev_index.into_files_not_in(profile, &updated_index)?;
+    let (cleanable_files, deleted_files) = match pull_opts.delete {
+        true => {
+            let cleaned =
+                clean::remove_files(profile, opts, cleanable_files, Vec::new(), &|_| true)
+                    .map_err(PullError::Delete)?;
+            (Vec::new(), cleaned.files_cleaned)
+        }
+        false => (
+            cleanable_files.into_iter().map(|f| f.into()).collect(),
+            Vec::new(),
+        ),
+    };
+
+    history::record(
+        profile,
+        opts,
+        HistoryOperation::Pull,
+        [
+            (
+                "files-pulled",
+                files_pulled.iter().map(|(_, files)| files.len()).sum(),
+            ),
+            ("files-failed", failed_files.len()),
+            ("files-deleted", deleted_files.len()),
+        ],
+    );
+
+    let post_hook_error = hook::run_post(profile, opts, HookKind::PostPull, &mut hooks_run);
     return Ok(PullSuccess {
         files_pulled,
         cleanable_files,
+        deleted_files,
+        permissions_adjusted,
+        files_backed_up: files_to_back_up.into_iter().map(|f| f.into()).collect(),
+        backup_dir,
+        skipped_files,
+        transfer_stats,
+        failed_files,
+        dest_root_files,
+        redundant_overrides,
+        skipped_sets: missing_sets,
+        // after the hooks, in case they commit or pull
+        repo_dirty: repo_dirty(profile),
+        hooks_run,
+        post_hook_error,
     });
 
+    // only advice, so any trouble running git just means we don't know
+    fn repo_dirty(profile: &MonjaProfile) -> Option<bool> {
+        // limited to the repo dir, in case the git repo starts further up
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&*profile.repo_root)
+            .args(["status", "--porcelain", "--", "."])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        output.status.success().then_some(!output.stdout.is_empty())
+    }
+
     // the code ends up being the cleanest when files takes ownership of its data from repo,
     // since that data becomes part of the result.
     // in order to take ownership, we .remove() them (from sets).
@@ -140,5 +690,457 @@ pub fn pull(profile: &MonjaProfile, opts: &ExecutionOptions) -> Result<PullSucce
     struct SetInfo {
         root: AbsolutePath,
         shortcut: repo::SetShortcut,
+        dir_mappings: Vec<repo::DirMapping>,
+    }
+
+    // secrets are encrypted differently every time, so they can't be compared without decrypting them.
+    // and symlinks are left out, since their targets could be anywhere.
+    fn is_redundant(
+        set_info: &HashMap<&SetName, SetInfo>,
+        overridden: &repo::File,
+        overriding: &repo::File,
+    ) -> Result<bool, PullError> {
+        if overridden.secret || overriding.secret || overridden.template != overriding.template {
+            return Ok(false);
+        }
+
+        let path_for = |file: &repo::File| {
+            let set = set_info
+                .get(&file.owning_set)
+                .expect("Sets are added to set_info before their files.");
+            file.path.path_in_set.to_path(&set.root)
+        };
+        let overridden_path = path_for(overridden);
+        let overriding_path = path_for(overriding);
+        if overridden_path.is_symlink() || overriding_path.is_symlink() {
+            return Ok(false);
+        }
+
+        let hash = |path: &Path| {
+            local::hash_contents(path)
+                .map_err(|e| PullError::OverrideComparison(path.to_path_buf(), e))
+        };
+        Ok(hash(&overridden_path)? == hash(&overriding_path)?)
+    }
+
+    // a file that another file would be under. skipped files can't conflict, so they can be used to work around one.
+    // if there are several, the first by path is picked so that the error is consistent.
+    fn find_path_conflict(
+        profile: &MonjaProfile,
+        files: &HashMap<local::FilePath, repo::File>,
+        skip: &GlobSet,
+    ) -> Option<(PathBuf, Vec<SetName>)> {
+        let is_pulled = |path: &local::FilePath| !skip.is_match(path.as_ref().to_path(""));
+        let conflicting_file = files
+            .keys()
+            .filter(|path| is_pulled(path))
+            .filter_map(|path| {
+                std::iter::successors(path.parent(), |dir| dir.parent())
+                    .find(|dir| files.contains_key(dir) && is_pulled(dir))
+            })
+            .min_by(|a, b| a.as_ref().cmp(b.as_ref()))?;
+
+        let file_set = &files[&conflicting_file].owning_set;
+        let mut dir_sets: Vec<&SetName> = files
+            .iter()
+            .filter(|(path, _)| **path != conflicting_file && path.is_child_of(&conflicting_file))
+            .filter(|(path, _)| is_pulled(path))
+            .map(|(_, file)| &file.owning_set)
+            .filter(|set| *set != file_set)
+            .collect();
+        dir_sets.sort_by_key(|set| profile.config.target_sets.iter().position(|s| s == *set));
+        dir_sets.dedup();
+
+        let sets = std::iter::once(file_set).chain(dir_sets).cloned().collect();
+        Some((conflicting_file.as_ref().to_path(""), sets))
+    }
+
+    fn build_skip_set(patterns: &[String]) -> Result<GlobSet, PullError> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob =
+                Glob::new(pattern).map_err(|e| PullError::SkipPattern(pattern.clone(), e))?;
+            builder.add(glob);
+        }
+        // individual globs were already validated, so there's no particular pattern to blame
+        builder
+            .build()
+            .map_err(|e| PullError::SkipPattern(patterns.join(", "), e))
+    }
+
+    fn pull_action(
+        local_path: &Path,
+        repo_path: &Path,
+        secret: bool,
+        rendered: Option<&[u8]>,
+    ) -> std::io::Result<PullAction> {
+        let Ok(local_metadata) = fs::symlink_metadata(local_path) else {
+            return Ok(PullAction::Created);
+        };
+        if let Some(rendered) = rendered {
+            let same = local_metadata.is_file() && fs::read(local_path)? == rendered;
+            return Ok(match same {
+                true => PullAction::Unchanged,
+                false => PullAction::Overwritten,
+            });
+        }
+        if secret {
+            return Ok(match secret::local_differs(local_path, repo_path)? {
+                true => PullAction::Overwritten,
+                false => PullAction::Unchanged,
+            });
+        }
+        let repo_metadata = fs::symlink_metadata(repo_path)?;
+
+        // links in sets are pulled as links, so they're compared by where they point.
+        // they may be relative to somewhere local, so the repo's link can't be followed.
+        if local_metadata.is_symlink() || repo_metadata.is_symlink() {
+            let same_target = local_metadata.is_symlink()
+                && repo_metadata.is_symlink()
+                && fs::read_link(local_path)? == fs::read_link(repo_path)?;
+            return Ok(match same_target {
+                true => PullAction::Unchanged,
+                false => PullAction::Overwritten,
+            });
+        }
+
+        if !local_metadata.is_file() {
+            return Ok(PullAction::Overwritten);
+        }
+
+        Ok(match crate::contents_differ(local_path, repo_path)? {
+            true => PullAction::Overwritten,
+            false => PullAction::Unchanged,
+        })
+    }
+
+    fn back_up(
+        profile: &MonjaProfile,
+        opts: &ExecutionOptions,
+        files: &[local::FilePath],
+    ) -> Result<PathBuf, PullError> {
+        // millis so that pulls in quick succession don't share a backup dir
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("The clock is after 1970.")
+            .as_millis();
+        let backup_dir = profile
+            .data_root
+            .join("monja-backup")
+            .join(timestamp.to_string());
+
+        if opts.dry_run {
+            return Ok(backup_dir);
+        }
+
+        for file in files {
+            let backup_path = file.as_ref().to_path(&backup_dir);
+            let backup_path_dir = backup_path.parent().expect("Joined onto backup_dir.");
+            fs::create_dir_all(backup_path_dir)
+                .and_then(|_| fs::copy(file.to_absolute_path(profile), &backup_path))
+                .map_err(|e| PullError::Backup(backup_path.clone(), e))?;
+        }
+
+        Ok(backup_dir)
+    }
+
+    // like restore, but under the set's dest root instead of the local root.
+    // every file is copied each time, since there's no index entry to tell whether it changed.
+    fn pull_dest_root_set(
+        profile: &MonjaProfile,
+        opts: &ExecutionOptions,
+        set: &repo::Set,
+    ) -> Result<Vec<PathBuf>, PullError> {
+        let dest_root = set
+            .dest_root
+            .as_deref()
+            .expect("Only sets with a dest root are pulled this way.");
+        let mut files: Vec<&repo::File> = set.locally_mapped_files.values().collect();
+        files.sort_by(|a, b| a.path.local_path.as_ref().cmp(b.path.local_path.as_ref()));
+        let dest_path = |file: &repo::File| file.path.local_path.as_ref().to_path(dest_root);
+        let files_pulled = files.iter().map(|f| dest_path(f)).collect();
+        if opts.dry_run {
+            return Ok(files_pulled);
+        }
+
+        log::info!(
+            "Pulling {} files from set `{}` into '{}'",
+            files.len(),
+            set.name,
+            dest_root.display()
+        );
+        fs::create_dir_all(dest_root)
+            .map_err(|e| PullError::DestRootWrite(dest_root.to_path_buf(), e))?;
+        let groups = repo::group_for_transfer(
+            &set.shortcut,
+            &set.dir_mappings,
+            files
+                .iter()
+                .filter(|f| !f.secret && !f.template)
+                .map(|f| f.path.path_in_set.as_relative_path()),
+        );
+        for group in groups {
+            rsync(
+                &group.dir_in_set.to_path(&set.root),
+                &group.local_dir.to_path(dest_root),
+                group.files.iter().map(|p| p.to_path("")),
+                false,
+                &profile.config.rsync_extra_args,
+                opts,
+                None,
+            )
+            .map_err(|e| PullError::Rsync(vec![(set.name.clone(), e)]))?;
+        }
+
+        for file in files.iter() {
+            let repo_path = file.path.path_in_set.to_path(&set.root);
+            let path = dest_path(file);
+            if file.secret {
+                secret::write_decrypted(&repo_path, &path)
+                    .map_err(|e| PullError::Decrypt(path.clone(), e))?;
+            } else if file.template {
+                let rendered = template::render_file(&repo_path, &profile.config.vars)
+                    .map_err(|e| PullError::Template(repo_path.clone(), e))?;
+                crate::write_pulled_contents(&rendered, &repo_path, &path)
+                    .map_err(|e| PullError::DestRootWrite(path.clone(), e))?;
+            }
+
+            if let Some(mode) = file.mode {
+                fs::set_permissions(&path, fs::Permissions::from_mode(mode))
+                    .map_err(|e| PullError::Permissions(path, e))?;
+            }
+        }
+
+        Ok(files_pulled)
+    }
+
+    // copies the set's files into the staging dir, returning those that failed as paths in the set.
+    // these can only fail with best_effort.
+    fn stage_set(
+        profile: &MonjaProfile,
+        opts: &ExecutionOptions,
+        set: &SetInfo,
+        file_paths: &[&repo::FilePath],
+        staged_dir: &Path,
+        best_effort: bool,
+    ) -> Result<Vec<(RelativePathBuf, RsyncError)>, RsyncError> {
+        // lets say set shortcut is foo/bar and file baz
+        // transfer looks something like this: /monja/set/baz -> <staging>/foo/bar/baz
+        // here, the source is /monja/set/, dest is <staging>/foo/bar/, and file is baz
+        // incidentally, local::FilePath is foo/bar/baz
+        // directories with their own shortcut in .monja-dir.toml get their own transfer, in the same way.
+        let groups = repo::group_for_transfer(
+            &set.shortcut,
+            &set.dir_mappings,
+            file_paths.iter().map(|p| p.path_in_set.as_relative_path()),
+        );
+        let mut failed_files = Vec::new();
+        for group in groups {
+            let transfer = |files: &[RelativePathBuf]| {
+                rsync(
+                    &group.dir_in_set.to_path(&set.root),
+                    &group.local_dir.to_path(staged_dir),
+                    files.iter().map(|p| p.to_path("")),
+                    false,
+                    &profile.config.rsync_extra_args,
+                    opts,
+                    None,
+                )
+            };
+            let mut group_failed_files = Vec::new();
+            match best_effort {
+                true => {
+                    transfer_best_effort(&group.files, &transfer, &mut group_failed_files);
+                }
+                false => {
+                    transfer(&group.files)?;
+                }
+            }
+            failed_files.extend(
+                group_failed_files
+                    .into_iter()
+                    .map(|(file, e)| (group.dir_in_set.join(file), e)),
+            );
+        }
+
+        Ok(failed_files)
+    }
+
+    // files that already made it in a failed attempt are unchanged when retried,
+    // so the stats can come up short of what was actually copied
+    fn transfer_best_effort(
+        files: &[RelativePathBuf],
+        transfer: &dyn Fn(&[RelativePathBuf]) -> Result<RsyncStats, RsyncError>,
+        failed_files: &mut Vec<(RelativePathBuf, RsyncError)>,
+    ) -> RsyncStats {
+        match transfer(files) {
+            Ok(stats) => stats,
+            Err(e) if files.len() == 1 => {
+                failed_files.push((files[0].clone(), e));
+                RsyncStats::default()
+            }
+            Err(_) => {
+                let (left, right) = files.split_at(files.len() / 2);
+                let mut stats = transfer_best_effort(left, transfer, failed_files);
+                stats += transfer_best_effort(right, transfer, failed_files);
+                stats
+            }
+        }
+    }
+
+    // removed once the pull is done with it, whether or not it succeeded
+    struct Staging {
+        dir: PathBuf,
+        // laid out like the local root
+        staged_dir: PathBuf,
+        // for when a rollback fails, leaving the copies of the overwritten files for the user to recover
+        keep: bool,
+    }
+
+    impl Staging {
+        fn new(profile: &MonjaProfile) -> Result<Staging, PullError> {
+            let dir = profile.data_root.join("monja-pull");
+            // would be left over from a pull that was interrupted.
+            // that pull's local files may be partly synced, but pulling again syncs the rest.
+            if dir.exists() {
+                fs::remove_dir_all(&dir).map_err(|e| PullError::Staging(dir.clone(), e))?;
+            }
+            Ok(Staging {
+                staged_dir: dir.join("staged"),
+                dir,
+                keep: false,
+            })
+        }
+    }
+
+    impl Drop for Staging {
+        fn drop(&mut self) {
+            if self.keep || !self.dir.exists() {
+                return;
+            }
+            // only leaves clutter, which the next pull removes anyway
+            if let Err(e) = fs::remove_dir_all(&self.dir) {
+                log::warn!("Unable to remove '{}': {}", self.dir.display(), e);
+            }
+        }
+    }
+
+    // enough of the local root's state from before syncing the staged files to put it back if that fails
+    struct Rollback {
+        // copies of the local files about to be overwritten, laid out like the local root
+        dir: PathBuf,
+        backed_up: Vec<RelativePathBuf>,
+        created: Vec<RelativePathBuf>,
+        // deepest first
+        created_dirs: Vec<PathBuf>,
+    }
+
+    impl Rollback {
+        fn prepare<'a>(
+            profile: &MonjaProfile,
+            dir: PathBuf,
+            local_paths: impl Iterator<Item = &'a RelativePathBuf>,
+        ) -> Result<Rollback, PullError> {
+            let mut backed_up = Vec::new();
+            let mut created = Vec::new();
+            let mut created_dirs = Vec::new();
+            for local_path in local_paths {
+                let local_abs_path = local_path.to_path(&profile.local_root);
+                match fs::symlink_metadata(&local_abs_path) {
+                    // rsync fails on these anyway, so there's nothing to restore
+                    Ok(metadata) if metadata.is_dir() => {}
+                    Ok(_) => {
+                        let backup_path = local_path.to_path(&dir);
+                        copy_as_is(&local_abs_path, &backup_path)
+                            .map_err(|e| PullError::Staging(backup_path, e))?;
+                        backed_up.push(local_path.clone());
+                    }
+                    // including when something other than a directory is in the way, which rsync also fails on
+                    Err(_) => {
+                        created_dirs.extend(
+                            local_abs_path
+                                .ancestors()
+                                .skip(1)
+                                .take_while(|d| d.symlink_metadata().is_err())
+                                .map(Path::to_path_buf),
+                        );
+                        created.push(local_path.clone());
+                    }
+                }
+            }
+            created_dirs.sort();
+            created_dirs.dedup();
+            created_dirs.reverse();
+
+            Ok(Rollback {
+                dir,
+                backed_up,
+                created,
+                created_dirs,
+            })
+        }
+
+        // modification times aren't restored, but rsync compares checksums anyway
+        fn restore(&self, profile: &MonjaProfile) -> std::io::Result<()> {
+            let remove_file = |path: &Path| match fs::symlink_metadata(path) {
+                Ok(metadata) if !metadata.is_dir() => fs::remove_file(path),
+                _ => Ok(()),
+            };
+            for local_path in self.created.iter() {
+                remove_file(&local_path.to_path(&profile.local_root))?;
+            }
+            for local_path in self.backed_up.iter() {
+                let local_abs_path = local_path.to_path(&profile.local_root);
+                remove_file(&local_abs_path)?;
+                copy_as_is(&local_path.to_path(&self.dir), &local_abs_path)?;
+            }
+            // only empty ones, in case something else ended up in them in the meantime
+            for dir in self.created_dirs.iter() {
+                let _ = fs::remove_dir(dir);
+            }
+            Ok(())
+        }
+    }
+
+    // symlinks are copied as links, and files along with their permissions
+    fn copy_as_is(from: &Path, to: &Path) -> std::io::Result<()> {
+        if let Some(to_dir) = to.parent() {
+            fs::create_dir_all(to_dir)?;
+        }
+        match fs::symlink_metadata(from)?.is_symlink() {
+            true => std::os::unix::fs::symlink(fs::read_link(from)?, to),
+            false => fs::copy(from, to).map(|_| ()),
+        }
+    }
+
+    // returns each job's result, in job order
+    fn run_concurrently<T: Sync, R: Send, E: Send>(
+        jobs: &[T],
+        run: impl Fn(&T) -> Result<R, E> + Sync,
+    ) -> Vec<Result<R, E>> {
+        let thread_count = thread::available_parallelism()
+            .map_or(1, NonZeroUsize::get)
+            .min(jobs.len());
+        let next_job = Mutex::new(jobs.iter().enumerate());
+        let results = Mutex::new(Vec::with_capacity(jobs.len()));
+
+        thread::scope(|scope| {
+            for _ in 0..thread_count {
+                scope.spawn(|| {
+                    loop {
+                        // the lock is only held long enough to grab the next job
+                        let Some((i, job)) = next_job.lock().expect("Not poisoned.").next() else {
+                            break;
+                        };
+                        let result = run(job);
+                        results.lock().expect("Not poisoned.").push((i, result));
+                    }
+                });
+            }
+        });
+
+        let mut results = results.into_inner().expect("Not poisoned.");
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, result)| result).collect()
     }
 }
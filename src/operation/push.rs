@@ -1,8 +1,22 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    process::{Command, ExitStatus, Stdio},
+};
+
+use relative_path::{Component, RelativePath, RelativePathBuf};
 use thiserror::Error;
 
 use crate::{
-    ExecutionOptions, LocalFilePath, MonjaProfile, convert_set_localfile_result, local, repo,
-    rsync::rsync,
+    ExecutionOptions, LocalFilePath, MonjaProfile,
+    acl::{self, AclError},
+    convert_set_localfile_result,
+    hook::{self, HookError, HookKind, HookRun},
+    local,
+    operation::history::{self, HistoryOperation},
+    repo,
+    rsync::{RsyncError, rsync},
+    secret,
 };
 
 #[derive(Error, Debug)]
@@ -19,18 +33,75 @@ pub enum PushError {
         missing_files: Vec<(repo::SetName, Vec<LocalFilePath>)>,
     },
 
+    #[error("Both the local files and the repo's copies changed since they were last synced.")]
+    Conflicts(Vec<(repo::SetName, Vec<LocalFilePath>)>),
+
     #[error("Failed to copy files via rsync.")]
-    Rsync(#[source] std::io::Error),
+    Rsync(#[source] RsyncError),
+
+    #[error("Failed to encrypt the secret file '{0}'.")]
+    Encrypt(PathBuf, #[source] std::io::Error),
+
+    #[error("Failed to record the ACLs of set `{0}`'s files.")]
+    Acl(repo::SetName, #[source] AclError),
+
+    #[error("Failed to save recorded ACLs in the set's config.")]
+    SetConfig(#[from] repo::SetConfigError),
+
+    #[error("Unable to update the file index.")]
+    FileIndex(#[from] local::FileIndexError),
+
+    #[error("Set `{0}` is not targeted by the profile.")]
+    SetNotTargeted(repo::SetName),
+
+    #[error("The pre-push hook failed.")]
+    Hook(#[from] HookError),
+
+    #[error("Unable to run `git diff` in the repo.")]
+    GitDiff(#[source] std::io::Error),
+
+    // like rsync, stderr is captured so that callers can find out why
+    #[error("`git diff` exited unsuccessfully ({status}): {stderr}")]
+    GitDiffUnsuccessful { status: ExitStatus, stderr: String },
+}
+
+// options specific to a single push
+#[derive(Debug, Default)]
+pub struct PushOptions {
+    // just this set gets pushed, leaving other sets' changes for later.
+    // consistency checks still apply to every set, though.
+    pub only: Option<repo::SetName>,
+    // a git ref. only files that `git diff` reports as changed in the repo since then get pushed.
+    pub since: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct PushSuccess {
     pub files_pushed: Vec<(repo::SetName, Vec<LocalFilePath>)>,
+    // the subset of files_pushed whose contents actually changed in the repo
+    pub files_modified: Vec<(repo::SetName, Vec<LocalFilePath>)>,
+
+    pub hooks_run: Vec<HookRun>,
+    // a failing post-push hook doesn't fail the push, since the files have already been pushed
+    pub post_hook_error: Option<HookError>,
 }
 
-pub fn push(profile: &MonjaProfile, opts: &ExecutionOptions) -> Result<PushSuccess, PushError> {
+pub fn push(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    push_opts: &PushOptions,
+) -> Result<PushSuccess, PushError> {
+    if let Some(only) = &push_opts.only
+        && !profile.config.target_sets.contains(only)
+    {
+        return Err(PushError::SetNotTargeted(only.clone()));
+    }
+
+    let mut hooks_run = Vec::new();
+    hooks_run.extend(hook::run(profile, opts, HookKind::PrePush)?);
+
     let repo = repo::initialize_full_state(profile).map_err(PushError::RepoStateInitialization)?;
-    let local_state = local::retrieve_state(profile, &repo)?;
+    let mut local_state = local::retrieve_state(profile, &repo)?;
 
     if !local_state.files_with_missing_sets.is_empty() || !local_state.missing_files.is_empty() {
         let files_with_missing_sets = convert_set_localfile_result(
@@ -49,12 +120,63 @@ pub fn push(profile: &MonjaProfile, opts: &ExecutionOptions) -> Result<PushSucce
             missing_files,
         });
     }
+
+    if let Some(only) = &push_opts.only {
+        local_state
+            .files_to_push
+            .retain(|set_name, _| set_name == only);
+        local_state
+            .modified_files
+            .retain(|set_name, _| set_name == only);
+        // conflicts in other sets don't matter, since they won't be pushed
+        local_state
+            .conflicted_files
+            .retain(|set_name, _| set_name == only);
+    }
+
+    if let Some(since) = &push_opts.since {
+        let changed_files = changed_in_repo(profile, since)?;
+        let is_changed = |set_name: &repo::SetName, local_path: &local::FilePath| {
+            let Some(set) = repo.sets.get(set_name) else {
+                return false;
+            };
+            set.get_repo_relative_path_for(local_path)
+                .is_ok_and(|path_in_set| {
+                    changed_files
+                        .get(set_name)
+                        .is_some_and(|paths| paths.contains(&path_in_set))
+                })
+        };
+        for files in [
+            &mut local_state.files_to_push,
+            &mut local_state.modified_files,
+            &mut local_state.conflicted_files,
+        ] {
+            for (set_name, local_paths) in files.iter_mut() {
+                local_paths.retain(|local_path| is_changed(set_name, local_path));
+            }
+            files.retain(|_, local_paths| !local_paths.is_empty());
+        }
+    }
+
+    if !local_state.conflicted_files.is_empty() {
+        return Err(PushError::Conflicts(convert_set_localfile_result(
+            &profile.config.target_sets,
+            local_state.conflicted_files,
+            &local::FilePath::current_location(),
+        )));
+    }
     if local_state.files_to_push.is_empty() {
+        let post_hook_error = hook::run_post(profile, opts, HookKind::PostPush, &mut hooks_run);
         return Ok(PushSuccess {
             files_pushed: Default::default(),
+            files_modified: Default::default(),
+            hooks_run,
+            post_hook_error,
         });
     }
 
+    // everything up to here is read-only, so a dry run reports the same files that a real push would
     if !opts.dry_run {
         for set_name in profile.config.target_sets.iter() {
             let Some(set) = repo.sets.get(set_name) else {
@@ -63,27 +185,81 @@ pub fn push(profile: &MonjaProfile, opts: &ExecutionOptions) -> Result<PushSucce
                 // however, if the profile specifies a non-existing set, we opt to continue if possible.
                 continue;
             };
-            let files = local_state
-                .files_to_push
-                .get(set_name)
-                .expect("Will be an empty vec if set has no files to push.");
+            let Some(files) = local_state.files_to_push.get(set_name) else {
+                // no local files for the set, which is always the case for nopush sets
+                continue;
+            };
 
             // lets say set shortcut is foo/bar and file baz
             // transfer looks something like this: /home/xx/foo/bar/baz -> /monja/set/baz
             // here, the source is /home/xx/foo/bar/, dest is /monja/set/, and file is baz
             // incidentally, local::FilePath is foo/bar/baz
-            rsync(
-                set.shortcut.to_path(&profile.local_root).as_path(),
-                set.root.as_ref(),
-                files.iter().map(|local_path| {
+            // directories with their own shortcut in .monja-dir.toml get their own transfer, in the same way.
+            // secrets get encrypted instead, and only when they changed, since encrypting gives a different file every time
+            let (secret_files, files): (Vec<_>, Vec<_>) = files
+                .iter()
+                .partition(|local_path| set.locally_mapped_files[*local_path].secret);
+            let modified_files = local_state.modified_files.get(set_name);
+            for local_path in secret_files {
+                if !modified_files.is_some_and(|m| m.contains(local_path)) {
+                    continue;
+                }
+                let local_abs_path = local_path.to_absolute_path(profile);
+                let repo_abs_path = set
+                    .get_repo_absolute_path_for(local_path)
+                    .expect("Tracked files always have a path in the set.");
+                secret::write_encrypted(&local_abs_path, &repo_abs_path)
+                    .map_err(|e| PushError::Encrypt(local_abs_path, e))?;
+            }
+
+            let paths_in_set: Vec<RelativePathBuf> = files
+                .iter()
+                .map(|local_path| {
                     set.get_repo_relative_path_for(local_path)
                         .expect("Files not generated by user and so are expected to be fine.")
-                        .to_path("")
-                }),
-                opts,
-            )
-            .map_err(PushError::Rsync)?;
+                })
+                .collect();
+            let groups = repo::group_for_transfer(
+                &set.shortcut,
+                &set.dir_mappings,
+                paths_in_set.iter().map(|p| p.as_relative_path()),
+            );
+            log::info!("Pushing {} files to set `{}`", files.len(), set_name);
+            for group in groups {
+                rsync(
+                    &group.local_dir.to_path(&profile.local_root),
+                    &group.dir_in_set.to_path(&set.root),
+                    group.files.iter().map(|p| p.to_path("")),
+                    !profile.config.preserve_symlinks,
+                    &profile.config.rsync_extra_args,
+                    opts,
+                    None,
+                )
+                .map_err(PushError::Rsync)?;
+            }
+
+            // secrets are left out, since they're always pulled as private
+            if set.preserve_acls {
+                let mut config = repo::SetConfig::load(profile, set_name)?;
+                let files = paths_in_set
+                    .into_iter()
+                    .zip(files.iter().map(|f| f.to_absolute_path(profile)));
+                if acl::record(&mut config, files)
+                    .map_err(|e| PushError::Acl(set_name.clone(), e))?
+                {
+                    config.save(profile, set_name)?;
+                }
+            }
+        }
+
+        // the pushed files are now in sync with the repo, so future conflicts are relative to what was just pushed
+        let mut index = local::FileIndex::load(profile, local::IndexKind::Current)?;
+        for local_path in local_state.files_to_push.values().flatten() {
+            let hash = local::hash_for_index(&local_path.to_absolute_path(profile))?;
+            index.set_hash(local_path, hash);
         }
+        log::debug!("Saving the file index");
+        index.save(profile, local::IndexKind::Current)?;
     }
 
     let files_pushed = convert_set_localfile_result(
@@ -91,5 +267,89 @@ pub fn push(profile: &MonjaProfile, opts: &ExecutionOptions) -> Result<PushSucce
         local_state.files_to_push,
         &local::FilePath::current_location(),
     );
-    Ok(PushSuccess { files_pushed })
+    let files_modified = convert_set_localfile_result(
+        &profile.config.target_sets,
+        local_state.modified_files,
+        &local::FilePath::current_location(),
+    );
+
+    history::record(
+        profile,
+        opts,
+        HistoryOperation::Push,
+        [
+            (
+                "files-pushed",
+                files_pushed.iter().map(|(_, files)| files.len()).sum(),
+            ),
+            (
+                "files-modified",
+                files_modified.iter().map(|(_, files)| files.len()).sum(),
+            ),
+        ],
+    );
+
+    let post_hook_error = hook::run_post(profile, opts, HookKind::PostPush, &mut hooks_run);
+    return Ok(PushSuccess {
+        files_pushed,
+        files_modified,
+        hooks_run,
+        post_hook_error,
+    });
+
+    // split into the set each path is in and the path within the set.
+    // files directly in the repo root, like a README, aren't in any set.
+    fn changed_in_repo(
+        profile: &MonjaProfile,
+        since: &str,
+    ) -> Result<HashMap<repo::SetName, HashSet<RelativePathBuf>>, PushError> {
+        // --relative makes paths relative to the repo root, even if the git repo starts further up
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&*profile.repo_root)
+            .args([
+                "diff",
+                "--name-only",
+                "--relative",
+                "-z",
+                "--end-of-options",
+            ])
+            .arg(since)
+            .arg("--")
+            .stdin(Stdio::null())
+            .output()
+            .map_err(PushError::GitDiff)?;
+        if !output.status.success() {
+            return Err(PushError::GitDiffUnsuccessful {
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            });
+        }
+
+        let mut changed_files = HashMap::new();
+        for path in output.stdout.split(|b| *b == 0) {
+            // set names and the paths in them are always utf-8, so anything else can't be in a set
+            let Ok(path) = std::str::from_utf8(path) else {
+                continue;
+            };
+            let mut components = RelativePath::new(path).components();
+            let Some(Component::Normal(set_name)) = components.next() else {
+                continue;
+            };
+            // files at the root of the repo aren't in any set
+            let Ok(set_name) = repo::SetName::new(set_name) else {
+                continue;
+            };
+            let path_in_set = components.as_relative_path();
+            if path_in_set.as_str().is_empty() {
+                continue;
+            }
+
+            changed_files
+                .entry(set_name)
+                .or_insert_with(HashSet::new)
+                .insert(path_in_set.to_relative_path_buf());
+        }
+        Ok(changed_files)
+    }
 }
@@ -1,14 +1,23 @@
 use std::{
     collections::{HashMap, HashSet},
     fs,
-    path::PathBuf,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+use relative_path::{RelativePath, RelativePathBuf};
 use thiserror::Error;
+use walkdir::WalkDir;
 
 use crate::{
-    ExecutionOptions, LocalFilePath, MonjaProfile, SetName, local,
+    ExecutionOptions, LocalFilePath, MonjaProfile, SetName,
+    acl::{self, AclError},
+    local,
+    operation::history::{self, HistoryOperation},
     repo::{self, SetPathError},
+    secret,
 };
 
 #[derive(Error, Debug)]
@@ -19,6 +28,18 @@ pub enum PutError {
     #[error("Set not found in repo.")]
     SetNotFound(repo::SetName),
 
+    #[error("Files are not in the index, so there's no set to put them back into.")]
+    NotInIndex(Vec<LocalFilePath>),
+
+    #[error("Files are monja's own, like a set's config, so they can't be put into a set.")]
+    SpecialFiles(Vec<LocalFilePath>),
+
+    #[error("The profile doesn't target any sets, so there is no last set.")]
+    NoTargetedSets,
+
+    #[error("Set index {index} is out of range, since the profile targets {count} sets.")]
+    SetIndexOutOfRange { index: usize, count: usize },
+
     #[error("Failed to load monja-index.toml.")]
     FileIndex(#[from] local::FileIndexError),
 
@@ -39,47 +60,387 @@ pub enum PutError {
 
     #[error("Unable to formulate the path as it would be in the set folder.")]
     SetPath(#[from] SetPathError),
+
+    #[error("Failed to walk directory '{0}' to find the files in it.")]
+    DirectoryWalk(PathBuf, #[source] walkdir::Error),
+
+    #[error(
+        "Unable to store '{0}' outside of the set's shortcut, since the directory it would go in already exists in the set."
+    )]
+    DirShortcutConflict(PathBuf),
+
+    #[error("Failed to write .monja-dir.toml to '{0}'.")]
+    DirShortcut(PathBuf, #[source] std::io::Error),
+
+    #[error("Unable to read the permissions of '{0}'.")]
+    Permissions(PathBuf, #[source] std::io::Error),
+
+    #[error("Failed to record permissions or ACLs in the set's config.")]
+    SetConfig(#[from] repo::SetConfigError),
+
+    #[error("Failed to record the ACLs of the files put into the set.")]
+    Acl(#[from] AclError),
+
+    #[error("Failed to back up the set's copy to '{0}'.")]
+    Backup(PathBuf, #[source] std::io::Error),
+
+    // same layout as PutSuccess::files_in_later_sets
+    #[error("Files are also in later sets, which would override them when pulling.")]
+    InLaterSets(Vec<(LocalFilePath, Vec<SetName>)>),
+}
+
+// options specific to a single put
+#[derive(Debug, Default)]
+pub struct PutOptions {
+    // files outside of the set's shortcut get stored in the set at their path relative to the local root,
+    // with a .monja-dir.toml giving their directory its own shortcut.
+    pub outside_shortcut: bool,
+    pub later_sets: LaterSetsPolicy,
+}
+
+// what to do with files that are also in a targeted set after the one they're being put into,
+// in which case the later set's copy is what gets pulled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LaterSetsPolicy {
+    // puts them anyway, reporting them in PutSuccess::files_in_later_sets
+    #[default]
+    Warn,
+    // fails with PutError::InLaterSets before putting anything
+    Error,
+    // puts them into the last targeted set that has them instead, so that they win
+    Promote,
+}
+
+#[derive(Error, Debug)]
+#[error("Later sets policy must be 'warn', 'error', or 'promote', not '{0}'.")]
+pub struct LaterSetsPolicyParseError(String);
+
+impl FromStr for LaterSetsPolicy {
+    type Err = LaterSetsPolicyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(LaterSetsPolicy::Warn),
+            "error" => Ok(LaterSetsPolicy::Error),
+            "promote" => Ok(LaterSetsPolicy::Promote),
+            _ => Err(LaterSetsPolicyParseError(s.to_string())),
+        }
+    }
+}
+
+// picks a set by its position in the profile's target_sets, instead of by name.
+// positions are 1-based, matching the order sets are layered in, after expanding globs and ordering by priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetIndex {
+    Position(usize),
+    Last,
+}
+
+#[derive(Error, Debug)]
+#[error("Set index must be 'last' or a position starting from 1, not '{0}'.")]
+pub struct SetIndexParseError(String);
+
+impl FromStr for SetIndex {
+    type Err = SetIndexParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last" => Ok(SetIndex::Last),
+            _ => match s.parse::<usize>() {
+                Ok(pos) if pos > 0 => Ok(SetIndex::Position(pos)),
+                _ => Err(SetIndexParseError(s.to_string())),
+            },
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct PutSuccess {
-    pub owning_set: repo::SetName,
+    // None if each file's set came from the index
+    pub owning_set: Option<repo::SetName>,
     pub files: Vec<LocalFilePath>,
+    // the same files, grouped by the set they were put into
+    pub files_by_set: Vec<(repo::SetName, Vec<LocalFilePath>)>,
 
+    // whether every set that files were put into is targeted by the profile
     pub set_is_targeted: bool,
     pub files_in_later_sets: Vec<(LocalFilePath, Vec<repo::SetName>)>,
+    // with LaterSetsPolicy::Promote, files put into a later set than asked for, along with that set
+    pub files_promoted: Vec<(LocalFilePath, repo::SetName)>,
     pub untracked_files: Vec<LocalFilePath>,
+    // files that were given their own shortcut because they were outside of the set's
+    pub files_outside_shortcut: Vec<LocalFilePath>,
+    // executable files whose mode was added to the set's permissions, so that pull keeps them executable
+    pub modes_recorded: Vec<LocalFilePath>,
+    // every file, along with whether it replaced a file already in the set
+    pub overwritten: Vec<(LocalFilePath, bool)>,
+
+    // only populated when ExecutionOptions::backup is set, with the set's copies that were replaced.
+    // they're laid out by set, then path in set, so that copying them back into the repo undoes the put.
+    // in a dry run, these are the files that would have been backed up.
+    pub files_backed_up: Vec<LocalFilePath>,
+    pub backup_dir: Option<PathBuf>,
+}
+
+// resolved separately from put, since callers typically want the set's name before putting anything in it
+pub fn resolve_set_index(profile: &MonjaProfile, index: SetIndex) -> Result<SetName, PutError> {
+    let target_sets = &profile.config.target_sets;
+    let set_name = match index {
+        SetIndex::Last => target_sets.last().ok_or(PutError::NoTargetedSets)?,
+        SetIndex::Position(pos) => pos.checked_sub(1).and_then(|i| target_sets.get(i)).ok_or(
+            PutError::SetIndexOutOfRange {
+                index: pos,
+                count: target_sets.len(),
+            },
+        )?,
+    };
+
+    Ok(set_name.clone())
 }
 
 pub fn put(
     profile: &MonjaProfile,
     opts: &ExecutionOptions,
     files: Vec<LocalFilePath>,
-    owning_set: repo::SetName,
+    owning_set: Option<repo::SetName>,
+    put_opts: &PutOptions,
 ) -> Result<PutSuccess, PutError> {
     let repo = repo::initialize_full_state(profile).map_err(PutError::RepoStateInitialization)?;
+    let files = expand_dirs(profile, files)?;
+    // directories already leave them out, but named files would otherwise end up configuring the set
+    let special_files: Vec<LocalFilePath> = files
+        .iter()
+        .filter(|f| {
+            local::is_monja_owned(profile, &f.to_internal().to_absolute_path(profile), false)
+        })
+        .cloned()
+        .collect();
+    if !special_files.is_empty() {
+        return Err(PutError::SpecialFiles(special_files));
+    }
     let mut index = local::FileIndex::load(profile, local::IndexKind::Current)?;
+    let backup_dir = opts.backup.then(|| backup_dir(profile));
+
+    // without a set, each file goes back into the set the index says it was last synced with.
+    // handy for restoring a set's files after they were accidentally deleted from the repo.
+    let groups = match &owning_set {
+        Some(set_name) => vec![(set_name.clone(), files)],
+        None => group_by_index(&index, files)?,
+    };
+    let (groups, mut files_promoted) = match put_opts.later_sets {
+        LaterSetsPolicy::Warn => (groups, Vec::new()),
+        LaterSetsPolicy::Error => {
+            let mut in_later_sets: Vec<(LocalFilePath, Vec<SetName>)> = groups
+                .iter()
+                .flat_map(|(set_name, files)| {
+                    files.iter().filter_map(|file| {
+                        let sets = later_sets(profile, &repo, set_name, file);
+                        (!sets.is_empty()).then(|| (file.clone(), sets))
+                    })
+                })
+                .collect();
+            if !in_later_sets.is_empty() {
+                in_later_sets.sort_by(|l, r| l.0.cmp(&r.0));
+                return Err(PutError::InLaterSets(in_later_sets));
+            }
+            (groups, Vec::new())
+        }
+        LaterSetsPolicy::Promote => promote(profile, &repo, groups),
+    };
+
+    let mut success = PutSuccess {
+        owning_set,
+        files: Vec::new(),
+        files_by_set: Vec::with_capacity(groups.len()),
+        set_is_targeted: true,
+        files_in_later_sets: Vec::new(),
+        files_promoted: Vec::new(),
+        untracked_files: Vec::new(),
+        files_outside_shortcut: Vec::new(),
+        modes_recorded: Vec::new(),
+        overwritten: Vec::new(),
+        files_backed_up: Vec::new(),
+        backup_dir: None,
+    };
+    for (set_name, files) in groups {
+        let set_success = put_into_set(
+            profile,
+            opts,
+            &repo,
+            &mut index,
+            &set_name,
+            files,
+            put_opts,
+            backup_dir.as_deref(),
+        )?;
+        success.files.extend(set_success.files.iter().cloned());
+        success.files_by_set.push((set_name, set_success.files));
+        success.set_is_targeted &= set_success.set_is_targeted;
+        success
+            .files_in_later_sets
+            .extend(set_success.files_in_later_sets);
+        success.untracked_files.extend(set_success.untracked_files);
+        success
+            .files_outside_shortcut
+            .extend(set_success.files_outside_shortcut);
+        success.modes_recorded.extend(set_success.modes_recorded);
+        success.overwritten.extend(set_success.overwritten);
+        success.files_backed_up.extend(set_success.files_backed_up);
+    }
+    if !success.files_backed_up.is_empty() {
+        success.backup_dir = backup_dir;
+    }
+    success.files_in_later_sets.sort_by(|l, r| l.0.cmp(&r.0));
+    files_promoted.sort_by(|l, r| l.0.cmp(&r.0));
+    success.files_promoted = files_promoted;
+
+    if !opts.dry_run {
+        index.save(profile, local::IndexKind::Current)?;
+    }
+
+    history::record(
+        profile,
+        opts,
+        HistoryOperation::Put,
+        [("files", success.files.len())],
+    );
+    Ok(success)
+}
+
+type FileGroups = Vec<(SetName, Vec<LocalFilePath>)>;
+
+// targeted sets after set_name that have the file, in profile order.
+// for an untargeted set_name, that's every targeted set that has it.
+fn later_sets(
+    profile: &MonjaProfile,
+    repo: &repo::RepoState,
+    set_name: &SetName,
+    file: &LocalFilePath,
+) -> Vec<SetName> {
+    let target_sets = &profile.config.target_sets;
+    let start = target_sets
+        .iter()
+        .position(|s| s == set_name)
+        .map_or(0, |pos| pos + 1);
+    let internal_path = file.to_internal();
+    target_sets[start..]
+        .iter()
+        .filter(|s| {
+            repo.sets
+                .get(*s)
+                .is_some_and(|set| set.tracks_file(&internal_path))
+        })
+        .cloned()
+        .collect()
+}
+
+// moves files that are in later sets into the last of them, which is the set pull would take them from.
+// files that stay put keep their groups, and new groups are added in the order their first file was given in.
+fn promote(
+    profile: &MonjaProfile,
+    repo: &repo::RepoState,
+    groups: FileGroups,
+) -> (FileGroups, Vec<(LocalFilePath, SetName)>) {
+    let mut promoted_groups: FileGroups = Vec::new();
+    let mut promoted = Vec::new();
+    let mut groups: FileGroups = groups
+        .into_iter()
+        .map(|(set_name, files)| {
+            let mut kept = Vec::with_capacity(files.len());
+            for file in files {
+                let Some(dest) = later_sets(profile, repo, &set_name, &file).pop() else {
+                    kept.push(file);
+                    continue;
+                };
+                promoted.push((file.clone(), dest.clone()));
+                match promoted_groups.iter_mut().find(|(s, _)| *s == dest) {
+                    Some((_, files)) => files.push(file),
+                    None => promoted_groups.push((dest, vec![file])),
+                }
+            }
+            (set_name, kept)
+        })
+        .filter(|(_, files)| !files.is_empty())
+        .collect();
+
+    for (set_name, files) in promoted_groups {
+        match groups.iter_mut().find(|(s, _)| *s == set_name) {
+            Some((_, existing)) => existing.extend(files),
+            None => groups.push((set_name, files)),
+        }
+    }
+    (groups, promoted)
+}
 
+// the result only covers this set, and files_by_set is left empty for put to fill in
+#[allow(clippy::too_many_arguments)]
+fn put_into_set(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    repo: &repo::RepoState,
+    index: &mut local::FileIndex,
+    owning_set: &SetName,
+    files: Vec<LocalFilePath>,
+    put_opts: &PutOptions,
+    backup_dir: Option<&Path>,
+) -> Result<PutSuccess, PutError> {
     let owning_set_pos = profile
         .config
         .target_sets
         .iter()
-        .position(|s: &SetName| *s == owning_set);
+        .position(|s: &SetName| s == owning_set);
 
     let owning_set = repo
         .sets
-        .get(&owning_set)
+        .get(owning_set)
         .ok_or_else(|| PutError::SetNotFound(owning_set.clone()))?;
 
     // will flip it later to calculate untracked files
     let mut tracked_files = HashSet::new();
     let mut files_in_later_sets: HashMap<LocalFilePath, Vec<SetName>> = HashMap::new();
     let mut result_files = Vec::with_capacity(files.len());
+    let mut files_outside_shortcut = Vec::new();
+    // directories given their own shortcut by this put, which later files can also go in
+    let mut shortcut_dirs = HashSet::new();
+    let mut executables = Vec::new();
+    let mut acl_files = Vec::new();
+    let mut overwritten = Vec::with_capacity(files.len());
+    let mut files_backed_up = Vec::new();
     for path in files.into_iter() {
         let internal_path: local::FilePath = path.to_internal();
 
+        let path_in_set = match owning_set.get_repo_relative_path_for(&internal_path) {
+            Ok(path_in_set) => path_in_set,
+            Err(SetPathError::OutsideOfSet { .. }) if put_opts.outside_shortcut => {
+                let path_in_set =
+                    place_outside_shortcut(opts, owning_set, &internal_path, &mut shortcut_dirs)?;
+                files_outside_shortcut.push(path.clone());
+                path_in_set
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        // symlink_metadata, so that a preserved symlink in the set counts, too
+        let repo_path = path_in_set.to_path(&owning_set.root);
+        let overwrites = repo_path.symlink_metadata().is_ok();
+        if overwrites && let Some(backup_dir) = backup_dir {
+            let backup_path = path_in_set.to_path(backup_dir.join(&owning_set.name));
+            if !opts.dry_run {
+                back_up(&repo_path, &backup_path)?;
+            }
+            files_backed_up.push(path.clone());
+        }
+        overwritten.push((path.clone(), overwrites));
+
         if !opts.dry_run {
-            copy_to_set(profile, owning_set, &internal_path)?;
+            copy_to_set(profile, owning_set, &internal_path, &path_in_set)?;
+        }
+        // same as push, secrets are left out
+        if !opts.dry_run && owning_set.preserve_acls && !owning_set.is_secret(&path_in_set) {
+            acl_files.push((path_in_set.clone(), internal_path.to_absolute_path(profile)));
+        }
+        if let Some(mode) = executable_mode(&internal_path.to_absolute_path(profile))? {
+            executables.push((path.clone(), path_in_set, mode));
         }
 
         // track which sets contain this file for reporting purposes
@@ -118,12 +479,17 @@ pub fn put(
         let owner = repo.get_owning_set(profile, &internal_path);
         let owner_pos = owner.and_then(|o| profile.config.target_sets.iter().position(|s| s == o));
         if owning_set_pos >= owner_pos {
-            index.set(internal_path, owning_set.name.clone());
+            let hash = local::hash_for_index(&internal_path.to_absolute_path(profile))?;
+            index.set(internal_path, owning_set.name.clone(), hash);
         }
     }
 
-    if !opts.dry_run {
-        index.save(profile, local::IndexKind::Current)?;
+    let modes_recorded = record_modes(profile, opts, &owning_set.name, executables)?;
+    if !acl_files.is_empty() {
+        let mut config = repo::SetConfig::load(profile, &owning_set.name)?;
+        if acl::record(&mut config, acl_files)? {
+            config.save(profile, &owning_set.name)?;
+        }
     }
 
     let untracked_files = result_files
@@ -131,28 +497,213 @@ pub fn put(
         .filter(|p| !tracked_files.contains(p))
         .cloned()
         .collect();
+    // each file's sets are put in profile order, and put sorts the files, since both come from hash maps
+    let mut files_in_later_sets: Vec<(LocalFilePath, Vec<SetName>)> =
+        files_in_later_sets.into_iter().collect();
+    for (_, sets) in files_in_later_sets.iter_mut() {
+        sets.sort_by_key(|s| profile.config.target_sets.iter().position(|t| t == s));
+    }
     Ok(PutSuccess {
-        owning_set: owning_set.name.clone(),
+        owning_set: Some(owning_set.name.clone()),
         files: result_files,
+        files_by_set: Vec::new(),
         set_is_targeted: owning_set_pos.is_some(),
-        files_in_later_sets: files_in_later_sets
-            .into_iter()
-            .map(|(path, sets)| (path.clone(), sets))
-            .collect(),
+        files_in_later_sets,
+        files_promoted: Vec::new(),
         untracked_files,
+        files_outside_shortcut,
+        modes_recorded,
+        overwritten,
+        files_backed_up,
+        backup_dir: None,
     })
 }
 
+// git only tracks the executable bit, and not even that with core.fileMode turned off.
+// so the mode goes in the set's permissions, which pull reapplies after copying.
+// modes already in the config are left alone, since the user may have picked them deliberately.
+fn record_modes(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    set_name: &SetName,
+    executables: Vec<(LocalFilePath, RelativePathBuf, u32)>,
+) -> Result<Vec<LocalFilePath>, PutError> {
+    if executables.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut config = repo::SetConfig::load(profile, set_name)?;
+    let mut recorded = Vec::new();
+    for (path, path_in_set, mode) in executables {
+        let key = path_in_set.to_path("");
+        if config.permissions.contains_key(&key) {
+            continue;
+        }
+        config.permissions.insert(key, format!("{:o}", mode));
+        recorded.push(path);
+    }
+
+    if !opts.dry_run && !recorded.is_empty() {
+        config.save(profile, set_name)?;
+    }
+
+    Ok(recorded)
+}
+
+// symlinks are skipped, since their target's mode isn't really theirs.
+// missing files are too, leaving it to copy_to_set to report them.
+fn executable_mode(path: &std::path::Path) -> Result<Option<u32>, PutError> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(PutError::Permissions(path.to_path_buf(), e)),
+    };
+    let mode = metadata.permissions().mode() & 0o7777;
+    match metadata.is_file() && mode & 0o111 != 0 {
+        true => Ok(Some(mode)),
+        false => Ok(None),
+    }
+}
+
+// sets are in the order their first file was given in
+fn group_by_index(
+    index: &local::FileIndex,
+    files: Vec<LocalFilePath>,
+) -> Result<Vec<(SetName, Vec<LocalFilePath>)>, PutError> {
+    let mut groups: Vec<(SetName, Vec<LocalFilePath>)> = Vec::new();
+    let mut not_in_index = Vec::new();
+    for file in files {
+        let Some(entry) = index.get(&file.to_internal()) else {
+            not_in_index.push(file);
+            continue;
+        };
+        match groups
+            .iter_mut()
+            .find(|(set_name, _)| *set_name == entry.set)
+        {
+            Some((_, files)) => files.push(file),
+            None => groups.push((entry.set.clone(), vec![file])),
+        }
+    }
+
+    // like restore, we'd rather not put anything if the user may have made a typo
+    match not_in_index.is_empty() {
+        true => Ok(groups),
+        false => Err(PutError::NotInIndex(not_in_index)),
+    }
+}
+
+// the file goes in the set at the same path it has locally, relative to the local root.
+// the directory it goes in can't already exist, since giving it a shortcut would move whatever's already there.
+fn place_outside_shortcut(
+    opts: &ExecutionOptions,
+    set: &repo::Set,
+    path: &local::FilePath,
+    shortcut_dirs: &mut HashSet<RelativePathBuf>,
+) -> Result<RelativePathBuf, PutError> {
+    let local_path: &RelativePath = path.as_ref();
+    let path_in_set = match set.secrets.is_match(local_path.as_str()) {
+        true => secret::add_suffix(local_path),
+        false => local_path.to_relative_path_buf(),
+    };
+    let copy_to = path_in_set.to_path(&set.root);
+    let dir = local_path
+        .parent()
+        .filter(|dir| !dir.as_str().is_empty())
+        // files directly in the local root would need the whole set to be remapped
+        .ok_or_else(|| PutError::DirShortcutConflict(copy_to.clone()))?;
+    if shortcut_dirs.contains(dir) {
+        return Ok(path_in_set);
+    }
+
+    let dir_path = dir.to_path(&set.root);
+    if dir_path.exists() {
+        return Err(PutError::DirShortcutConflict(copy_to));
+    }
+
+    if !opts.dry_run {
+        fs::create_dir_all(&dir_path).map_err(|e| PutError::CreateDestDir(dir_path.clone(), e))?;
+        repo::save_dir_shortcut(&dir_path, dir)
+            .map_err(|e| PutError::DirShortcut(dir_path.clone(), e))?;
+    }
+    shortcut_dirs.insert(dir.to_relative_path_buf());
+
+    Ok(path_in_set)
+}
+
+// directories are replaced with the files under them.
+// like the rest of put, .monjaignore files aren't respected.
+pub(crate) fn expand_dirs(
+    profile: &MonjaProfile,
+    files: Vec<LocalFilePath>,
+) -> Result<Vec<LocalFilePath>, PutError> {
+    let mut expanded = Vec::with_capacity(files.len());
+    for path in files.into_iter() {
+        let abs_path = path.to_internal().to_absolute_path(profile);
+        if !abs_path.is_dir() {
+            // anything else, including paths that don't exist, gets handled when copying
+            expanded.push(path);
+            continue;
+        }
+
+        let walker = WalkDir::new(&abs_path)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            // putting the home directory shouldn't put the repo into itself, nor monja's own files into a set
+            .filter_entry(|e| !local::is_monja_owned(profile, e.path(), e.file_type().is_dir()));
+        for entry in walker {
+            let entry = entry.map_err(|e| PutError::DirectoryWalk(abs_path.clone(), e))?;
+            let file_type = entry.file_type();
+            // same as when walking local files for other operations
+            let is_trackable =
+                file_type.is_file() || (file_type.is_symlink() && entry.path().is_file());
+            if !is_trackable {
+                continue;
+            }
+
+            expanded.push(
+                LocalFilePath::from(profile, entry.path(), &profile.local_root)
+                    .expect("Walked from a directory under the local root."),
+            );
+        }
+    }
+
+    Ok(expanded)
+}
+
+// millis, like pull's backups, so that puts in quick succession don't share a backup dir
+fn backup_dir(profile: &MonjaProfile) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("The clock is after 1970.")
+        .as_millis();
+    profile
+        .data_root
+        .join("put-backups")
+        .join(timestamp.to_string())
+}
+
+// the set's copy is backed up as-is, so secrets stay encrypted
+fn back_up(repo_path: &Path, backup_path: &Path) -> Result<(), PutError> {
+    let backup_path_dir = backup_path.parent().expect("Joined onto the backup dir.");
+    fs::create_dir_all(backup_path_dir)
+        .and_then(|_| fs::copy(repo_path, backup_path))
+        .map(|_| ())
+        .map_err(|e| PutError::Backup(backup_path.to_path_buf(), e))
+}
+
 fn copy_to_set(
     profile: &MonjaProfile,
     set: &repo::Set,
     path: &local::FilePath,
+    path_in_set: &RelativePath,
 ) -> Result<(), PutError> {
+    let copy_to = path_in_set.to_path(&set.root);
     let copy_from = path.to_absolute_path(profile);
     if !copy_from.is_file() {
         return Err(PutError::NotValidFile(copy_from));
     }
-    let copy_to = set.get_repo_absolute_path_for(path)?;
     let copy_to_dir = copy_to
         .parent()
         .ok_or_else(|| PutError::NotValidFile(copy_to.to_path_buf()))?;
@@ -160,11 +711,14 @@ fn copy_to_set(
     fs::create_dir_all(copy_to_dir)
         .map_err(|e| PutError::CreateDestDir(copy_to_dir.to_path_buf(), e))?;
 
-    fs::copy(&copy_from, &copy_to).map_err(|e| PutError::CopyToSet {
-        set_name: set.name.clone(),
-        local_path: copy_from,
-        repo_path: copy_to,
-        source: e,
+    let secret = set.is_secret(path_in_set);
+    crate::copy_local_file_to_set(profile, &copy_from, &copy_to, secret).map_err(|e| {
+        PutError::CopyToSet {
+            set_name: set.name.clone(),
+            local_path: copy_from,
+            repo_path: copy_to,
+            source: e,
+        }
     })?;
 
     Ok(())
@@ -0,0 +1,105 @@
+use std::{fs, path::PathBuf};
+
+use thiserror::Error;
+
+use crate::{
+    AbsolutePath, ExecutionOptions, LocalFilePath, MonjaProfile, MonjaProfileConfig,
+    MonjaProfileConfigError, local, repo,
+};
+
+#[derive(Error, Debug)]
+pub enum RemoveSetError {
+    #[error("Set not found in repo.")]
+    SetNotFound(repo::SetName),
+
+    #[error("Failed to load monja-index.toml.")]
+    FileIndex(#[from] local::FileIndexError),
+
+    #[error("Set '{set_name}' is still used by local files. Use force to remove it anyway.")]
+    SetInUse {
+        set_name: repo::SetName,
+        files: Vec<LocalFilePath>,
+    },
+
+    #[error("Failed to remove set directory '{0}'.")]
+    RemoveDir(PathBuf, #[source] std::io::Error),
+
+    #[error("Unable to remove set from profile.")]
+    ProfileModification(#[source] MonjaProfileConfigError),
+
+    #[error("Set removal cancelled by user.")]
+    UserCancellation,
+}
+
+#[derive(Debug)]
+pub struct RemoveSetSuccess {
+    pub set_name: repo::SetName,
+    pub removed_from_profile: bool,
+    // files that the index still maps to the removed set. only non-empty if forced.
+    pub orphaned_files: Vec<LocalFilePath>,
+}
+
+pub fn remove_set(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    profile_config_path: &AbsolutePath,
+    set_name: repo::SetName,
+    force: bool,
+) -> Result<RemoveSetSuccess, RemoveSetError> {
+    let set_dir = profile.repo_root.join(&set_name);
+    if !set_dir.is_dir() {
+        return Err(RemoveSetError::SetNotFound(set_name));
+    }
+
+    let index = local::FileIndex::load(profile, local::IndexKind::Current)?;
+    let mut orphaned_files: Vec<LocalFilePath> = index
+        .files_owned_by(&set_name)
+        .map(|f| f.clone().into())
+        .collect();
+    orphaned_files.sort();
+
+    if !orphaned_files.is_empty() && !force {
+        return Err(RemoveSetError::SetInUse {
+            set_name,
+            files: orphaned_files,
+        });
+    }
+
+    let confirmation = opts.user_confirm(&format!(
+        "Set '{}' will be removed from the repo, along with all of its files.",
+        set_name
+    ));
+    if !confirmation {
+        return Err(RemoveSetError::UserCancellation);
+    }
+
+    // goes by the profile as written, since profile.config has its globs expanded.
+    // a set that's only matched by a glob has no entry to remove, and the glob stops matching it anyway.
+    let mut profile_config = MonjaProfileConfig::load(profile_config_path)
+        .map_err(RemoveSetError::ProfileModification)?;
+    let removed_from_profile = profile_config
+        .target_sets
+        .iter()
+        .any(|s| repo::names_target_set(s, &set_name));
+
+    if !opts.dry_run {
+        // the profile goes first, so that a failure leaves it pointing at a set that still exists.
+        // a set that's left behind because its removal failed can simply be removed again.
+        if removed_from_profile {
+            profile_config
+                .target_sets
+                .retain(|s| !repo::names_target_set(s, &set_name));
+            profile_config
+                .save(profile_config_path)
+                .map_err(RemoveSetError::ProfileModification)?;
+        }
+
+        fs::remove_dir_all(&set_dir).map_err(|e| RemoveSetError::RemoveDir(set_dir, e))?;
+    }
+
+    Ok(RemoveSetSuccess {
+        set_name,
+        removed_from_profile,
+        orphaned_files,
+    })
+}
@@ -0,0 +1,146 @@
+use std::{fs, path::PathBuf};
+
+use thiserror::Error;
+
+use crate::{
+    AbsolutePath, ExecutionOptions, LocalFilePath, MonjaProfile, MonjaProfileConfig,
+    MonjaProfileConfigError, local, repo,
+};
+
+#[derive(Error, Debug)]
+pub enum RenameSetError {
+    #[error("Set not found in repo.")]
+    SetNotFound(repo::SetName),
+
+    #[error("A set with the new name already exists.")]
+    SetExists(repo::SetName),
+
+    #[error("Failed to load or save an index file.")]
+    FileIndex(#[from] local::FileIndexError),
+
+    #[error("Failed to rename set directory from '{from}' to '{to}'.")]
+    RenameDir {
+        from: PathBuf,
+        to: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Unable to rename set in profile.")]
+    ProfileModification(#[source] MonjaProfileConfigError),
+}
+
+#[derive(Debug)]
+pub struct RenameSetSuccess {
+    pub old_name: repo::SetName,
+    pub new_name: repo::SetName,
+    pub profile_updated: bool,
+    // the old name was only targeted by a glob in target-sets that the new name doesn't match,
+    // so the set won't be pulled anymore until it's added back.
+    pub no_longer_targeted: bool,
+    // files in the current index that now map to the new name
+    pub files_reindexed: Vec<LocalFilePath>,
+}
+
+pub fn rename_set(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    profile_config_path: &AbsolutePath,
+    old_name: repo::SetName,
+    new_name: repo::SetName,
+) -> Result<RenameSetSuccess, RenameSetError> {
+    let old_dir = profile.repo_root.join(&old_name);
+    if !old_dir.is_dir() {
+        return Err(RenameSetError::SetNotFound(old_name));
+    }
+
+    let new_dir = profile.repo_root.join(&new_name);
+    if new_dir.exists() {
+        return Err(RenameSetError::SetExists(new_name));
+    }
+
+    // the originals are kept to put back if the rename fails partway
+    let curr_index = local::FileIndex::load(profile, local::IndexKind::Current)?;
+    let prev_index = local::FileIndex::load(profile, local::IndexKind::Previous)?;
+    let mut renamed_curr_index = curr_index.clone();
+    let mut renamed_prev_index = prev_index.clone();
+    let curr_renamed = renamed_curr_index.rename_set(&old_name, &new_name);
+    let prev_renamed = renamed_prev_index.rename_set(&old_name, &new_name);
+
+    // goes by the profile as written, since profile.config has its globs expanded
+    let profile_config = MonjaProfileConfig::load(profile_config_path)
+        .map_err(RenameSetError::ProfileModification)?;
+    let profile_updated = profile_config
+        .target_sets
+        .iter()
+        .any(|s| repo::names_target_set(s, &old_name));
+    let no_longer_targeted = !profile_updated
+        && profile.config.target_sets.contains(&old_name)
+        && !profile_config
+            .target_sets
+            .iter()
+            .any(|s| repo::glob_targets_set(s, &new_name));
+
+    if !opts.dry_run {
+        let mut renamed_profile_config = profile_config.clone();
+        for set_name in renamed_profile_config.target_sets.iter_mut() {
+            if repo::names_target_set(set_name, &old_name) {
+                *set_name = new_name.clone();
+            }
+        }
+
+        // the profile and index are written before the directory is renamed.
+        // if anything fails, whatever was already written is put back, so they keep pointing at the set as it is.
+        let mut profile_written = false;
+        let mut curr_written = false;
+        let mut prev_written = false;
+        let result = (|| {
+            if profile_updated {
+                renamed_profile_config
+                    .save(profile_config_path)
+                    .map_err(RenameSetError::ProfileModification)?;
+                profile_written = true;
+            }
+            // avoids creating index files that didn't previously exist
+            if !curr_renamed.is_empty() {
+                renamed_curr_index.save(profile, local::IndexKind::Current)?;
+                curr_written = true;
+            }
+            if !prev_renamed.is_empty() {
+                renamed_prev_index.save(profile, local::IndexKind::Previous)?;
+                prev_written = true;
+            }
+            fs::rename(&old_dir, &new_dir).map_err(|e| RenameSetError::RenameDir {
+                from: old_dir.clone(),
+                to: new_dir.clone(),
+                source: e,
+            })
+        })();
+
+        if let Err(e) = result {
+            // the original error is the one worth reporting, so these only get logged
+            if profile_written && let Err(e) = profile_config.save(profile_config_path) {
+                log::warn!("Unable to put back the profile's target-sets: {}", e);
+            }
+            if curr_written && let Err(e) = curr_index.save(profile, local::IndexKind::Current) {
+                log::warn!("Unable to put back the file index: {}", e);
+            }
+            if prev_written && let Err(e) = prev_index.save(profile, local::IndexKind::Previous) {
+                log::warn!("Unable to put back the previous file index: {}", e);
+            }
+            return Err(e);
+        }
+    }
+
+    let mut files_reindexed: Vec<LocalFilePath> =
+        curr_renamed.into_iter().map(|f| f.into()).collect();
+    files_reindexed.sort();
+
+    Ok(RenameSetSuccess {
+        old_name,
+        new_name,
+        profile_updated,
+        no_longer_targeted,
+        files_reindexed,
+    })
+}
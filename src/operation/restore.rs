@@ -0,0 +1,135 @@
+use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+use thiserror::Error;
+
+use crate::{
+    ExecutionOptions, LocalFilePath, MonjaProfile, SetName, repo,
+    rsync::{RsyncError, rsync},
+    secret,
+    template::{self, TemplateError},
+};
+
+#[derive(Error, Debug)]
+pub enum RestoreError {
+    #[error("Unable to initialize repo state.")]
+    RepoStateInitialization(Vec<repo::StateInitializationError>),
+
+    #[error("Set not found in repo.")]
+    SetNotFound(SetName),
+
+    #[error("Files are not tracked by the set.")]
+    NotInSet {
+        set_name: SetName,
+        files: Vec<LocalFilePath>,
+    },
+
+    #[error("Failed to copy files via rsync.")]
+    Rsync(#[source] RsyncError),
+
+    #[error("Failed to decrypt the secret file '{0}'.")]
+    Decrypt(PathBuf, #[source] std::io::Error),
+
+    #[error("Failed to render the template '{0}'.")]
+    Template(PathBuf, #[source] TemplateError),
+
+    #[error("Failed to write the rendered template to '{0}'.")]
+    WriteTemplate(PathBuf, #[source] std::io::Error),
+
+    #[error("Failed to set permissions of '{0}'.")]
+    Permissions(PathBuf, #[source] std::io::Error),
+}
+
+#[derive(Debug)]
+pub struct RestoreSuccess {
+    pub set_name: SetName,
+    pub files_restored: Vec<LocalFilePath>,
+}
+
+// unlike pull, set layering doesn't come into play. the file comes from the given set, even if another set would win.
+// the index is also left alone, since the file's owning set hasn't changed.
+pub fn restore(
+    profile: &MonjaProfile,
+    opts: &ExecutionOptions,
+    set_name: SetName,
+    files: Vec<LocalFilePath>,
+) -> Result<RestoreSuccess, RestoreError> {
+    let repo =
+        repo::initialize_full_state(profile).map_err(RestoreError::RepoStateInitialization)?;
+    let set = repo
+        .sets
+        .get(&set_name)
+        .ok_or_else(|| RestoreError::SetNotFound(set_name.clone()))?;
+
+    let mut repo_files = Vec::with_capacity(files.len());
+    let mut missing_files = Vec::new();
+    for public_file in files.iter() {
+        match set.locally_mapped_files.get(&public_file.to_internal()) {
+            Some(repo_file) => repo_files.push(repo_file),
+            None => missing_files.push(public_file.clone()),
+        }
+    }
+
+    // we'd rather not restore anything if the user may have made a typo
+    if !missing_files.is_empty() {
+        return Err(RestoreError::NotInSet {
+            set_name,
+            files: missing_files,
+        });
+    }
+
+    if !opts.dry_run {
+        let groups = repo::group_for_transfer(
+            &set.shortcut,
+            &set.dir_mappings,
+            repo_files
+                .iter()
+                .filter(|f| !f.secret && !f.template)
+                .map(|f| f.path.path_in_set.as_relative_path()),
+        );
+        for group in groups {
+            rsync(
+                &group.dir_in_set.to_path(&set.root),
+                &group.local_dir.to_path(&profile.local_root),
+                group.files.iter().map(|p| p.to_path("")),
+                false,
+                &profile.config.rsync_extra_args,
+                opts,
+                None,
+            )
+            .map_err(RestoreError::Rsync)?;
+        }
+
+        // same as pull, secrets are decrypted instead of copied
+        for repo_file in repo_files.iter().filter(|f| f.secret) {
+            let repo_path = repo_file.path.path_in_set.to_path(&set.root);
+            let path = repo_file.path.local_path.to_absolute_path(profile);
+            secret::write_decrypted(&repo_path, &path)
+                .map_err(|e| RestoreError::Decrypt(path, e))?;
+        }
+
+        // and templates are rendered
+        for repo_file in repo_files.iter().filter(|f| f.template) {
+            let repo_path = repo_file.path.path_in_set.to_path(&set.root);
+            let rendered = template::render_file(&repo_path, &profile.config.vars)
+                .map_err(|e| RestoreError::Template(repo_path.clone(), e))?;
+            let path = repo_file.path.local_path.to_absolute_path(profile);
+            crate::write_pulled_contents(&rendered, &repo_path, &path)
+                .map_err(|e| RestoreError::WriteTemplate(path, e))?;
+        }
+
+        // same as pull, since git doesn't track most of the mode
+        for repo_file in repo_files.iter() {
+            let Some(mode) = repo_file.mode else {
+                continue;
+            };
+            let path = repo_file.path.local_path.to_absolute_path(profile);
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode))
+                .map_err(|e| RestoreError::Permissions(path, e))?;
+        }
+    }
+
+    Ok(RestoreSuccess {
+        set_name,
+        files_restored: files,
+    })
+}
@@ -73,6 +73,11 @@ pub fn set_shortcut(
     let mut files_moved: Vec<PathBuf> = Vec::new();
     let new_shortcut_path = new_shortcut.to_path("");
     for file in set.locally_mapped_files.values() {
+        // these use the shortcut in their .monja-dir.toml instead
+        if set.has_dir_mapping(&file.path.path_in_set) {
+            continue;
+        }
+
         let new_relative = new_shortcut.relative(file.path.local_path.as_ref());
 
         // check the new relative path doesn't escape the set
@@ -1,7 +1,49 @@
+use std::{collections::HashMap, path::PathBuf, time::SystemTime};
+
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::{LocalFilePath, MonjaProfile, convert_set_localfile_result, local, repo};
 
+#[derive(Error, Debug)]
+pub enum FileStatusError {
+    #[error("Unable to load the file index.")]
+    FileIndex(#[from] local::FileIndexError),
+
+    #[error("Unable to initialize the state of set `{0}`.")]
+    SetStateInitialization(repo::SetName, #[source] Box<repo::StateInitializationError>),
+
+    #[error("Unable to compare '{0}' with its copy in the repo.")]
+    Comparison(PathBuf, #[source] std::io::Error),
+}
+
+// where a single file would show up in Status
+#[derive(Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    // the file doesn't exist locally (or isn't a file)
+    NotFound,
+    Untracked,
+    SetMissing(repo::SetName),
+    FileMissing(repo::SetName),
+    // the file is in sync with its set, or its set is never pushed to
+    Tracked(repo::SetName),
+    // the file differs from its set's copy
+    ToPush(repo::SetName),
+}
+
+// just the number of files in each part of Status, for the whole local root
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct StatusSummary {
+    pub to_push: usize,
+    pub modified: usize,
+    pub conflicted: usize,
+    pub untracked: usize,
+    pub missing_files: usize,
+    // files whose set is missing, not the number of sets
+    pub missing_sets: usize,
+    pub cleanable: usize,
+}
+
 #[derive(Error, Debug)]
 pub enum StatusError {
     #[error("Unable to initialize repo state.")]
@@ -14,15 +56,63 @@ pub enum StatusError {
     Location(LocalFilePath),
 }
 
-#[derive(Debug)]
+// serialized with each set's files as a [set, [files]] pair, keeping the sets in profile order
+#[derive(Debug, Serialize)]
 pub struct Status {
     pub files_to_push: Vec<(repo::SetName, Vec<LocalFilePath>)>,
+    // where each of files_to_push goes in the repo, mostly for debugging mappings.
+    // left out of the json, since it's already available through each set's config.
+    #[serde(skip)]
+    pub repo_paths_to_push: HashMap<LocalFilePath, PathBuf>,
+    // the subset of files_to_push that would actually change the repo
+    pub modified_files: Vec<(repo::SetName, Vec<LocalFilePath>)>,
+    // the subset of modified_files that also changed in the repo since they were last synced
+    pub conflicted_files: Vec<(repo::SetName, Vec<LocalFilePath>)>,
     pub files_with_missing_sets: Vec<(repo::SetName, Vec<LocalFilePath>)>,
     pub missing_files: Vec<(repo::SetName, Vec<LocalFilePath>)>,
     pub untracked_files: Vec<LocalFilePath>,
     pub old_files_after_last_pull: Vec<LocalFilePath>,
 }
 
+impl Status {
+    // one `<code>\t<path>` line per file, for scripts and editor integrations.
+    // unlike the normal output, this is kept stable across versions: codes and their order never change.
+    //   S: previously pulled, but its set is missing
+    //   M: previously pulled, but no longer in its set
+    //   P: would be pushed, even if unchanged
+    //   U: untracked
+    //   O: removed from the repo since the last pull, so also untracked
+    // lines are grouped by code in the order above, then sorted by path.
+    // paths are relative to the local root and always `/`-separated.
+    pub fn to_porcelain(&self) -> String {
+        let groups = [
+            ('S', flatten(&self.files_with_missing_sets)),
+            ('M', flatten(&self.missing_files)),
+            ('P', flatten(&self.files_to_push)),
+            ('U', self.untracked_files.iter().collect()),
+            ('O', self.old_files_after_last_pull.iter().collect()),
+        ];
+
+        let mut porcelain = String::new();
+        for (code, paths) in groups {
+            let mut paths: Vec<String> = paths
+                .into_iter()
+                .map(LocalFilePath::as_relative_str)
+                .collect();
+            paths.sort();
+
+            for path in paths {
+                porcelain.push_str(&format!("{}\t{}\n", code, path));
+            }
+        }
+        return porcelain;
+
+        fn flatten(files: &[(repo::SetName, Vec<LocalFilePath>)]) -> Vec<&LocalFilePath> {
+            files.iter().flat_map(|(_, paths)| paths).collect()
+        }
+    }
+}
+
 pub fn local_status(
     profile: &MonjaProfile,
     location: LocalFilePath,
@@ -33,12 +123,40 @@ pub fn local_status(
     // only cloning in case error. but it's just one clone so cheap enough.
     let location = location.to_internal();
 
+    let repo_paths_to_push = local_state
+        .files_to_push
+        .iter()
+        .filter_map(|(set_name, files)| repo.sets.get(set_name).map(|set| (set, files)))
+        .flat_map(|(set, files)| {
+            files
+                .iter()
+                .filter(|p| p.is_child_of(&location))
+                .filter_map(|p| {
+                    set.get_repo_absolute_path_for(p)
+                        .ok()
+                        .map(|repo_path| (p.clone().into(), repo_path))
+                })
+        })
+        .collect();
+
     let files_to_push = convert_set_localfile_result(
         &profile.config.target_sets,
         local_state.files_to_push,
         &location,
     );
 
+    let modified_files = convert_set_localfile_result(
+        &profile.config.target_sets,
+        local_state.modified_files,
+        &location,
+    );
+
+    let conflicted_files = convert_set_localfile_result(
+        &profile.config.target_sets,
+        local_state.conflicted_files,
+        &location,
+    );
+
     let files_with_missing_sets = convert_set_localfile_result(
         &profile.config.target_sets,
         local_state.files_with_missing_sets,
@@ -67,9 +185,100 @@ pub fn local_status(
 
     Ok(Status {
         files_to_push,
+        repo_paths_to_push,
+        modified_files,
+        conflicted_files,
         files_with_missing_sets,
         missing_files,
         old_files_after_last_pull,
         untracked_files,
     })
 }
+
+// the repo still gets read and local files walked, but files are only counted, skipping the sorting and conversion.
+// handy for shell prompts that only show how many files need attention.
+pub fn status_summary(profile: &MonjaProfile) -> Result<StatusSummary, StatusError> {
+    let repo =
+        repo::initialize_full_state(profile).map_err(StatusError::RepoStateInitialization)?;
+    let local_state = local::retrieve_state(profile, &repo)?;
+
+    let count = |files: &HashMap<repo::SetName, Vec<local::FilePath>>| -> usize {
+        files.values().map(Vec::len).sum()
+    };
+    Ok(StatusSummary {
+        to_push: count(&local_state.files_to_push),
+        modified: count(&local_state.modified_files),
+        conflicted: count(&local_state.conflicted_files),
+        untracked: local_state.untracked_files.len(),
+        missing_files: count(&local_state.missing_files),
+        missing_sets: count(&local_state.files_with_missing_sets),
+        cleanable: local_state.old_files_since_last_pull.len(),
+    })
+}
+
+// a much cheaper alternative to local_status for just old_files_after_last_pull (aka what an index clean would remove),
+// since only the indexes are loaded, instead of the repo's full state.
+pub fn cleanable_files(
+    profile: &MonjaProfile,
+) -> Result<Vec<LocalFilePath>, local::FileIndexError> {
+    let old_files = local::old_files_since_last_pull(profile)?;
+    Ok(old_files.into_iter().map(|f| f.into()).collect())
+}
+
+// None if monja has never pulled, or the last pull was by a version that didn't record it.
+// like cleanable_files, only the index is loaded.
+pub fn last_pull_time(profile: &MonjaProfile) -> Result<Option<SystemTime>, local::FileIndexError> {
+    let index = local::FileIndex::load(profile, local::IndexKind::Current)?;
+    Ok(index.last_pull())
+}
+
+// a much cheaper alternative to local_status for a single file, such as for a shell prompt.
+// only the file's owning set gets read, and local files aren't walked.
+// as such, .monjaignore files aren't consulted.
+pub fn file_status(
+    profile: &MonjaProfile,
+    file: LocalFilePath,
+) -> Result<FileStatus, FileStatusError> {
+    let local_path = file.to_internal();
+    let local_abs_path = local_path.to_absolute_path(profile);
+    // symlinks to files are tracked, too
+    if !local_abs_path.is_file() {
+        return Ok(FileStatus::NotFound);
+    }
+
+    let index = local::FileIndex::load(profile, local::IndexKind::Current)?;
+    let Some(entry) = index.get(&local_path) else {
+        return Ok(FileStatus::Untracked);
+    };
+    let set_name = entry.set.clone();
+
+    let set = repo::initialize_set_state(profile, &set_name)
+        .map_err(|e| FileStatusError::SetStateInitialization(set_name.clone(), Box::new(e)))?;
+    let Some(set) = set else {
+        return Ok(FileStatus::SetMissing(set_name));
+    };
+
+    if set.nopush {
+        return Ok(FileStatus::Tracked(set_name));
+    }
+
+    if !set.tracks_file(&local_path) {
+        return Ok(FileStatus::FileMissing(set_name));
+    }
+
+    // like nopush sets, since local edits to rendered templates never get pushed
+    if set.locally_mapped_files[&local_path].template {
+        return Ok(FileStatus::Tracked(set_name));
+    }
+
+    let repo_abs_path = set
+        .get_repo_absolute_path_for(&local_path)
+        .expect("Tracked files always have a path in the set.");
+    let secret = set.locally_mapped_files[&local_path].secret;
+    match crate::local_differs_from_set(profile, &local_abs_path, &repo_abs_path, secret)
+        .map_err(|e| FileStatusError::Comparison(local_abs_path, e))?
+    {
+        true => Ok(FileStatus::ToPush(set_name)),
+        false => Ok(FileStatus::Tracked(set_name)),
+    }
+}
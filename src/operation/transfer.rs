@@ -82,20 +82,22 @@ pub fn transfer(
         .get(&dest_set)
         .ok_or_else(|| TransferError::DestSetNotFound(dest_set.clone()))?;
 
-    let mut result_files = Vec::with_capacity(files.len());
-    for public_file in files.into_iter() {
+    // validate every file before doing anything, so a typo doesn't leave the sets half-moved.
+    // the destination path would fail if the file would become outside the set.
+    for public_file in files.iter() {
         let file = public_file.to_internal();
-
         if !source.tracks_file(&file) {
             return Err(TransferError::NotInSourceSet {
                 set_name: source_set.clone(),
-                local_path: public_file,
+                local_path: public_file.clone(),
             });
         }
-
-        // validate destination path before doing anything.
-        // this would fail if the file would become outside the set.
         let _ = dest.get_repo_relative_path_for(&file)?;
+    }
+
+    let mut result_files = Vec::with_capacity(files.len());
+    for public_file in files.into_iter() {
+        let file = public_file.to_internal();
 
         if !opts.dry_run {
             copy_to_dest(profile, dest, &file)?;
@@ -106,7 +108,8 @@ pub fn transfer(
             .get_owning_set(profile, &file)
             .expect("File is tracked by source set, so it should have an owner");
         if *owner == dest_set || *owner == source_set {
-            index.set(file, dest_set.clone());
+            let hash = local::hash_for_index(&file.to_absolute_path(profile))?;
+            index.set(file, dest_set.clone(), hash);
         }
 
         result_files.push(public_file);
@@ -132,7 +135,8 @@ fn copy_to_dest(
     if !copy_from.is_file() {
         return Err(TransferError::NotValidFile(copy_from));
     }
-    let copy_to = dest.get_repo_absolute_path_for(path)?;
+    let path_in_set = dest.get_repo_relative_path_for(path)?;
+    let copy_to = path_in_set.to_path(&dest.root);
     let copy_to_dir = copy_to
         .parent()
         .ok_or_else(|| TransferError::NotValidFile(copy_to.to_path_buf()))?;
@@ -140,11 +144,14 @@ fn copy_to_dest(
     fs::create_dir_all(copy_to_dir)
         .map_err(|e| TransferError::CreateDestDir(copy_to_dir.to_path_buf(), e))?;
 
-    fs::copy(&copy_from, &copy_to).map_err(|e| TransferError::CopyToDest {
-        set_name: dest.name.clone(),
-        local_path: copy_from,
-        repo_path: copy_to,
-        source: e,
+    let secret = dest.is_secret(&path_in_set);
+    crate::copy_local_file_to_set(profile, &copy_from, &copy_to, secret).map_err(|e| {
+        TransferError::CopyToDest {
+            set_name: dest.name.clone(),
+            local_path: copy_from,
+            repo_path: copy_to,
+            source: e,
+        }
     })?;
 
     Ok(())
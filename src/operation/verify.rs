@@ -0,0 +1,153 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    LocalFilePath, MonjaProfile, SetName, local, repo,
+    template::{self, TemplateError},
+};
+
+#[derive(Error, Debug)]
+pub enum VerifyError {
+    #[error("Unable to initialize repo state.")]
+    RepoStateInitialization(Vec<repo::StateInitializationError>),
+
+    #[error("Unable to load the file index.")]
+    FileIndex(#[from] local::FileIndexError),
+
+    #[error("Unable to hash '{0}'.")]
+    Hash(PathBuf, #[source] std::io::Error),
+
+    #[error("Failed to render the template '{0}'.")]
+    Template(PathBuf, #[source] TemplateError),
+}
+
+// like Status, each set's files are a [set, [files]] pair.
+// sets targeted by the profile come first, in profile order, followed by the rest alphabetically.
+#[derive(Debug, Serialize)]
+pub struct VerifyReport {
+    // how many files had matching contents locally and in the repo
+    pub verified_count: usize,
+    pub mismatched_files: Vec<(SetName, Vec<LocalFilePath>)>,
+    pub missing_local_files: Vec<(SetName, Vec<LocalFilePath>)>,
+    // files whose set no longer has them, including when the set itself is gone
+    pub missing_repo_files: Vec<(SetName, Vec<LocalFilePath>)>,
+}
+
+impl VerifyReport {
+    pub fn is_in_sync(&self) -> bool {
+        self.mismatched_files.is_empty()
+            && self.missing_local_files.is_empty()
+            && self.missing_repo_files.is_empty()
+    }
+}
+
+// unlike status, this doesn't care about what's pushable. it's a check that every file in the index
+// still matches its set's copy, such as after editing files by hand.
+// a file can show up as both missing locally and missing from the repo.
+pub fn verify(profile: &MonjaProfile, only: Option<&SetName>) -> Result<VerifyReport, VerifyError> {
+    let repo =
+        repo::initialize_full_state(profile).map_err(VerifyError::RepoStateInitialization)?;
+    let index = local::FileIndex::load(profile, local::IndexKind::Current)?;
+
+    let mut verified_count = 0;
+    let mut mismatched_files = HashMap::new();
+    let mut missing_local_files = HashMap::new();
+    let mut missing_repo_files = HashMap::new();
+
+    for (local_path, entry) in index.entries() {
+        if only.is_some_and(|only| *only != entry.set) {
+            continue;
+        }
+
+        let local_abs_path = local_path.to_absolute_path(profile);
+        let local_exists = local_abs_path.exists();
+        if !local_exists {
+            add(&mut missing_local_files, &entry.set, local_path);
+        }
+
+        let repo_abs_path = repo
+            .sets
+            .get(&entry.set)
+            .filter(|set| set.tracks_file(local_path))
+            .and_then(|set| set.get_repo_absolute_path_for(local_path).ok())
+            .filter(|path| path.exists());
+        let Some(repo_abs_path) = repo_abs_path else {
+            add(&mut missing_repo_files, &entry.set, local_path);
+            continue;
+        };
+
+        if !local_exists {
+            continue;
+        }
+
+        let repo_file = &repo.sets[&entry.set].locally_mapped_files[local_path];
+        let secret = repo_file.secret;
+        let is_link = profile.config.preserve_symlinks && local_abs_path.is_symlink();
+        let differs = match secret || is_link {
+            // the local file should match what the template renders to, rather than the template itself
+            false if repo_file.template => {
+                let rendered = template::render_file(&repo_abs_path, &profile.config.vars)
+                    .map_err(|e| VerifyError::Template(repo_abs_path.clone(), e))?;
+                local::hash_contents(&local_abs_path)
+                    .map_err(|e| VerifyError::Hash(local_abs_path.clone(), e))?
+                    != local::hash_bytes(&rendered)
+            }
+            // the link itself is what's synced, so there's no content to hash.
+            // and secrets need decrypting first.
+            true => crate::local_differs_from_set(profile, &local_abs_path, &repo_abs_path, secret)
+                .map_err(|e| VerifyError::Hash(local_abs_path.clone(), e))?,
+            false => {
+                local::hash_contents(&local_abs_path)
+                    .map_err(|e| VerifyError::Hash(local_abs_path.clone(), e))?
+                    != local::hash_contents(&repo_abs_path)
+                        .map_err(|e| VerifyError::Hash(repo_abs_path.clone(), e))?
+            }
+        };
+        match differs {
+            true => add(&mut mismatched_files, &entry.set, local_path),
+            false => verified_count += 1,
+        }
+    }
+
+    return Ok(VerifyReport {
+        verified_count,
+        mismatched_files: into_ordered(profile, mismatched_files),
+        missing_local_files: into_ordered(profile, missing_local_files),
+        missing_repo_files: into_ordered(profile, missing_repo_files),
+    });
+
+    fn add(
+        files: &mut HashMap<SetName, Vec<LocalFilePath>>,
+        set_name: &SetName,
+        local_path: &local::FilePath,
+    ) {
+        files
+            .entry(set_name.clone())
+            .or_default()
+            .push(local_path.clone().into());
+    }
+
+    // the index is a hashmap, so files are sorted to keep output stable across runs
+    fn into_ordered(
+        profile: &MonjaProfile,
+        mut files: HashMap<SetName, Vec<LocalFilePath>>,
+    ) -> Vec<(SetName, Vec<LocalFilePath>)> {
+        let mut result: Vec<(SetName, Vec<LocalFilePath>)> = profile
+            .config
+            .target_sets
+            .iter()
+            .filter_map(|name| files.remove_entry(name))
+            .collect();
+
+        let mut rest: Vec<(SetName, Vec<LocalFilePath>)> = files.into_iter().collect();
+        rest.sort_by(|(l, _), (r, _)| l.0.cmp(&r.0));
+        result.extend(rest);
+
+        for (_, file_paths) in result.iter_mut() {
+            file_paths.sort();
+        }
+        result
+    }
+}
@@ -0,0 +1,18 @@
+use thiserror::Error;
+
+use crate::{LocalFilePath, MonjaProfile, SetName, repo};
+
+#[derive(Error, Debug)]
+pub enum WhichError {
+    #[error("Unable to initialize repo state.")]
+    RepoStateInitialization(Vec<repo::StateInitializationError>),
+}
+
+// None if no targeted set has the file.
+// unlike resolve_repo_path, this goes by the repo's current layering instead of the index,
+// so it's the set the file would come from if pulled now.
+pub fn which(profile: &MonjaProfile, file: LocalFilePath) -> Result<Option<SetName>, WhichError> {
+    let repo = repo::initialize_full_state(profile).map_err(WhichError::RepoStateInitialization)?;
+
+    Ok(repo.get_owning_set(profile, &file.to_internal()).cloned())
+}
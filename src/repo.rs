@@ -1,15 +1,28 @@
-use std::{collections::HashMap, fmt::Display, fs, ops::Deref, path::PathBuf};
-
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use indoc::indoc;
 use relative_path::{RelativePath, RelativePathBuf};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use walkdir::WalkDir;
 
-use crate::{AbsolutePath, MonjaProfile, local};
+use crate::{AbsolutePath, FileLocation, MonjaProfile, local, secret, template};
 
 pub(crate) struct RepoState {
     pub sets: HashMap<SetName, Set>,
+    // sets with a dest-root, kept apart so that push, status, and clean never treat their files as local ones.
+    // only pull does anything with them.
+    pub dest_root_sets: HashMap<SetName, Set>,
+    // directories at the repo root that its .monjaignore excludes from being sets, sorted
+    pub ignored_dirs: Vec<PathBuf>,
 }
 
 impl RepoState {
@@ -31,7 +44,18 @@ pub(crate) struct Set {
     pub name: SetName,
     pub shortcut: SetShortcut,
     pub root: AbsolutePath,
-    // directories: HashMap<ObjectPath, Directory>,
+    // directories whose .monja-dir.toml places them somewhere other than under the set's shortcut
+    pub dir_mappings: Vec<DirMapping>,
+    // local directories whose files are never removed by a full clean
+    pub noclean_dirs: Vec<local::FilePath>,
+    // local edits to the set's files are never pushed back
+    pub nopush: bool,
+    // replaces the local root as the base of the set's shortcut. its files' local paths are relative to this instead.
+    pub dest_root: Option<PathBuf>,
+    // whether push and put record the ACLs of the set's files
+    pub preserve_acls: bool,
+    // matched against paths in the set, without the secret suffix
+    pub secrets: GlobSet,
     pub locally_mapped_files: HashMap<local::FilePath, File>,
 }
 
@@ -50,10 +74,53 @@ impl Set {
             .to_path(&self.root))
     }
 
+    pub(crate) fn is_noclean(&self, local_path: &local::FilePath) -> bool {
+        self.noclean_dirs.iter().any(|d| local_path.is_child_of(d))
+    }
+
+    // whether the file in the set is an encrypted secret
+    pub(crate) fn is_secret(&self, path_in_set: &RelativePath) -> bool {
+        secret::strip_suffix(path_in_set).is_some_and(|p| self.secrets.is_match(p.as_str()))
+    }
+
+    pub(crate) fn has_dir_mapping(&self, path_in_set: &RelativePath) -> bool {
+        self.dir_mappings
+            .iter()
+            .any(|m| path_in_set.starts_with(&m.dir_in_set))
+    }
+
     pub(crate) fn get_repo_relative_path_for(
         &self,
         local_path: &local::FilePath,
     ) -> Result<RelativePathBuf, SetPathError> {
+        if let Some(file) = self.locally_mapped_files.get(local_path) {
+            return Ok(file.path.path_in_set.clone());
+        }
+
+        // files that would be secrets, once in the set, are stored encrypted
+        let path_in_set = self.get_unencrypted_path_for(local_path)?;
+        match self.secrets.is_match(path_in_set.as_str()) {
+            true => Ok(secret::add_suffix(&path_in_set)),
+            false => Ok(path_in_set),
+        }
+    }
+
+    fn get_unencrypted_path_for(
+        &self,
+        local_path: &local::FilePath,
+    ) -> Result<RelativePathBuf, SetPathError> {
+        // the most specific local directory wins, since a mapping can point to somewhere under the set's shortcut
+        let mapping = self
+            .dir_mappings
+            .iter()
+            .filter(|m| local_path.as_ref().starts_with(&*m.local_dir))
+            .max_by_key(|m| m.local_dir.components().count());
+        if let Some(mapping) = mapping {
+            return Ok(mapping
+                .dir_in_set
+                .join(mapping.local_dir.relative(local_path)));
+        }
+
         let path = self.shortcut.relative(local_path);
 
         //  for `shortcut=foo/bar; path=foo/baz.file` we should fail
@@ -80,14 +147,86 @@ pub enum SetPathError {
     NotSure { shortcut: PathBuf, path: PathBuf },
 }
 
+#[derive(Debug)]
+pub(crate) struct DirMapping {
+    pub dir_in_set: RelativePathBuf,
+    // where the directory's contents go locally, relative to the local root
+    pub local_dir: SetShortcut,
+}
+
+// the deepest mapped directory containing the path wins, falling back to the set's shortcut
+fn resolve_mapping<'a>(
+    shortcut: &'a SetShortcut,
+    dir_mappings: &'a [DirMapping],
+    path_in_set: &RelativePath,
+) -> (&'a RelativePath, &'a SetShortcut) {
+    dir_mappings
+        .iter()
+        .filter(|m| path_in_set.starts_with(&m.dir_in_set))
+        .max_by_key(|m| m.dir_in_set.components().count())
+        .map_or((RelativePath::new(""), shortcut), |m| {
+            (m.dir_in_set.as_relative_path(), &m.local_dir)
+        })
+}
+
+// rsync needs a common root on both the set side and local side,
+// so files get grouped by the directory mapping they fall under.
+pub(crate) struct TransferGroup<'a> {
+    pub dir_in_set: &'a RelativePath,
+    pub local_dir: &'a SetShortcut,
+    // relative to dir_in_set and local_dir
+    pub files: Vec<RelativePathBuf>,
+}
+
+pub(crate) fn group_for_transfer<'a, 'p>(
+    shortcut: &'a SetShortcut,
+    dir_mappings: &'a [DirMapping],
+    paths_in_set: impl Iterator<Item = &'p RelativePath>,
+) -> Vec<TransferGroup<'a>> {
+    let mut groups: Vec<TransferGroup<'a>> = Vec::with_capacity(dir_mappings.len() + 1);
+    for path_in_set in paths_in_set {
+        let (dir_in_set, local_dir) = resolve_mapping(shortcut, dir_mappings, path_in_set);
+        let file = dir_in_set.relative(path_in_set);
+
+        match groups.iter_mut().find(|g| g.dir_in_set == dir_in_set) {
+            Some(group) => group.files.push(file),
+            None => groups.push(TransferGroup {
+                dir_in_set,
+                local_dir,
+                files: vec![file],
+            }),
+        }
+    }
+
+    groups
+}
+
 pub(crate) struct FilePath {
     pub path_in_set: RelativePathBuf,
     pub local_path: local::FilePath,
 }
 
 impl FilePath {
-    fn new(shortcut: &SetShortcut, path_in_set: RelativePathBuf) -> FilePath {
-        let local_path = local::FilePath::for_set(shortcut, &path_in_set);
+    fn new(
+        shortcut: &SetShortcut,
+        dir_mappings: &[DirMapping],
+        path_in_set: RelativePathBuf,
+        secret: bool,
+        template: bool,
+    ) -> FilePath {
+        // secrets and templates are pulled to their path without the suffix.
+        // templates matched by a glob may not have one, though.
+        let mapped_path = match (secret, template) {
+            (true, _) => {
+                secret::strip_suffix(&path_in_set).expect("Secrets always have the suffix.")
+            }
+            (false, true) => {
+                template::strip_suffix(&path_in_set).unwrap_or_else(|| path_in_set.clone())
+            }
+            (false, false) => path_in_set.clone(),
+        };
+        let (dir_in_set, local_dir) = resolve_mapping(shortcut, dir_mappings, &mapped_path);
+        let local_path = local::FilePath::for_set(local_dir, &dir_in_set.relative(&mapped_path));
 
         FilePath {
             path_in_set,
@@ -99,6 +238,14 @@ impl FilePath {
 pub(crate) struct File {
     pub owning_set: SetName,
     pub path: FilePath,
+    // only set if the set config specifies permissions for the file
+    pub mode: Option<u32>,
+    // only set if the set preserves ACLs and has some recorded for the file
+    pub acl: Option<String>,
+    // encrypted in the set, so it needs to be decrypted instead of copied
+    pub secret: bool,
+    // rendered with the profile's vars when pulled, so local edits can't be pushed back
+    pub template: bool,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -107,6 +254,65 @@ pub struct SetConfig {
     // used to be called root, but it was hard to disambiguate with other uses of the term
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub shortcut: Option<PathBuf>,
+
+    // keys are paths in the set (not local paths), and values are octal modes like '600'.
+    // a BTreeMap keeps the order stable when saving.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub permissions: BTreeMap<PathBuf, String>,
+
+    // for sets that should only ever be pulled, like vendored configs that aren't edited locally
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nopush: Option<bool>,
+
+    // globs of paths in the set (without the .age suffix) whose files are stored encrypted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
+
+    // globs of paths in the set that are templates, for those that can't be named with a .tmpl suffix
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<String>,
+
+    // sets with a higher priority win over lower ones, wherever they're listed in target-sets.
+    // handy for a machine-local overrides set that should always win. unset is the same as 0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+
+    // an absolute directory that the set gets pulled into instead of the local root, like /etc for system config.
+    // can't overlap with the local root, and the set is only pulled with PullOptions::allow_dest_roots.
+    // since its files aren't local files, they're never pushed, cleaned, or in the index.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dest_root: Option<PathBuf>,
+
+    // POSIX ACLs, like a config shared with a group, aren't tracked by git, so sets opt into keeping them.
+    // push and put then record them in acls, and pull reapplies them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preserve_acls: Option<bool>,
+
+    // keys are paths in the set, like permissions, and values are the file's extended entries
+    // in setfacl's form, like 'user:alice:r--,mask::r--'. only used with preserve-acls.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub acls: BTreeMap<PathBuf, String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+struct DirConfig {
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    noclean: bool,
+
+    // like a set's shortcut, but only for the directory's contents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shortcut: Option<PathBuf>,
+}
+
+// gives a directory in a set its own shortcut, such that its contents go to local_dir
+pub(crate) fn save_dir_shortcut(dir: &Path, local_dir: &RelativePath) -> std::io::Result<()> {
+    let config = DirConfig {
+        noclean: false,
+        shortcut: Some(local_dir.to_path("")),
+    };
+    let config = toml::to_string(&config).expect("Relative paths are always valid UTF-8.");
+    fs::write(dir.join(".monja-dir.toml"), config)
 }
 
 impl SetConfig {
@@ -133,8 +339,44 @@ impl SetConfig {
     }
 }
 
+// names get joined onto the repo root, so anything that would go somewhere other than a direct child is rejected.
+// names read from the profile or index are validated when deserialized.
 #[derive(PartialEq, Eq, Hash, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String")]
 pub struct SetName(pub String);
+
+impl SetName {
+    pub fn new(name: &str) -> Result<SetName, SetNameError> {
+        if name.is_empty() {
+            return Err(SetNameError::Empty);
+        }
+        if name == "." || name == ".." {
+            return Err(SetNameError::Reserved(name.to_string()));
+        }
+        if name.contains(std::path::is_separator) {
+            return Err(SetNameError::PathSeparator(name.to_string()));
+        }
+
+        Ok(SetName(name.to_string()))
+    }
+}
+
+impl TryFrom<String> for SetName {
+    type Error = SetNameError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        SetName::new(&value)
+    }
+}
+
+impl std::str::FromStr for SetName {
+    type Err = SetNameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SetName::new(s)
+    }
+}
+
 impl Display for SetName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -193,6 +435,16 @@ impl Deref for SetShortcut {
     }
 }
 
+#[derive(Error, Debug)]
+pub enum SetNameError {
+    #[error("Set names can't be empty.")]
+    Empty,
+    #[error("Set name '{0}' refers to a directory other than a set.")]
+    Reserved(String),
+    #[error("Set name '{0}' can't contain path separators.")]
+    PathSeparator(String),
+}
+
 #[derive(Error, Debug)]
 pub enum SetConfigError {
     #[error("Unable to deserialize .monja-set.toml for set '{0}'.")]
@@ -225,6 +477,28 @@ pub enum StateInitializationError {
     SetConfig(#[from] SetConfigError),
     #[error("Unable to parse set's shortcut: {0}")]
     InvalidShortcut(PathBuf, #[source] relative_path::FromPathError),
+    #[error("Invalid permissions '{2}' configured for '{1}' in set '{0}'.")]
+    InvalidPermissions(SetName, PathBuf, String),
+    #[error("Unable to read .monja-dir.toml in '{0}'.")]
+    DirConfigRead(PathBuf, #[source] std::io::Error),
+    #[error("Unable to deserialize .monja-dir.toml in '{0}'.")]
+    DirConfigDeserialization(PathBuf, #[source] toml::de::Error),
+    #[error("Shortcut in .monja-dir.toml in '{0}' is invalid.")]
+    DirConfigShortcut(PathBuf, #[source] SetShortcutError),
+    #[error("Invalid secret pattern '{1}' in set '{0}'.")]
+    InvalidSecretPattern(SetName, String, #[source] globset::Error),
+    #[error("Invalid template pattern '{1}' in set '{0}'.")]
+    InvalidTemplatePattern(SetName, String, #[source] globset::Error),
+    #[error(
+        "'{0}' and '{1}' only differ in case, so they would collide on a case-insensitive file system."
+    )]
+    CaseCollision(PathBuf, PathBuf),
+    #[error("Unable to read the repo's .monjaignore.")]
+    RepoIgnore(#[source] ignore::Error),
+    #[error(
+        "The dest-root '{1}' of set '{0}' must be absolute and can't overlap with the local root or the repo."
+    )]
+    InvalidDestRoot(SetName, PathBuf),
 }
 
 #[derive(Error, Debug)]
@@ -246,12 +520,20 @@ pub(crate) fn initialize_full_state(
     let read_dir = fs::read_dir(&profile.repo_root)
         .map_err(|e| vec![StateInitializationError::ReadSetDirs(e)])?;
 
+    let ignores = repo_ignores(&profile.repo_root)
+        .map_err(|e| vec![StateInitializationError::RepoIgnore(e)])?;
+
     let mut set_info = Vec::new();
+    let mut ignored_dirs = Vec::new();
     let mut errors = Vec::new();
 
     for result in read_dir {
         match result {
             Err(err) => errors.push(StateInitializationError::ReadSetDirs(err)),
+            Ok(e) if e.path().is_dir() && ignores.matched(e.path(), true).is_ignore() => {
+                log::debug!("Skipping ignored directory in repo: {}", e.path().display());
+                ignored_dirs.push(e.path());
+            }
             Ok(e) if e.path().is_dir() => {
                 match e.file_name().into_string() {
                     Ok(str) => set_info.push((SetName(str), e.path())),
@@ -263,19 +545,196 @@ pub(crate) fn initialize_full_state(
     }
 
     let mut sets = HashMap::with_capacity(set_info.len());
+    let mut dest_root_sets = HashMap::new();
     for (set_name, set_path) in set_info {
         let set = load_set_state(profile, &set_name, set_path);
         match set {
+            Ok(set) if set.dest_root.is_some() => _ = dest_root_sets.insert(set_name, set),
             Ok(set) => _ = sets.insert(set_name, set),
             Err(err) => errors.push(err),
         };
     }
 
+    if profile.config.case_insensitive_check {
+        errors.extend(find_case_collisions(profile, &sets));
+    }
+
     if !errors.is_empty() {
         return Err(errors);
     }
 
-    Ok(RepoState { sets })
+    ignored_dirs.sort();
+    Ok(RepoState {
+        sets,
+        dest_root_sets,
+        ignored_dirs,
+    })
+}
+
+// the repo root's .monjaignore keeps directories like .github from being treated as sets.
+// since it's not in a set, it's never pulled, unlike the .monjaignore files in sets.
+fn repo_ignores(repo_root: &Path) -> Result<Gitignore, ignore::Error> {
+    let mut builder = GitignoreBuilder::new(repo_root);
+    let path = repo_root.join(".monjaignore");
+    if path.is_file()
+        && let Some(e) = builder.add(path)
+    {
+        return Err(e);
+    }
+    builder.build()
+}
+
+#[derive(Error, Debug)]
+pub enum TargetSetsError {
+    #[error("Invalid pattern '{0}' in target-sets.")]
+    Pattern(String, #[source] globset::Error),
+    #[error("Unable to read the sets in the repo to expand target-sets.")]
+    ReadSetDirs(#[source] std::io::Error),
+    #[error("Unable to read the repo's .monjaignore.")]
+    RepoIgnore(#[source] ignore::Error),
+}
+
+enum TargetSet {
+    Literal(SetName),
+    Glob(String, globset::GlobMatcher),
+}
+
+// target-sets entries can be globs like `laptop*`, which expand to the sets in the repo they match, alphabetically.
+// sets named explicitly elsewhere in target-sets keep that position, and a set matching more than one glob goes with the first.
+// like shells, hidden directories only match globs that start with a dot, so `*` doesn't pick up `.git`.
+// to name a set with glob metacharacters in it, escape them with a backslash.
+pub(crate) fn expand_target_sets(
+    repo_root: &Path,
+    target_sets: Vec<SetName>,
+) -> Result<Vec<SetName>, TargetSetsError> {
+    let mut entries = Vec::with_capacity(target_sets.len());
+    for set_name in target_sets {
+        let entry = match unescape_set_name(&set_name.0) {
+            Some(literal) => TargetSet::Literal(SetName(literal)),
+            None => {
+                let glob = Glob::new(&set_name.0)
+                    .map_err(|e| TargetSetsError::Pattern(set_name.0.clone(), e))?;
+                TargetSet::Glob(set_name.0, glob.compile_matcher())
+            }
+        };
+        entries.push(entry);
+    }
+
+    // no need to read the repo if there's nothing to expand
+    if entries.iter().all(|e| matches!(e, TargetSet::Literal(_))) {
+        return Ok(entries
+            .into_iter()
+            .filter_map(|e| match e {
+                TargetSet::Literal(set_name) => Some(set_name),
+                TargetSet::Glob(..) => None,
+            })
+            .collect());
+    }
+
+    let ignores = repo_ignores(repo_root).map_err(TargetSetsError::RepoIgnore)?;
+    let mut set_names = Vec::new();
+    for entry in fs::read_dir(repo_root).map_err(TargetSetsError::ReadSetDirs)? {
+        let entry = entry.map_err(TargetSetsError::ReadSetDirs)?;
+        if !entry.path().is_dir() || ignores.matched(entry.path(), true).is_ignore() {
+            continue;
+        }
+        // non-utf8 names get reported when the repo is read
+        if let Ok(name) = entry.file_name().into_string() {
+            set_names.push(name);
+        }
+    }
+    set_names.sort();
+
+    let literals: HashSet<&SetName> = entries
+        .iter()
+        .filter_map(|e| match e {
+            TargetSet::Literal(set_name) => Some(set_name),
+            TargetSet::Glob(..) => None,
+        })
+        .collect();
+    let mut matched = HashSet::new();
+    let mut expanded = Vec::with_capacity(entries.len());
+    for entry in entries.iter() {
+        match entry {
+            TargetSet::Literal(set_name) => expanded.push(set_name.clone()),
+            TargetSet::Glob(pattern, matcher) => {
+                for name in set_names.iter() {
+                    if !glob_matches(pattern, matcher, name) {
+                        continue;
+                    }
+                    let set_name = SetName(name.clone());
+                    if !literals.contains(&set_name) && matched.insert(name) {
+                        expanded.push(set_name);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(expanded)
+}
+
+fn glob_matches(pattern: &str, matcher: &globset::GlobMatcher, name: &str) -> bool {
+    let hidden_mismatch = name.starts_with('.') && !pattern.starts_with('.');
+    !hidden_mismatch && matcher.is_match(name)
+}
+
+// for a target-sets entry as written in the profile, before it's expanded
+pub(crate) fn names_target_set(entry: &SetName, set_name: &SetName) -> bool {
+    unescape_set_name(&entry.0).is_some_and(|literal| literal == set_name.0)
+}
+
+// like names_target_set, but for entries that are globs.
+// invalid ones already fail when the profile is loaded, so they simply don't match here.
+pub(crate) fn glob_targets_set(entry: &SetName, set_name: &SetName) -> bool {
+    if unescape_set_name(&entry.0).is_some() {
+        return false;
+    }
+    Glob::new(&entry.0)
+        .is_ok_and(|glob| glob_matches(&entry.0, &glob.compile_matcher(), &set_name.0))
+}
+
+// None if the name has unescaped glob metacharacters
+fn unescape_set_name(name: &str) -> Option<String> {
+    let mut literal = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => literal.extend(chars.next()),
+            '*' | '?' | '[' | ']' | '{' | '}' => return None,
+            _ => literal.push(c),
+        }
+    }
+    Some(literal)
+}
+
+// only the targeted sets are checked, since those are the files that end up locally together.
+// the same path in multiple sets is just an override, so each local path is only counted once.
+fn find_case_collisions(
+    profile: &MonjaProfile,
+    sets: &HashMap<SetName, Set>,
+) -> Vec<StateInitializationError> {
+    let local_paths: BTreeSet<&RelativePath> = profile
+        .config
+        .target_sets
+        .iter()
+        .filter_map(|set_name| sets.get(set_name))
+        .flat_map(|set| set.locally_mapped_files.keys())
+        .map(|local_path| local_path.as_ref())
+        .collect();
+
+    let mut seen: HashMap<String, &RelativePath> = HashMap::with_capacity(local_paths.len());
+    let mut errors = Vec::new();
+    for local_path in local_paths {
+        match seen.get(&local_path.as_str().to_lowercase()) {
+            Some(existing) => errors.push(StateInitializationError::CaseCollision(
+                existing.to_path(""),
+                local_path.to_path(""),
+            )),
+            None => _ = seen.insert(local_path.as_str().to_lowercase(), local_path),
+        }
+    }
+    errors
 }
 
 pub(crate) fn create_empty_set(
@@ -300,6 +759,20 @@ pub(crate) fn create_empty_set(
     Ok(AbsolutePath::for_existing_path(&set_path).expect("Just created it."))
 }
 
+// for when only one set matters, which avoids walking the rest of the repo.
+// None if the set doesn't exist.
+pub(crate) fn initialize_set_state(
+    profile: &MonjaProfile,
+    set_name: &SetName,
+) -> Result<Option<Set>, StateInitializationError> {
+    let set_path = profile.repo_root.join(set_name);
+    if !set_path.is_dir() {
+        return Ok(None);
+    }
+
+    load_set_state(profile, set_name, set_path).map(Some)
+}
+
 fn load_set_state(
     profile: &MonjaProfile,
     set_name: &SetName,
@@ -310,35 +783,171 @@ fn load_set_state(
     let shortcut = set_config.shortcut.unwrap_or("".into());
     let shortcut = SetShortcut::from_path(shortcut)?;
 
+    let permissions = parse_permissions(set_name, set_config.permissions)?;
+    let nopush = set_config.nopush.unwrap_or(false);
+    let preserve_acls = set_config.preserve_acls.unwrap_or(false);
+    let acls: HashMap<RelativePathBuf, String> = match preserve_acls {
+        true => set_config
+            .acls
+            .into_iter()
+            .filter_map(|(path, acl)| Some((RelativePathBuf::from_path(path).ok()?, acl)))
+            .collect(),
+        false => HashMap::new(),
+    };
+    // anything overlapping the local root would either get walked as local files or put local files under the dest root.
+    // the repo would have pulled files written into it, which could then end up committed.
+    let overlaps =
+        |dest_root: &Path, root: &Path| dest_root.starts_with(root) || root.starts_with(dest_root);
+    if let Some(dest_root) = &set_config.dest_root
+        && (dest_root.is_relative()
+            || overlaps(dest_root, &profile.local_root)
+            || overlaps(dest_root, &profile.repo_root))
+    {
+        return Err(StateInitializationError::InvalidDestRoot(
+            set_name.clone(),
+            dest_root.clone(),
+        ));
+    }
+    let secrets = parse_globs(&set_config.secrets, |pattern, e| {
+        StateInitializationError::InvalidSecretPattern(set_name.clone(), pattern, e)
+    })?;
+    let templates = parse_globs(&set_config.templates, |pattern, e| {
+        StateInitializationError::InvalidTemplatePattern(set_name.clone(), pattern, e)
+    })?;
+
     let root = AbsolutePath::for_existing_path(&profile.repo_root.join(set_name))
         .expect("This function gets called after reading dirs in repo root.");
 
+    let mut dir_mappings = Vec::new();
+    let mut noclean_dirs = Vec::new();
     let mut locally_mapped_files = HashMap::new();
+    // parent directories get walked before their contents, so mappings are known before we get to the files
     for entry in WalkDir::new(&set_path) {
         let entry =
             entry.map_err(|e| StateInitializationError::DirectoryWalk(set_name.clone(), e))?;
-        if entry.file_type().is_file() && !crate::is_monja_special_file(entry.path()) {
-            let path_in_set = entry.path().strip_prefix(&set_path).expect(
-                "The entry path should start with set_path, since that's what we called it with.",
-            );
-            let path_in_set = RelativePathBuf::from_path(path_in_set)
-                .expect("Stripping of the prefix should make path relative");
-            let path = FilePath::new(&shortcut, path_in_set);
+        let path_in_set = entry.path().strip_prefix(&set_path).expect(
+            "The entry path should start with set_path, since that's what we called it with.",
+        );
+        let path_in_set = RelativePathBuf::from_path(path_in_set)
+            .expect("Stripping of the prefix should make path relative");
+
+        if entry.file_type().is_dir() {
+            let Some(dir_config) = load_dir_config(entry.path())? else {
+                continue;
+            };
+
+            if let Some(dir_shortcut) = dir_config.shortcut {
+                let local_dir = SetShortcut::from_path(dir_shortcut).map_err(|e| {
+                    StateInitializationError::DirConfigShortcut(entry.path().to_path_buf(), e)
+                })?;
+                dir_mappings.push(DirMapping {
+                    dir_in_set: path_in_set.clone(),
+                    local_dir,
+                });
+            }
+
+            if dir_config.noclean {
+                let (dir_in_set, local_dir) =
+                    resolve_mapping(&shortcut, &dir_mappings, &path_in_set);
+                noclean_dirs.push(local::FilePath::for_set(
+                    local_dir,
+                    &dir_in_set.relative(&path_in_set),
+                ));
+            }
+        } else if is_file_or_file_link(&entry)
+            && !profile
+                .config
+                .is_special_file(FileLocation::InSet(&path_in_set.to_path("")))
+        {
+            let mode = permissions.get(&path_in_set).copied();
+            let acl = acls.get(&path_in_set).cloned();
+            let secret =
+                secret::strip_suffix(&path_in_set).is_some_and(|p| secrets.is_match(p.as_str()));
+            let template = !secret
+                && (template::strip_suffix(&path_in_set).is_some()
+                    || templates.is_match(path_in_set.as_str()));
+            let path = FilePath::new(&shortcut, &dir_mappings, path_in_set, secret, template);
 
             let file = File {
                 owning_set: set_name.clone(),
                 path,
+                mode,
+                acl,
+                secret,
+                template,
             };
 
             locally_mapped_files.insert(file.path.local_path.clone(), file);
         }
-        // ignore dirs
     }
 
-    Ok(Set {
+    return Ok(Set {
         name: set_name.clone(),
         shortcut,
         root,
+        dir_mappings,
+        noclean_dirs,
+        nopush,
+        dest_root: set_config.dest_root,
+        preserve_acls,
+        secrets,
         locally_mapped_files,
-    })
+    });
+
+    // symlinks only end up in a set when the profile preserves them, and they get pulled as links.
+    // since they're usually relative to somewhere in the local dir, we can't check that they point to a file,
+    // so we just skip the ones that we can tell point to a directory.
+    fn is_file_or_file_link(entry: &walkdir::DirEntry) -> bool {
+        let file_type = entry.file_type();
+        file_type.is_file() || (file_type.is_symlink() && !entry.path().is_dir())
+    }
+
+    fn load_dir_config(dir: &Path) -> Result<Option<DirConfig>, StateInitializationError> {
+        let config_path = dir.join(".monja-dir.toml");
+        if !config_path.is_file() {
+            return Ok(None);
+        }
+
+        let config = fs::read(config_path)
+            .map_err(|e| StateInitializationError::DirConfigRead(dir.to_path_buf(), e))?;
+        let config = toml::from_slice(&config).map_err(|e| {
+            StateInitializationError::DirConfigDeserialization(dir.to_path_buf(), e)
+        })?;
+        Ok(Some(config))
+    }
+
+    fn parse_globs(
+        patterns: &[String],
+        error: impl Fn(String, globset::Error) -> StateInitializationError,
+    ) -> Result<GlobSet, StateInitializationError> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = Glob::new(pattern).map_err(|e| error(pattern.clone(), e))?;
+            builder.add(glob);
+        }
+        builder.build().map_err(|e| error(patterns.join(", "), e))
+    }
+
+    fn parse_permissions(
+        set_name: &SetName,
+        permissions: BTreeMap<PathBuf, String>,
+    ) -> Result<HashMap<RelativePathBuf, u32>, StateInitializationError> {
+        let mut result = HashMap::with_capacity(permissions.len());
+        for (path, mode) in permissions {
+            let parsed_mode = u32::from_str_radix(&mode, 8).ok().filter(|m| *m <= 0o7777);
+            let (Ok(relative_path), Some(parsed_mode)) =
+                (RelativePathBuf::from_path(&path), parsed_mode)
+            else {
+                return Err(StateInitializationError::InvalidPermissions(
+                    set_name.clone(),
+                    path,
+                    mode,
+                ));
+            };
+
+            result.insert(relative_path, parsed_mode);
+        }
+
+        Ok(result)
+    }
 }
@@ -1,19 +1,113 @@
 use std::{
     ffi::OsStr,
-    io::Write,
+    fs,
+    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    os::unix::ffi::OsStrExt,
     path::{Path, PathBuf},
-    process::{Command, Stdio},
+    process::{Command, ExitStatus, Stdio},
+    thread,
 };
 
+use thiserror::Error;
+
 use crate::ExecutionOptions;
 
-// keeping as io result because basically everything is io result
+#[derive(Error, Debug)]
+pub enum RsyncError {
+    #[error("Unable to run rsync.")]
+    Io(#[from] std::io::Error),
+
+    // stderr is captured so that callers can find out why without scraping the terminal
+    #[error("rsync exited unsuccessfully ({status}): {stderr}")]
+    Unsuccessful { status: ExitStatus, stderr: String },
+
+    #[error("rsync isn't available, and copying '{0}' without it failed.")]
+    NativeCopy(PathBuf, #[source] std::io::Error),
+}
+
+// called as each file finishes transferring, with the file's path relative to the local root
+// and the number of bytes transferred for it. unchanged files aren't reported.
+// since sets are pulled concurrently, it may be called from several threads at once.
+pub struct ProgressCallback(pub Box<ProgressFn<'static>>);
+
+pub(crate) type ProgressFn<'a> = dyn Fn(&Path, u64) + Send + Sync + 'a;
+
+impl std::fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+// what rsync reports having transferred, which leaves out files that were already up to date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RsyncStats {
+    pub files_transferred: u64,
+    // None if any rsync run didn't report it, such as with a version whose --stats output we don't recognize
+    pub bytes_transferred: Option<u64>,
+}
+
+impl Default for RsyncStats {
+    fn default() -> Self {
+        RsyncStats {
+            files_transferred: 0,
+            bytes_transferred: Some(0),
+        }
+    }
+}
+
+impl std::ops::AddAssign for RsyncStats {
+    fn add_assign(&mut self, other: Self) {
+        self.files_transferred += other.files_transferred;
+        self.bytes_transferred = self
+            .bytes_transferred
+            .zip(other.bytes_transferred)
+            .map(|(a, b)| a + b);
+    }
+}
+
+// extra args go before the source and dest, so they have to be options, with any value given as `--option=value`.
+// the rest of these would change where rsync reads the file list from, or what it prints, which we parse.
+// --dry-run would also have us record transfers that never happened.
+const DISALLOWED_EXTRA_ARGS: [&str; 7] = [
+    "files-from",
+    "from0",
+    "out-format",
+    "log-format",
+    "info",
+    "quiet",
+    "dry-run",
+];
+const DISALLOWED_SHORT_EXTRA_ARGS: [char; 3] = ['0', 'q', 'n'];
+
+pub(crate) fn is_allowed_extra_arg(arg: &str) -> bool {
+    // stdin is where the file list goes, so nothing else can read from it, like `--exclude-from=-`
+    if arg.ends_with("=-") {
+        return false;
+    }
+    match (arg.strip_prefix("--"), arg.strip_prefix('-')) {
+        (Some(long), _) => {
+            let name = long.split('=').next().unwrap_or_default();
+            !name.is_empty() && !DISALLOWED_EXTRA_ARGS.contains(&name)
+        }
+        // short options can be combined, like -0v
+        (None, Some(short)) => {
+            !short.is_empty() && !short.contains(DISALLOWED_SHORT_EXTRA_ARGS.as_slice())
+        }
+        (None, None) => false,
+    }
+}
+
+// progress is given paths relative to source and dest.
+// extra_args are assumed to have gone through is_allowed_extra_arg, and aren't used when copying natively.
 pub(crate) fn rsync(
     source: &Path,
     dest: &Path,
     files: impl Iterator<Item = PathBuf>,
+    copy_links: bool,
+    extra_args: &[String],
     opts: &ExecutionOptions,
-) -> std::io::Result<()> {
+    progress: Option<&ProgressFn<'_>>,
+) -> Result<RsyncStats, RsyncError> {
     // we use checksum mainly because, in integration tests, some files have same size and modified time
     // this could hypothetically happen in practice, so checksum is perhaps good.
     // note that file sizes still get compared before checksum, so most cases will still be fast.
@@ -22,20 +116,42 @@ pub(crate) fn rsync(
         "--files-from=-".as_ref(),
         "--checksum".as_ref(),
         "--mkpath".as_ref(),
+        "--stats".as_ref(),
     ];
+    // -a on its own copies symlinks as symlinks
+    if copy_links {
+        args.push("--copy-links".as_ref());
+    }
     if opts.verbosity > 0 {
         args.push("-v".as_ref());
     }
+    // one line per transferred file, which replaces -v's output
+    if progress.is_some() {
+        args.push("--out-format=%b %n".as_ref());
+    }
+    args.extend(extra_args.iter().map(AsRef::<OsStr>::as_ref));
     args.push(source.as_os_str());
     // append a /
     // works with mkpath to ensure the dir is properly created if needed
     let dest = dest.join("").into_os_string();
     args.push(&dest);
 
-    let mut child = Command::new("rsync")
+    let child = Command::new("rsync")
         .args(args)
         .stdin(Stdio::piped())
-        .spawn()?;
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            log::info!(
+                "rsync not found, so copying to '{}' natively",
+                dest.display()
+            );
+            return copy_natively(source, Path::new(&dest), files, copy_links, progress);
+        }
+        child => child?,
+    };
 
     {
         let mut stdin = child.stdin.take().expect("Added above");
@@ -47,17 +163,149 @@ pub(crate) fn rsync(
         // dropping sends eof
     }
 
-    let status = child.wait_with_output()?;
-    if opts.verbosity > 0 {
-        println!(
-            "Finished rsync for '{}' with status {}",
-            dest.display(),
-            status.status
-        );
+    // stderr gets its own thread so that neither pipe can fill up and block rsync
+    let mut stderr_pipe = child.stderr.take().expect("Added above");
+    let stderr_reader = thread::spawn(move || {
+        let mut stderr = Vec::new();
+        stderr_pipe.read_to_end(&mut stderr).map(|_| stderr)
+    });
+
+    let mut files_transferred = None;
+    let mut bytes_transferred = None;
+    let stdout = BufReader::new(child.stdout.take().expect("Added above"));
+    for line in stdout.split(b'\n') {
+        let line = line?;
+        if opts.verbosity > 0 {
+            log::info!("{}", String::from_utf8_lossy(&line));
+        }
+        if let Some(count) = parse_stat(&line, &FILES_TRANSFERRED_STATS) {
+            files_transferred = Some(count);
+        } else if let Some(bytes) = parse_stat(&line, &[BYTES_TRANSFERRED_STAT]) {
+            bytes_transferred = Some(bytes);
+        } else if let Some((progress, (file, bytes))) = progress.zip(parse_progress(&line)) {
+            progress(file, bytes);
+        }
+    }
+
+    let status = child.wait()?;
+    let stderr = stderr_reader.join().expect("Only reads from a pipe.")?;
+    let stderr = String::from_utf8_lossy(&stderr).into_owned();
+    if !stderr.is_empty() {
+        log::info!("{}", stderr.trim_end());
+    }
+    log::debug!(
+        "Finished rsync for '{}' with status {}",
+        dest.display(),
+        status
+    );
+
+    match status.success() {
+        true => Ok(RsyncStats {
+            files_transferred: files_transferred.unwrap_or(0),
+            bytes_transferred,
+        }),
+        false => Err(RsyncError::Unsuccessful { status, stderr }),
     }
+}
+
+// older versions of rsync don't distinguish regular files from the rest
+const FILES_TRANSFERRED_STATS: [&str; 2] = [
+    "Number of regular files transferred: ",
+    "Number of files transferred: ",
+];
+const BYTES_TRANSFERRED_STAT: &str = "Total transferred file size: ";
+
+// lines look like `Total transferred file size: 1,234 bytes`.
+// depending on version and locale, numbers may have thousands separators.
+fn parse_stat(line: &[u8], labels: &[&str]) -> Option<u64> {
+    let line = std::str::from_utf8(line).ok()?;
+    let value = labels.iter().find_map(|label| line.strip_prefix(label))?;
+    let number = value.split_whitespace().next()?;
+    number
+        .chars()
+        .filter(|c| !matches!(c, ',' | '.' | '\''))
+        .collect::<String>()
+        .parse()
+        .ok()
+}
+
+// lines look like `1234 foo/bar`, per --out-format.
+// directories, which end in a /, get created along the way and aren't files that were asked for.
+fn parse_progress(line: &[u8]) -> Option<(&Path, u64)> {
+    let separator = line.iter().position(|b| *b == b' ')?;
+    let (bytes, file) = (&line[..separator], &line[separator + 1..]);
+    if file.is_empty() || file.ends_with(b"/") {
+        return None;
+    }
+    let bytes = std::str::from_utf8(bytes).ok()?.parse().ok()?;
+    Some((Path::new(OsStr::from_bytes(file)), bytes))
+}
+
+// a stand-in for `rsync -a --mkpath [--copy-links]` for systems without rsync.
+// --checksum is approximated by skipping files whose size and mtime already match.
+fn copy_natively(
+    source: &Path,
+    dest: &Path,
+    files: impl Iterator<Item = PathBuf>,
+    copy_links: bool,
+    progress: Option<&ProgressFn<'_>>,
+) -> Result<RsyncStats, RsyncError> {
+    let mut stats = RsyncStats::default();
+    for file in files {
+        let from = source.join(&file);
+        let to = dest.join(&file);
+        let copied =
+            copy_file(&from, &to, copy_links).map_err(|e| RsyncError::NativeCopy(from, e))?;
+        let Some(bytes) = copied else {
+            continue;
+        };
+        stats += RsyncStats {
+            files_transferred: 1,
+            bytes_transferred: Some(bytes),
+        };
+        if let Some(progress) = progress {
+            progress(&file, bytes);
+        }
+    }
+
+    return Ok(stats);
+
+    // returns the bytes copied, or None if the file was already up to date
+    fn copy_file(from: &Path, to: &Path, copy_links: bool) -> std::io::Result<Option<u64>> {
+        let from_metadata = match copy_links {
+            true => fs::metadata(from)?,
+            false => fs::symlink_metadata(from)?,
+        };
+        let to_metadata = fs::symlink_metadata(to).ok();
+
+        if let Some(to_dir) = to.parent() {
+            fs::create_dir_all(to_dir)?;
+        }
+
+        if from_metadata.is_symlink() {
+            if to_metadata.is_some() {
+                fs::remove_file(to)?;
+            }
+            std::os::unix::fs::symlink(fs::read_link(from)?, to)?;
+            return Ok(Some(0));
+        }
+
+        let from_modified = from_metadata.modified()?;
+        if let Some(to_metadata) = to_metadata {
+            if to_metadata.is_file()
+                && to_metadata.len() == from_metadata.len()
+                && to_metadata.modified()? == from_modified
+            {
+                return Ok(None);
+            }
+            // like rsync, replace the file instead of writing into it, which also works for read-only files
+            fs::remove_file(to)?;
+        }
 
-    match status.status.success() {
-        true => Ok(()),
-        false => Err(std::io::Error::other("Unsuccessful status code for rsync.")),
+        // also copies permissions
+        let bytes = fs::copy(from, to)?;
+        // only the owner is needed to set times, so we don't need write access
+        fs::File::open(to)?.set_modified(from_modified)?;
+        Ok(Some(bytes))
     }
 }
@@ -0,0 +1,97 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+    str::FromStr,
+};
+
+use relative_path::{RelativePath, RelativePathBuf};
+use thiserror::Error;
+
+use crate::local;
+
+// secret files are stored in their set encrypted with age, under their usual name plus this suffix.
+// the identity comes from this env var, as opposed to the profile, so that the key never ends up in a file monja manages.
+pub(crate) const SECRET_SUFFIX: &str = ".age";
+pub const SECRET_KEY_VAR: &str = "MONJA_AGE_KEY";
+
+#[derive(Error, Debug)]
+pub enum SecretError {
+    #[error(
+        "{SECRET_KEY_VAR} must be set to an age identity (AGE-SECRET-KEY-...) to work with secret files."
+    )]
+    MissingKey,
+
+    #[error("{SECRET_KEY_VAR} is not a valid age identity: {0}")]
+    InvalidKey(&'static str),
+
+    #[error("Failed to encrypt secret file.")]
+    Encrypt(#[source] age::EncryptError),
+
+    #[error("Failed to decrypt secret file.")]
+    Decrypt(#[source] age::DecryptError),
+}
+
+// the path the file would be at if it weren't encrypted, which is what secret patterns are matched against
+pub(crate) fn strip_suffix(path_in_set: &RelativePath) -> Option<RelativePathBuf> {
+    path_in_set
+        .as_str()
+        .strip_suffix(SECRET_SUFFIX)
+        .filter(|p| !p.is_empty() && !p.ends_with('/'))
+        .map(RelativePathBuf::from)
+}
+
+pub(crate) fn add_suffix(path_in_set: &RelativePath) -> RelativePathBuf {
+    RelativePathBuf::from(format!("{}{}", path_in_set, SECRET_SUFFIX))
+}
+
+// errors are wrapped in io errors, since they're used in the same places as regular file operations
+pub(crate) fn read_decrypted(repo_path: &Path) -> std::io::Result<Vec<u8>> {
+    let identity = identity().map_err(std::io::Error::other)?;
+    let ciphertext = fs::read(repo_path)?;
+    age::decrypt(&identity, &ciphertext).map_err(|e| std::io::Error::other(SecretError::Decrypt(e)))
+}
+
+pub(crate) fn write_encrypted(local_path: &Path, repo_path: &Path) -> std::io::Result<()> {
+    let identity = identity().map_err(std::io::Error::other)?;
+    let plaintext = fs::read(local_path)?;
+    let ciphertext = age::encrypt(&identity.to_public(), &plaintext)
+        .map_err(|e| std::io::Error::other(SecretError::Encrypt(e)))?;
+    fs::write(repo_path, ciphertext)
+}
+
+// unlike other pulled files, the ciphertext's permissions aren't copied, since git leaves it readable by everyone.
+// the plaintext is instead only readable by the user from the moment it's created.
+// a set's config can still widen that with explicit permissions, which get applied afterwards.
+pub(crate) fn write_decrypted(repo_path: &Path, local_path: &Path) -> std::io::Result<u64> {
+    let contents = read_decrypted(repo_path)?;
+    if let Some(local_dir) = local_path.parent() {
+        fs::create_dir_all(local_dir)?;
+    }
+    // like rsync, replace the file instead of writing into it, which would keep its old permissions
+    if local_path.symlink_metadata().is_ok() {
+        fs::remove_file(local_path)?;
+    }
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(local_path)?
+        .write_all(&contents)?;
+    Ok(contents.len() as u64)
+}
+
+pub(crate) fn local_differs(local_path: &Path, repo_path: &Path) -> std::io::Result<bool> {
+    Ok(fs::read(local_path)? != read_decrypted(repo_path)?)
+}
+
+pub(crate) fn hash_decrypted(repo_path: &Path) -> std::io::Result<String> {
+    Ok(local::hash_bytes(&read_decrypted(repo_path)?))
+}
+
+fn identity() -> Result<age::x25519::Identity, SecretError> {
+    let key = std::env::var(SECRET_KEY_VAR).map_err(|_| SecretError::MissingKey)?;
+    age::x25519::Identity::from_str(key.trim()).map_err(SecretError::InvalidKey)
+}
@@ -0,0 +1,111 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use relative_path::{RelativePath, RelativePathBuf};
+use thiserror::Error;
+
+// files in a set with this suffix are always templates, and are pulled to their path without it.
+// sets can also mark other files as templates with globs, in which case their path is left alone.
+pub(crate) const TEMPLATE_SUFFIX: &str = ".tmpl";
+
+#[derive(Error, Debug)]
+pub enum TemplateError {
+    #[error("Unable to read the template.")]
+    Read(#[source] std::io::Error),
+
+    #[error("The template is not valid UTF-8.")]
+    NotUtf8(#[source] std::string::FromUtf8Error),
+
+    #[error("Variable '{0}' is not defined in the profile's [vars].")]
+    UndefinedVariable(String),
+
+    // the line is 1-based, like an editor would show
+    #[error("A '{{{{' on line {0} is never closed with '}}}}'.")]
+    Unclosed(usize),
+}
+
+pub(crate) fn strip_suffix(path_in_set: &RelativePath) -> Option<RelativePathBuf> {
+    path_in_set
+        .as_str()
+        .strip_suffix(TEMPLATE_SUFFIX)
+        .filter(|p| !p.is_empty() && !p.ends_with('/'))
+        .map(RelativePathBuf::from)
+}
+
+pub(crate) fn render_file(
+    repo_path: &Path,
+    vars: &BTreeMap<String, String>,
+) -> Result<Vec<u8>, TemplateError> {
+    let template = fs::read(repo_path).map_err(TemplateError::Read)?;
+    let template = String::from_utf8(template).map_err(TemplateError::NotUtf8)?;
+    render(&template, vars).map(String::into_bytes)
+}
+
+// variables look like `{{ name }}`, with the whitespace being optional
+pub(crate) fn render(
+    template: &str,
+    vars: &BTreeMap<String, String>,
+) -> Result<String, TemplateError> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after_start = &rest[start + 2..];
+        let Some(end) = after_start.find("}}") else {
+            let consumed = template.len() - rest.len() + start;
+            return Err(TemplateError::Unclosed(
+                template[..consumed].matches('\n').count() + 1,
+            ));
+        };
+
+        let name = after_start[..end].trim();
+        let value = vars
+            .get(name)
+            .ok_or_else(|| TemplateError::UndefinedVariable(name.to_string()))?;
+        rendered.push_str(value);
+        rest = &after_start[end + 2..];
+    }
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use googletest::prelude::*;
+
+    use super::{TemplateError, render};
+
+    fn vars() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("email".to_string(), "me@example.com".to_string()),
+            ("host".to_string(), "desktop".to_string()),
+        ])
+    }
+
+    #[gtest]
+    fn substitutes() {
+        expect_that!(
+            render("email = {{email}}\nhost = {{ host }}{{host}}\n", &vars()),
+            ok(eq("email = me@example.com\nhost = desktopdesktop\n"))
+        );
+        expect_that!(render("no vars } {", &vars()), ok(eq("no vars } {")));
+    }
+
+    #[gtest]
+    fn undefined_variable() {
+        expect_that!(
+            render("{{ email }} {{ name }}", &vars()),
+            err(pat!(TemplateError::UndefinedVariable(eq("name"))))
+        );
+    }
+
+    #[gtest]
+    fn unclosed() {
+        expect_that!(
+            render("{{ email }}\n\nhost = {{ host", &vars()),
+            err(pat!(TemplateError::Unclosed(eq(&3))))
+        );
+    }
+}
@@ -1,7 +1,9 @@
-use std::path::Path;
+use std::{cell::RefCell, path::Path};
 
 use googletest::prelude::*;
-use monja::{CleanMode, MonjaProfileConfig};
+use monja::{
+    CleanDecision, CleanError, CleanMode, CleanSuccess, ExecutionOptions, MonjaProfileConfig,
+};
 
 use crate::sim::{Simulator, set_names};
 
@@ -26,7 +28,11 @@ fn index_clean() -> Result<()> {
         file "set2b" "set2b-pull1"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     sim.configure_profile(|old| MonjaProfileConfig {
         target_sets: set_names(["set2"]),
@@ -42,11 +48,20 @@ fn index_clean() -> Result<()> {
         file "set2b" "set2b-pull2"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
     fs_operation! { LocalManipulation, sim,
         file "notinrepo" "notinrepo"
     };
-    let clean_result = monja::clean(&sim.profile()?, sim.execution_options(), CleanMode::Index)?;
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Index,
+        &|_| true,
+    )?;
     expect_that!(
         clean_result.files_cleaned,
         {
@@ -64,6 +79,59 @@ fn index_clean() -> Result<()> {
     Ok(())
 }
 
+#[gtest]
+fn index_clean_after_repeated_pull() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "set1a" "set1a"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "set2a" "set2a"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set2"]),
+        ..old
+    });
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    // nothing changed since the last pull, so set1's files are still cleanable
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Index,
+        &|_| true,
+    )?;
+    expect_that!(
+        clean_result.files_cleaned,
+        elements_are![eq(Path::new("set1a"))]
+    );
+    fs_operation! { LocalValidation, sim,
+        file "set2a" "set2a"
+    };
+
+    Ok(())
+}
+
 #[gtest]
 fn index_clean_ignorefile() -> Result<()> {
     let sim = Simulator::create();
@@ -79,7 +147,11 @@ fn index_clean_ignorefile() -> Result<()> {
     };
 
     // old index will have foo/bar
-    let _ = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _ = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { SetManipulation, sim, "set1",
         dir "foo"
@@ -93,10 +165,19 @@ fn index_clean_ignorefile() -> Result<()> {
     };
 
     // new index doesn't have foo/bar, so should be eligible for index clean if not for ignore
-    let pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
     expect_that!(pull_result.cleanable_files, is_empty());
 
-    let clean_result = monja::clean(&sim.profile()?, sim.execution_options(), CleanMode::Index)?;
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Index,
+        &|_| true,
+    )?;
     expect_that!(clean_result.files_cleaned, is_empty());
 
     fs_operation! { LocalValidation, sim,
@@ -127,7 +208,11 @@ fn full_clean() -> Result<()> {
         file "set2b" "set2b-pull1"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     sim.configure_profile(|old| MonjaProfileConfig {
         target_sets: set_names(["set2"]),
@@ -143,11 +228,22 @@ fn full_clean() -> Result<()> {
         file "set2b" "set2b-pull2"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
     fs_operation! { LocalManipulation, sim,
         file "notinrepo" "notinrepo"
     };
-    let clean_result = monja::clean(&sim.profile()?, sim.execution_options(), CleanMode::Full)?;
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Full {
+            exclude: Vec::new(),
+        },
+        &|_| true,
+    )?;
     expect_that!(
         clean_result.files_cleaned,
         {
@@ -177,7 +273,11 @@ fn full_clean_ignorefile() -> Result<()> {
         file ".monjaignore" "foo/bar"
     };
 
-    _ = monja::pull(&sim.profile()?, sim.execution_options())?;
+    _ = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { LocalManipulation, sim,
         dir "foo"
@@ -186,10 +286,21 @@ fn full_clean_ignorefile() -> Result<()> {
         file "notignored" "notignored"
     };
 
-    let pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
     expect_that!(pull_result.cleanable_files, is_empty());
 
-    let clean_result = monja::clean(&sim.profile()?, sim.execution_options(), CleanMode::Full)?;
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Full {
+            exclude: Vec::new(),
+        },
+        &|_| true,
+    )?;
     expect_that!(clean_result.files_cleaned, { eq(Path::new("notignored")) });
 
     fs_operation! { LocalValidation, sim,
@@ -219,7 +330,11 @@ fn index_clean_dryrun() -> Result<()> {
         file "set2b" "set2b-pull1"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     sim.configure_profile(|old| MonjaProfileConfig {
         target_sets: set_names(["set2"]),
@@ -235,13 +350,22 @@ fn index_clean_dryrun() -> Result<()> {
         file "set2b" "set2b-pull2"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
     fs_operation! { LocalManipulation, sim,
         file "notinrepo" "notinrepo"
     };
 
     sim.dryrun(true);
-    let clean_result = monja::clean(&sim.profile()?, sim.execution_options(), CleanMode::Index)?;
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Index,
+        &|_| true,
+    )?;
     expect_that!(
         clean_result.files_cleaned,
         {
@@ -279,7 +403,11 @@ fn full_clean_dryrun() -> Result<()> {
         file "set2b" "set2b-pull1"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     sim.configure_profile(|old| MonjaProfileConfig {
         target_sets: set_names(["set2"]),
@@ -295,13 +423,24 @@ fn full_clean_dryrun() -> Result<()> {
         file "set2b" "set2b-pull2"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
     fs_operation! { LocalManipulation, sim,
         file "notinrepo" "notinrepo"
     };
 
     sim.dryrun(true);
-    let clean_result = monja::clean(&sim.profile()?, sim.execution_options(), CleanMode::Full)?;
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Full {
+            exclude: Vec::new(),
+        },
+        &|_| true,
+    )?;
     expect_that!(
         clean_result.files_cleaned,
         {
@@ -322,3 +461,462 @@ fn full_clean_dryrun() -> Result<()> {
 
     Ok(())
 }
+
+#[gtest]
+fn full_clean_noclean_dir() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        dir "plugins"
+            file ".monja-dir.toml" "noclean = true"
+            file "plugin1" "plugin1"
+        end
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    fs_operation! { LocalManipulation, sim,
+        dir "plugins"
+            file "generated" "generated"
+        end
+        file "notinrepo" "notinrepo"
+    };
+
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Full {
+            exclude: Vec::new(),
+        },
+        &|_| true,
+    )?;
+    expect_that!(clean_result.files_cleaned, { eq(Path::new("notinrepo")) });
+
+    fs_operation! { LocalValidation, sim,
+        dir "plugins"
+            file "plugin1" "plugin1"
+            file "generated" "generated"
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn confirmation_declined() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.skip_confirmations(false);
+
+    fs_operation! { LocalManipulation, sim,
+        file "notinrepo" "notinrepo"
+    };
+
+    let planned = RefCell::new(Vec::new());
+    let confirm = |p: &CleanSuccess| {
+        planned.replace(p.files_cleaned.clone());
+        false
+    };
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Full {
+            exclude: Vec::new(),
+        },
+        &confirm,
+    );
+    expect_that!(clean_result, err(pat!(CleanError::UserCancellation)));
+    expect_that!(planned.into_inner(), { eq(Path::new("notinrepo")) });
+
+    fs_operation! { LocalValidation, sim,
+        file "notinrepo" "notinrepo"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn confirmation_accepted() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.skip_confirmations(false);
+
+    fs_operation! { LocalManipulation, sim,
+        file "notinrepo" "notinrepo"
+    };
+
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Full {
+            exclude: Vec::new(),
+        },
+        &|_| true,
+    )?;
+    expect_that!(clean_result.files_cleaned, { eq(Path::new("notinrepo")) });
+
+    fs_operation! { LocalValidation, sim,
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn confirmation_skipped() -> Result<()> {
+    let sim = Simulator::create();
+
+    fs_operation! { LocalManipulation, sim,
+        file "notinrepo" "notinrepo"
+    };
+
+    // the simulator skips confirmations, so the callback never gets a say
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Full {
+            exclude: Vec::new(),
+        },
+        &|_| false,
+    )?;
+    expect_that!(clean_result.files_cleaned, { eq(Path::new("notinrepo")) });
+
+    Ok(())
+}
+
+#[gtest]
+fn interactive() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.skip_confirmations(false);
+
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "pie"
+        file "blueberry" "tart"
+        dir "cherry"
+            file "cobbler" "cobbler"
+        end
+    };
+
+    let mut asked = Vec::new();
+    let clean_result = monja::clean_interactive(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Full {
+            exclude: Vec::new(),
+        },
+        &mut |path| {
+            asked.push(path.clone());
+            match path.as_relative_str().as_str() {
+                "blueberry" => CleanDecision::Keep,
+                _ => CleanDecision::Remove,
+            }
+        },
+    )?;
+    expect_that!(
+        asked,
+        elements_are![
+            eq(Path::new("apple")),
+            eq(Path::new("blueberry")),
+            eq(Path::new("cherry/cobbler"))
+        ]
+    );
+    expect_that!(
+        clean_result.files_cleaned,
+        elements_are![eq(Path::new("apple")), eq(Path::new("cherry/cobbler"))]
+    );
+    expect_that!(clean_result.dirs_pruned, { eq(Path::new("cherry")) });
+
+    fs_operation! { LocalValidation, sim,
+        file "blueberry" "tart"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn interactive_quit() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.skip_confirmations(false);
+
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "pie"
+        file "blueberry" "tart"
+    };
+
+    // quitting after saying to remove the first file still removes nothing
+    let clean_result = monja::clean_interactive(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Full {
+            exclude: Vec::new(),
+        },
+        &mut |path| match path.as_relative_str().as_str() {
+            "apple" => CleanDecision::Remove,
+            _ => CleanDecision::Quit,
+        },
+    );
+    expect_that!(clean_result, err(pat!(CleanError::UserCancellation)));
+
+    fs_operation! { LocalValidation, sim,
+        file "apple" "pie"
+        file "blueberry" "tart"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn empty_dirs_pruned() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        dir ".config"
+            dir "oldapp"
+                dir "nested"
+                    file "config" "config"
+                end
+            end
+            dir "sharedapp"
+                file "old" "old"
+            end
+            file "kept" "kept"
+        end
+        file "rootfile" "rootfile"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { SetManipulation, sim, "set1",
+        remdir ".config/oldapp"
+        remfile ".config/sharedapp/old"
+        remfile "rootfile"
+    };
+    fs_operation! { LocalManipulation, sim,
+        dir ".config/sharedapp"
+            file "notinrepo" "notinrepo"
+        end
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Index,
+        &|_| true,
+    )?;
+    expect_that!(
+        clean_result.dirs_pruned,
+        elements_are![
+            eq(Path::new(".config/oldapp")),
+            eq(Path::new(".config/oldapp/nested"))
+        ]
+    );
+
+    fs_operation! { LocalValidation, sim,
+        dir ".config"
+            dir "sharedapp"
+                file "notinrepo" "notinrepo"
+            end
+            file "kept" "kept"
+        end
+    };
+    expect_that!(sim.local_root().join(".config/oldapp").exists(), eq(false));
+
+    Ok(())
+}
+
+#[gtest]
+fn empty_dirs_pruned_dryrun() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        dir "oldapp"
+            file "config" "config"
+        end
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { SetManipulation, sim, "set1",
+        remdir "oldapp"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    sim.dryrun(true);
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Index,
+        &|_| true,
+    )?;
+    expect_that!(
+        clean_result.dirs_pruned,
+        elements_are![eq(Path::new("oldapp"))]
+    );
+    expect_that!(sim.local_root().join("oldapp/config").exists(), eq(true));
+
+    Ok(())
+}
+
+#[gtest]
+fn full_clean_exclude() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        dir ".config/app"
+            file "config" "config"
+        end
+    };
+    _ = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        dir ".config/app"
+            file "local-only" "local-only"
+            dir "cache"
+                file "data" "data"
+            end
+        end
+        dir ".config/app2"
+            file "notinrepo" "notinrepo"
+        end
+        file "notinrepo" "notinrepo"
+    };
+
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Full {
+            exclude: vec![sim.local_path(".config/app")],
+        },
+        &|_| true,
+    )?;
+    // .config/app2 only shares a prefix as a string, so it's still cleaned
+    expect_that!(
+        clean_result.files_cleaned,
+        elements_are![
+            eq(Path::new(".config/app2/notinrepo")),
+            eq(Path::new("notinrepo")),
+        ]
+    );
+    expect_that!(
+        clean_result.protected,
+        elements_are![
+            eq(Path::new(".config/app/cache/data")),
+            eq(Path::new(".config/app/local-only")),
+        ]
+    );
+
+    fs_operation! { LocalValidation, sim,
+        dir ".config/app"
+            file "config" "config"
+            file "local-only" "local-only"
+            dir "cache"
+                file "data" "data"
+            end
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn too_many_files() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.skip_confirmations(false);
+    sim.configure_profile(|old| MonjaProfileConfig {
+        clean_threshold: Some(1),
+        ..old
+    });
+
+    fs_operation! { LocalManipulation, sim,
+        file "notinrepo1" "notinrepo1"
+        file "notinrepo2" "notinrepo2"
+    };
+
+    // refused before the callback is asked anything
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Full {
+            exclude: Vec::new(),
+        },
+        &|_| panic!("Should not ask for confirmation."),
+    );
+    expect_that!(
+        clean_result,
+        err(pat!(CleanError::TooManyFiles {
+            count: eq(&2),
+            threshold: eq(&1)
+        }))
+    );
+    let clean_result = monja::clean_interactive(
+        &sim.profile()?,
+        sim.execution_options(),
+        CleanMode::Full {
+            exclude: Vec::new(),
+        },
+        &mut |_| panic!("Should not ask for a decision."),
+    );
+    expect_that!(clean_result, err(pat!(CleanError::TooManyFiles { .. })));
+    fs_operation! { LocalValidation, sim,
+        file "notinrepo1" "notinrepo1"
+        file "notinrepo2" "notinrepo2"
+    };
+
+    let opts = ExecutionOptions {
+        force: true,
+        ..sim.execution_options().clone()
+    };
+    let clean_result = monja::clean(
+        &sim.profile()?,
+        &opts,
+        CleanMode::Full {
+            exclude: Vec::new(),
+        },
+        &|_| true,
+    )?;
+    expect_that!(
+        clean_result.files_cleaned,
+        elements_are![eq(Path::new("notinrepo1")), eq(Path::new("notinrepo2"))]
+    );
+    fs_operation! { LocalValidation, sim,
+    };
+
+    Ok(())
+}
@@ -0,0 +1,355 @@
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+use googletest::prelude::*;
+use tempfile::TempDir;
+
+// runs the actual binary, for what only the cli does, like its flags and output.
+// everything is under a temporary home, where xdg puts the profile and data directory.
+struct Cli {
+    home: TempDir,
+}
+
+impl Cli {
+    // a profile targeting set1, with the repo at ~/repo
+    fn create() -> Self {
+        let home = tempfile::Builder::new()
+            .prefix("MonjaHome")
+            .tempdir()
+            .unwrap();
+        let cli = Cli { home };
+
+        fs::create_dir_all(cli.home().join(".config/monja")).unwrap();
+        fs::write(
+            cli.home().join(".config/monja/monja-profile.toml"),
+            "repo-dir = \"repo\"\ntarget-sets = [\"set1\"]\n",
+        )
+        .unwrap();
+        fs::create_dir_all(cli.home().join("repo/set1")).unwrap();
+
+        cli
+    }
+
+    fn home(&self) -> &Path {
+        self.home.path()
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.run_in(self.home(), args)
+    }
+
+    fn run_in(&self, cwd: &Path, args: &[&str]) -> Output {
+        self.command(cwd, args).output().unwrap()
+    }
+
+    // like run_in, with input piped through stdin
+    fn run_with_stdin(&self, cwd: &Path, args: &[&str], input: &str) -> Output {
+        let mut child = self
+            .command(cwd, args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // dropped right after, so the binary sees the end of input
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    fn command(&self, cwd: &Path, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_monja"));
+        command
+            .args(args)
+            .current_dir(cwd)
+            .env("HOME", self.home())
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_DATA_HOME");
+        command
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[gtest]
+fn verbosity() -> Result<()> {
+    let cli = Cli::create();
+
+    let output = cli.run(&["-vv", "status"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+
+    // -v is only a count, so a value is a usage error instead of a panic
+    let output = cli.run(&["--verbose=loud", "status"]);
+    expect_that!(output.status.code(), some(eq(2)));
+    expect_that!(stderr(&output), contains_substring("error:"));
+    expect_that!(stderr(&output), not(contains_substring("panicked")));
+
+    Ok(())
+}
+
+#[gtest]
+fn local_root() -> Result<()> {
+    let cli = Cli::create();
+    fs::write(cli.home().join("repo/set1/apple"), "pie")?;
+    let staging = tempfile::tempdir()?;
+
+    let output = cli.run(&["--local-root", &staging.path().to_string_lossy(), "pull"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+
+    // the repo is still the one relative to home, but its files go to the other root
+    expect_that!(fs::read_to_string(staging.path().join("apple"))?, eq("pie"));
+    expect_that!(cli.home().join("apple").exists(), is_false());
+
+    // which also has to exist, since it's not created
+    let output = cli.run(&[
+        "--local-root",
+        &staging.path().join("nonexistent").to_string_lossy(),
+        "pull",
+    ]);
+    expect_that!(output.status.success(), is_false());
+    expect_that!(
+        stderr(&output),
+        contains_substring("The local root must be an existing directory")
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn repo_dir() -> Result<()> {
+    let cli = Cli::create();
+    fs::create_dir_all(cli.home().join("clone/set1"))?;
+    fs::write(cli.home().join("clone/set1/apple"), "pie")?;
+    fs::create_dir(cli.home().join("elsewhere"))?;
+    fs::create_dir_all(cli.home().join("elsewhere/clone/set1"))?;
+
+    // relative to home, like the profile's repo-dir, even from some other directory
+    let output = cli.run_in(
+        &cli.home().join("elsewhere"),
+        &["--repo-dir", "clone", "pull"],
+    );
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(fs::read_to_string(cli.home().join("apple"))?, eq("pie"));
+
+    Ok(())
+}
+
+#[gtest]
+fn name_only() -> Result<()> {
+    let cli = Cli::create();
+    fs::create_dir_all(cli.home().join("repo/set1/dir"))?;
+    fs::write(cli.home().join("repo/set1/dir/apple"), "pie")?;
+    fs::write(cli.home().join("repo/set1/banana"), "bread")?;
+    let profile = cli.home().join(".config/monja/monja-profile.toml");
+    let config = fs::read_to_string(&profile)? + "[hooks]\npost-pull = \"true\"\n";
+    fs::write(&profile, config)?;
+
+    // just the paths, for piping into other tools, so no headers or hook report either
+    let output = cli.run(&["pull", "--name-only"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(stdout(&output), eq("banana\ndir/apple\n"));
+
+    fs::write(cli.home().join("banana"), "split")?;
+    let output = cli.run(&["push", "--name-only"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(stdout(&output), eq("banana\ndir/apple\n"));
+
+    Ok(())
+}
+
+#[gtest]
+fn quiet_push() -> Result<()> {
+    let cli = Cli::create();
+    fs::write(cli.home().join("repo/set1/apple"), "pie")?;
+    let output = cli.run(&["pull"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    fs::write(cli.home().join("apple"), "crumble")?;
+
+    // nothing at all, not even the sets and files that got pushed
+    let output = cli.run(&["-q", "push"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(stdout(&output), eq(""));
+    expect_that!(stderr(&output), eq(""));
+    expect_that!(
+        fs::read_to_string(cli.home().join("repo/set1/apple"))?,
+        eq("crumble")
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn rename_set_out_of_glob() -> Result<()> {
+    let cli = Cli::create();
+    fs::write(
+        cli.home().join(".config/monja/monja-profile.toml"),
+        "repo-dir = \"repo\"\ntarget-sets = [\"set*\"]\n",
+    )?;
+
+    let output = cli.run(&["renameset", "--from", "set1", "--to", "other"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(
+        stderr(&output),
+        contains_substring("Warning: `set1` was only targeted by a glob")
+    );
+    expect_that!(
+        stdout(&output),
+        not(contains_substring("The profile has also been updated"))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn put_from_stdin() -> Result<()> {
+    let cli = Cli::create();
+    fs::write(cli.home().join("apple"), "pie")?;
+    fs::write(cli.home().join("my notes"), "todo")?;
+
+    // newline-delimited, so spaces are part of the path, and blank lines don't count
+    let output = cli.run_with_stdin(
+        cli.home(),
+        &["put", "--set", "set1", "--", "-"],
+        "apple\n\nmy notes\n\n",
+    );
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(
+        fs::read_to_string(cli.home().join("repo/set1/apple"))?,
+        eq("pie")
+    );
+    expect_that!(
+        fs::read_to_string(cli.home().join("repo/set1/my notes"))?,
+        eq("todo")
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn put_from_stdin_nocwd() -> Result<()> {
+    let cli = Cli::create();
+    fs::create_dir(cli.home().join("sub"))?;
+    fs::write(cli.home().join("apple"), "pie")?;
+
+    // relative to cwd, where there's no such file
+    let output = cli.run_with_stdin(
+        &cli.home().join("sub"),
+        &["put", "--set", "set1", "--", "-"],
+        "apple\n",
+    );
+    expect_that!(output.status.success(), is_false());
+    expect_that!(cli.home().join("repo/set1/apple").exists(), is_false());
+
+    let output = cli.run_with_stdin(
+        &cli.home().join("sub"),
+        &["put", "--set", "set1", "--nocwd", "--", "-"],
+        "apple\n",
+    );
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(
+        fs::read_to_string(cli.home().join("repo/set1/apple"))?,
+        eq("pie")
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn put_from_stdin_limit() -> Result<()> {
+    let cli = Cli::create();
+    let mut input = String::new();
+    for i in 0..=100 {
+        fs::write(cli.home().join(format!("file{i}")), "")?;
+        input.push_str(&format!("file{i}\n"));
+    }
+
+    let output = cli.run_with_stdin(cli.home(), &["put", "--set", "set1", "--", "-"], &input);
+    expect_that!(output.status.success(), is_false());
+    expect_that!(
+        stderr(&output),
+        contains_substring("There is a limit of 100 paths passed through stdin.")
+    );
+    expect_that!(cli.home().join("repo/set1/file0").exists(), is_false());
+
+    // right at the limit is fine, blank lines included
+    let input = input.replacen("file100\n", "\n", 1);
+    let output = cli.run_with_stdin(cli.home(), &["put", "--set", "set1", "--", "-"], &input);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(cli.home().join("repo/set1/file99").exists(), is_true());
+    expect_that!(cli.home().join("repo/set1/file100").exists(), is_false());
+
+    Ok(())
+}
+
+#[gtest]
+fn pull_stat() -> Result<()> {
+    let cli = Cli::create();
+    fs::write(cli.home().join("repo/set1/apple"), "pie\n")?;
+    fs::write(cli.home().join("repo/set1/banana"), "x\n")?;
+
+    let output = cli.run(&["pull", "--stat"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(
+        stdout(&output),
+        ends_with("\n2 files pulled (6 bytes), 0 cleanable\n")
+    );
+
+    fs::remove_file(cli.home().join("repo/set1/banana"))?;
+    let output = cli.run(&["pull", "--stat"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(
+        stdout(&output),
+        ends_with("\n0 files pulled (0 bytes), 1 cleanable\n")
+    );
+
+    // the footer is output like any other, so it goes along with the rest
+    fs::write(cli.home().join("repo/set1/cherry"), "pie\n")?;
+    let output = cli.run(&["-q", "pull", "--stat"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(stdout(&output), eq(""));
+    expect_that!(fs::read_to_string(cli.home().join("cherry"))?, eq("pie\n"));
+
+    Ok(())
+}
+
+#[gtest]
+fn clean_stat() -> Result<()> {
+    let cli = Cli::create();
+    fs::write(cli.home().join("repo/set1/apple"), "pie")?;
+    fs::write(cli.home().join("repo/set1/banana"), "bread")?;
+    fs::write(cli.home().join("repo/set1/cherry"), "tart")?;
+    let output = cli.run(&["pull"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+
+    fs::remove_file(cli.home().join("repo/set1/banana"))?;
+    let output = cli.run(&["pull"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    let output = cli.run(&["--yes", "clean", "--stat"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(stdout(&output), ends_with("\n1 files cleaned\n"));
+    expect_that!(cli.home().join("banana").exists(), is_false());
+
+    fs::remove_file(cli.home().join("repo/set1/cherry"))?;
+    let output = cli.run(&["pull"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    let output = cli.run(&["-q", "--yes", "clean", "--stat"]);
+    expect_that!(output.status.success(), is_true(), "{}", stderr(&output));
+    expect_that!(stdout(&output), eq(""));
+    expect_that!(cli.home().join("cherry").exists(), is_false());
+
+    Ok(())
+}
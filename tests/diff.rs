@@ -0,0 +1,65 @@
+use std::fs;
+
+use googletest::prelude::*;
+use indoc::indoc;
+use monja::{FileDiff, MonjaProfileConfig, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn files_to_push() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie\ncrust\n"
+        file "blueberry" "tart\n"
+        file "cherry" "cobbler"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "pie\nfilling\n"
+    };
+    fs::write(sim.local_root().join("cherry"), b"cob\0bler")?;
+
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    let diffs = monja::diff(&sim.profile()?, &status.files_to_push)?;
+    expect_that!(
+        diffs,
+        elements_are![(
+            pat!(SetName("set1")),
+            unordered_elements_are![
+                (
+                    eq(&sim.local_path("apple")),
+                    eq(&FileDiff::Text(
+                        indoc! {"
+                            --- a/apple
+                            +++ b/apple
+                            @@ -1,2 +1,2 @@
+                             pie
+                            -crust
+                            +filling
+                        "}
+                        .to_string()
+                    ))
+                ),
+                (eq(&sim.local_path("blueberry")), eq(&FileDiff::Unchanged)),
+                (eq(&sim.local_path("cherry")), eq(&FileDiff::Binary)),
+            ]
+        )]
+    );
+
+    Ok(())
+}
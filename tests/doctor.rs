@@ -0,0 +1,114 @@
+use googletest::prelude::*;
+use monja::{DoctorCheck, DoctorStatus, MonjaProfileConfig, SetConfig, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn healthy() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let report = monja::doctor(&sim.profile()?);
+    expect_that!(report.has_failures(), is_false());
+    // rsync may or may not be installed, so it's not checked here
+    expect_that!(
+        report.checks,
+        contains(pat!(DoctorCheck {
+            name: eq(&"target sets"),
+            status: eq(&DoctorStatus::Ok),
+            ..
+        }))
+    );
+    expect_that!(
+        report.checks,
+        contains(pat!(DoctorCheck {
+            name: eq(&"file index"),
+            status: eq(&DoctorStatus::Ok),
+            ..
+        }))
+    );
+    expect_that!(
+        report.checks,
+        contains(pat!(DoctorCheck {
+            name: eq(&"set shortcuts"),
+            status: eq(&DoctorStatus::Ok),
+            ..
+        }))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn problems() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tart"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    sim.rem_set(SetName("set2".into()));
+    sim.configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some("..".into()),
+        ..old
+    });
+
+    let report = monja::doctor(&sim.profile()?);
+    expect_that!(report.has_failures(), is_true());
+    expect_that!(
+        report.checks,
+        contains(pat!(DoctorCheck {
+            name: eq(&"target sets"),
+            status: eq(&DoctorStatus::Failed),
+            detail: contains_substring("set2"),
+        }))
+    );
+    expect_that!(
+        report.checks,
+        contains(pat!(DoctorCheck {
+            name: eq(&"file index"),
+            status: eq(&DoctorStatus::Failed),
+            detail: contains_substring("set2"),
+        }))
+    );
+    expect_that!(
+        report.checks,
+        contains(pat!(DoctorCheck {
+            name: eq(&"set shortcuts"),
+            status: eq(&DoctorStatus::Failed),
+            detail: contains_substring("set1"),
+        }))
+    );
+
+    Ok(())
+}
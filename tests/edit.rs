@@ -0,0 +1,105 @@
+use googletest::prelude::*;
+use monja::{MonjaProfileConfig, ResolveRepoPathError, SetConfig, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn resolves_owning_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    })
+    .configure_set(SetName("set2".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        dir ".config"
+            file "apple" "pie"
+        end
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "apple" "fritter"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // later sets win
+    let result = monja::resolve_repo_path(&sim.profile()?, sim.local_path(".config/apple"))?;
+    expect_that!(
+        result,
+        some((
+            pat!(SetName("set2")),
+            eq(&sim.repo_root().join("set2").join("apple"))
+        ))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn untracked() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "blueberry" "tart"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let result = monja::resolve_repo_path(&sim.profile()?, sim.local_path("blueberry"))?;
+    expect_that!(result, none());
+
+    Ok(())
+}
+
+#[gtest]
+fn missing_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    sim.rem_set(SetName("set1".into()));
+
+    let result = monja::resolve_repo_path(&sim.profile()?, sim.local_path("apple"));
+    expect_that!(
+        result.err(),
+        some(pat!(ResolveRepoPathError::SetNotFound(pat!(SetName(
+            "set1"
+        )))))
+    );
+
+    Ok(())
+}
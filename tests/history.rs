@@ -0,0 +1,141 @@
+use std::{collections::BTreeMap, fs};
+
+use googletest::prelude::*;
+use monja::{HistoryOperation, HistoryRecord, LocalFilePath, MonjaProfileConfig, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn records_operations() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    expect_that!(monja::history(&sim.profile()?, None)?, is_empty());
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "blueberry" "tart"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "cherry" "cobbler"
+    };
+    sim.dryrun(true);
+    let _put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("cherry")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+    sim.dryrun(false);
+
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "crumble"
+    };
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // newest first
+    expect_that!(
+        monja::history(&sim.profile()?, None)?,
+        elements_are![
+            matches_pattern!(HistoryRecord {
+                operation: eq(&HistoryOperation::Push),
+                dry_run: eq(&false),
+                counts: eq(&counts([("files-modified", 1), ("files-pushed", 2)])),
+                ..
+            }),
+            matches_pattern!(HistoryRecord {
+                operation: eq(&HistoryOperation::Put),
+                dry_run: eq(&true),
+                counts: eq(&counts([("files", 1)])),
+                ..
+            }),
+            matches_pattern!(HistoryRecord {
+                operation: eq(&HistoryOperation::Pull),
+                dry_run: eq(&false),
+                counts: eq(&counts([
+                    ("files-deleted", 0),
+                    ("files-failed", 0),
+                    ("files-pulled", 2)
+                ])),
+                ..
+            }),
+        ]
+    );
+    expect_that!(
+        monja::history(&sim.profile()?, Some(1))?,
+        elements_are![matches_pattern!(HistoryRecord {
+            operation: eq(&HistoryOperation::Push),
+            ..
+        })]
+    );
+
+    // the history file isn't something to push
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(
+        status.untracked_files,
+        not(contains(predicate(
+            |p: &LocalFilePath| p.ends_with("monja-history.jsonl")
+        )))
+    );
+
+    Ok(())
+}
+
+fn counts<const N: usize>(counts: [(&str, usize); N]) -> BTreeMap<String, usize> {
+    counts
+        .into_iter()
+        .map(|(name, count)| (name.to_string(), count))
+        .collect()
+}
+
+#[gtest]
+fn unparseable_lines_skipped() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // like a write cut short by a crash
+    let history_path = sim.data_root().join("monja-history.jsonl");
+    let mut contents = fs::read_to_string(&history_path)?;
+    contents.push_str("{\"timestamp\":");
+    fs::write(&history_path, contents)?;
+
+    expect_that!(
+        monja::history(&sim.profile()?, None)?,
+        elements_are![matches_pattern!(HistoryRecord {
+            operation: eq(&HistoryOperation::Pull),
+            ..
+        })]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use googletest::prelude::*;
+use monja::{HookError, HookKind, HookRun, HooksConfig, MonjaProfileConfig, PullError, PushError};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn pull_hooks() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        hooks: HooksConfig {
+            // hooks run from the local root
+            pre_pull: Some("echo pre > pre-hook".into()),
+            // which means the pulled file is there for the post-hook
+            post_pull: Some("cat apple > post-hook".into()),
+            ..Default::default()
+        },
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    expect_that!(
+        result.hooks_run,
+        elements_are![
+            pat!(HookRun {
+                kind: eq(&HookKind::PrePull),
+                command: eq("echo pre > pre-hook"),
+            }),
+            pat!(HookRun {
+                kind: eq(&HookKind::PostPull),
+                command: eq("cat apple > post-hook"),
+            }),
+        ]
+    );
+    expect_that!(result.post_hook_error, none());
+
+    fs_operation! { LocalValidation, sim,
+        file "apple" "pie"
+        file "pre-hook" "pre\n"
+        file "post-hook" "pie"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn failing_pre_pull() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        hooks: HooksConfig {
+            pre_pull: Some("echo oops >&2; exit 3".into()),
+            post_pull: Some("echo post > post-hook".into()),
+            ..Default::default()
+        },
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+
+    expect_that!(
+        result.err(),
+        some(pat!(PullError::Hook(pat!(HookError::Unsuccessful {
+            kind: eq(&HookKind::PrePull),
+            status: anything(),
+            stderr: eq("oops\n"),
+        }))))
+    );
+    fs_operation! { LocalValidation, sim,
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn failing_post_push() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        hooks: HooksConfig {
+            post_push: Some("exit 1".into()),
+            ..Default::default()
+        },
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "crumble"
+    };
+
+    let result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // the push itself still happened
+    expect_that!(
+        result.post_hook_error,
+        some(pat!(HookError::Unsuccessful {
+            kind: eq(&HookKind::PostPush),
+            status: anything(),
+            stderr: eq(""),
+        }))
+    );
+    fs_operation! { SetValidation, sim, "set1",
+        file "apple" "crumble"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn failing_pre_push() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        hooks: HooksConfig {
+            pre_push: Some("false".into()),
+            ..Default::default()
+        },
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "crumble"
+    };
+
+    let result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+
+    expect_that!(
+        result.err(),
+        some(pat!(PushError::Hook(pat!(HookError::Unsuccessful {
+            kind: eq(&HookKind::PrePush),
+            status: anything(),
+            stderr: anything(),
+        }))))
+    );
+    fs_operation! { SetValidation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn dryrun() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        hooks: HooksConfig {
+            pre_pull: Some("echo pre > pre-hook".into()),
+            ..Default::default()
+        },
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    sim.dryrun(true);
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // reported, but not actually run
+    expect_that!(
+        result.hooks_run,
+        elements_are![pat!(HookRun {
+            kind: eq(&HookKind::PrePull),
+            command: eq("echo pre > pre-hook"),
+        })]
+    );
+    fs_operation! { LocalValidation, sim,
+    };
+
+    Ok(())
+}
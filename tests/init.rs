@@ -4,8 +4,7 @@ use std::{
 };
 
 use googletest::prelude::*;
-use monja::{AbsolutePath, InitError, InitSpec, InitSuccess};
-use relative_path::PathExt;
+use monja::{AbsolutePath, InitError, InitSpec, InitSuccess, MonjaProfile, MonjaProfileConfig};
 
 use crate::sim::Simulator;
 
@@ -105,12 +104,137 @@ fn init(sim: &Simulator) -> std::result::Result<InitSuccess, InitError> {
         local_root: AbsolutePath::for_existing_path(sim.local_root()).unwrap(),
         repo_root: AbsolutePath::for_existing_path(sim.repo_root()).unwrap(),
         data_root: AbsolutePath::for_existing_path(sim.data_root()).unwrap(),
-        relative_repo_root: sim
-            .repo_root()
-            .relative_to(sim.local_root())
-            .unwrap()
-            .to_path(""),
         initial_set_name: "initialset".into(),
+        force: false,
+    };
+
+    monja::init(sim.execution_options(), spec)
+}
+
+#[gtest]
+fn repo_outside_local_root() -> Result<()> {
+    let sim = Simulator::create();
+    fs::remove_file(sim.profile_path())?;
+    let repo_dir = tempfile::Builder::new().prefix("MonjaRepo").tempdir()?;
+
+    let spec = InitSpec {
+        profile_config_path: sim.profile_path().to_path_buf(),
+        local_root: AbsolutePath::for_existing_path(sim.local_root())?,
+        repo_root: AbsolutePath::for_existing_path(repo_dir.path())?,
+        data_root: AbsolutePath::for_existing_path(sim.data_root())?,
+        initial_set_name: "initialset".into(),
+        force: false,
+    };
+    let _result = monja::init(sim.execution_options(), spec)?;
+
+    let profile_config =
+        MonjaProfileConfig::load(&AbsolutePath::for_existing_path(sim.profile_path())?)?;
+    expect_that!(
+        profile_config.repo_dir,
+        eq(&repo_dir.path().canonicalize()?)
+    );
+    expect_that!(
+        repo_dir.path().join("initialset/.monjaignore").exists(),
+        is_true()
+    );
+    expect_that!(sim.local_root().join(".monjaignore").exists(), is_true());
+
+    Ok(())
+}
+
+#[gtest]
+fn existing_data_root() -> Result<()> {
+    let sim = Simulator::create();
+    fs::remove_file(sim.profile_path())?;
+
+    // done at the start of every run, and the simulator already made it
+    let data_root = MonjaProfile::create_data_root(sim.data_root())?;
+    expect_that!(
+        data_root.to_path_buf(),
+        eq(&sim.data_root().canonicalize()?)
+    );
+
+    let _result = init(&sim)?;
+
+    let _data_root = MonjaProfile::create_data_root(sim.data_root())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    Ok(())
+}
+
+#[gtest]
+fn force_overwrites_profile() -> Result<()> {
+    let sim = Simulator::create();
+    fs_operation! { SetManipulation, sim, "initialset",
+        file ".monjaignore" "custom"
+        file "apple" "pie"
+    };
+    fs::write(sim.profile_path(), "not even toml [")?;
+
+    let result = force_init(&sim)?;
+    expect_that!(result.profile_overwritten, is_true());
+    expect_that!(result.set_created, is_false());
+
+    // the profile is usable again, and the set's contents were left alone
+    let profile = sim.profile()?;
+    expect_that!(
+        profile.config.target_sets,
+        elements_are![pat!(monja::SetName("initialset"))]
+    );
+    fs_operation! { SetValidation, sim, "initialset",
+        file ".monjaignore" "custom"
+        file "apple" "pie"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn force_creates_missing_set() -> Result<()> {
+    let sim = Simulator::create();
+
+    let result = force_init(&sim)?;
+    expect_that!(result.profile_overwritten, is_true());
+    expect_that!(result.set_created, is_true());
+    expect_that!(
+        sim.repo_root().join("initialset/.monjaignore").exists(),
+        is_true()
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn force_dry_run() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.dryrun(true);
+    fs::write(sim.profile_path(), "not even toml [")?;
+
+    let result = force_init(&sim)?;
+    expect_that!(result.profile, none());
+    expect_that!(result.profile_overwritten, is_true());
+    expect_that!(result.set_created, is_true());
+    expect_that!(
+        fs::read_to_string(sim.profile_path())?,
+        eq("not even toml [")
+    );
+    expect_that!(sim.repo_root().join("initialset").exists(), is_false());
+
+    Ok(())
+}
+
+fn force_init(sim: &Simulator) -> std::result::Result<InitSuccess, InitError> {
+    let spec = InitSpec {
+        profile_config_path: sim.profile_path().to_path_buf(),
+        local_root: AbsolutePath::for_existing_path(sim.local_root()).unwrap(),
+        repo_root: AbsolutePath::for_existing_path(sim.repo_root()).unwrap(),
+        data_root: AbsolutePath::for_existing_path(sim.data_root()).unwrap(),
+        initial_set_name: "initialset".into(),
+        force: true,
     };
 
     monja::init(sim.execution_options(), spec)
@@ -0,0 +1,107 @@
+use std::{fs, path::Path};
+
+use googletest::prelude::*;
+
+use monja::{MonjaProfileConfig, SetConfig, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn ordering() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["targeted2", "targeted1", "missing"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "targeted1",
+        file "foo" "foo"
+        file "bar" "bar"
+    };
+    fs_operation! { SetManipulation, sim, "targeted2",
+    };
+    fs_operation! { SetManipulation, sim, "untargeted2",
+        file "foo" "foo"
+    };
+    fs_operation! { SetManipulation, sim, "untargeted1",
+    };
+    sim.configure_set(SetName("targeted1".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    });
+
+    let sets = monja::list_sets(&sim.profile()?)?.sets;
+    expect_that!(
+        sets,
+        elements_are![
+            pat!(monja::SetInfo {
+                name: pat!(SetName("targeted2")),
+                shortcut: eq(Path::new("")),
+                file_count: eq(&0),
+                profile_position: some(eq(&0)),
+            }),
+            pat!(monja::SetInfo {
+                name: pat!(SetName("targeted1")),
+                shortcut: eq(Path::new(".config")),
+                file_count: eq(&2),
+                profile_position: some(eq(&1)),
+            }),
+            pat!(monja::SetInfo {
+                name: pat!(SetName("untargeted1")),
+                shortcut: eq(Path::new("")),
+                file_count: eq(&0),
+                profile_position: none(),
+            }),
+            pat!(monja::SetInfo {
+                name: pat!(SetName("untargeted2")),
+                shortcut: eq(Path::new("")),
+                file_count: eq(&1),
+                profile_position: none(),
+            }),
+        ]
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn empty_repo() -> Result<()> {
+    let sim = Simulator::create();
+
+    let sets = monja::list_sets(&sim.profile()?)?.sets;
+    expect_that!(sets, is_empty());
+
+    Ok(())
+}
+
+#[gtest]
+fn repo_ignore() -> Result<()> {
+    let sim = Simulator::create();
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "foo" "foo"
+    };
+    fs::create_dir_all(sim.repo_root().join(".github/workflows"))?;
+    fs::write(sim.repo_root().join(".github/workflows/ci.yml"), "ci")?;
+    fs::create_dir_all(sim.repo_root().join("docs"))?;
+    fs::write(sim.repo_root().join(".monjaignore"), ".github/\ndocs\n")?;
+
+    let set_list = monja::list_sets(&sim.profile()?)?;
+    expect_that!(
+        set_list.sets,
+        elements_are![pat!(monja::SetInfo {
+            name: pat!(SetName("set1")),
+            ..
+        })]
+    );
+    expect_that!(
+        set_list.ignored_dirs,
+        elements_are![eq(Path::new(".github")), eq(Path::new("docs"))]
+    );
+
+    Ok(())
+}
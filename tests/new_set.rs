@@ -1,7 +1,7 @@
-use std::path::Path;
+use std::{fs, path::Path};
 
 use googletest::prelude::*;
-use monja::{AbsolutePath, NewSetError, SetCreationError, SetName};
+use monja::{AbsolutePath, NewSetError, SetConfig, SetCreationError, SetName};
 
 use crate::sim::Simulator;
 
@@ -24,6 +24,7 @@ fn basic() -> Result<()> {
         &AbsolutePath::for_existing_path(sim.profile_path())?,
         vec![sim.local_path("notinrepo"), sim.local_path("alsonotinrepo")],
         SetName("newset".into()),
+        None,
     )?;
     expect_that!(new_set_result.new_set, pat!(SetName("newset")));
     expect_that!(new_set_result.files, { eq(Path::new("notinrepo")), eq(Path::new("alsonotinrepo")) });
@@ -68,6 +69,7 @@ fn common_prefix() -> Result<()> {
             sim.local_path("a/b/c/d/3"),
         ],
         SetName("newset".into()),
+        None,
     )?;
     expect_that!(new_set_result.new_set, pat!(SetName("newset")));
     expect_that!(new_set_result.files, {
@@ -104,6 +106,80 @@ fn common_prefix() -> Result<()> {
     Ok(())
 }
 
+#[gtest]
+fn single_file() -> Result<()> {
+    let sim = Simulator::create();
+
+    fs_operation! { LocalManipulation, sim,
+        dir ".config/app"
+            file "config.toml" "config"
+        end
+    };
+
+    let _new_set_result = monja::new_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        vec![sim.local_path(".config/app/config.toml")],
+        SetName("newset".into()),
+        None,
+    )?;
+
+    // the shortcut is the file's directory, not the file itself
+    fs_operation! { SetValidation, sim, "newset",
+        file "config.toml" "config"
+    };
+    expect_that!(
+        SetConfig::load(&sim.profile()?, &SetName("newset".into()))?.shortcut,
+        some(eq(Path::new(".config/app")))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn explicit_shortcut() -> Result<()> {
+    let sim = Simulator::create();
+
+    fs_operation! { LocalManipulation, sim,
+        dir ".config/app"
+            file "config.toml" "config"
+        end
+    };
+
+    let _new_set_result = monja::new_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        vec![sim.local_path(".config/app/config.toml")],
+        SetName("newset".into()),
+        Some(".config".into()),
+    )?;
+
+    fs_operation! { SetValidation, sim, "newset",
+        dir "app"
+            file "config.toml" "config"
+        end
+    };
+
+    // validated before anything gets created
+    let new_set_result = monja::new_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        vec![sim.local_path(".config/app/config.toml")],
+        SetName("otherset".into()),
+        Some("/outside".into()),
+    );
+    expect_that!(
+        *new_set_result.unwrap_err(),
+        pat!(NewSetError::InvalidShortcut(_))
+    );
+    expect_that!(sim.repo_root().join("otherset").exists(), is_false());
+
+    Ok(())
+}
+
 #[gtest]
 fn dryrun() -> Result<()> {
     let mut sim = Simulator::create();
@@ -120,6 +196,7 @@ fn dryrun() -> Result<()> {
         &AbsolutePath::for_existing_path(sim.profile_path())?,
         vec![sim.local_path("notinrepo"), sim.local_path("alsonotinrepo")],
         SetName("newset".into()),
+        None,
     )?;
     expect_that!(new_set_result.new_set, pat!(SetName("newset")));
     expect_that!(new_set_result.files, { eq(Path::new("notinrepo")), eq(Path::new("alsonotinrepo")) });
@@ -151,6 +228,7 @@ fn set_exists() -> Result<()> {
         &AbsolutePath::for_existing_path(sim.profile_path())?,
         vec![sim.local_path("notinrepo"), sim.local_path("alsonotinrepo")],
         SetName("newset".into()),
+        None,
     );
 
     let set_name = SetName("newset".into());
@@ -162,3 +240,124 @@ fn set_exists() -> Result<()> {
 
     Ok(())
 }
+
+#[gtest]
+fn from_dir() -> Result<()> {
+    let sim = Simulator::create();
+
+    fs_operation! { LocalManipulation, sim,
+        file "notinrepo" "notinrepo"
+        dir ".config/nvim"
+            file "init.lua" "init"
+            dir "lua"
+                file "plugins.lua" "plugins"
+            end
+        end
+    };
+
+    let new_set_result = monja::new_set_from_dir(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        sim.local_path(".config/nvim"),
+        SetName("nvim".into()),
+    )?;
+    expect_that!(new_set_result.new_set, pat!(SetName("nvim")));
+    expect_that!(new_set_result.files, {
+        eq(Path::new(".config/nvim/init.lua")),
+        eq(Path::new(".config/nvim/lua/plugins.lua"))
+    });
+
+    // the directory itself is the shortcut
+    fs_operation! { SetValidation, sim, "nvim",
+        file "init.lua" "init"
+        dir "lua"
+            file "plugins.lua" "plugins"
+        end
+    };
+
+    // and pulling puts them back where they came from
+    fs::remove_dir_all(sim.local_root().join(".config"))?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    fs_operation! { LocalValidation, sim,
+        file "notinrepo" "notinrepo"
+        dir ".config/nvim"
+            file "init.lua" "init"
+            dir "lua"
+                file "plugins.lua" "plugins"
+            end
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn from_dir_dryrun() -> Result<()> {
+    let mut sim = Simulator::create();
+
+    fs_operation! { LocalManipulation, sim,
+        dir ".config/nvim"
+            file "init.lua" "init"
+        end
+    };
+
+    sim.dryrun(true);
+    let new_set_result = monja::new_set_from_dir(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        sim.local_path(".config/nvim"),
+        SetName("nvim".into()),
+    )?;
+    expect_that!(new_set_result.files, {
+        eq(Path::new(".config/nvim/init.lua"))
+    });
+    expect_that!(sim.repo_root().join("nvim").exists(), is_false());
+
+    Ok(())
+}
+
+#[gtest]
+fn from_dir_missing_or_empty() -> Result<()> {
+    let sim = Simulator::create();
+
+    fs_operation! { LocalManipulation, sim,
+        dir ".config/empty"
+        end
+    };
+
+    let new_set_result = monja::new_set_from_dir(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        sim.local_path(".config/missing"),
+        SetName("missing".into()),
+    );
+    expect_that!(
+        *new_set_result.unwrap_err(),
+        pat!(NewSetError::DirectoryNotFound(..))
+    );
+
+    let new_set_result = monja::new_set_from_dir(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        sim.local_path(".config/empty"),
+        SetName("empty".into()),
+    );
+    expect_that!(
+        *new_set_result.unwrap_err(),
+        pat!(NewSetError::EmptyDirectory(..))
+    );
+
+    // neither got created
+    expect_that!(sim.repo_root().join("missing").exists(), is_false());
+    expect_that!(sim.repo_root().join("empty").exists(), is_false());
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use std::path::Path;
+
+use googletest::prelude::*;
+
+use monja::{MonjaProfileConfig, PreviewSetError, RepoFilePath, SetConfig, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn untargeted_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        dir "nvim"
+            file "init.lua" "init.lua"
+        end
+        file "zshrc" "zshrc"
+    };
+    sim.configure_set(SetName("set2".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    });
+    fs_operation! { LocalManipulation, sim,
+        dir ".config/nvim"
+            file "init.lua" "local init.lua"
+        end
+    };
+
+    let files = monja::preview_set(&sim.profile()?, &SetName("set2".into()))?;
+    expect_that!(
+        files,
+        elements_are![
+            (
+                pat!(RepoFilePath {
+                    path_in_set: eq(Path::new("nvim/init.lua")),
+                    local_path: eq(Path::new(".config/nvim/init.lua")),
+                }),
+                eq(&true)
+            ),
+            (
+                pat!(RepoFilePath {
+                    path_in_set: eq(Path::new("zshrc")),
+                    local_path: eq(Path::new(".config/zshrc")),
+                }),
+                eq(&false)
+            ),
+        ]
+    );
+
+    // nothing gets pulled
+    fs_operation! { LocalValidation, sim,
+        dir ".config/nvim"
+            file "init.lua" "local init.lua"
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn nonexistent_set() -> Result<()> {
+    let sim = Simulator::create();
+
+    let result = monja::preview_set(&sim.profile()?, &SetName("set1".into()));
+    expect_that!(
+        result,
+        err(pat!(PreviewSetError::SetNotFound(pat!(SetName("set1")))))
+    );
+
+    Ok(())
+}
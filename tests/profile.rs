@@ -0,0 +1,346 @@
+use googletest::prelude::*;
+use monja::{
+    AbsolutePath, MonjaProfileConfig, MonjaProfileConfigError, MonjaProfileError,
+    ProfileValidationError, SetName, SetNameError,
+};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn valid() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tart"
+    };
+
+    let profile = sim.profile()?;
+    expect_that!(profile.config.validate(&profile.repo_root), ok(()));
+
+    Ok(())
+}
+
+#[gtest]
+fn invalid() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2", "set1", "set3", "set3"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tart"
+    };
+
+    let profile = sim.profile()?;
+    expect_that!(
+        profile.config.validate(&profile.repo_root),
+        err(elements_are![
+            pat!(ProfileValidationError::DuplicateSet(pat!(SetName("set1")))),
+            pat!(ProfileValidationError::MissingSet(pat!(SetName("set3")))),
+            pat!(ProfileValidationError::DuplicateSet(pat!(SetName("set3")))),
+        ])
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn set_names_validated() -> Result<()> {
+    expect_that!(SetName::new("set1"), ok(pat!(SetName("set1"))));
+    expect_that!(SetName::new(".set1"), ok(pat!(SetName(".set1"))));
+    expect_that!(SetName::new(""), err(pat!(SetNameError::Empty)));
+    expect_that!(
+        SetName::new("."),
+        err(pat!(SetNameError::Reserved(eq("."))))
+    );
+    expect_that!(
+        SetName::new(".."),
+        err(pat!(SetNameError::Reserved(eq(".."))))
+    );
+    expect_that!(
+        SetName::new("../evil"),
+        err(pat!(SetNameError::PathSeparator(eq("../evil"))))
+    );
+    expect_that!(
+        SetName::new("set/with/slashes"),
+        err(pat!(SetNameError::PathSeparator(eq("set/with/slashes"))))
+    );
+
+    // the cli parses set names through FromStr, which validates the same way
+    expect_that!("set1".parse::<SetName>(), ok(pat!(SetName("set1"))));
+    expect_that!("".parse::<SetName>(), err(pat!(SetNameError::Empty)));
+    expect_that!(
+        "../evil".parse::<SetName>(),
+        err(pat!(SetNameError::PathSeparator(eq("../evil"))))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn invalid_set_name_fails_load() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "../evil"]),
+        ..old
+    });
+
+    expect_that!(
+        sim.profile(),
+        err(pat!(MonjaProfileConfigError::Deserialization(displays_as(
+            contains_substring("can't contain path separators")
+        ))))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn repo_dir_is_file() -> Result<()> {
+    let sim = Simulator::create();
+    let repo_file = sim.local_root().join("notarepo");
+    std::fs::write(&repo_file, "")?;
+    sim.configure_profile(|old| MonjaProfileConfig {
+        repo_dir: repo_file.clone(),
+        ..old
+    });
+
+    expect_that!(
+        sim.profile(),
+        err(pat!(MonjaProfileConfigError::Load(pat!(
+            MonjaProfileError::RepoNotDirectory(eq(&repo_file.canonicalize()?))
+        ))))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn target_set_globs() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["common", "*top*", "laptop", "missing"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "common",
+    };
+    fs_operation! { SetManipulation, sim, "laptop-work",
+    };
+    fs_operation! { SetManipulation, sim, "laptop",
+    };
+    fs_operation! { SetManipulation, sim, "desktop",
+    };
+    fs_operation! { SetManipulation, sim, ".toplevel",
+    };
+
+    // alphabetical within the glob. laptop is listed explicitly, so it stays where it was listed.
+    // hidden directories need a glob that starts with a dot, and literal names are kept even if missing.
+    let profile = sim.profile()?;
+    expect_that!(
+        profile.config.target_sets,
+        elements_are![
+            pat!(SetName("common")),
+            pat!(SetName("desktop")),
+            pat!(SetName("laptop-work")),
+            pat!(SetName("laptop")),
+            pat!(SetName("missing")),
+        ]
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn target_set_glob_escaped() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names([r"odd\[1\]", "nothing*"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "odd[1]",
+    };
+    fs_operation! { SetManipulation, sim, "odd1",
+    };
+
+    // a glob matching nothing expands to nothing
+    let profile = sim.profile()?;
+    expect_that!(
+        profile.config.target_sets,
+        elements_are![pat!(SetName("odd[1]"))]
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn target_set_glob_layering() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["host-*"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "host-b",
+        file "apple" "b"
+    };
+    fs_operation! { SetManipulation, sim, "host-a",
+        file "apple" "a"
+        file "banana" "a"
+    };
+
+    // later sets win, in the glob's alphabetical order
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    fs_operation! { LocalValidation, sim,
+        file "apple" "b"
+        file "banana" "a"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn redirect() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    let repo_profile = sim.repo_root().join("monja-profile.toml");
+    std::fs::copy(sim.profile_path(), &repo_profile)?;
+    let redirect = format!("redirect = '{}'\n", repo_profile.display());
+    std::fs::write(sim.profile_path(), &redirect)?;
+
+    expect_that!(
+        sim.profile()?.config.target_sets,
+        elements_are![pat!(SetName("set1"))]
+    );
+
+    // saving goes to the actual profile, leaving the redirect alone
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+    expect_that!(std::fs::read_to_string(sim.profile_path())?, eq(&redirect));
+    expect_that!(
+        MonjaProfileConfig::load(&AbsolutePath::for_existing_path(&repo_profile)?)?.target_sets,
+        elements_are![pat!(SetName("set1")), pat!(SetName("set2"))]
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn redirect_relative_chain() -> Result<()> {
+    let sim = Simulator::create();
+    let profile_dir = sim.profile_path().parent().unwrap();
+    std::fs::create_dir(profile_dir.join("profiles"))?;
+    std::fs::copy(sim.profile_path(), profile_dir.join("profiles/real.toml"))?;
+    // relative to the redirecting file, not the first one
+    std::fs::write(
+        profile_dir.join("profiles/redirect.toml"),
+        "redirect = 'real.toml'",
+    )?;
+    std::fs::write(sim.profile_path(), "redirect = 'profiles/redirect.toml'")?;
+
+    expect_that!(
+        MonjaProfileConfig::resolve_redirects(sim.profile_path()),
+        ok(eq(&profile_dir.join("profiles/real.toml")))
+    );
+    expect_that!(sim.profile(), ok(anything()));
+
+    Ok(())
+}
+
+#[gtest]
+fn redirect_broken() -> Result<()> {
+    let sim = Simulator::create();
+    let profile_dir = sim.profile_path().parent().unwrap();
+
+    std::fs::write(sim.profile_path(), "redirect = 'missing.toml'")?;
+    expect_that!(
+        sim.profile(),
+        err(pat!(MonjaProfileConfigError::RedirectTarget(
+            eq(&profile_dir.join("missing.toml")),
+            anything()
+        )))
+    );
+
+    std::fs::write(
+        profile_dir.join("other.toml"),
+        "redirect = 'monja-profile.toml'",
+    )?;
+    std::fs::write(sim.profile_path(), "redirect = 'other.toml'")?;
+    expect_that!(
+        sim.profile(),
+        err(pat!(MonjaProfileConfigError::RedirectLoop(anything())))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn rsync_extra_args_validated() -> Result<()> {
+    let sim = Simulator::create();
+    let profile_path = AbsolutePath::for_existing_path(sim.profile_path())?;
+    let config = MonjaProfileConfig::load(&profile_path)?;
+    // written directly, since configure_profile needs the current profile to be valid
+    let with_args = |args: &[&str]| {
+        MonjaProfileConfig {
+            rsync_extra_args: args.iter().map(|a| a.to_string()).collect(),
+            ..config.clone()
+        }
+        .save(&profile_path)
+    };
+
+    for arg in [
+        "--files-from=other",
+        "--from0",
+        "-0",
+        "-av0",
+        "--out-format=%n",
+        "--info=stats0",
+        "--dry-run",
+        "--exclude-from=-",
+        "F644",
+        "-",
+        "--",
+    ] {
+        with_args(&["--bwlimit=1000", arg])?;
+        expect_that!(
+            sim.profile(),
+            err(pat!(MonjaProfileConfigError::Load(pat!(
+                MonjaProfileError::RsyncExtraArg(eq(arg))
+            ))))
+        );
+    }
+
+    with_args(&["--chmod=F644", "-v", "--bwlimit=1000"])?;
+    expect_that!(sim.profile(), ok(anything()));
+
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use std::fs;
+
+use googletest::prelude::*;
+use monja::{MonjaProfileConfig, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn prune() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "blueberry" "tart"
+        file "cherry" "cobbler"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // only blueberry is gone from both sides
+    fs_operation! { SetManipulation, sim, "set1",
+        remfile "blueberry"
+        remfile "cherry"
+    };
+    fs::remove_file(sim.local_root().join("apple"))?;
+    fs::remove_file(sim.local_root().join("blueberry"))?;
+
+    let prune_result = monja::prune_index(&sim.profile()?, sim.execution_options())?;
+    expect_that!(
+        prune_result.entries_removed,
+        elements_are![(
+            eq(&sim.local_path("blueberry")),
+            eq(&SetName("set1".into()))
+        )]
+    );
+
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.missing_files, {
+        (
+            pat!(SetName("set1")),
+            unordered_elements_are![eq(&sim.local_path("cherry"))],
+        )
+    });
+
+    // nothing left to prune
+    let prune_result = monja::prune_index(&sim.profile()?, sim.execution_options())?;
+    expect_that!(prune_result.entries_removed, is_empty());
+
+    Ok(())
+}
+
+#[gtest]
+fn missing_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    sim.rem_set(SetName("set1".into()));
+    fs::remove_file(sim.local_root().join("apple"))?;
+
+    let prune_result = monja::prune_index(&sim.profile()?, sim.execution_options())?;
+    expect_that!(
+        prune_result.entries_removed,
+        elements_are![(eq(&sim.local_path("apple")), eq(&SetName("set1".into())))]
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn dryrun() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "blueberry" "tart"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { SetManipulation, sim, "set1",
+        remfile "blueberry"
+    };
+    fs::remove_file(sim.local_root().join("blueberry"))?;
+
+    sim.dryrun(true);
+    let prune_result = monja::prune_index(&sim.profile()?, sim.execution_options())?;
+    expect_that!(
+        prune_result.entries_removed,
+        elements_are![(
+            eq(&sim.local_path("blueberry")),
+            eq(&SetName("set1".into()))
+        )]
+    );
+
+    // still in the index, since nothing was saved
+    sim.dryrun(false);
+    let prune_result = monja::prune_index(&sim.profile()?, sim.execution_options())?;
+    expect_that!(prune_result.entries_removed, len(eq(1)));
+
+    Ok(())
+}
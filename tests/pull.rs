@@ -1,11 +1,21 @@
-use std::{fs, path::Path};
+use std::{
+    collections::BTreeMap,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
 
 use googletest::prelude::*;
 
 use crate::sim::{Simulator, set_names};
 use monja::{
-    AbsolutePath, MonjaProfile, MonjaProfileConfig, PullError, RepoStateInitializationError,
-    SetConfig, SetName,
+    AbsolutePath, AclError, FileIndexError, FileStatus, IndexKind, MonjaProfile,
+    MonjaProfileConfig, MonjaProfileConfigError, ProgressCallback, PullAction, PullError,
+    PullOptions, RepoFilePath, RepoStateInitializationError, RsyncError, RsyncStats, SetConfig,
+    SetName, TemplateError,
 };
 
 #[allow(dead_code)]
@@ -33,7 +43,11 @@ fn simple_set() -> Result<()> {
         file "blueberry" "tart"
     };
 
-    let pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
     expect_that!(pull_result.cleanable_files, is_empty());
 
     fs_operation! { LocalValidation, sim,
@@ -63,7 +77,11 @@ fn empty_set() -> Result<()> {
     fs_operation! { SetManipulation, sim, "simple",
     };
 
-    let pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
     expect_that!(pull_result.files_pulled, is_empty());
 
     Ok(())
@@ -94,7 +112,11 @@ fn multiple_sets() -> Result<()> {
         file "set2only" "set2only"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { LocalValidation, sim,
         dir "foo"
@@ -112,7 +134,11 @@ fn multiple_sets() -> Result<()> {
         ..old
     });
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { LocalValidation, sim,
         dir "foo"
@@ -134,15 +160,18 @@ fn shortcuts() -> Result<()> {
         target_sets: set_names(["set1", "set2", "set3"]),
         ..old
     })
-    .configure_set(SetName("set1".into()), |_| SetConfig {
+    .configure_set(SetName("set1".into()), |old| SetConfig {
         // start with nested directory structure just in case
         shortcut: Some(".config/myconfig".into()),
+        ..old
     })
-    .configure_set(SetName("set2".into()), |_| SetConfig {
+    .configure_set(SetName("set2".into()), |old| SetConfig {
         shortcut: Some(".config".into()),
+        ..old
     })
-    .configure_set(SetName("set3".into()), |_| SetConfig {
+    .configure_set(SetName("set3".into()), |old| SetConfig {
         shortcut: Some("".into()),
+        ..old
     });
 
     fs_operation! { SetManipulation, sim, "set1",
@@ -164,7 +193,11 @@ fn shortcuts() -> Result<()> {
         end
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { LocalValidation, sim,
         dir ".config"
@@ -187,15 +220,20 @@ fn shorcut_directory_traversal() -> Result<()> {
         target_sets: set_names(["set1"]),
         ..old
     })
-    .configure_set(SetName("set1".into()), |_| SetConfig {
+    .configure_set(SetName("set1".into()), |old| SetConfig {
         shortcut: Some("..".into()),
+        ..old
     });
 
     fs_operation! { SetManipulation, sim, "set1",
         file "foo" "set1"
     };
 
-    let result = monja::pull(&sim.profile()?, sim.execution_options());
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
     let specific_error = contains(pat!(RepoStateInitializationError::SetShortcutInvalid(
         pat!(monja::SetShortcutError::TraversalToParent(..))
     )));
@@ -213,15 +251,20 @@ fn shorcut_absolute_path() -> Result<()> {
         target_sets: set_names(["set1"]),
         ..old
     })
-    .configure_set(SetName("set1".into()), |_| SetConfig {
+    .configure_set(SetName("set1".into()), |old| SetConfig {
         shortcut: Some("/".into()),
+        ..old
     });
 
     fs_operation! { SetManipulation, sim, "set1",
         file "foo" "set1"
     };
 
-    let result = monja::pull(&sim.profile()?, sim.execution_options());
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
     let specific_error = contains(pat!(RepoStateInitializationError::SetShortcutInvalid(
         pat!(monja::SetShortcutError::NotRelative(..))
     )));
@@ -232,6 +275,51 @@ fn shorcut_absolute_path() -> Result<()> {
     Ok(())
 }
 
+#[gtest]
+fn case_collision() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "Config" "set1"
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "config" "set2"
+        file "apple" "crumble"
+    };
+
+    // fine on this file system, as long as we aren't checking
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    sim.configure_profile(|old| MonjaProfileConfig {
+        case_insensitive_check: true,
+        ..old
+    });
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+    // apple is only an override, so it isn't reported
+    let specific_error = elements_are![pat!(RepoStateInitializationError::CaseCollision(
+        eq(Path::new("Config")),
+        eq(Path::new("config"))
+    ))];
+    expect_that!(
+        result,
+        err(pat!(PullError::RepoStateInitialization(specific_error)))
+    );
+    Ok(())
+}
+
 #[gtest]
 fn missing_set() -> Result<()> {
     let sim = Simulator::create();
@@ -248,7 +336,11 @@ fn missing_set() -> Result<()> {
         end
         file "set1only" "set1only"
     };
-    let result = monja::pull(&sim.profile()?, sim.execution_options());
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
     expect_that!(
         result,
         err(pat!(PullError::MissingSets(contains(eq(&SetName(
@@ -259,6 +351,183 @@ fn missing_set() -> Result<()> {
     Ok(())
 }
 
+#[gtest]
+fn missing_set_allowed() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2", "set3"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "set1pie"
+        file "blueberry" "tart"
+    };
+    fs_operation! { SetManipulation, sim, "set3",
+        file "apple" "set3pie"
+    };
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &PullOptions {
+            allow_missing: true,
+            ..Default::default()
+        },
+    )?;
+    expect_that!(
+        result.skipped_sets,
+        elements_are![eq(&SetName("set2".into()))]
+    );
+    fs_operation! { LocalValidation, sim,
+        file "apple" "set3pie"
+        file "blueberry" "tart"
+    };
+
+    // the present sets are still layered in profile order
+    expect_that!(
+        monja::file_status(&sim.profile()?, sim.local_path("apple"))?,
+        eq(&FileStatus::Tracked(SetName("set3".into())))
+    );
+    expect_that!(
+        monja::file_status(&sim.profile()?, sim.local_path("blueberry"))?,
+        eq(&FileStatus::Tracked(SetName("set1".into())))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn sets_override() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "blueberry" "tart"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "apple" "set2pie"
+    };
+    fs_operation! { SetManipulation, sim, "set3",
+        file "apple" "set3pie"
+    };
+
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &PullOptions {
+            sets: Some(set_names(["set1", "set3"])),
+            ..Default::default()
+        },
+    )?;
+    expect_that!(
+        pull_result.files_pulled,
+        elements_are![
+            (
+                pat!(SetName("set1")),
+                elements_are![(
+                    pat!(RepoFilePath {
+                        local_path: eq(Path::new("blueberry")),
+                        ..
+                    }),
+                    eq(&PullAction::Created)
+                )]
+            ),
+            (
+                pat!(SetName("set3")),
+                elements_are![(
+                    pat!(RepoFilePath {
+                        local_path: eq(Path::new("apple")),
+                        ..
+                    }),
+                    eq(&PullAction::Created)
+                )]
+            ),
+        ]
+    );
+    fs_operation! { LocalValidation, sim,
+        file "apple" "set3pie"
+        file "blueberry" "tart"
+    };
+
+    // the index is what the overridden pull did, even though the profile is unchanged
+    expect_that!(
+        monja::file_status(&sim.profile()?, sim.local_path("apple"))?,
+        eq(&FileStatus::Tracked(SetName("set3".into())))
+    );
+    expect_that!(
+        sim.profile()?.config.target_sets,
+        eq(&set_names(["set1", "set2"]))
+    );
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &PullOptions {
+            sets: Some(set_names(["set1", "set4"])),
+            ..Default::default()
+        },
+    );
+    expect_that!(
+        result,
+        err(pat!(PullError::MissingSets(elements_are![eq(&SetName(
+            "set4".into()
+        ))])))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn redundant_overrides() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2", "set3"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "blueberry" "tart"
+        file "cherry" "cobbler"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "apple" "pie"
+        file "blueberry" "muffin"
+    };
+    fs_operation! { SetManipulation, sim, "set3",
+        file "apple" "pie"
+        file "blueberry" "tart"
+    };
+
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    // blueberry in set3 matches set1, but it's overriding set2's, which differs
+    expect_that!(
+        pull_result.redundant_overrides,
+        elements_are![
+            (
+                eq(Path::new("apple")),
+                pat!(SetName("set1")),
+                pat!(SetName("set2"))
+            ),
+            (
+                eq(Path::new("apple")),
+                pat!(SetName("set2")),
+                pat!(SetName("set3"))
+            ),
+        ]
+    );
+
+    Ok(())
+}
+
 // #[gtest]
 // fn missing_local_folder() -> Result<()> {
 //     // this test case realistically does not exist.
@@ -288,7 +557,7 @@ fn missing_repo_folder() -> Result<()> {
         repo_root,
         ..sim.profile()?
     };
-    let result = monja::pull(&profile, sim.execution_options());
+    let result = monja::pull(&profile, sim.execution_options(), &Default::default());
     let specific_error = contains(pat!(RepoStateInitializationError::ReadSetDirs(..)));
     expect_that!(
         result,
@@ -306,14 +575,12 @@ fn set_with_empty_name() -> Result<()> {
         ..old
     });
 
-    fs_operation! { SetManipulation, sim, "set1",
-        file "foo" "set1"
-    };
-
-    let result = monja::pull(&sim.profile()?, sim.execution_options());
+    // the profile can't even be loaded, let alone pulled
     expect_that!(
-        result,
-        err(pat!(PullError::MissingSets(container_eq(set_names([""])))))
+        sim.profile(),
+        err(pat!(MonjaProfileConfigError::Deserialization(displays_as(
+            contains_substring("Set names can't be empty.")
+        ))))
     );
 
     Ok(())
@@ -332,7 +599,11 @@ fn dry_run() -> Result<()> {
         file "blueberry" "tart"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { LocalValidation, sim,
     };
@@ -359,13 +630,21 @@ fn cleanable_files() -> Result<()> {
         file "lemon" "pie"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { SetManipulation, sim, "simple",
         remfile "blueberry"
     };
 
-    let pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     expect_that!(pull_result.cleanable_files, { Path::new("blueberry") });
 
@@ -378,7 +657,11 @@ fn cleanable_files() -> Result<()> {
         remfile "lemon"
     };
 
-    let pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     expect_that!(pull_result.cleanable_files, { Path::new("lemon") });
 
@@ -389,3 +672,1589 @@ fn cleanable_files() -> Result<()> {
 
     Ok(())
 }
+
+#[gtest]
+fn delete() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["simple"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "simple",
+        file "apple" "pie"
+        file "blueberry" "tart"
+        file "lemon" "pie"
+        dir "nested"
+            file "cherry" "cobbler"
+        end
+    };
+    fs_operation! { LocalManipulation, sim,
+        file ".monjaignore" "lemon"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { SetManipulation, sim, "simple",
+        remfile "blueberry"
+        remfile "lemon"
+        remfile "nested/cherry"
+    };
+
+    let pull_opts = PullOptions {
+        delete: true,
+        ..Default::default()
+    };
+
+    sim.dryrun(true);
+    let pull_result = monja::pull(&sim.profile()?, sim.execution_options(), &pull_opts)?;
+    expect_that!(
+        pull_result.deleted_files,
+        elements_are![eq(Path::new("blueberry")), eq(Path::new("nested/cherry"))]
+    );
+    fs_operation! { LocalValidation, sim,
+        file ".monjaignore" "lemon"
+        file "apple" "pie"
+        file "blueberry" "tart"
+        file "lemon" "pie"
+        dir "nested"
+            file "cherry" "cobbler"
+        end
+    };
+
+    sim.dryrun(false);
+    let pull_result = monja::pull(&sim.profile()?, sim.execution_options(), &pull_opts)?;
+    expect_that!(
+        pull_result.deleted_files,
+        elements_are![eq(Path::new("blueberry")), eq(Path::new("nested/cherry"))]
+    );
+    expect_that!(pull_result.cleanable_files, is_empty());
+    // ignored files are never deleted
+    fs_operation! { LocalValidation, sim,
+        file ".monjaignore" "lemon"
+        file "apple" "pie"
+        file "lemon" "pie"
+    };
+    expect_that!(sim.local_root().join("nested").exists(), is_false());
+
+    Ok(())
+}
+
+#[gtest]
+fn path_conflict() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    })
+    .configure_set(SetName("set2".into()), |old| SetConfig {
+        shortcut: Some(".config/app".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "app" "file"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "settings" "dark"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+    expect_that!(
+        result,
+        err(pat!(PullError::PathConflict {
+            path: eq(Path::new(".config/app")),
+            sets: container_eq(set_names(["set1", "set2"])),
+        }))
+    );
+    fs_operation! { LocalValidation, sim,
+    };
+
+    // skipping either side avoids the conflict
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &PullOptions {
+            skip: vec![".config/app".into()],
+            ..Default::default()
+        },
+    )?;
+    fs_operation! { LocalValidation, sim,
+        dir ".config/app"
+            file "settings" "dark"
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn permissions() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".ssh".into()),
+        permissions: BTreeMap::from([("id_ed25519".into(), "600".into())]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "id_ed25519" "secret"
+        file "config" "config"
+    };
+    let repo_key = sim.repo_root().join("set1/id_ed25519");
+    fs::set_permissions(&repo_key, fs::Permissions::from_mode(0o644))?;
+
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        pull_result.permissions_adjusted,
+        elements_are![eq(Path::new(".ssh/id_ed25519"))]
+    );
+
+    let local_key = sim.local_root().join(".ssh/id_ed25519");
+    expect_that!(
+        fs::metadata(&local_key)?.permissions().mode() & 0o7777,
+        eq(0o600)
+    );
+
+    // rsync brings over the repo's mode each pull, so it only stops needing adjustment once the repo matches
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        pull_result.permissions_adjusted,
+        elements_are![eq(Path::new(".ssh/id_ed25519"))]
+    );
+
+    fs::set_permissions(&repo_key, fs::Permissions::from_mode(0o600))?;
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(pull_result.permissions_adjusted, is_empty());
+
+    Ok(())
+}
+
+#[gtest]
+fn invalid_permissions() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        permissions: BTreeMap::from([("foo".into(), "rw-------".into())]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "foo" "set1"
+    };
+
+    let set_name = SetName("set1".into());
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+    let specific_error = contains(pat!(RepoStateInitializationError::InvalidPermissions(
+        eq(&set_name),
+        eq(Path::new("foo")),
+        eq("rw-------")
+    )));
+    expect_that!(
+        result,
+        err(pat!(PullError::RepoStateInitialization(specific_error)))
+    );
+    Ok(())
+}
+
+#[gtest]
+fn rsync_failure_has_stderr() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        dir "foo"
+            file "bar" "set1"
+        end
+    };
+    // a file where rsync needs a directory
+    fs_operation! { LocalManipulation, sim,
+        file "foo" "local"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+    expect_that!(
+        result,
+        err(pat!(PullError::Rsync(elements_are![(
+            eq(&SetName("set1".into())),
+            pat!(RsyncError::Unsuccessful {
+                status: anything(),
+                stderr: not(eq("")),
+            })
+        )])))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn dir_shortcut() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        dir "nvim"
+            file "init.lua" "init"
+        end
+        dir "bin"
+            file ".monja-dir.toml" "shortcut = '.local/bin'"
+            file "script" "script"
+            dir "nested"
+                file "script2" "script2"
+            end
+        end
+    };
+
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(pull_result.files_pulled, {
+        (
+            pat!(SetName("set1")),
+            unordered_elements_are![
+                (
+                    pat!(RepoFilePath {
+                        path_in_set: eq(Path::new("nvim/init.lua")),
+                        local_path: eq(Path::new(".config/nvim/init.lua")),
+                    }),
+                    eq(&PullAction::Created)
+                ),
+                (
+                    pat!(RepoFilePath {
+                        path_in_set: eq(Path::new("bin/script")),
+                        local_path: eq(Path::new(".local/bin/script")),
+                    }),
+                    eq(&PullAction::Created)
+                ),
+                (
+                    pat!(RepoFilePath {
+                        path_in_set: eq(Path::new("bin/nested/script2")),
+                        local_path: eq(Path::new(".local/bin/nested/script2")),
+                    }),
+                    eq(&PullAction::Created)
+                ),
+            ],
+        )
+    });
+
+    fs_operation! { LocalValidation, sim,
+        dir ".config/nvim"
+            file "init.lua" "init"
+        end
+        dir ".local/bin"
+            file "script" "script"
+            dir "nested"
+                file "script2" "script2"
+            end
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn invalid_dir_config() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        dir "foo"
+            file ".monja-dir.toml" "noclean = 'yes please'"
+            file "bar" "bar"
+        end
+    };
+
+    let bad_dir = sim.repo_root().join("set1/foo");
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+    let specific_error = contains(pat!(
+        RepoStateInitializationError::DirConfigDeserialization(eq(&bad_dir), anything())
+    ));
+    expect_that!(
+        result,
+        err(pat!(PullError::RepoStateInitialization(specific_error)))
+    );
+    Ok(())
+}
+
+#[gtest]
+fn rsync_failures_aggregated() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2", "set3"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        dir "foo"
+            file "bar" "set1"
+        end
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "fine" "set2"
+    };
+    fs_operation! { SetManipulation, sim, "set3",
+        dir "baz"
+            file "bar" "set3"
+        end
+    };
+    // files where rsync needs directories
+    fs_operation! { LocalManipulation, sim,
+        file "foo" "local"
+        file "baz" "local"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+    // in profile order, regardless of which finished first
+    expect_that!(
+        result,
+        err(pat!(PullError::Rsync(elements_are![
+            (eq(&SetName("set1".into())), anything()),
+            (eq(&SetName("set3".into())), anything())
+        ])))
+    );
+
+    // nothing is pulled, not even from the other sets
+    fs_operation! { LocalValidation, sim,
+        file "foo" "local"
+        file "baz" "local"
+    };
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.files_to_push, is_empty());
+
+    Ok(())
+}
+
+#[gtest]
+fn rsync_failure_rolls_back() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "banana" "split"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    let index_path = sim.data_root().join("monja-index.toml");
+    let index = fs::read_to_string(&index_path)?;
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "crumble"
+        dir "new"
+            dir "dir"
+                file "cherry" "tart"
+            end
+        end
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "banana" "bread"
+        dir "nested"
+            file "durian" "candy"
+        end
+    };
+    // rsync fails on this one after the files before it have already been synced
+    fs_operation! { LocalManipulation, sim,
+        file "nested" "in the way"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+    expect_that!(
+        result,
+        err(pat!(PullError::Rsync(elements_are![(
+            eq(&SetName("set2".into())),
+            anything()
+        )])))
+    );
+
+    // overwritten files are restored, and created ones are removed, along with their directories
+    fs_operation! { LocalValidation, sim,
+        file "apple" "pie"
+        file "banana" "split"
+        file "nested" "in the way"
+    };
+    expect_that!(sim.local_root().join("new").exists(), is_false());
+    expect_that!(fs::read_to_string(&index_path)?, eq(&index));
+    expect_that!(sim.data_root().join("monja-pull").exists(), is_false());
+
+    Ok(())
+}
+
+#[gtest]
+fn backup() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+    sim.backup(true);
+
+    fs_operation! { SetManipulation, sim, "set1",
+        dir "foo"
+            file "edited" "repo"
+            file "samesize" "aaaa"
+        end
+        file "unchanged" "unchanged"
+        file "new" "new"
+    };
+
+    fs_operation! { LocalManipulation, sim,
+        dir "foo"
+            file "edited" "local edit"
+            file "samesize" "bbbb"
+        end
+        file "unchanged" "unchanged"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        result.files_backed_up,
+        elements_are![eq(Path::new("foo/edited")), eq(Path::new("foo/samesize"))]
+    );
+
+    let backup_dir = result.backup_dir.expect("Files were backed up.");
+    expect_that!(
+        backup_dir.starts_with(sim.data_root().join("monja-backup")),
+        eq(true)
+    );
+    expect_that!(
+        fs::read_to_string(backup_dir.join("foo/edited"))?,
+        eq("local edit")
+    );
+    expect_that!(
+        fs::read_to_string(backup_dir.join("foo/samesize"))?,
+        eq("bbbb")
+    );
+    expect_that!(backup_dir.join("unchanged").exists(), eq(false));
+
+    // nothing differs anymore
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(result.files_backed_up, is_empty());
+    expect_that!(result.backup_dir, none());
+
+    Ok(())
+}
+
+#[gtest]
+fn backup_dryrun() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+    sim.backup(true).dryrun(true);
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "edited" "repo"
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "edited" "local edit"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        result.files_backed_up,
+        elements_are![eq(Path::new("edited"))]
+    );
+    expect_that!(sim.data_root().join("monja-backup").exists(), eq(false));
+
+    Ok(())
+}
+
+#[gtest]
+fn previous_index_write_failure() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "file" "file"
+    };
+
+    // the current index gets written fine, but a directory can't be written to like a file
+    fs::create_dir(sim.data_root().join("monja-index-prev.toml"))?;
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+    expect_that!(
+        result,
+        err(pat!(PullError::FileIndex(pat!(FileIndexError::Write(
+            pat!(IndexKind::Previous),
+            anything()
+        )))))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn skip() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        dir ".config"
+            dir "Code"
+                file "state" "heavy"
+            end
+            file "settings" "light"
+        end
+    };
+
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &PullOptions {
+            skip: vec![".config/Code/**".into()],
+            ..Default::default()
+        },
+    )?;
+
+    expect_that!(pull_result.skipped_files, {
+        eq(Path::new(".config/Code/state"))
+    });
+    fs_operation! { LocalValidation, sim,
+        file "apple" "pie"
+        dir ".config"
+            file "settings" "light"
+        end
+    };
+
+    // skipped files aren't in the index, so they don't get pushed either
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.files_to_push, {
+        (
+            pat!(SetName("set1")),
+            unordered_elements_are![eq(Path::new("apple")), eq(Path::new(".config/settings"))],
+        )
+    });
+
+    Ok(())
+}
+
+#[gtest]
+fn invalid_skip_pattern() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &PullOptions {
+            skip: vec!["a{b".into()],
+            ..Default::default()
+        },
+    );
+
+    expect_that!(
+        result,
+        err(pat!(PullError::SkipPattern(eq("a{b"), anything())))
+    );
+    fs_operation! { LocalValidation, sim,
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn pull_actions() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "unchanged" "same"
+        file "overwritten" "repo"
+        file "created" "new"
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "unchanged" "same"
+        file "overwritten" "local"
+    };
+
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    expect_that!(pull_result.files_pulled, {
+        (
+            pat!(SetName("set1")),
+            unordered_elements_are![
+                (
+                    field!(RepoFilePath.local_path, eq(Path::new("unchanged"))),
+                    eq(&PullAction::Unchanged)
+                ),
+                (
+                    field!(RepoFilePath.local_path, eq(Path::new("overwritten"))),
+                    eq(&PullAction::Overwritten)
+                ),
+                (
+                    field!(RepoFilePath.local_path, eq(Path::new("created"))),
+                    eq(&PullAction::Created)
+                ),
+            ],
+        )
+    });
+
+    Ok(())
+}
+
+#[gtest]
+fn special_files_only_special_where_they_apply() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        // only configures the set at its root
+        dir "nested"
+            file ".monja-set.toml" "nested"
+        end
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    expect_that!(
+        fs::read_to_string(sim.local_root().join("nested/.monja-set.toml"))?,
+        eq("nested")
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn reserved_names() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        reserved_names: vec!["secret.toml".into()],
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        dir "nested"
+            file "secret.toml" "secret"
+        end
+    };
+
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    expect_that!(pull_result.files_pulled, {
+        (
+            pat!(SetName("set1")),
+            elements_are![(
+                pat!(RepoFilePath {
+                    local_path: eq(Path::new("apple")),
+                    ..
+                }),
+                anything()
+            )],
+        )
+    });
+    expect_that!(
+        sim.local_root().join("nested/secret.toml").exists(),
+        is_false()
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn progress() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+    sim.configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        dir "nested"
+            file "blueberry" "tarts"
+        end
+    };
+
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let pull_opts = || PullOptions {
+        progress: Some(ProgressCallback(Box::new({
+            let reported = reported.clone();
+            move |file, bytes| {
+                let mut reported = reported.lock().expect("Not poisoned.");
+                reported.push((file.to_path_buf(), bytes));
+            }
+        }))),
+        ..Default::default()
+    };
+
+    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options(), &pull_opts())?;
+    expect_that!(
+        *reported.lock().expect("Not poisoned."),
+        unordered_elements_are![
+            (eq(Path::new(".config/apple")), eq(&3)),
+            (eq(Path::new(".config/nested/blueberry")), eq(&5)),
+        ]
+    );
+
+    // files that didn't change aren't reported
+    reported.lock().expect("Not poisoned.").clear();
+    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options(), &pull_opts())?;
+    expect_that!(*reported.lock().expect("Not poisoned."), is_empty());
+
+    Ok(())
+}
+
+#[gtest]
+fn templates() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        vars: BTreeMap::from([
+            ("email".into(), "me@example.com".into()),
+            ("host".into(), "desktop".into()),
+        ]),
+        ..old
+    });
+    sim.configure_set(SetName("set1".into()), |old| SetConfig {
+        templates: vec!["hosts".into()],
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "gitconfig.tmpl" "email = {{ email }}"
+        // matched by the set's templates glob, so its name is left alone
+        file "hosts" "127.0.0.1 {{host}}"
+        file "plain" "{{ not a template }}"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(result.files_pulled, {
+        (
+            pat!(SetName("set1")),
+            unordered_elements_are![
+                (
+                    pat!(RepoFilePath {
+                        path_in_set: eq(Path::new("gitconfig.tmpl")),
+                        local_path: eq(Path::new("gitconfig")),
+                    }),
+                    eq(&PullAction::Created)
+                ),
+                (
+                    pat!(RepoFilePath {
+                        path_in_set: eq(Path::new("hosts")),
+                        local_path: eq(Path::new("hosts")),
+                    }),
+                    eq(&PullAction::Created)
+                ),
+                (
+                    pat!(RepoFilePath {
+                        path_in_set: eq(Path::new("plain")),
+                        local_path: eq(Path::new("plain")),
+                    }),
+                    eq(&PullAction::Created)
+                ),
+            ],
+        )
+    });
+    fs_operation! { LocalValidation, sim,
+        file "gitconfig" "email = me@example.com"
+        file "hosts" "127.0.0.1 desktop"
+        file "plain" "{{ not a template }}"
+    };
+
+    // compared by what they render to
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(result.files_pulled, {
+        (
+            pat!(SetName("set1")),
+            each((anything(), eq(&PullAction::Unchanged))),
+        )
+    });
+
+    // and local edits to them are never pushed back
+    fs_operation! { LocalManipulation, sim,
+        file "gitconfig" "email = someone@example.com"
+    };
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(push_result.files_modified, is_empty());
+    fs_operation! { SetValidation, sim, "set1",
+        file "gitconfig.tmpl" "email = {{ email }}"
+        file "hosts" "127.0.0.1 {{host}}"
+        file "plain" "{{ not a template }}"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn template_undefined_variable() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        vars: BTreeMap::from([("email".into(), "me@example.com".into())]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "gitconfig.tmpl" "email = {{ email }}\nname = {{ name }}"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+    expect_that!(
+        result,
+        err(pat!(PullError::Template(
+            eq(&sim.repo_root().join("set1/gitconfig.tmpl")),
+            pat!(TemplateError::UndefinedVariable(eq("name")))
+        )))
+    );
+
+    // nothing gets pulled, since templates are rendered before anything is copied
+    fs_operation! { LocalValidation, sim,
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn transfer_stats() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tarts"
+    };
+    // big enough for rsync to use a thousands separator
+    fs::write(sim.repo_root().join("set2/cherry"), "c".repeat(1500))?;
+
+    sim.dryrun(true);
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(result.transfer_stats, eq(RsyncStats::default()));
+
+    // sets are pulled separately, so their stats get added together
+    sim.dryrun(false);
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        result.transfer_stats,
+        eq(RsyncStats {
+            files_transferred: 3,
+            bytes_transferred: Some(1508),
+        })
+    );
+
+    // unchanged files aren't transferred
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "crumble"
+    };
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        result.transfer_stats,
+        eq(RsyncStats {
+            files_transferred: 1,
+            bytes_transferred: Some(7),
+        })
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn index_dir() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        index_dir: Some("state/index".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // relative to the repo, and created as needed
+    let index_dir = sim.repo_root().join("state/index");
+    expect_that!(index_dir.join("monja-index.toml").is_file(), is_true());
+    expect_that!(index_dir.join("monja-index-prev.toml").is_file(), is_true());
+    expect_that!(
+        sim.data_root().join("monja-index.toml").exists(),
+        is_false()
+    );
+
+    // and read from there, too
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "crumble"
+    };
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(push_result.files_modified, {
+        (
+            pat!(SetName("set1")),
+            unordered_elements_are![eq(Path::new("apple"))],
+        )
+    });
+
+    Ok(())
+}
+
+#[gtest]
+fn last_pull() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    expect_that!(monja::last_pull_time(&sim.profile()?)?, none());
+
+    // dry runs don't count
+    sim.dryrun(true);
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(monja::last_pull_time(&sim.profile()?)?, none());
+
+    sim.dryrun(false);
+    let before = SystemTime::now();
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    let after = SystemTime::now();
+
+    expect_that!(
+        monja::last_pull_time(&sim.profile()?)?,
+        some(all!(ge(before), le(after)))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn set_priority() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["overrides", "base", "extra"]),
+        ..old
+    })
+    .configure_set(SetName("overrides".into()), |old| SetConfig {
+        priority: Some(10),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "overrides",
+        file "apple" "overrides"
+    };
+    fs_operation! { SetManipulation, sim, "base",
+        file "apple" "base"
+        file "banana" "base"
+    };
+    fs_operation! { SetManipulation, sim, "extra",
+        file "banana" "extra"
+    };
+
+    // sets without a priority keep their order relative to each other
+    let profile = sim.profile()?;
+    expect_that!(
+        profile.config.target_sets,
+        elements_are![
+            pat!(SetName("base")),
+            pat!(SetName("extra")),
+            pat!(SetName("overrides")),
+        ]
+    );
+
+    let _pull_result = monja::pull(&profile, sim.execution_options(), &Default::default())?;
+    fs_operation! { LocalValidation, sim,
+        file "apple" "overrides"
+        file "banana" "extra"
+    };
+
+    // and pushing goes to the set that won
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "changed"
+    };
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(push_result.files_pushed, {
+        (
+            pat!(SetName("overrides")),
+            unordered_elements_are![eq(Path::new("apple"))],
+        ),
+        (
+            pat!(SetName("extra")),
+            unordered_elements_are![eq(Path::new("banana"))],
+        )
+    });
+    fs_operation! { SetValidation, sim, "overrides",
+        file "apple" "changed"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn rsync_extra_args() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        rsync_extra_args: vec!["--bwlimit=100000".into()],
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        dir "nested"
+            file "blueberry" "tart"
+        end
+    };
+
+    // the file list and stats still work with them added
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(pull_result.transfer_stats.files_transferred, eq(2));
+    fs_operation! { LocalValidation, sim,
+        file "apple" "pie"
+        dir "nested"
+            file "blueberry" "tart"
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn repo_dirty() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    // not a git repo yet
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(pull_result.repo_dirty, none());
+
+    let git = |args: &[&str]| -> Result<()> {
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(sim.repo_root())
+            .args([
+                "-c",
+                "user.name=monja",
+                "-c",
+                "user.email=monja@example.com",
+            ])
+            .args(args)
+            .stdout(std::process::Stdio::null())
+            .status()?;
+        verify_that!(status.success(), eq(true))
+    };
+    git(&["init", "--quiet"])?;
+    git(&["add", "--all"])?;
+    git(&["commit", "--quiet", "--message", "initial"])?;
+
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(pull_result.repo_dirty, some(eq(false)));
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "crumble"
+    };
+    let pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(pull_result.repo_dirty, some(eq(true)));
+
+    Ok(())
+}
+
+#[gtest]
+fn best_effort() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "blueberry" "tart"
+        dir "nested"
+            file "cherry" "pie"
+        end
+    };
+    // a file where the directory should be, so that rsync can't create nested/cherry
+    fs_operation! { LocalManipulation, sim,
+        file "nested" "in the way"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &PullOptions {
+            best_effort: true,
+            ..Default::default()
+        },
+    )?;
+    expect_that!(
+        result.failed_files,
+        elements_are![(
+            pat!(RepoFilePath {
+                local_path: eq(Path::new("nested/cherry")),
+                ..
+            }),
+            anything()
+        )]
+    );
+    expect_that!(
+        result.files_pulled,
+        elements_are![(
+            pat!(SetName("set1")),
+            unordered_elements_are![
+                (
+                    pat!(RepoFilePath {
+                        local_path: eq(Path::new("apple")),
+                        ..
+                    }),
+                    anything()
+                ),
+                (
+                    pat!(RepoFilePath {
+                        local_path: eq(Path::new("blueberry")),
+                        ..
+                    }),
+                    anything()
+                ),
+            ]
+        )]
+    );
+    fs_operation! { LocalValidation, sim,
+        file "apple" "pie"
+        file "blueberry" "tart"
+        file "nested" "in the way"
+    };
+
+    // once fixed, the failed file gets pulled like any other
+    fs::remove_file(sim.local_root().join("nested"))?;
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(result.failed_files, is_empty());
+    fs_operation! { LocalValidation, sim,
+        file "apple" "pie"
+        file "blueberry" "tart"
+        dir "nested"
+            file "cherry" "pie"
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn dest_root() -> Result<()> {
+    let dest_root = tempfile::Builder::new().prefix("MonjaDest").tempdir()?;
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "system"]),
+        ..old
+    })
+    .configure_set(SetName("system".into()), |old| SetConfig {
+        dest_root: Some(dest_root.path().to_path_buf()),
+        shortcut: Some("etc".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "system",
+        file "hosts" "127.0.0.1 localhost"
+        file "apple" "not a local file"
+    };
+
+    // writing outside of the local root takes opting in
+    expect_that!(
+        monja::pull(
+            &sim.profile()?,
+            sim.execution_options(),
+            &Default::default(),
+        ),
+        err(pat!(PullError::DestRootNotAllowed(elements_are![eq(
+            &SetName("system".into())
+        )])))
+    );
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &PullOptions {
+            allow_dest_roots: true,
+            ..Default::default()
+        },
+    )?;
+    expect_that!(
+        result.dest_root_files,
+        elements_are![(
+            eq(&SetName("system".into())),
+            elements_are![
+                eq(&dest_root.path().join("etc/apple")),
+                eq(&dest_root.path().join("etc/hosts"))
+            ]
+        )]
+    );
+    expect_that!(
+        fs::read_to_string(dest_root.path().join("etc/hosts"))?,
+        eq("127.0.0.1 localhost")
+    );
+    // the system set's apple doesn't override the local one
+    fs_operation! { LocalValidation, sim,
+        file "apple" "pie"
+    };
+
+    // its files aren't local files, so there's nothing to push
+    fs_operation! { LocalManipulation, sim,
+        dir "etc"
+            file "hosts" "changed"
+        end
+    };
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(
+        status.files_to_push,
+        elements_are![(eq(&SetName("set1".into())), anything())]
+    );
+    expect_that!(status.untracked_files, { eq(Path::new("etc/hosts")) });
+
+    Ok(())
+}
+
+#[gtest]
+fn dest_root_overlapping_local_root() -> Result<()> {
+    let sim = Simulator::create();
+    let local_dest_root = sim.local_root().join("system");
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["system"]),
+        ..old
+    })
+    .configure_set(SetName("system".into()), |old| SetConfig {
+        dest_root: Some(local_dest_root.clone()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "system",
+        file "hosts" "127.0.0.1 localhost"
+    };
+
+    expect_that!(
+        monja::pull(
+            &sim.profile()?,
+            sim.execution_options(),
+            &PullOptions {
+                allow_dest_roots: true,
+                ..Default::default()
+            },
+        ),
+        err(pat!(PullError::RepoStateInitialization(elements_are![
+            pat!(RepoStateInitializationError::InvalidDestRoot(
+                eq(&SetName("system".into())),
+                anything()
+            ))
+        ])))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn dest_root_relative_or_in_repo() -> Result<()> {
+    let sim = Simulator::create();
+    // outside of the local root, so only the repo check catches it
+    let repo_dir = tempfile::Builder::new().prefix("MonjaRepo").tempdir()?;
+    let repo_root = AbsolutePath::for_existing_path(repo_dir.path())?;
+    fs::create_dir(repo_root.join("system"))?;
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["system"]),
+        repo_dir: repo_root.to_path_buf(),
+        ..old
+    });
+    fs::write(repo_root.join("system/hosts"), "127.0.0.1 localhost")?;
+
+    for dest_root in [PathBuf::from("etc"), repo_root.join("etc")] {
+        sim.configure_set(SetName("system".into()), |old| SetConfig {
+            dest_root: Some(dest_root.clone()),
+            ..old
+        });
+
+        expect_that!(
+            monja::pull(
+                &sim.profile()?,
+                sim.execution_options(),
+                &PullOptions {
+                    allow_dest_roots: true,
+                    ..Default::default()
+                },
+            ),
+            err(pat!(PullError::RepoStateInitialization(elements_are![
+                pat!(RepoStateInitializationError::InvalidDestRoot(
+                    eq(&SetName("system".into())),
+                    eq(&dest_root)
+                ))
+            ]))),
+            "{}",
+            dest_root.display()
+        );
+    }
+    expect_that!(repo_root.join("etc").exists(), is_false());
+
+    Ok(())
+}
+
+// with the acl package around, recorded ACLs end up on the pulled file.
+// without it, the pull says so instead of silently dropping them.
+#[gtest]
+fn acls_reapplied() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        acls: BTreeMap::from([("apple".into(), "user:root:r--,mask::r--".into())]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    // recorded ACLs are only used by sets that opt in
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    sim.configure_set(SetName("set1".into()), |old| SetConfig {
+        preserve_acls: Some(true),
+        ..old
+    });
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+    if Command::new("setfacl").arg("--version").output().is_err() {
+        expect_that!(
+            result,
+            err(pat!(PullError::Acl(
+                anything(),
+                pat!(AclError::Missing(eq(&"setfacl")))
+            )))
+        );
+        return Ok(());
+    }
+    // the other way it can fail, which is just as clear
+    if let Err(PullError::Acl(_, AclError::Unsuccessful { .. })) = result {
+        return Ok(());
+    }
+    result?;
+
+    let output = Command::new("getfacl")
+        .args(["--omit-header", "--"])
+        .arg(sim.local_root().join("apple"))
+        .output()?;
+    expect_that!(
+        String::from_utf8_lossy(&output.stdout),
+        contains_substring("user:root:r--")
+    );
+
+    Ok(())
+}
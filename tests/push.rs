@@ -4,8 +4,8 @@ use googletest::prelude::*;
 
 use crate::sim::{Simulator, set_names};
 use monja::{
-    AbsolutePath, LocalStateInitializationError, MonjaProfileConfig, MonjaProfileConfigError,
-    PushError, SetName,
+    AbsolutePath, LocalFilePath, LocalStateInitializationError, MonjaProfileConfig,
+    MonjaProfileConfigError, PushError, PushOptions, SetConfig, SetName,
 };
 
 #[allow(dead_code)]
@@ -37,7 +37,11 @@ fn simple_set() -> Result<()> {
         file "blueberry" "tart"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { LocalManipulation, sim,
         dir "apple"
@@ -47,7 +51,11 @@ fn simple_set() -> Result<()> {
         file "newfile" "newfile"
     };
 
-    let _push_result = monja::push(&sim.profile()?, sim.execution_options())?;
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { SetValidation, sim, "simple",
         dir "foo"
@@ -91,7 +99,11 @@ fn multi_set() -> Result<()> {
         file "set2only" "set2only"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { LocalManipulation, sim,
         dir "foo/bar"
@@ -101,7 +113,11 @@ fn multi_set() -> Result<()> {
         file "set2only" "stillset2"
     };
 
-    let _push_result = monja::push(&sim.profile()?, sim.execution_options())?;
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { SetValidation, sim, "set1",
         dir "foo"
@@ -137,11 +153,19 @@ fn missing_set() -> Result<()> {
         file "blueberry" "tart"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     sim.rem_set(SetName("simple".into()));
 
-    let push_result = monja::push(&sim.profile()?, sim.execution_options());
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
     expect_that!(
         push_result,
         err(pat!(PushError::Consistency {
@@ -166,13 +190,21 @@ fn missing_files() -> Result<()> {
         file "apple" "pie"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { SetManipulation, sim, "simple",
         remfile "blueberry"
     };
 
-    let push_result = monja::push(&sim.profile()?, sim.execution_options());
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
     expect_that!(
         push_result,
         err(pat!(PushError::Consistency {
@@ -194,9 +226,10 @@ fn missing_repo_folder_pre_profile() -> Result<()> {
         .tempdir()?;
     let repo_root = AbsolutePath::for_existing_path(temp_repo_root.path())?;
 
-    sim.configure_profile(|_| MonjaProfileConfig {
+    sim.configure_profile(|old| MonjaProfileConfig {
         target_sets: set_names(["simple"]),
         repo_dir: repo_root.to_path_buf(),
+        ..old
     });
 
     fs_operation! { SetManipulation, sim, "simple",
@@ -204,7 +237,11 @@ fn missing_repo_folder_pre_profile() -> Result<()> {
         file "apple" "pie"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options());
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
 
     std::mem::drop(temp_repo_root);
 
@@ -226,9 +263,10 @@ fn missing_repo_folder_post_profile() -> Result<()> {
         .tempdir()?;
     let repo_root = AbsolutePath::for_existing_path(temp_repo_root.path())?;
 
-    sim.configure_profile(|_| MonjaProfileConfig {
+    sim.configure_profile(|old| MonjaProfileConfig {
         target_sets: set_names(["simple"]),
         repo_dir: repo_root.to_path_buf(),
+        ..old
     });
 
     fs_operation! { SetManipulation, sim, "simple",
@@ -236,12 +274,16 @@ fn missing_repo_folder_post_profile() -> Result<()> {
         file "apple" "pie"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options());
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
 
     let profile = sim.profile()?;
     std::mem::drop(temp_repo_root);
 
-    let push_result = monja::push(&profile, sim.execution_options());
+    let push_result = monja::push(&profile, sim.execution_options(), &Default::default());
     expect_that!(
         push_result,
         err(pat!(PushError::RepoStateInitialization(_)))
@@ -265,7 +307,11 @@ fn no_index() -> Result<()> {
     // let _pull_result = monja::pull(&sim.profile()?)?;
     // no pull, no index
 
-    let push_result = monja::push(&sim.profile()?, sim.execution_options())?;
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
     expect_that!(push_result.files_pushed, is_empty());
 
     Ok(())
@@ -283,7 +329,11 @@ fn index_based_directory_traversal_absolute() -> Result<()> {
         file "blueberry" "tart"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     // a bit of a leaky implementation detail, but oh well
     let index_path = sim.profile()?.data_root.join("monja-index.toml");
@@ -292,7 +342,11 @@ fn index_based_directory_traversal_absolute() -> Result<()> {
     let replacement_index = r#""/etc/passwd" = "simple""#;
     fs::write(index_path, replacement_index)?;
 
-    let push_result = monja::push(&sim.profile()?, sim.execution_options());
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
 
     let specific_error = pat!(LocalStateInitializationError::FileIndex(_));
     expect_that!(
@@ -315,7 +369,11 @@ fn index_based_directory_traversal_relative() -> Result<()> {
         file "blueberry" "tart"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     // a bit of a leaky implementation detail, but oh well
     let index_path = sim.profile()?.data_root.join("monja-index.toml");
@@ -327,7 +385,11 @@ fn index_based_directory_traversal_relative() -> Result<()> {
     let replacement_index = r#""../foo" = "simple""#;
     fs::write(index_path, replacement_index)?;
 
-    let push_result = monja::push(&sim.profile()?, sim.execution_options())?;
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     // since this file lives outside of the local root, it shouldn't get picked up whether or not it's mentioned in the index
     // this is because we do a full scan of the directory to find inconsistencies and flag them for the user/recover.
@@ -357,7 +419,11 @@ fn dry_run() -> Result<()> {
         file "blueberry" "tart"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { LocalManipulation, sim,
         dir "apple"
@@ -367,8 +433,15 @@ fn dry_run() -> Result<()> {
         file "newfile" "newfile"
     };
 
+    let index_path = sim.data_root().join("monja-index.toml");
+    let index_before = fs::read_to_string(&index_path)?;
+
     sim.dryrun(true);
-    let _push_result = monja::push(&sim.profile()?, sim.execution_options())?;
+    let dry_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { SetValidation, sim, "simple",
         dir "foo"
@@ -382,8 +455,32 @@ fn dry_run() -> Result<()> {
         end
         file "blueberry" "tart"
     };
+    expect_that!(fs::read_to_string(&index_path)?, eq(&index_before));
+
+    // a dry run reports exactly what a real push would do
+    sim.dryrun(false);
+    let real_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        sorted(dry_result.files_pushed),
+        eq(&sorted(real_result.files_pushed))
+    );
+    expect_that!(
+        sorted(dry_result.files_modified),
+        eq(&sorted(real_result.files_modified))
+    );
 
-    Ok(())
+    return Ok(());
+
+    fn sorted(mut files: Vec<(SetName, Vec<LocalFilePath>)>) -> Vec<(SetName, Vec<LocalFilePath>)> {
+        for (_, file_paths) in files.iter_mut() {
+            file_paths.sort();
+        }
+        files
+    }
 }
 
 #[gtest]
@@ -399,13 +496,21 @@ fn ignore() -> Result<()> {
         file ".monjaignore" "blueberry"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { LocalManipulation, sim,
         file "blueberry" "pie"
     };
 
-    let push_result = monja::push(&sim.profile()?, sim.execution_options())?;
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
     expect_that!(push_result.files_pushed, {
         (
             pat!(SetName("simple")),
@@ -420,3 +525,671 @@ fn ignore() -> Result<()> {
 
     Ok(())
 }
+
+#[gtest]
+fn set_ignore() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        // rooted at local_root, even though set1's files would end up in .config
+        file ".monjaignore" "/blueberry"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tart"
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "untargeted",
+        file ".monjaignore" "apple"
+    };
+    sim.configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    });
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "blueberry" "muffin"
+        file "apple" "crumble"
+    };
+
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(push_result.files_pushed, {
+        (
+            pat!(SetName("set1")),
+            unordered_elements_are![eq(Path::new(".config/.monjaignore"))],
+        ),
+        (
+            pat!(SetName("set2")),
+            unordered_elements_are![eq(Path::new("apple"))],
+        )
+    });
+
+    fs_operation! { SetValidation, sim, "set2",
+        file "blueberry" "tart"
+        file "apple" "crumble"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn dir_shortcut() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        dir "nvim"
+            file "init.lua" "init"
+        end
+        dir "bin"
+            file ".monja-dir.toml" "shortcut = '.local/bin'"
+            file "script" "script"
+        end
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        dir ".config/nvim"
+            file "init.lua" "init-local"
+        end
+        dir ".local/bin"
+            file "script" "script-local"
+            file "newscript" "newscript"
+        end
+    };
+
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    // newscript isn't pulled, so it would need a put
+    let _put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path(".local/bin/newscript")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+
+    fs_operation! { SetValidation, sim, "set1",
+        dir "nvim"
+            file "init.lua" "init-local"
+        end
+        dir "bin"
+            file "script" "script-local"
+            file "newscript" "newscript"
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn nopush_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["vendored", "mine"]),
+        ..old
+    })
+    .configure_set(SetName("vendored".into()), |old| SetConfig {
+        nopush: Some(true),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "vendored",
+        file "upstream" "upstream"
+        file "gone" "gone"
+    };
+    fs_operation! { SetManipulation, sim, "mine",
+        file "mine" "mine"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "upstream" "edited"
+        file "mine" "edited"
+    };
+    // would normally fail consistency checks
+    fs_operation! { SetManipulation, sim, "vendored",
+        remfile "gone"
+    };
+
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        push_result.files_pushed,
+        elements_are![(eq(&SetName("mine".into())), len(eq(1)))]
+    );
+
+    fs_operation! { SetValidation, sim, "vendored",
+        file "upstream" "upstream"
+    };
+    fs_operation! { SetValidation, sim, "mine",
+        file "mine" "edited"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn symlink_pushed_as_file() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "gitconfig" "old"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        dir "synced"
+            file "gitconfig" "new"
+        end
+        remfile "gitconfig"
+    };
+    std::os::unix::fs::symlink("synced/gitconfig", sim.local_root().join("gitconfig"))?;
+
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let repo_file = sim.repo_root().join("set1/gitconfig");
+    expect_that!(fs::symlink_metadata(&repo_file)?.is_file(), eq(true));
+    fs_operation! { SetValidation, sim, "set1",
+        file "gitconfig" "new"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn symlink_preserved() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        preserve_symlinks: true,
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "gitconfig" "old"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        dir "synced"
+            file "gitconfig" "new"
+        end
+        remfile "gitconfig"
+    };
+    std::os::unix::fs::symlink("synced/gitconfig", sim.local_root().join("gitconfig"))?;
+
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let repo_file = sim.repo_root().join("set1/gitconfig");
+    expect_that!(
+        fs::read_link(&repo_file)?,
+        eq(Path::new("synced/gitconfig"))
+    );
+
+    // and it comes back as a link on the next pull
+    fs::remove_file(sim.local_root().join("gitconfig"))?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        fs::read_link(sim.local_root().join("gitconfig"))?,
+        eq(Path::new("synced/gitconfig"))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn modified_files() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "unchanged" "unchanged"
+        file "edited" "edited"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "edited" "edited locally"
+    };
+
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        push_result.files_pushed,
+        elements_are![(eq(&SetName("set1".into())), len(eq(2)))]
+    );
+    expect_that!(
+        push_result.files_modified,
+        elements_are![(
+            eq(&SetName("set1".into())),
+            elements_are![eq(Path::new("edited"))]
+        )]
+    );
+
+    // and nothing is modified once pushed
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(push_result.files_modified, is_empty());
+
+    Ok(())
+}
+
+#[gtest]
+fn conflicts() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "shared" "original"
+        file "localonly" "original"
+        file "repoonly" "original"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // as if another machine pushed, followed by a git pull
+    fs_operation! { SetManipulation, sim, "set1",
+        file "shared" "other machine"
+        file "repoonly" "other machine"
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "shared" "this machine"
+        file "localonly" "this machine"
+    };
+
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
+    expect_that!(
+        push_result,
+        err(pat!(PushError::Conflicts(elements_are![(
+            eq(&SetName("set1".into())),
+            elements_are![eq(Path::new("shared"))]
+        )])))
+    );
+
+    // nothing gets pushed
+    fs_operation! { SetValidation, sim, "set1",
+        file "shared" "other machine"
+        file "localonly" "original"
+        file "repoonly" "other machine"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn no_conflicts_after_pushing() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "file" "original"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "file" "first edit"
+    };
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // the repo now differs from what was pulled, but only because of our own push
+    fs_operation! { LocalManipulation, sim,
+        file "file" "second edit"
+    };
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { SetValidation, sim, "set1",
+        file "file" "second edit"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn index_without_hashes() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "file" "other machine"
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "file" "this machine"
+    };
+    // from before hashes were stored, so conflicts can't be detected
+    fs::write(
+        sim.data_root().join("monja-index.toml"),
+        "file = \"set1\"\n",
+    )?;
+
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { SetValidation, sim, "set1",
+        file "file" "this machine"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn only_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "conflicted" "original"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tart"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // a conflict in set1 doesn't stop set2 from being pushed on its own
+    fs_operation! { SetManipulation, sim, "set1",
+        file "conflicted" "other machine"
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "crumble"
+        file "conflicted" "this machine"
+        file "blueberry" "muffin"
+    };
+
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &PushOptions {
+            only: Some(SetName("set2".into())),
+            ..Default::default()
+        },
+    )?;
+
+    expect_that!(push_result.files_pushed, {
+        (
+            eq(&SetName("set2".into())),
+            elements_are![eq(Path::new("blueberry"))],
+        )
+    });
+    fs_operation! { SetValidation, sim, "set1",
+        file "apple" "pie"
+        file "conflicted" "other machine"
+    };
+    fs_operation! { SetValidation, sim, "set2",
+        file "blueberry" "muffin"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn only_set_still_checks_consistency() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tart"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { SetManipulation, sim, "set1",
+        remfile "apple"
+    };
+
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &PushOptions {
+            only: Some(SetName("set2".into())),
+            ..Default::default()
+        },
+    );
+
+    expect_that!(
+        push_result,
+        err(pat!(PushError::Consistency {
+            files_with_missing_sets: is_empty(),
+            missing_files: elements_are![(
+                eq(&SetName("set1".into())),
+                elements_are![eq(Path::new("apple"))]
+            )],
+        }))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn only_untargeted_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tart"
+    };
+
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &PushOptions {
+            only: Some(SetName("set2".into())),
+            ..Default::default()
+        },
+    );
+
+    expect_that!(
+        push_result,
+        err(pat!(PushError::SetNotTargeted(eq(&SetName("set2".into())))))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn since() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["simple"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "simple",
+        file "apple" "pie"
+        file "blueberry" "tart"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let git = |args: &[&str]| -> Result<()> {
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(sim.repo_root())
+            .args([
+                "-c",
+                "user.name=monja",
+                "-c",
+                "user.email=monja@example.com",
+            ])
+            .args(args)
+            .stdout(std::process::Stdio::null())
+            .status()?;
+        verify_that!(status.success(), eq(true))
+    };
+    git(&["init", "--quiet"])?;
+    git(&["add", "--all"])?;
+    git(&["commit", "--quiet", "--message", "initial"])?;
+
+    // apple changes in the repo without being committed
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "crumble"
+    };
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "fritter"
+        file "blueberry" "muffin"
+    };
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &PushOptions {
+            since: Some("HEAD".into()),
+            ..Default::default()
+        },
+    )?;
+
+    expect_that!(push_result.files_pushed, {
+        (
+            pat!(SetName("simple")),
+            unordered_elements_are![eq(Path::new("apple"))],
+        )
+    });
+    fs_operation! { SetValidation, sim, "simple",
+        file "apple" "fritter"
+        file "blueberry" "tart"
+    };
+
+    Ok(())
+}
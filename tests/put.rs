@@ -1,7 +1,10 @@
-use std::path::Path;
+use std::{collections::BTreeMap, fs, os::unix::fs::PermissionsExt, path::Path, process::Command};
 
 use googletest::prelude::*;
-use monja::{MonjaProfileConfig, PushError, PutError, SetConfig, SetName};
+use monja::{
+    AclError, LaterSetsPolicy, MonjaProfileConfig, PushError, PutError, PutOptions, SetConfig,
+    SetIndex, SetName,
+};
 
 use crate::sim::{Simulator, set_names};
 
@@ -23,23 +26,36 @@ fn fix_missing_set() -> Result<()> {
     fs_operation! { SetManipulation, sim, "set2",
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     sim.rem_set(SetName("set1".into()));
 
-    let push_result = monja::push(&sim.profile()?, sim.execution_options());
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
     expect_that!(push_result, err(pat!(PushError::Consistency { .. })));
 
     let put_result = monja::put(
         &sim.profile()?,
         sim.execution_options(),
         vec![sim.local_path("blueberry")],
-        SetName("set2".into()),
+        Some(SetName("set2".into())),
+        &Default::default(),
     )?;
-    expect_that!(put_result.owning_set, pat!(SetName("set2")));
+    expect_that!(put_result.owning_set, some(pat!(SetName("set2"))));
     expect_that!(put_result.files, { eq(Path::new("blueberry")) });
 
-    let _push_result = monja::push(&sim.profile()?, sim.execution_options())?;
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     Ok(())
 }
@@ -59,26 +75,140 @@ fn fix_missing_files() -> Result<()> {
     fs_operation! { SetManipulation, sim, "set2",
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { SetManipulation, sim, "set1",
         remfile "blueberry"
     };
 
-    let push_result = monja::push(&sim.profile()?, sim.execution_options());
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
     expect_that!(push_result, err(pat!(PushError::Consistency { .. })));
 
     let put_result = monja::put(
         &sim.profile()?,
         sim.execution_options(),
         vec![sim.local_path("blueberry")],
-        SetName("set2".into()),
+        Some(SetName("set2".into())),
+        &Default::default(),
     )?;
-    expect_that!(put_result.owning_set, pat!(SetName("set2")));
+    expect_that!(put_result.owning_set, some(pat!(SetName("set2"))));
     expect_that!(put_result.files, { eq(Path::new("blueberry")) });
 
     // succeeding is good enough
-    let _push_result = monja::push(&sim.profile()?, sim.execution_options())?;
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    Ok(())
+}
+
+#[gtest]
+fn from_index() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "blueberry" "tart"
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "cherry" "cobbler"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // as if accidentally deleted
+    fs_operation! { SetManipulation, sim, "set1",
+        remfile "blueberry"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        remfile "cherry"
+    };
+
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("cherry"), sim.local_path("blueberry")],
+        None,
+        &Default::default(),
+    )?;
+    expect_that!(put_result.owning_set, none());
+    expect_that!(
+        put_result.files_by_set,
+        elements_are![
+            (
+                pat!(SetName("set2")),
+                elements_are![eq(Path::new("cherry"))]
+            ),
+            (
+                pat!(SetName("set1")),
+                elements_are![eq(Path::new("blueberry"))]
+            ),
+        ]
+    );
+    expect_that!(put_result.set_is_targeted, is_true());
+
+    fs_operation! { SetValidation, sim, "set1",
+        file "blueberry" "tart"
+        file "apple" "pie"
+    };
+    fs_operation! { SetValidation, sim, "set2",
+        file "cherry" "cobbler"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn from_index_not_in_index() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    fs_operation! { LocalManipulation, sim,
+        file "notinrepo" "notinrepo"
+    };
+
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("apple"), sim.local_path("notinrepo")],
+        None,
+        &Default::default(),
+    );
+    expect_that!(
+        put_result,
+        err(pat!(PutError::NotInIndex(elements_are![eq(Path::new(
+            "notinrepo"
+        ))])))
+    );
 
     Ok(())
 }
@@ -97,11 +227,19 @@ fn dryrun() -> Result<()> {
     fs_operation! { SetManipulation, sim, "set2",
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     sim.rem_set(SetName("set1".into()));
 
-    let push_result = monja::push(&sim.profile()?, sim.execution_options());
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    );
     expect_that!(push_result, err(pat!(PushError::Consistency { .. })));
 
     sim.dryrun(true);
@@ -109,9 +247,10 @@ fn dryrun() -> Result<()> {
         &sim.profile()?,
         sim.execution_options(),
         vec![sim.local_path("blueberry")],
-        SetName("set2".into()),
+        Some(SetName("set2".into())),
+        &Default::default(),
     )?;
-    expect_that!(put_result.owning_set, pat!(SetName("set2")));
+    expect_that!(put_result.owning_set, some(pat!(SetName("set2"))));
     expect_that!(put_result.files, { eq(Path::new("blueberry")) });
 
     fs_operation! { SetValidation, sim, "set2",
@@ -148,7 +287,8 @@ fn nonexistent_set() -> Result<()> {
         &sim.profile()?,
         sim.execution_options(),
         vec![sim.local_path("notinrepo")],
-        SetName("set2".into()),
+        Some(SetName("set2".into())),
+        &Default::default(),
     );
     expect_that!(
         put_result,
@@ -158,6 +298,45 @@ fn nonexistent_set() -> Result<()> {
     Ok(())
 }
 
+#[gtest]
+fn set_index() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2", "set3"]),
+        ..old
+    });
+
+    let profile = sim.profile()?;
+    expect_that!(
+        monja::resolve_set_index(&profile, "last".parse()?),
+        ok(eq(&SetName("set3".into())))
+    );
+    expect_that!(
+        monja::resolve_set_index(&profile, "2".parse()?),
+        ok(eq(&SetName("set2".into())))
+    );
+    expect_that!(
+        monja::resolve_set_index(&profile, SetIndex::Position(4)),
+        err(pat!(PutError::SetIndexOutOfRange {
+            index: eq(&4),
+            count: eq(&3)
+        }))
+    );
+    expect_that!("0".parse::<SetIndex>(), err(anything()));
+    expect_that!("first".parse::<SetIndex>(), err(anything()));
+
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: Vec::new(),
+        ..old
+    });
+    expect_that!(
+        monja::resolve_set_index(&sim.profile()?, SetIndex::Last),
+        err(pat!(PutError::NoTargetedSets))
+    );
+
+    Ok(())
+}
+
 #[gtest]
 fn nonexistent_file() -> Result<()> {
     let sim = Simulator::create();
@@ -173,7 +352,8 @@ fn nonexistent_file() -> Result<()> {
         &sim.profile()?,
         sim.execution_options(),
         vec![sim.local_path("notinlocal")],
-        SetName("set1".into()),
+        Some(SetName("set1".into())),
+        &Default::default(),
     );
     expect_that!(
         put_result,
@@ -192,9 +372,10 @@ fn shortcut() -> Result<()> {
         target_sets: set_names(["set1"]),
         ..old
     })
-    .configure_set(SetName("set1".into()), |_| SetConfig {
+    .configure_set(SetName("set1".into()), |old| SetConfig {
         // start with nested directory structure just in case
         shortcut: Some("foo/bar".into()),
+        ..old
     });
 
     fs_operation! { SetManipulation, sim, "set1",
@@ -210,11 +391,12 @@ fn shortcut() -> Result<()> {
         &sim.profile()?,
         sim.execution_options(),
         vec![sim.local_path("foo/bar/notinrepo")],
-        SetName("set1".into()),
+        Some(SetName("set1".into())),
+        &Default::default(),
     )?;
 
     expect_that!(put_result.files, { Path::new("foo/bar/notinrepo") });
-    expect_that!(put_result.owning_set, eq(&SetName("set1".into())));
+    expect_that!(put_result.owning_set, some(eq(&SetName("set1".into()))));
     fs_operation! { SetValidation, sim, "set1",
         file "notinrepo" "notinrepo"
     };
@@ -229,9 +411,10 @@ fn path_outside_of_shortcut() -> Result<()> {
         target_sets: set_names(["set1"]),
         ..old
     })
-    .configure_set(SetName("set1".into()), |_| SetConfig {
+    .configure_set(SetName("set1".into()), |old| SetConfig {
         // start with nested directory structure just in case
         shortcut: Some("foo/bar".into()),
+        ..old
     });
 
     fs_operation! { SetManipulation, sim, "set1",
@@ -245,13 +428,133 @@ fn path_outside_of_shortcut() -> Result<()> {
         &sim.profile()?,
         sim.execution_options(),
         vec![sim.local_path("notinrepo")],
-        SetName("set1".into()),
+        Some(SetName("set1".into())),
+        &Default::default(),
     );
     expect_that!(put_result, err(pat!(PutError::SetPath(..))));
 
     Ok(())
 }
 
+#[gtest]
+fn outside_shortcut() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some("foo/bar".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "inrepo" "inrepo"
+    };
+
+    fs_operation! { LocalManipulation, sim,
+        dir "baz/qux"
+            file "apple" "pie"
+            file "blueberry" "tart"
+        end
+    };
+
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![
+            sim.local_path("baz/qux/apple"),
+            sim.local_path("baz/qux/blueberry"),
+        ],
+        Some(SetName("set1".into())),
+        &PutOptions {
+            outside_shortcut: true,
+            ..Default::default()
+        },
+    )?;
+
+    expect_that!(
+        put_result.files_outside_shortcut,
+        elements_are![
+            eq(Path::new("baz/qux/apple")),
+            eq(Path::new("baz/qux/blueberry"))
+        ]
+    );
+    fs_operation! { SetValidation, sim, "set1",
+        file "inrepo" "inrepo"
+        dir "baz/qux"
+            file "apple" "pie"
+            file "blueberry" "tart"
+        end
+    };
+
+    // the directory's shortcut maps the files back to where they came from
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    fs_operation! { LocalValidation, sim,
+        dir "foo/bar"
+            file "inrepo" "inrepo"
+        end
+        dir "baz/qux"
+            file "apple" "pie"
+            file "blueberry" "tart"
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn outside_shortcut_existing_dir() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some("foo/bar".into()),
+        ..old
+    });
+
+    // would be moved to baz/ if it got a shortcut
+    fs_operation! { SetManipulation, sim, "set1",
+        dir "baz"
+            file "cherry" "cobbler"
+        end
+    };
+
+    fs_operation! { LocalManipulation, sim,
+        dir "baz"
+            file "apple" "pie"
+        end
+    };
+
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("baz/apple")],
+        Some(SetName("set1".into())),
+        &PutOptions {
+            outside_shortcut: true,
+            ..Default::default()
+        },
+    );
+    expect_that!(
+        put_result.err(),
+        some(pat!(PutError::DirShortcutConflict(..)))
+    );
+    fs_operation! { SetValidation, sim, "set1",
+        dir "baz"
+            file "cherry" "cobbler"
+        end
+    };
+
+    Ok(())
+}
+
 #[gtest]
 fn only_in_pushed_set() -> Result<()> {
     let sim = Simulator::create();
@@ -271,7 +574,8 @@ fn only_in_pushed_set() -> Result<()> {
         &sim.profile()?,
         sim.execution_options(),
         vec![sim.local_path("notinrepo")],
-        SetName("set1".into()),
+        Some(SetName("set1".into())),
+        &Default::default(),
     )?;
 
     expect_that!(put_result.untracked_files, is_empty());
@@ -304,7 +608,8 @@ fn untracked_files() -> Result<()> {
         &sim.profile()?,
         sim.execution_options(),
         vec![sim.local_path("notinrepo")],
-        SetName("set1".into()),
+        Some(SetName("set1".into())),
+        &Default::default(),
     )?;
 
     expect_that!(put_result.untracked_files, { Path::new("notinrepo") });
@@ -334,13 +639,18 @@ fn files_in_later_sets() -> Result<()> {
     };
 
     // so index at this point points to set3
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     let put_result = monja::put(
         &sim.profile()?,
         sim.execution_options(),
         vec![sim.local_path("notinrepo")],
-        SetName("set1".into()),
+        Some(SetName("set1".into())),
+        &Default::default(),
     )?;
 
     expect_that!(put_result.untracked_files, is_empty());
@@ -367,7 +677,7 @@ fn files_in_later_sets() -> Result<()> {
 }
 
 #[gtest]
-fn latest_set_of_many() -> Result<()> {
+fn files_in_later_sets_error() -> Result<()> {
     let sim = Simulator::create();
     sim.configure_profile(|old| MonjaProfileConfig {
         target_sets: set_names(["set1", "set2", "set3"]),
@@ -375,71 +685,726 @@ fn latest_set_of_many() -> Result<()> {
     });
 
     fs_operation! { SetManipulation, sim, "set1",
-        file "notinrepo" "notinrepo"
     };
     fs_operation! { SetManipulation, sim, "set2",
-        file "notinrepo" "notinrepo"
+        file "apple" "pie"
     };
     fs_operation! { SetManipulation, sim, "set3",
     };
-
-    // so index at this point points to set2
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "crumble"
+        file "blueberry" "tart"
+    };
 
     let put_result = monja::put(
         &sim.profile()?,
         sim.execution_options(),
-        vec![sim.local_path("notinrepo")],
-        SetName("set3".into()),
-    )?;
+        vec![sim.local_path("apple"), sim.local_path("blueberry")],
+        Some(SetName("set1".into())),
+        &PutOptions {
+            later_sets: LaterSetsPolicy::Error,
+            ..Default::default()
+        },
+    );
+    expect_that!(
+        put_result,
+        err(pat!(PutError::InLaterSets(elements_are![(
+            eq(Path::new("apple")),
+            elements_are![pat!(SetName("set2"))],
+        )])))
+    );
 
-    expect_that!(put_result.untracked_files, is_empty());
-    expect_that!(put_result.files_in_later_sets, is_empty());
-    fs_operation! { SetValidation, sim, "set3",
-        file "notinrepo" "notinrepo"
+    // nothing gets put, including files that aren't in later sets
+    fs_operation! { SetValidation, sim, "set1",
     };
 
-    // index should be updated from set2 to set3
-    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
-    expect_that!(status.files_to_push, {
-        (
-            pat!(SetName("set3")),
-            unordered_elements_are![eq(Path::new("notinrepo"))],
-        )
-    });
-
     Ok(())
 }
 
 #[gtest]
-fn ignores_ignore_file() -> Result<()> {
+fn files_in_later_sets_promote() -> Result<()> {
     let sim = Simulator::create();
     sim.configure_profile(|old| MonjaProfileConfig {
-        target_sets: set_names(["set1"]),
+        target_sets: set_names(["set1", "set2", "set3", "set4"]),
         ..old
     });
 
     fs_operation! { SetManipulation, sim, "set1",
-        file ".monjaignore" "notinrepo"
     };
-    _ = monja::pull(&sim.profile()?, sim.execution_options())?;
+    fs_operation! { SetManipulation, sim, "set2",
+        file "apple" "pie"
+        file "blueberry" "tart"
+    };
+    fs_operation! { SetManipulation, sim, "set3",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set4",
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { LocalManipulation, sim,
-        file "notinrepo" "notinrepo"
+        file "apple" "crumble"
+        file "blueberry" "muffin"
+        file "cherry" "cobbler"
     };
-
     let put_result = monja::put(
         &sim.profile()?,
         sim.execution_options(),
-        vec![sim.local_path("notinrepo")],
-        SetName("set1".into()),
+        vec![
+            sim.local_path("apple"),
+            sim.local_path("blueberry"),
+            sim.local_path("cherry"),
+        ],
+        Some(SetName("set1".into())),
+        &PutOptions {
+            later_sets: LaterSetsPolicy::Promote,
+            ..Default::default()
+        },
     )?;
-    expect_that!(put_result.files, len(eq(1)));
 
+    expect_that!(
+        put_result.files_promoted,
+        elements_are![
+            (eq(Path::new("apple")), pat!(SetName("set3"))),
+            (eq(Path::new("blueberry")), pat!(SetName("set2"))),
+        ]
+    );
+    expect_that!(put_result.files_in_later_sets, is_empty());
+    expect_that!(
+        put_result.files_by_set,
+        elements_are![
+            (
+                pat!(SetName("set1")),
+                elements_are![eq(Path::new("cherry"))]
+            ),
+            (pat!(SetName("set3")), elements_are![eq(Path::new("apple"))]),
+            (
+                pat!(SetName("set2")),
+                elements_are![eq(Path::new("blueberry"))]
+            ),
+        ]
+    );
     fs_operation! { SetValidation, sim, "set1",
-        file "notinrepo" "notinrepo"
-        file ".monjaignore" "notinrepo"
+        file "cherry" "cobbler"
+    };
+    fs_operation! { SetValidation, sim, "set2",
+        file "apple" "pie"
+        file "blueberry" "muffin"
     };
+    fs_operation! { SetValidation, sim, "set3",
+        file "apple" "crumble"
+    };
+
+    // the index follows the promoted files, since their sets are the ones that win
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.files_to_push, {
+        (
+            pat!(SetName("set1")),
+            unordered_elements_are![eq(Path::new("cherry"))],
+        ),
+        (
+            pat!(SetName("set2")),
+            unordered_elements_are![eq(Path::new("blueberry"))],
+        ),
+        (
+            pat!(SetName("set3")),
+            unordered_elements_are![eq(Path::new("apple"))],
+        )
+    });
+
+    Ok(())
+}
+
+#[gtest]
+fn latest_set_of_many() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2", "set3"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "notinrepo" "notinrepo"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "notinrepo" "notinrepo"
+    };
+    fs_operation! { SetManipulation, sim, "set3",
+    };
+
+    // so index at this point points to set2
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("notinrepo")],
+        Some(SetName("set3".into())),
+        &Default::default(),
+    )?;
+
+    expect_that!(put_result.untracked_files, is_empty());
+    expect_that!(put_result.files_in_later_sets, is_empty());
+    fs_operation! { SetValidation, sim, "set3",
+        file "notinrepo" "notinrepo"
+    };
+
+    // index should be updated from set2 to set3
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.files_to_push, {
+        (
+            pat!(SetName("set3")),
+            unordered_elements_are![eq(Path::new("notinrepo"))],
+        )
+    });
+
+    Ok(())
+}
+
+#[gtest]
+fn ignores_ignore_file() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file ".monjaignore" "notinrepo"
+    };
+    _ = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "notinrepo" "notinrepo"
+    };
+
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("notinrepo")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+    expect_that!(put_result.files, len(eq(1)));
+
+    fs_operation! { SetValidation, sim, "set1",
+        file "notinrepo" "notinrepo"
+        file ".monjaignore" "notinrepo"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn set_config_not_put() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".config/app".into()),
+        ..old
+    });
+    let set_config_path = sim.repo_root().join("set1/.monja-set.toml");
+    let set_config = fs::read_to_string(&set_config_path)?;
+
+    // would end up at the set's root, replacing its config
+    fs_operation! { LocalManipulation, sim,
+        dir ".config/app"
+            file ".monja-set.toml" "shortcut = 'elsewhere'"
+            file "init" "init"
+        end
+    };
+
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path(".config/app/.monja-set.toml")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    );
+    expect_that!(
+        put_result,
+        err(pat!(PutError::SpecialFiles(elements_are![eq(Path::new(
+            ".config/app/.monja-set.toml"
+        ))])))
+    );
+
+    // and directories leave it out
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path(".config/app")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+    expect_that!(put_result.files, { eq(Path::new(".config/app/init")) });
+    expect_that!(fs::read_to_string(&set_config_path)?, eq(&set_config));
+
+    // nor is it something to push
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.untracked_files, is_empty());
+
+    Ok(())
+}
+
+#[gtest]
+fn directory() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+    };
+
+    fs_operation! { LocalManipulation, sim,
+        dir ".config"
+            dir "nvim"
+                file "init.lua" "init"
+                dir "lua"
+                    file "plugins.lua" "plugins"
+                end
+                // put ignores .monjaignore files, so init.lua still gets put
+                file ".monjaignore" "init.lua"
+            end
+            file "other" "other"
+        end
+    };
+
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path(".config/nvim")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+
+    expect_that!(
+        put_result.files,
+        {
+            eq(Path::new(".config/nvim/.monjaignore")),
+            eq(Path::new(".config/nvim/init.lua")),
+            eq(Path::new(".config/nvim/lua/plugins.lua"))
+        }
+    );
+    fs_operation! { SetValidation, sim, "set1",
+        dir "nvim"
+            file ".monjaignore" "init.lua"
+            file "init.lua" "init"
+            dir "lua"
+                file "plugins.lua" "plugins"
+            end
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn local_root_directory() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+    };
+
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "pie"
+    };
+
+    // the repo and data dir are both under the local root in tests, and neither should get put
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+
+    expect_that!(put_result.files, { eq(Path::new("apple")) });
+
+    Ok(())
+}
+
+#[gtest]
+fn executable_modes() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        permissions: BTreeMap::from([("bin/private".into(), "700".into())]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+    };
+    fs_operation! { LocalManipulation, sim,
+        dir "bin"
+            file "script" "echo hi"
+            file "private" "echo secret"
+            file "notes" "not a script"
+        end
+    };
+    for (file, mode) in [("script", 0o755), ("private", 0o755), ("notes", 0o644)] {
+        let path = sim.local_root().join("bin").join(file);
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("bin")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+    // the existing entry is left as-is
+    expect_that!(
+        put_result.modes_recorded,
+        elements_are![eq(&sim.local_path("bin/script"))]
+    );
+    expect_that!(
+        SetConfig::load(&sim.profile()?, &SetName("set1".into()))?.permissions,
+        eq(&BTreeMap::from([
+            ("bin/private".into(), "700".into()),
+            ("bin/script".into(), "755".into()),
+        ]))
+    );
+
+    // like a checkout with core.fileMode turned off
+    let repo_script = sim.repo_root().join("set1/bin/script");
+    fs::set_permissions(&repo_script, fs::Permissions::from_mode(0o644))?;
+    fs::remove_file(sim.local_root().join("bin/script"))?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        fs::metadata(sim.local_root().join("bin/script"))?
+            .permissions()
+            .mode()
+            & 0o7777,
+        eq(0o755)
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn nested_shortcut() -> Result<()> {
+    // the ones with extra slashes and dots are easy to end up with when editing .monja-set.toml by hand
+    for shortcut in [
+        ".config/myconfig",
+        ".config/myconfig/",
+        "./.config//myconfig",
+    ] {
+        let sim = Simulator::create();
+        sim.configure_profile(|old| MonjaProfileConfig {
+            target_sets: set_names(["set1"]),
+            ..old
+        })
+        .configure_set(SetName("set1".into()), |old| SetConfig {
+            shortcut: Some(shortcut.into()),
+            ..old
+        });
+
+        fs_operation! { SetManipulation, sim, "set1",
+        };
+
+        fs_operation! { LocalManipulation, sim,
+            dir ".config/myconfig"
+                file "top" "top"
+                dir "sub"
+                    file "file" "file"
+                    dir "deeper"
+                        file "file" "deeper"
+                    end
+                end
+            end
+        };
+
+        let put_result = monja::put(
+            &sim.profile()?,
+            sim.execution_options(),
+            vec![
+                sim.local_path(".config/myconfig/top"),
+                sim.local_path(".config/myconfig/sub/file"),
+                sim.local_path(".config/myconfig/sub/deeper/file"),
+            ],
+            Some(SetName("set1".into())),
+            &Default::default(),
+        )?;
+
+        expect_that!(
+            put_result.files,
+            unordered_elements_are![
+                eq(Path::new(".config/myconfig/top")),
+                eq(Path::new(".config/myconfig/sub/file")),
+                eq(Path::new(".config/myconfig/sub/deeper/file")),
+            ],
+            "shortcut: {shortcut}"
+        );
+        fs_operation! { SetValidation, sim, "set1",
+            file "top" "top"
+            dir "sub"
+                file "file" "file"
+                dir "deeper"
+                    file "file" "deeper"
+                end
+            end
+        };
+
+        // and they come back to the same place
+        fs::remove_dir_all(sim.local_root().join(".config"))?;
+        let _pull_result = monja::pull(
+            &sim.profile()?,
+            sim.execution_options(),
+            &Default::default(),
+        )?;
+        fs_operation! { LocalValidation, sim,
+            dir ".config/myconfig"
+                file "top" "top"
+                dir "sub"
+                    file "file" "file"
+                    dir "deeper"
+                        file "file" "deeper"
+                    end
+                end
+            end
+        };
+    }
+
+    Ok(())
+}
+
+#[gtest]
+fn nested_shortcut_sibling_with_same_prefix() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".config/myconfig".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+    };
+
+    // only shares a prefix as a string, so it's outside of the shortcut
+    fs_operation! { LocalManipulation, sim,
+        dir ".config/myconfig2"
+            file "file" "file"
+        end
+    };
+
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path(".config/myconfig2/file")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    );
+    expect_that!(put_result, err(pat!(PutError::SetPath(..))));
+
+    Ok(())
+}
+
+#[gtest]
+fn files_in_later_sets_sorted() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2", "set3", "set4"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "c" "c"
+        file "a" "a"
+        file "b" "b"
+    };
+    fs_operation! { SetManipulation, sim, "set3",
+        file "b" "b"
+        file "c" "c"
+        file "a" "a"
+    };
+    fs_operation! { SetManipulation, sim, "set4",
+        file "a" "a"
+        file "b" "b"
+        file "c" "c"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![
+            sim.local_path("c"),
+            sim.local_path("a"),
+            sim.local_path("b"),
+        ],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+
+    let later_sets = || {
+        elements_are![
+            pat!(SetName("set2")),
+            pat!(SetName("set3")),
+            pat!(SetName("set4"))
+        ]
+    };
+    expect_that!(
+        put_result.files_in_later_sets,
+        elements_are![
+            (eq(Path::new("a")), later_sets()),
+            (eq(Path::new("b")), later_sets()),
+            (eq(Path::new("c")), later_sets()),
+        ]
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn backup() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        dir "foo"
+            file "bar" "original"
+        end
+    };
+    fs_operation! { LocalManipulation, sim,
+        dir "foo"
+            file "bar" "changed"
+        end
+        file "new" "new"
+    };
+
+    sim.backup(true);
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("foo/bar"), sim.local_path("new")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+
+    expect_that!(
+        put_result.overwritten,
+        unordered_elements_are![
+            (eq(Path::new("foo/bar")), eq(&true)),
+            (eq(Path::new("new")), eq(&false)),
+        ]
+    );
+    expect_that!(put_result.files_backed_up, { eq(Path::new("foo/bar")) });
+    let backup_dir = put_result.backup_dir.expect("Something was backed up.");
+    expect_that!(backup_dir.starts_with(sim.data_root()), is_true());
+    expect_that!(
+        fs::read_to_string(backup_dir.join("set1/foo/bar"))?,
+        eq("original")
+    );
+    fs_operation! { SetValidation, sim, "set1",
+        dir "foo"
+            file "bar" "changed"
+        end
+        file "new" "new"
+    };
+
+    // without --backup, we still find out what was replaced
+    sim.backup(false);
+    let put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("foo/bar")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+    expect_that!(put_result.overwritten, {
+        (eq(Path::new("foo/bar")), eq(&true))
+    });
+    expect_that!(put_result.files_backed_up, is_empty());
+    expect_that!(put_result.backup_dir, none());
+
+    Ok(())
+}
+
+// as with pulling, without the acl package, putting into a set that preserves ACLs says so
+#[gtest]
+fn acls_recorded() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        preserve_acls: Some(true),
+        // no longer there locally, so it's dropped
+        acls: BTreeMap::from([("shared".into(), "group:root:rw-,mask::rw-".into())]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "shared" "config"
+    };
+
+    let result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("shared")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    );
+    if Command::new("getfacl").arg("--version").output().is_err() {
+        expect_that!(
+            result,
+            err(pat!(PutError::Acl(pat!(AclError::Missing(eq(&"getfacl"))))))
+        );
+        return Ok(());
+    }
+    result?;
+    expect_that!(
+        SetConfig::load(&sim.profile()?, &SetName("set1".into()))?.acls,
+        is_empty()
+    );
 
     Ok(())
 }
@@ -0,0 +1,277 @@
+use std::path::Path;
+
+use googletest::prelude::*;
+use monja::{AbsolutePath, MonjaProfileConfig, RemoveSetError, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn basic() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "foo" "set1"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "bar" "set2"
+    };
+
+    let result = monja::remove_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        SetName("set1".into()),
+        false,
+    )?;
+    expect_that!(result.set_name, pat!(SetName("set1")));
+    expect_that!(result.removed_from_profile, is_true());
+    expect_that!(result.orphaned_files, is_empty());
+
+    expect_that!(sim.repo_root().join("set1").exists(), is_false());
+    expect_that!(
+        sim.profile()?.config.target_sets,
+        container_eq(set_names(["set2"]))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn untargeted() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "foo" "set1"
+    };
+
+    let result = monja::remove_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        SetName("set1".into()),
+        false,
+    )?;
+    expect_that!(result.removed_from_profile, is_false());
+
+    expect_that!(sim.repo_root().join("set1").exists(), is_false());
+    expect_that!(
+        sim.profile()?.config.target_sets,
+        container_eq(set_names(["set2"]))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn glob_targeted() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["work-*"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "work-a",
+        file "foo" "work-a"
+    };
+    fs_operation! { SetManipulation, sim, "work-b",
+        file "bar" "work-b"
+    };
+
+    let result = monja::remove_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        SetName("work-a".into()),
+        false,
+    )?;
+    // the glob has no entry of its own to remove, and it simply stops matching the set
+    expect_that!(result.removed_from_profile, is_false());
+
+    expect_that!(sim.repo_root().join("work-a").exists(), is_false());
+    expect_that!(
+        MonjaProfileConfig::load(&AbsolutePath::for_existing_path(sim.profile_path())?)?
+            .target_sets,
+        container_eq(set_names(["work-*"]))
+    );
+    expect_that!(
+        sim.profile()?.config.target_sets,
+        container_eq(set_names(["work-b"]))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn in_use() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "foo" "set1"
+    };
+    let _pull = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let result = monja::remove_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        SetName("set1".into()),
+        false,
+    );
+    expect_that!(
+        result,
+        err(pat!(RemoveSetError::SetInUse {
+            set_name: pat!(SetName("set1")),
+            files: elements_are![eq(Path::new("foo"))],
+        }))
+    );
+    expect_that!(sim.repo_root().join("set1").exists(), is_true());
+    expect_that!(
+        sim.profile()?.config.target_sets,
+        container_eq(set_names(["set1"]))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn in_use_forced() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "foo" "set1"
+    };
+    let _pull = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let result = monja::remove_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        SetName("set1".into()),
+        true,
+    )?;
+    expect_that!(result.orphaned_files, elements_are![eq(Path::new("foo"))]);
+    expect_that!(sim.repo_root().join("set1").exists(), is_false());
+    expect_that!(sim.profile()?.config.target_sets, is_empty());
+
+    // the local file is left alone
+    fs_operation! { LocalValidation, sim,
+        file "foo" "set1"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn missing_set() -> Result<()> {
+    let sim = Simulator::create();
+
+    let result = monja::remove_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        SetName("set1".into()),
+        false,
+    );
+    expect_that!(
+        result,
+        err(pat!(RemoveSetError::SetNotFound(pat!(SetName("set1")))))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn profile_failure_keeps_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "foo" "set1"
+    };
+
+    // a directory can't be loaded as a profile, so updating it fails
+    let bad_profile_path = sim.local_root().join("notaprofile");
+    std::fs::create_dir(&bad_profile_path)?;
+    let result = monja::remove_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(&bad_profile_path)?,
+        SetName("set1".into()),
+        false,
+    );
+    expect_that!(
+        result,
+        err(pat!(RemoveSetError::ProfileModification(anything())))
+    );
+
+    // since the profile still targets it, the set is left alone
+    fs_operation! { SetValidation, sim, "set1",
+        file "foo" "set1"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn dry_run() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.dryrun(true);
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "foo" "set1"
+    };
+
+    let result = monja::remove_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        SetName("set1".into()),
+        false,
+    )?;
+    expect_that!(result.removed_from_profile, is_true());
+
+    fs_operation! { SetValidation, sim, "set1",
+        file "foo" "set1"
+    };
+    expect_that!(
+        sim.profile()?.config.target_sets,
+        container_eq(set_names(["set1"]))
+    );
+
+    Ok(())
+}
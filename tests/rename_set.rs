@@ -0,0 +1,247 @@
+use std::path::Path;
+
+use googletest::prelude::*;
+use monja::{AbsolutePath, MonjaProfileConfig, RenameSetError, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn basic() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "other"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "foo" "set1"
+    };
+    fs_operation! { SetManipulation, sim, "other",
+        file "bar" "other"
+    };
+    let _pull = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let result = monja::rename_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        SetName("set1".into()),
+        SetName("renamed".into()),
+    )?;
+    expect_that!(result.old_name, pat!(SetName("set1")));
+    expect_that!(result.new_name, pat!(SetName("renamed")));
+    expect_that!(result.profile_updated, is_true());
+    expect_that!(result.files_reindexed, elements_are![eq(Path::new("foo"))]);
+
+    expect_that!(sim.repo_root().join("set1").exists(), is_false());
+    fs_operation! { SetValidation, sim, "renamed",
+        file "foo" "set1"
+    };
+    expect_that!(
+        sim.profile()?.config.target_sets,
+        container_eq(set_names(["renamed", "other"]))
+    );
+
+    // the index should be usable without another pull
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.files_with_missing_sets, is_empty());
+    expect_that!(status.files_to_push, {
+        (pat!(SetName("renamed")), elements_are![eq(Path::new("foo"))]),
+        (pat!(SetName("other")), elements_are![eq(Path::new("bar"))])
+    });
+
+    Ok(())
+}
+
+#[gtest]
+fn glob_targeted() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["work-*"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "work-a",
+        file "foo" "work-a"
+    };
+    fs_operation! { SetManipulation, sim, "work-b",
+        file "bar" "work-b"
+    };
+    let profile_path = AbsolutePath::for_existing_path(sim.profile_path())?;
+
+    // the glob still matches the new name, so nothing changes as far as the profile goes
+    let result = monja::rename_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &profile_path,
+        SetName("work-a".into()),
+        SetName("work-c".into()),
+    )?;
+    expect_that!(result.profile_updated, is_false());
+    expect_that!(result.no_longer_targeted, is_false());
+
+    // but here it doesn't, so the set drops out of target-sets
+    let result = monja::rename_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &profile_path,
+        SetName("work-c".into()),
+        SetName("home-a".into()),
+    )?;
+    expect_that!(result.profile_updated, is_false());
+    expect_that!(result.no_longer_targeted, is_true());
+
+    expect_that!(
+        MonjaProfileConfig::load(&profile_path)?.target_sets,
+        container_eq(set_names(["work-*"]))
+    );
+    expect_that!(
+        sim.profile()?.config.target_sets,
+        container_eq(set_names(["work-b"]))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn new_name_exists() -> Result<()> {
+    let sim = Simulator::create();
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "foo" "set1"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "foo" "set2"
+    };
+
+    let result = monja::rename_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        SetName("set1".into()),
+        SetName("set2".into()),
+    );
+    expect_that!(
+        result,
+        err(pat!(RenameSetError::SetExists(pat!(SetName("set2")))))
+    );
+
+    fs_operation! { SetValidation, sim, "set1",
+        file "foo" "set1"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn rename_failure_keeps_profile_and_index() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "foo" "set1"
+    };
+    let _pull = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // a dangling symlink doesn't count as an existing set, but a directory can't be renamed over it
+    std::os::unix::fs::symlink("nowhere", sim.repo_root().join("renamed"))?;
+    let result = monja::rename_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        SetName("set1".into()),
+        SetName("renamed".into()),
+    );
+    expect_that!(result, err(pat!(RenameSetError::RenameDir { .. })));
+
+    // the profile and index were already written by then, and got put back
+    fs_operation! { SetValidation, sim, "set1",
+        file "foo" "set1"
+    };
+    expect_that!(
+        sim.profile()?.config.target_sets,
+        container_eq(set_names(["set1"]))
+    );
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.files_with_missing_sets, is_empty());
+    expect_that!(status.files_to_push, {
+        (pat!(SetName("set1")), elements_are![eq(Path::new("foo"))])
+    });
+
+    Ok(())
+}
+
+#[gtest]
+fn missing_set() -> Result<()> {
+    let sim = Simulator::create();
+
+    let result = monja::rename_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        SetName("set1".into()),
+        SetName("set2".into()),
+    );
+    expect_that!(
+        result,
+        err(pat!(RenameSetError::SetNotFound(pat!(SetName("set1")))))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn dry_run() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "foo" "set1"
+    };
+    let _pull = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    sim.dryrun(true);
+    let result = monja::rename_set(
+        &sim.profile()?,
+        sim.execution_options(),
+        &AbsolutePath::for_existing_path(sim.profile_path())?,
+        SetName("set1".into()),
+        SetName("renamed".into()),
+    )?;
+    expect_that!(result.profile_updated, is_true());
+    expect_that!(result.files_reindexed, elements_are![eq(Path::new("foo"))]);
+
+    expect_that!(sim.repo_root().join("renamed").exists(), is_false());
+    expect_that!(
+        sim.profile()?.config.target_sets,
+        container_eq(set_names(["set1"]))
+    );
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.files_to_push, {
+        (pat!(SetName("set1")), elements_are![eq(Path::new("foo"))])
+    });
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use std::{fs, path::Path};
+
+use googletest::prelude::*;
+use monja::{MonjaProfileConfig, RestoreError, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn restore_from_overridden_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "blueberry" "tart"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "apple" "fritter"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "blueberry" "muffin"
+    };
+
+    let result = monja::restore(
+        &sim.profile()?,
+        sim.execution_options(),
+        SetName("set1".into()),
+        vec![sim.local_path("apple")],
+    )?;
+
+    expect_that!(result.set_name, pat!(SetName("set1")));
+    expect_that!(result.files_restored, { eq(Path::new("apple")) });
+
+    fs_operation! { LocalValidation, sim,
+        file "apple" "pie"
+        // only requested files get restored
+        file "blueberry" "muffin"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn files_not_in_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tart"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "crumble"
+    };
+
+    let result = monja::restore(
+        &sim.profile()?,
+        sim.execution_options(),
+        SetName("set1".into()),
+        vec![sim.local_path("apple"), sim.local_path("blueberry")],
+    );
+
+    expect_that!(
+        result,
+        err(pat!(RestoreError::NotInSet {
+            set_name: pat!(SetName("set1")),
+            files: elements_are![eq(Path::new("blueberry"))],
+        }))
+    );
+
+    // nothing is restored if any file is missing
+    fs_operation! { LocalValidation, sim,
+        file "apple" "crumble"
+        file "blueberry" "tart"
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn missing_set() -> Result<()> {
+    let sim = Simulator::create();
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    let result = monja::restore(
+        &sim.profile()?,
+        sim.execution_options(),
+        SetName("set2".into()),
+        vec![sim.local_path("apple")],
+    );
+
+    expect_that!(
+        result,
+        err(pat!(RestoreError::SetNotFound(pat!(SetName("set2")))))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn index_untouched() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "apple" "fritter"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    let index_path = sim.data_root().join("monja-index.toml");
+    let index_before = fs::read_to_string(&index_path)?;
+
+    let _result = monja::restore(
+        &sim.profile()?,
+        sim.execution_options(),
+        SetName("set1".into()),
+        vec![sim.local_path("apple")],
+    )?;
+
+    expect_that!(fs::read_to_string(&index_path)?, eq(&index_before));
+
+    Ok(())
+}
+
+#[gtest]
+fn dryrun() -> Result<()> {
+    let mut sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "apple" "fritter"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    sim.dryrun(true);
+    let result = monja::restore(
+        &sim.profile()?,
+        sim.execution_options(),
+        SetName("set1".into()),
+        vec![sim.local_path("apple")],
+    )?;
+
+    expect_that!(result.files_restored, { eq(Path::new("apple")) });
+    fs_operation! { LocalValidation, sim,
+        file "apple" "fritter"
+    };
+
+    Ok(())
+}
@@ -0,0 +1,230 @@
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Mutex, Once},
+};
+
+use googletest::prelude::*;
+use monja::{MonjaProfileConfig, ProgressCallback, PullOptions, RsyncStats, SetConfig, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+// every test in this file runs without rsync, so it's kept separate from the other tests.
+// the env is only modified once, before any test in this process spawns anything.
+fn without_rsync() {
+    static HIDE_RSYNC: Once = Once::new();
+    HIDE_RSYNC.call_once(|| {
+        // SAFETY: all tests go through here before reading the env, and call_once blocks until this completes
+        unsafe { std::env::set_var("PATH", "/nonexistent") };
+    });
+}
+
+#[gtest]
+fn pull_and_push() -> Result<()> {
+    without_rsync();
+
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+    sim.configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        dir "nested"
+            file "blueberry" "tart"
+        end
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalValidation, sim,
+        dir ".config"
+            file "apple" "pie"
+            dir "nested"
+                file "blueberry" "tart"
+            end
+        end
+    };
+    // mtime is preserved, as with rsync -a
+    expect_that!(
+        fs::metadata(sim.local_root().join(".config/apple"))?.modified()?,
+        eq(fs::metadata(sim.repo_root().join("set1/apple"))?.modified()?)
+    );
+
+    fs_operation! { LocalManipulation, sim,
+        dir ".config"
+            file "apple" "crumble"
+        end
+    };
+
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { SetValidation, sim, "set1",
+        file "apple" "crumble"
+        dir "nested"
+            file "blueberry" "tart"
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn symlinks() -> Result<()> {
+    without_rsync();
+
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "gitconfig" "old"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        dir "synced"
+            file "gitconfig" "new"
+        end
+        remfile "gitconfig"
+    };
+    std::os::unix::fs::symlink("synced/gitconfig", sim.local_root().join("gitconfig"))?;
+
+    // without preserve_symlinks, the link's target gets copied
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let repo_file = sim.repo_root().join("set1/gitconfig");
+    expect_that!(fs::symlink_metadata(&repo_file)?.is_symlink(), eq(false));
+    expect_that!(fs::read_to_string(&repo_file)?, eq("new"));
+
+    // and links in the set come back as links
+    fs::remove_file(&repo_file)?;
+    std::os::unix::fs::symlink("synced/gitconfig", &repo_file)?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        fs::read_link(sim.local_root().join("gitconfig"))?,
+        eq(Path::new("synced/gitconfig"))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn progress() -> Result<()> {
+    without_rsync();
+
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+    sim.configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        dir "nested"
+            file "blueberry" "tarts"
+        end
+    };
+
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let pull_opts = || PullOptions {
+        progress: Some(ProgressCallback(Box::new({
+            let reported = reported.clone();
+            move |file, bytes| {
+                let mut reported = reported.lock().expect("Not poisoned.");
+                reported.push((file.to_path_buf(), bytes));
+            }
+        }))),
+        ..Default::default()
+    };
+
+    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options(), &pull_opts())?;
+    expect_that!(
+        *reported.lock().expect("Not poisoned."),
+        unordered_elements_are![
+            (eq(Path::new(".config/apple")), eq(&3)),
+            (eq(Path::new(".config/nested/blueberry")), eq(&5)),
+        ]
+    );
+
+    // same as rsync, files that didn't change aren't reported
+    reported.lock().expect("Not poisoned.").clear();
+    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options(), &pull_opts())?;
+    expect_that!(*reported.lock().expect("Not poisoned."), is_empty());
+
+    Ok(())
+}
+
+#[gtest]
+fn transfer_stats() -> Result<()> {
+    without_rsync();
+
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "blueberry" "tarts"
+    };
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        result.transfer_stats,
+        eq(RsyncStats {
+            files_transferred: 2,
+            bytes_transferred: Some(8),
+        })
+    );
+
+    let result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(result.transfer_stats, eq(RsyncStats::default()));
+
+    Ok(())
+}
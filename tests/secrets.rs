@@ -0,0 +1,223 @@
+use std::{collections::BTreeMap, fs, os::unix::fs::PermissionsExt, path::Path, sync::Once};
+
+use googletest::prelude::*;
+use monja::{MonjaProfileConfig, SECRET_KEY_VAR, SetConfig, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+// every test in this file uses the same key, so it's kept separate from the other tests.
+// the env is only modified once, before any test in this process reads it.
+fn with_key() {
+    static SET_KEY: Once = Once::new();
+    SET_KEY.call_once(|| {
+        // SAFETY: all tests go through here before reading the env, and call_once blocks until this completes
+        unsafe {
+            std::env::set_var(
+                SECRET_KEY_VAR,
+                "AGE-SECRET-KEY-180FJQKX8WLNYAPVK4S4KRAX0F2EG9P0A8QSCZTCM0GRZECRED83STZ2KVT",
+            )
+        };
+    });
+}
+
+#[gtest]
+fn put_push_pull() -> Result<()> {
+    with_key();
+
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        secrets: vec!["tokens/*".into()],
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        dir ".config/tokens"
+            file "github" "hunter2"
+        end
+    };
+    let _put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path(".config/tokens/github")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+
+    // only the encrypted file is in the set
+    let repo_secret = sim.repo_root().join("set1/tokens/github.age");
+    expect_that!(fs::read(&repo_secret)?, not(eq(b"hunter2")));
+    expect_that!(
+        sim.repo_root().join("set1/tokens/github").exists(),
+        is_false()
+    );
+
+    fs_operation! { LocalManipulation, sim,
+        dir ".config/tokens"
+            file "github" "hunter3"
+        end
+    };
+    let push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(push_result.files_modified, {
+        (
+            pat!(SetName("set1")),
+            unordered_elements_are![eq(Path::new(".config/tokens/github"))],
+        )
+    });
+
+    // unchanged secrets aren't encrypted again, which would change the file in the repo anyway
+    let encrypted = fs::read(&repo_secret)?;
+    let _push_result = monja::push(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(fs::read(&repo_secret)?, eq(&encrypted));
+
+    fs::remove_file(sim.local_root().join(".config/tokens/github"))?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    fs_operation! { LocalValidation, sim,
+        dir ".config"
+            file "apple" "pie"
+            dir "tokens"
+                file "github" "hunter3"
+            end
+        end
+    };
+
+    Ok(())
+}
+
+#[gtest]
+fn status_and_verify() -> Result<()> {
+    with_key();
+
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        secrets: vec!["token".into()],
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "token" "hunter2"
+    };
+    let _put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("token")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+
+    // compared with the decrypted contents, not the encrypted file
+    let report = monja::verify(&sim.profile()?, None)?;
+    expect_that!(report.is_in_sync(), is_true());
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.modified_files, is_empty());
+
+    fs_operation! { LocalManipulation, sim,
+        file "token" "hunter3"
+    };
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.modified_files, {
+        (
+            pat!(SetName("set1")),
+            unordered_elements_are![eq(Path::new("token"))],
+        )
+    });
+    expect_that!(status.conflicted_files, is_empty());
+
+    Ok(())
+}
+
+#[gtest]
+fn pulled_secret_private() -> Result<()> {
+    with_key();
+
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        secrets: vec!["token".into()],
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "token" "hunter2"
+    };
+    let _put_result = monja::put(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("token")],
+        Some(SetName("set1".into())),
+        &Default::default(),
+    )?;
+
+    // like a fresh clone would have it
+    let repo_secret = sim.repo_root().join("set1/token.age");
+    fs::set_permissions(&repo_secret, fs::Permissions::from_mode(0o644))?;
+    let local_secret = sim.local_root().join("token");
+    fs::remove_file(&local_secret)?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        fs::metadata(&local_secret)?.permissions().mode() & 0o777,
+        eq(0o600)
+    );
+
+    // only the set's config can widen them
+    sim.configure_set(SetName("set1".into()), |old| SetConfig {
+        permissions: BTreeMap::from([("token.age".into(), "640".into())]),
+        ..old
+    });
+    fs::remove_file(&local_secret)?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    expect_that!(
+        fs::metadata(&local_secret)?.permissions().mode() & 0o777,
+        eq(0o640)
+    );
+
+    Ok(())
+}
@@ -23,11 +23,16 @@ fn basic_change() -> Result<()> {
             file "baz.conf" "world"
         end
     };
-    sim.configure_set(SetName("myset".into()), |_| SetConfig {
+    sim.configure_set(SetName("myset".into()), |old| SetConfig {
         shortcut: Some(".config".into()),
+        ..old
     });
 
-    let _pull = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     let result = monja::set_shortcut(
         &sim.profile()?,
@@ -67,11 +72,16 @@ fn widen_shortcut() -> Result<()> {
         file "bar.conf" "hello"
         file "baz.conf" "world"
     };
-    sim.configure_set(SetName("myset".into()), |_| SetConfig {
+    sim.configure_set(SetName("myset".into()), |old| SetConfig {
         shortcut: Some(".config/foo".into()),
+        ..old
     });
 
-    let _pull = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     let result = monja::set_shortcut(
         &sim.profile()?,
@@ -101,11 +111,16 @@ fn remove_shortcut() -> Result<()> {
     fs_operation! { SetManipulation, sim, "myset",
         file "bar.conf" "hello"
     };
-    sim.configure_set(SetName("myset".into()), |_| SetConfig {
+    sim.configure_set(SetName("myset".into()), |old| SetConfig {
         shortcut: Some(".config".into()),
+        ..old
     });
 
-    let _pull = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     // remove shortcut entirely
     let result = monja::set_shortcut(
@@ -146,7 +161,11 @@ fn file_outside_new_shortcut() -> Result<()> {
     };
     // no shortcut, so these are stored as-is
 
-    let _pull = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     // try to set shortcut to .config — .local/bar.conf can't fit
     let result = monja::set_shortcut(
@@ -207,11 +226,16 @@ fn dryrun() -> Result<()> {
             file "bar.conf" "hello"
         end
     };
-    sim.configure_set(SetName("myset".into()), |_| SetConfig {
+    sim.configure_set(SetName("myset".into()), |old| SetConfig {
         shortcut: Some(".config".into()),
+        ..old
     });
 
-    let _pull = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     sim.dryrun(true);
     let result = monja::set_shortcut(
@@ -247,11 +271,16 @@ fn same_shortcut_noop() -> Result<()> {
     fs_operation! { SetManipulation, sim, "myset",
         file "foo.conf" "hello"
     };
-    sim.configure_set(SetName("myset".into()), |_| SetConfig {
+    sim.configure_set(SetName("myset".into()), |old| SetConfig {
         shortcut: Some(".config".into()),
+        ..old
     });
 
-    let _pull = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     let result = monja::set_shortcut(
         &sim.profile()?,
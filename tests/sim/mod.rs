@@ -1,5 +1,5 @@
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     fs,
     path::{Path, PathBuf},
 };
@@ -8,7 +8,7 @@ use googletest::prelude::*;
 use tempfile::TempDir;
 
 use monja::{
-    AbsolutePath, ExecutionOptions, LocalFilePath, MonjaProfile, MonjaProfileConfig,
+    AbsolutePath, ExecutionOptions, FileLocation, LocalFilePath, MonjaProfile, MonjaProfileConfig,
     MonjaProfileConfigError, SetConfig, SetName,
 };
 use walkdir::WalkDir;
@@ -44,6 +44,15 @@ impl Simulator {
         let profile_config = MonjaProfileConfig {
             repo_dir: repo_dir.path().to_path_buf(),
             target_sets: Vec::new(),
+            preserve_symlinks: false,
+            clean_threshold: None,
+            rsync_extra_args: Vec::new(),
+            hooks: Default::default(),
+            reserved_names: Vec::new(),
+            excludes: Vec::new(),
+            vars: BTreeMap::new(),
+            case_insensitive_check: false,
+            index_dir: None,
         };
 
         let profile_path = local_dir.path().join("monja-profile.toml");
@@ -62,8 +71,11 @@ impl Simulator {
             profile_path,
             opts: ExecutionOptions {
                 verbosity: 0,
+                quiet: false,
                 dry_run: false,
                 skip_confirmations: true,
+                backup: false,
+                force: false,
             },
         }
     }
@@ -108,7 +120,7 @@ impl Simulator {
         let local_root = AbsolutePath::for_existing_path(self.local_root.path()).unwrap();
         let data_root = AbsolutePath::for_existing_path(self.data_root.path()).unwrap();
 
-        // NOTE: MonjaProfile::from_config just gives an io::Error, but that's getting into'd into a MonjaProfileConfigError
+        // NOTE: MonjaProfile::from_config gives a MonjaProfileError, but that's getting into'd into a MonjaProfileConfigError
         // which works fine for our case, but don't be misled!
         MonjaProfile::from_config(
             MonjaProfileConfig::load(&self.profile_path)?,
@@ -128,6 +140,18 @@ impl Simulator {
         self
     }
 
+    pub(crate) fn skip_confirmations(&mut self, skip_confirmations: bool) -> &mut Self {
+        self.opts.skip_confirmations = skip_confirmations;
+
+        self
+    }
+
+    pub(crate) fn backup(&mut self, backup: bool) -> &mut Self {
+        self.opts.backup = backup;
+
+        self
+    }
+
     pub(crate) fn configure_profile<P>(&self, mut config: P) -> &Self
     where
         P: FnMut(MonjaProfileConfig) -> MonjaProfileConfig,
@@ -233,7 +257,11 @@ impl OperationHandler for LocalValidation {
             .map(|e| e.unwrap())
             .filter(|e| e.file_type().is_file())
             .map(|e| e.into_path())
-            .filter(|p| !monja::is_monja_special_file(p))
+            .filter(|p| {
+                !monja::is_monja_special_file(FileLocation::Local(
+                    p.strip_prefix(&self.local_root).unwrap(),
+                ))
+            })
             .filter(|p| !p.starts_with(&self.repo_root))
             .collect();
 
@@ -278,7 +306,11 @@ impl OperationHandler for SetValidation {
             .map(|e| e.unwrap())
             .filter(|e| e.file_type().is_file())
             .map(|e| e.into_path())
-            .filter(|p| !monja::is_monja_special_file(p))
+            .filter(|p| {
+                !monja::is_monja_special_file(FileLocation::InSet(
+                    p.strip_prefix(&self.set_root).unwrap(),
+                ))
+            })
             .collect();
 
         expect_that!(repo_files, container_eq(self.general_validation.files));
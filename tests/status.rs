@@ -1,8 +1,11 @@
-use std::path::Path;
+use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt, path::Path};
 
 use googletest::prelude::*;
 
-use monja::{MonjaProfileConfig, SetName};
+use monja::{
+    AbsolutePath, FileStatus, LocalFilePath, LocalStateInitializationError, MonjaProfile,
+    MonjaProfileConfig, SetConfig, SetName, StatusError, StatusSummary,
+};
 
 use crate::sim::{Simulator, set_names};
 
@@ -29,7 +32,11 @@ fn status() -> Result<()> {
         file "set2b" "set2b"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     sim.rem_set(SetName("set1".into()));
     fs_operation! { SetManipulation, sim, "set2",
@@ -64,7 +71,11 @@ fn status() -> Result<()> {
         target_sets: set_names(["set2"]),
         ..old
     });
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
     let status = monja::local_status(&sim.profile()?, sim.cwd())?;
     expect_that!(
         status.untracked_files,
@@ -101,7 +112,11 @@ fn subdir() -> Result<()> {
         end
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     fs_operation! { LocalManipulation, sim,
         file "notinrepo" "notinrepo"
@@ -140,7 +155,11 @@ fn subdir() -> Result<()> {
             remfile "b"
         end
     };
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
     let status = monja::local_status(&sim.profile()?, sim.local_path("subdir".as_ref()))?;
     expect_that!(status.old_files_after_last_pull, {
         eq(Path::new("subdir/a")),
@@ -174,7 +193,11 @@ notinrepo
     "
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     sim.rem_set(SetName("set1".into()));
     fs_operation! { SetManipulation, sim, "set2",
@@ -203,7 +226,11 @@ notinrepo
         target_sets: set_names(["set2"]),
         ..old
     });
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     let status = monja::local_status(&sim.profile()?, sim.cwd())?;
     expect_that!(status.old_files_after_last_pull, is_empty());
@@ -283,3 +310,650 @@ foo/*
 
     Ok(())
 }
+
+// like .gitignore files, the deepest .monjaignore with a matching pattern decides,
+// so the root's negations can't re-include what a deeper one ignores, but the reverse works.
+#[gtest]
+fn ignore_nested_negation() -> Result<()> {
+    let sim = Simulator::create();
+
+    fs_operation! { LocalManipulation, sim,
+        file ".monjaignore"
+        "
+*.log
+!.config/keep.log
+!.config/app/cache
+    "
+        dir ".config"
+            file "keep.log" "keep.log"
+            file "other.log" "other.log"
+            dir "app"
+                file ".monjaignore"
+                "
+cache
+!debug.log
+    "
+                file "cache" "cache"
+                file "debug.log" "debug.log"
+                file "error.log" "error.log"
+            end
+        end
+    };
+
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.untracked_files, {
+        eq(Path::new(".monjaignore")),
+        eq(Path::new(".config/keep.log")),
+        eq(Path::new(".config/app/.monjaignore")),
+        eq(Path::new(".config/app/debug.log"))
+    });
+
+    Ok(())
+}
+
+// unlike a local .monjaignore, the profile's excludes always apply, and only sets' .monjaignore files come before them
+#[gtest]
+fn profile_excludes() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        excludes: vec!["/machine".into(), "*.secret".into(), "!keep.bak".into()],
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file ".monjaignore" "*.bak"
+    };
+    fs_operation! { LocalManipulation, sim,
+        file ".monjaignore" "!*.secret"
+        file "token.secret" "hunter2"
+        file "apple" "pie"
+        file "drop.bak" "drop.bak"
+        file "keep.bak" "keep.bak"
+        dir "machine"
+            file "hostname" "laptop"
+        end
+    };
+
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.untracked_files, {
+        eq(Path::new(".monjaignore")),
+        eq(Path::new("apple")),
+        eq(Path::new("keep.bak"))
+    });
+
+    Ok(())
+}
+
+#[gtest]
+fn data_root_not_walked() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "pie"
+    };
+    // the simulator's data root is under the local root, like ~/.local/share/monja would be
+    fs::create_dir(sim.data_root().join("backups"))?;
+    fs::write(sim.data_root().join("backups/apple"), "old pie")?;
+    fs::write(sim.data_root().join("notes.txt"), "not a special file")?;
+
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.untracked_files, { eq(Path::new("apple")) });
+
+    Ok(())
+}
+
+#[gtest]
+fn symlinked_repo_not_walked() -> Result<()> {
+    let sim = Simulator::create();
+    // like a repo kept at ~/.local/share/monja/repo, with ~/dotfiles as a more convenient link to it
+    let repo_link = sim.local_root().join("dotfiles");
+    std::os::unix::fs::symlink(sim.repo_root(), &repo_link)?;
+    sim.configure_profile(|old| MonjaProfileConfig {
+        repo_dir: repo_link.clone(),
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "orange" "juice"
+    };
+
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.untracked_files, { eq(Path::new("orange")) });
+
+    // and the other way around, where the repo is only reached through a symlinked local root
+    let link_dir = tempfile::tempdir()?;
+    let local_link = link_dir.path().join("home");
+    std::os::unix::fs::symlink(sim.local_root(), &local_link)?;
+    let profile = sim.profile()?;
+    let profile = MonjaProfile::from_roots(
+        AbsolutePath::trusted(local_link),
+        profile.repo_root,
+        profile.data_root,
+        profile.config,
+    )?;
+    let location = LocalFilePath::from(&profile, &profile.local_root, &profile.local_root)?;
+    let status = monja::local_status(&profile, location)?;
+    expect_that!(status.untracked_files, { eq(Path::new("orange")) });
+
+    Ok(())
+}
+
+#[gtest]
+fn non_utf8_file_name() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    std::fs::write(
+        sim.local_root().join(OsStr::from_bytes(b"bad\xffname")),
+        "bad",
+    )?;
+
+    // an error instead of a panic, since it can't be a relative path
+    let result = monja::local_status(&sim.profile()?, sim.cwd());
+    expect_that!(
+        result,
+        err(pat!(StatusError::LocalStateInitialization(pat!(
+            LocalStateInitializationError::LocalWalk(_)
+        ))))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn symlinks() -> Result<()> {
+    let sim = Simulator::create();
+
+    fs_operation! { LocalManipulation, sim,
+        dir "synced"
+            file "gitconfig" "gitconfig"
+        end
+    };
+    let local_root = sim.local_root();
+    std::os::unix::fs::symlink("synced/gitconfig", local_root.join("gitconfig"))?;
+    std::os::unix::fs::symlink("synced", local_root.join("linkeddir"))?;
+    std::os::unix::fs::symlink("nonexistent", local_root.join("dangling"))?;
+
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    // the directory link isn't followed, and the dangling link has nothing to push
+    expect_that!(
+        status.untracked_files,
+        {
+            eq(Path::new("gitconfig")),
+            eq(Path::new("synced/gitconfig"))
+        }
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn repo_paths_to_push() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    })
+    .configure_set(SetName("set1".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // only to-push files get a repo path, and only those under the location
+    fs_operation! { LocalManipulation, sim,
+        file "untracked" "untracked"
+    };
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(
+        status.repo_paths_to_push,
+        unordered_elements_are![(
+            eq(Path::new(".config/apple")),
+            eq(&sim.repo_root().join("set1/apple"))
+        )]
+    );
+
+    let status = monja::local_status(&sim.profile()?, sim.local_path("other"))?;
+    expect_that!(status.repo_paths_to_push, is_empty());
+
+    Ok(())
+}
+
+#[gtest]
+fn serializes_as_json() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "tracked" "tracked"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "notinrepo" "notinrepo"
+    };
+
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    let json = serde_json::to_value(&status)?;
+    // set names and paths should come out as plain strings
+    expect_that!(
+        json,
+        eq(&serde_json::json!({
+            "files_to_push": [["set1", ["tracked"]]],
+            "modified_files": [],
+            "conflicted_files": [],
+            "files_with_missing_sets": [],
+            "missing_files": [],
+            "untracked_files": ["notinrepo"],
+            "old_files_after_last_pull": [],
+        }))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn porcelain() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2", "set3"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "set1" "set1"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "zucchini" "bread"
+        dir "nested"
+            file "apple" "pie"
+        end
+        file "set2b" "set2b"
+    };
+    fs_operation! { SetManipulation, sim, "set3",
+        file "old" "old"
+        file "kept" "kept"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    fs_operation! { SetManipulation, sim, "set3",
+        remfile "old"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    sim.rem_set(SetName("set1".into()));
+    fs_operation! { SetManipulation, sim, "set2",
+        remfile "set2b"
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "notinrepo" "notinrepo"
+    };
+
+    // grouped by code in a fixed order, then sorted by path, regardless of which set the files are in
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(
+        status.to_porcelain(),
+        eq(indoc::indoc! {"
+            S\tset1
+            M\tset2b
+            P\tkept
+            P\tnested/apple
+            P\tzucchini
+            U\tnotinrepo
+            U\told
+            O\told
+        "})
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn summary() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2", "set3"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "set1" "set1"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "zucchini" "bread"
+        file "apple" "pie"
+        file "set2b" "set2b"
+    };
+    fs_operation! { SetManipulation, sim, "set3",
+        file "old" "old"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    fs_operation! { SetManipulation, sim, "set3",
+        remfile "old"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    sim.rem_set(SetName("set1".into()));
+    fs_operation! { SetManipulation, sim, "set2",
+        remfile "set2b"
+    };
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "crumble"
+        file "notinrepo" "notinrepo"
+    };
+
+    // the same counts as local_status would have
+    expect_that!(
+        monja::status_summary(&sim.profile()?)?,
+        eq(&StatusSummary {
+            to_push: 2,
+            modified: 1,
+            conflicted: 0,
+            untracked: 2,
+            missing_files: 1,
+            missing_sets: 1,
+            cleanable: 1,
+        })
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn modified_files() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "unchanged" "unchanged"
+        file "edited" "edited"
+        file "samesize" "aaaa"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "edited" "edited locally"
+        file "samesize" "bbbb"
+    };
+
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.files_to_push, {
+        (
+            pat!(SetName("set1")),
+            unordered_elements_are![
+                eq(Path::new("unchanged")),
+                eq(Path::new("edited")),
+                eq(Path::new("samesize"))
+            ],
+        )
+    });
+    expect_that!(status.modified_files, {
+        (
+            pat!(SetName("set1")),
+            unordered_elements_are![eq(Path::new("edited")), eq(Path::new("samesize"))],
+        )
+    });
+
+    Ok(())
+}
+
+#[gtest]
+fn cleanable_files() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "blueberry" "tart"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { SetManipulation, sim, "set1",
+        remfile "blueberry"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(status.old_files_after_last_pull, {
+        eq(Path::new("blueberry"))
+    });
+
+    // the repo isn't read at all, so it works even if the repo is in a bad state
+    fs_operation! { SetManipulation, sim, "set1",
+        file ".monja-set.toml" "not valid toml = = ="
+    };
+    expect_that!(monja::cleanable_files(&sim.profile()?)?, {
+        eq(Path::new("blueberry"))
+    });
+
+    Ok(())
+}
+
+#[gtest]
+fn file_status() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2", "set3"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "blueberry" "tart"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "cherry" "cobbler"
+    };
+    fs_operation! { SetManipulation, sim, "set3",
+        file "date" "loaf"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "blueberry" "muffin"
+        file "untracked" "untracked"
+    };
+    fs_operation! { SetManipulation, sim, "set3",
+        remfile "date"
+    };
+    sim.rem_set(SetName("set2".into()));
+    // only the file's set is read, so other broken sets don't matter
+    fs_operation! { SetManipulation, sim, "set3",
+        file ".monja-set.toml" "not valid toml = = ="
+    };
+
+    let profile = sim.profile()?;
+    expect_that!(
+        monja::file_status(&profile, sim.local_path("apple"))?,
+        eq(&FileStatus::Tracked(SetName("set1".into())))
+    );
+    expect_that!(
+        monja::file_status(&profile, sim.local_path("blueberry"))?,
+        eq(&FileStatus::ToPush(SetName("set1".into())))
+    );
+    expect_that!(
+        monja::file_status(&profile, sim.local_path("cherry"))?,
+        eq(&FileStatus::SetMissing(SetName("set2".into())))
+    );
+    expect_that!(
+        monja::file_status(&profile, sim.local_path("untracked"))?,
+        eq(&FileStatus::Untracked)
+    );
+    expect_that!(
+        monja::file_status(&profile, sim.local_path("nothing"))?,
+        eq(&FileStatus::NotFound)
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn file_status_file_missing() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { SetManipulation, sim, "set1",
+        remfile "apple"
+    };
+
+    expect_that!(
+        monja::file_status(&sim.profile()?, sim.local_path("apple"))?,
+        eq(&FileStatus::FileMissing(SetName("set1".into())))
+    );
+
+    Ok(())
+}
+
+#[gtest]
+fn sorted() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "m" "m"
+        file "d" "d"
+        dir "x"
+            file "k" "k"
+        end
+        file "a" "a"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "z" "untracked"
+        file "b" "untracked"
+        dir "y"
+            file "q" "untracked"
+        end
+        file "n" "untracked"
+        file "m" "modified"
+        file "a" "modified"
+    };
+
+    // the walk's order depends on the file system, so the files shouldn't come back in it
+    let status = monja::local_status(&sim.profile()?, sim.cwd())?;
+    expect_that!(
+        status.untracked_files,
+        elements_are![
+            eq(Path::new("b")),
+            eq(Path::new("n")),
+            eq(Path::new("y/q")),
+            eq(Path::new("z")),
+        ]
+    );
+    expect_that!(status.files_to_push, {
+        (
+            pat!(SetName("set1")),
+            elements_are![
+                eq(Path::new("a")),
+                eq(Path::new("d")),
+                eq(Path::new("m")),
+                eq(Path::new("x/k")),
+            ],
+        )
+    });
+    expect_that!(status.modified_files, {
+        (
+            pat!(SetName("set1")),
+            elements_are![eq(Path::new("a")), eq(Path::new("m"))],
+        )
+    });
+
+    Ok(())
+}
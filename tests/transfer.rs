@@ -24,7 +24,11 @@ fn basic_move() -> Result<()> {
     fs_operation! { SetManipulation, sim, "set2",
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     let result = monja::transfer(
         &sim.profile()?,
@@ -63,7 +67,11 @@ fn move_updates_index() -> Result<()> {
     fs_operation! { SetManipulation, sim, "set2",
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     let _result = monja::transfer(
         &sim.profile()?,
@@ -96,7 +104,11 @@ fn source_set_not_found() -> Result<()> {
         file "apple" "pie"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     let result = monja::transfer(
         &sim.profile()?,
@@ -127,7 +139,11 @@ fn dest_set_not_found() -> Result<()> {
         file "apple" "pie"
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     let result = monja::transfer(
         &sim.profile()?,
@@ -175,6 +191,48 @@ fn file_not_in_source_set() -> Result<()> {
     Ok(())
 }
 
+#[gtest]
+fn nothing_moved_if_any_file_not_in_source_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+    fs_operation! { LocalManipulation, sim,
+        file "notinset" "notinset"
+    };
+
+    let result = monja::transfer(
+        &sim.profile()?,
+        sim.execution_options(),
+        vec![sim.local_path("apple"), sim.local_path("notinset")],
+        SetName("set1".into()),
+        SetName("set2".into()),
+    );
+    expect_that!(result, err(pat!(TransferError::NotInSourceSet { .. })));
+
+    fs_operation! { SetValidation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetValidation, sim, "set2",
+        remfile "apple"
+    };
+
+    Ok(())
+}
+
 #[gtest]
 fn dest_shortcut_incompatible() -> Result<()> {
     let sim = Simulator::create();
@@ -182,8 +240,9 @@ fn dest_shortcut_incompatible() -> Result<()> {
         target_sets: set_names(["set1", "set2"]),
         ..old
     })
-    .configure_set(SetName("set2".into()), |_| SetConfig {
+    .configure_set(SetName("set2".into()), |old| SetConfig {
         shortcut: Some("other/prefix".into()),
+        ..old
     });
 
     fs_operation! { SetManipulation, sim, "set1",
@@ -192,7 +251,11 @@ fn dest_shortcut_incompatible() -> Result<()> {
     fs_operation! { SetManipulation, sim, "set2",
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     let result = monja::transfer(
         &sim.profile()?,
@@ -224,7 +287,11 @@ fn dryrun() -> Result<()> {
     fs_operation! { SetManipulation, sim, "set2",
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     sim.dryrun(true);
     let result = monja::transfer(
@@ -254,11 +321,13 @@ fn move_with_shortcut() -> Result<()> {
         target_sets: set_names(["set1", "set2"]),
         ..old
     })
-    .configure_set(SetName("set1".into()), |_| SetConfig {
+    .configure_set(SetName("set1".into()), |old| SetConfig {
         shortcut: Some("foo/bar".into()),
+        ..old
     })
-    .configure_set(SetName("set2".into()), |_| SetConfig {
+    .configure_set(SetName("set2".into()), |old| SetConfig {
         shortcut: Some("foo/bar".into()),
+        ..old
     });
 
     fs_operation! { SetManipulation, sim, "set1",
@@ -267,7 +336,11 @@ fn move_with_shortcut() -> Result<()> {
     fs_operation! { SetManipulation, sim, "set2",
     };
 
-    let _pull_result = monja::pull(&sim.profile()?, sim.execution_options())?;
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
 
     let result = monja::transfer(
         &sim.profile()?,
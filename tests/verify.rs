@@ -0,0 +1,153 @@
+use std::{fs, path::Path};
+
+use googletest::prelude::*;
+use monja::{MonjaProfileConfig, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn in_sync() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tart"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    let report = monja::verify(&sim.profile()?, None)?;
+    expect_that!(report.is_in_sync(), is_true());
+    expect_that!(report.verified_count, eq(2));
+
+    Ok(())
+}
+
+#[gtest]
+fn out_of_sync() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        file "cherry" "cobbler"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tart"
+        file "date" "loaf"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "crumble"
+    };
+    fs::remove_file(sim.local_root().join("blueberry"))?;
+    fs::remove_file(sim.repo_root().join("set2").join("date"))?;
+
+    let report = monja::verify(&sim.profile()?, None)?;
+    expect_that!(report.is_in_sync(), is_false());
+    expect_that!(report.verified_count, eq(1));
+    expect_that!(report.mismatched_files, {
+        (pat!(SetName("set1")), elements_are![eq(Path::new("apple"))])
+    });
+    expect_that!(report.missing_local_files, {
+        (
+            pat!(SetName("set2")),
+            elements_are![eq(Path::new("blueberry"))],
+        )
+    });
+    expect_that!(report.missing_repo_files, {
+        (pat!(SetName("set2")), elements_are![eq(Path::new("date"))])
+    });
+
+    Ok(())
+}
+
+#[gtest]
+fn only_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tart"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    fs_operation! { LocalManipulation, sim,
+        file "apple" "crumble"
+    };
+
+    let report = monja::verify(&sim.profile()?, Some(&SetName("set2".into())))?;
+    expect_that!(report.is_in_sync(), is_true());
+    expect_that!(report.verified_count, eq(1));
+
+    Ok(())
+}
+
+#[gtest]
+fn missing_set() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "tart"
+    };
+
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    sim.rem_set(SetName("set2".into()));
+
+    let report = monja::verify(&sim.profile()?, None)?;
+    expect_that!(report.missing_repo_files, {
+        (
+            pat!(SetName("set2")),
+            elements_are![eq(Path::new("blueberry"))],
+        )
+    });
+    expect_that!(report.verified_count, eq(1));
+
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use googletest::prelude::*;
+use monja::{MonjaProfileConfig, SetConfig, SetName};
+
+use crate::sim::{Simulator, set_names};
+
+#[allow(dead_code)]
+#[macro_use]
+mod sim;
+
+#[gtest]
+fn layering() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    })
+    .configure_set(SetName("set2".into()), |old| SetConfig {
+        shortcut: Some(".config".into()),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+        dir ".config"
+            file "blueberry" "tart"
+        end
+    };
+    fs_operation! { SetManipulation, sim, "set2",
+        file "blueberry" "muffin"
+    };
+    fs_operation! { SetManipulation, sim, "untargeted",
+        file "cherry" "cobbler"
+    };
+
+    let profile = sim.profile()?;
+    expect_that!(
+        monja::which(&profile, sim.local_path("apple"))?,
+        some(pat!(SetName("set1")))
+    );
+    expect_that!(
+        monja::which(&profile, sim.local_path(".config/blueberry"))?,
+        some(pat!(SetName("set2")))
+    );
+    expect_that!(monja::which(&profile, sim.local_path("cherry"))?, none());
+    expect_that!(monja::which(&profile, sim.local_path("missing"))?, none());
+
+    Ok(())
+}
+
+#[gtest]
+fn ignores_index() -> Result<()> {
+    let sim = Simulator::create();
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1"]),
+        ..old
+    });
+
+    fs_operation! { SetManipulation, sim, "set1",
+        file "apple" "pie"
+    };
+    let _pull_result = monja::pull(
+        &sim.profile()?,
+        sim.execution_options(),
+        &Default::default(),
+    )?;
+
+    // not pulled yet, but it's what the next pull would do
+    fs_operation! { SetManipulation, sim, "set2",
+        file "apple" "fritter"
+    };
+    sim.configure_profile(|old| MonjaProfileConfig {
+        target_sets: set_names(["set1", "set2"]),
+        ..old
+    });
+
+    expect_that!(
+        monja::which(&sim.profile()?, sim.local_path("apple"))?,
+        some(pat!(SetName("set2")))
+    );
+
+    Ok(())
+}